@@ -142,7 +142,13 @@ fi
 
 jar_path="${CESOP_VM_JAR:-scripts/CESOP Validation Module/SDEV-CESOP-VM-v1.7.1/cesop-vm-application-1.7.1.jar}"
 
+if [ -z "${CESOP_RUN_ID:-}" ]; then
+  CESOP_RUN_ID="demo-$(date +%Y%m%d%H%M%S)-$$"
+fi
+export CESOP_RUN_ID
+
 echo "Using binary: $bin"
+echo "Run ID: $CESOP_RUN_ID"
 echo "Using Java: $java_bin"
 echo "Using VM jar: $jar_path"
 
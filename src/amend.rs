@@ -0,0 +1,217 @@
+use crate::analysis::payee_threshold_counts;
+use crate::codes;
+use crate::compression::{uncompressed_path, Compression};
+use crate::generator::generate_account_identifier;
+use crate::location::account_country_code;
+use crate::models::PaymentRecord;
+use crate::Error;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+const LEGAL_FORMS: &[&str] = &["BV", "NV", "Ltd", "LLC", "GmbH", "SA", "SRL", "AB", "Oy"];
+
+// Payees above this many cross-border payments are reported, as `render`
+// counts them by default.
+const REPORTING_THRESHOLD: usize = 25;
+
+// Labels for the amended dataset written by `generate --with-corrections`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AmendmentLabels {
+    pub seed: u64,
+    pub original: String,
+    pub amended: String,
+    pub payees_amended: usize,
+    // Amended payees that are in a report, so a resubmission corrects them.
+    pub payees_reported: usize,
+    pub amendments: Vec<PayeeAmendment>,
+}
+
+// One payee whose data differs between the original and the amended dataset.
+#[derive(Debug, Clone, Serialize)]
+pub struct PayeeAmendment {
+    pub payee_id: String,
+    pub kind: &'static str,
+    pub field: &'static str,
+    pub original: String,
+    pub amended: String,
+    pub transactions: usize,
+    // Whether the original rows report the payee (over 25 cross-border
+    // payments, refunds not counted).
+    pub reported: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Amendment {
+    NameFix,
+    AccountChange,
+}
+
+// Keeps the compression of `output`: `payments.csv.gz` is amended into
+// `payments.amended.csv.gz`.
+pub fn amended_path(output: &Path) -> PathBuf {
+    match Compression::from_path(output).extension() {
+        Some(ext) => sibling_path(output, &format!("amended.csv.{}", ext)),
+        None => sibling_path(output, "amended.csv"),
+    }
+}
+
+pub fn labels_path(output: &Path) -> PathBuf {
+    sibling_path(output, "amendments.json")
+}
+
+fn sibling_path(output: &Path, suffix: &str) -> PathBuf {
+    let output = uncompressed_path(output);
+    let stem = output
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "payments".to_string());
+    output.with_file_name(format!("{}.{}", stem, suffix))
+}
+
+// A copy of `records` in which a `payee_rate` share of the payees has a
+// corrected registered name or a new payee account. Payment ids are kept, so
+// every amended row matches its original. Half of the amended payees (more
+// when there are too few others) are reported ones, so the amended dataset
+// feeds `resubmit`; most payees are below the threshold.
+pub fn amend_records(
+    records: &[PaymentRecord],
+    payee_rate: f64,
+    seed: u64,
+) -> Result<(Vec<PaymentRecord>, Vec<PayeeAmendment>), Error> {
+    if !(0.0..=1.0).contains(&payee_rate) {
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("correction payee rate must be 0..1"));
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(4);
+    let mut amended = records.to_vec();
+
+    let mut payee_map: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (idx, record) in records.iter().enumerate() {
+        payee_map.entry(record.payee_id.as_str()).or_default().push(idx);
+    }
+    let reported: HashSet<String> = payee_threshold_counts(records, false)?
+        .into_iter()
+        .filter(|(_, count)| *count > REPORTING_THRESHOLD)
+        .map(|(key, _)| key.payee_id)
+        .collect();
+    let mut payee_ids: Vec<&str> = payee_map.keys().copied().collect();
+    payee_ids.shuffle(&mut rng);
+    let target_payees = ((payee_ids.len() as f64) * payee_rate).round() as usize;
+    let (reported_ids, other_ids): (Vec<&str>, Vec<&str>) = payee_ids
+        .into_iter()
+        .partition(|payee_id| reported.contains(*payee_id));
+    let other_take = (target_payees / 2).min(other_ids.len());
+    let reported_take = (target_payees - other_take).min(reported_ids.len());
+    let other_take = (target_payees - reported_take).min(other_ids.len());
+    let selected: Vec<&str> = reported_ids
+        .into_iter()
+        .take(reported_take)
+        .chain(other_ids.into_iter().take(other_take))
+        .collect();
+
+    let mut amendments = Vec::with_capacity(target_payees);
+    for payee_id in selected {
+        let indices = &payee_map[payee_id];
+        let amendment = *[Amendment::NameFix, Amendment::AccountChange]
+            .choose(&mut rng)
+            .unwrap_or(&Amendment::NameFix);
+        let account = indices
+            .iter()
+            .map(|idx| &records[*idx])
+            .find(|record| {
+                !record.payee_account.trim().is_empty() && record.payee_account_type != "BIC"
+            });
+        let change = match (amendment, account) {
+            (Amendment::AccountChange, Some(account)) => {
+                change_account(&mut amended, indices, account, &mut rng)
+            }
+            _ => fix_name(&mut amended, indices, &mut rng),
+        };
+        amendments.push(PayeeAmendment {
+            payee_id: payee_id.to_string(),
+            reported: reported.contains(payee_id),
+            ..change
+        });
+    }
+    amendments.sort_by(|a, b| a.payee_id.cmp(&b.payee_id));
+
+    Ok((amended, amendments))
+}
+
+// Registered-name corrections: the legal form is added or replaced.
+fn fix_name<R: Rng + ?Sized>(
+    records: &mut [PaymentRecord],
+    indices: &[usize],
+    rng: &mut R,
+) -> PayeeAmendment {
+    let original = records[indices[0]].payee_name.clone();
+    let trimmed = original.trim();
+    let current = LEGAL_FORMS
+        .iter()
+        .find(|form| trimmed.ends_with(&format!(" {}", form)));
+    let base = match current {
+        Some(form) => &trimmed[..trimmed.len() - form.len() - 1],
+        None => trimmed,
+    };
+    let forms: Vec<&str> = LEGAL_FORMS
+        .iter()
+        .copied()
+        .filter(|form| Some(form) != current)
+        .collect();
+    let form = forms.choose(rng).unwrap_or(&"Ltd");
+    let name = format!("{} {}", base, form).trim().to_string();
+    for idx in indices {
+        records[*idx].payee_name = name.clone();
+    }
+    PayeeAmendment {
+        payee_id: String::new(),
+        kind: "name_fix",
+        field: "payee_name",
+        original,
+        amended: name,
+        transactions: indices.len(),
+        reported: false,
+    }
+}
+
+// The payee moves its first non-BIC account to a new one in the same country.
+fn change_account<R: Rng + ?Sized>(
+    records: &mut [PaymentRecord],
+    indices: &[usize],
+    account: &PaymentRecord,
+    rng: &mut R,
+) -> PayeeAmendment {
+    let original = account.payee_account.clone();
+    let country = account_country_code(&account.payee_account_type, &original)
+        .unwrap_or_else(|| account.payee_country.clone());
+    let (new_account, new_type) = generate_account_identifier(rng, &country);
+    let mut transactions = 0;
+    for idx in indices {
+        if records[*idx].payee_account == original {
+            records[*idx].payee_account = new_account.clone();
+            records[*idx].payee_account_type = new_type.clone();
+            transactions += 1;
+        }
+    }
+    PayeeAmendment {
+        payee_id: String::new(),
+        kind: "account_change",
+        field: "payee_account",
+        original,
+        amended: new_account,
+        transactions,
+        reported: false,
+    }
+}
+
+impl AmendmentLabels {
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        std::fs::write(path, json).map_err(|err| err.to_string())
+    }
+}
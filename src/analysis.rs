@@ -1,7 +1,13 @@
+use crate::input::{read_payment_records, CsvReadOptions};
 use crate::location::resolve_payee_country;
 use crate::models::PaymentRecord;
+use crate::rates::RateTable;
 use crate::reference::is_eu_member_state;
-use std::collections::{HashMap, HashSet};
+use crate::run_summary::PhaseTimings;
+use crate::Error;
+use chrono::Datelike;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::Path;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
@@ -18,36 +24,334 @@ struct IdentifierKey {
     identifier: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ThresholdReport {
     pub threshold: usize,
     pub total_records: usize,
     pub cross_border_records: usize,
     pub total_payees: usize,
     pub payees_over_threshold: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eur_totals: Option<EurTotals>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity_collisions: Option<IdentityCollisions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub borderline_payees: Option<Vec<BorderlinePayee>>,
+}
+
+// A payee whose count is close to the threshold. `count` is the count as
+// configured; the counts with and without refunds show whether including
+// refunds would change whether the payee is reported.
+#[derive(Debug, Clone, Serialize)]
+pub struct BorderlinePayee {
+    pub psp_id: String,
+    pub payee_id: String,
+    pub payee_country: String,
+    pub count: usize,
+    pub reportable: bool,
+    pub count_without_refunds: usize,
+    pub count_with_refunds: usize,
+    pub refunds_change_outcome: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnalyzeOptions<'a> {
+    pub threshold: usize,
+    pub include_refunds: bool,
+    // EUR totals are added when rates are given.
+    pub rates: Option<&'a RateTable>,
+    pub identity_collisions: bool,
+    // Payees within this many transactions of the threshold are listed.
+    pub proximity: Option<usize>,
+    pub csv: CsvReadOptions,
+}
+
+impl Default for AnalyzeOptions<'_> {
+    fn default() -> Self {
+        Self {
+            threshold: 25,
+            include_refunds: false,
+            rates: None,
+            identity_collisions: false,
+            proximity: None,
+            csv: CsvReadOptions::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EurTotals {
+    pub rate_source: String,
+    pub total_eur: f64,
+    pub converted_records: usize,
+    pub unconverted_records: usize,
+    pub unconverted_currencies: Vec<String>,
+    pub by_payee_ms: BTreeMap<String, f64>,
+    pub by_payee: Vec<PayeeTotal>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PayeeTotal {
+    pub psp_id: String,
+    pub payee_id: String,
+    pub payee_country: String,
+    pub records: usize,
+    pub total_eur: f64,
+}
+
+// Probable identity collisions: they either merge distinct payees into one
+// threshold count (shared account) or split one payee across several
+// (conflicting names or countries).
+#[derive(Debug, Clone, Serialize)]
+pub struct IdentityCollisions {
+    pub shared_accounts: Vec<SharedAccount>,
+    pub conflicting_payees: Vec<ConflictingPayee>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SharedAccount {
+    pub account: String,
+    pub account_type: String,
+    pub psp_ids: Vec<String>,
+    pub payee_ids: Vec<String>,
+    pub records: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConflictingPayee {
+    pub payee_id: String,
+    pub names: Vec<String>,
+    pub countries: Vec<String>,
+    pub records: usize,
 }
 
 pub fn analyze_threshold_csv(
     path: &Path,
+    options: &AnalyzeOptions,
+    timings: &mut PhaseTimings,
+) -> Result<ThresholdReport, Error> {
+    let records = timings.time("csv_read", || read_payment_records(path, &options.csv))?;
+    let (threshold, include_refunds) = (options.threshold, options.include_refunds);
+
+    let (_payees, mut report) = timings.time("threshold", || {
+        compute_reportability(&records, threshold, include_refunds)
+    })?;
+    if let Some(rates) = options.rates {
+        report.eur_totals = Some(timings.time("eur_totals", || {
+            eur_totals(&records, include_refunds, rates)
+        })?);
+    }
+    if options.identity_collisions {
+        report.identity_collisions = Some(timings.time("identity_collisions", || {
+            find_identity_collisions(&records)
+        })?);
+    }
+    if let Some(margin) = options.proximity {
+        report.borderline_payees = Some(timings.time("threshold_proximity", || {
+            threshold_proximity(&records, threshold, include_refunds, margin)
+        })?);
+    }
+    Ok(report)
+}
+
+// Payees whose count is within `margin` transactions of the threshold, on
+// either side, closest first.
+pub fn threshold_proximity(
+    records: &[PaymentRecord],
     threshold: usize,
     include_refunds: bool,
-) -> Result<ThresholdReport, String> {
-    let mut reader = csv::Reader::from_path(path).map_err(|err| err.to_string())?;
-    let mut records = Vec::new();
-    for result in reader.deserialize() {
-        let record: PaymentRecord = result.map_err(|err| err.to_string())?;
-        records.push(record);
+    margin: usize,
+) -> Result<Vec<BorderlinePayee>, Error> {
+    let without_refunds = payee_threshold_counts(records, false)?;
+    let with_refunds = payee_threshold_counts(records, true)?;
+    let mut payees: Vec<BorderlinePayee> = with_refunds
+        .into_iter()
+        .map(|(key, count_with_refunds)| {
+            let count_without_refunds = without_refunds.get(&key).copied().unwrap_or(0);
+            let count = if include_refunds {
+                count_with_refunds
+            } else {
+                count_without_refunds
+            };
+            BorderlinePayee {
+                psp_id: key.psp_id,
+                payee_id: key.payee_id,
+                payee_country: key.payee_country,
+                count,
+                reportable: count > threshold,
+                count_without_refunds,
+                count_with_refunds,
+                refunds_change_outcome: (count_without_refunds > threshold)
+                    != (count_with_refunds > threshold),
+            }
+        })
+        .filter(|payee| payee.count.abs_diff(threshold) <= margin)
+        .collect();
+    payees.sort_by(|a, b| {
+        a.count
+            .abs_diff(threshold)
+            .cmp(&b.count.abs_diff(threshold))
+            .then_with(|| (&a.psp_id, &a.payee_id).cmp(&(&b.psp_id, &b.payee_id)))
+    });
+    Ok(payees)
+}
+
+pub fn find_identity_collisions(records: &[PaymentRecord]) -> Result<IdentityCollisions, Error> {
+    #[derive(Default)]
+    struct AccountUse {
+        psp_ids: BTreeSet<String>,
+        payee_ids: BTreeSet<String>,
+        records: usize,
+    }
+    #[derive(Default)]
+    struct PayeeUse {
+        names: BTreeSet<String>,
+        countries: BTreeSet<String>,
+        records: usize,
     }
 
-    let (_payees, report) = compute_reportability(&records, threshold, include_refunds)?;
-    Ok(report)
+    let mut accounts: BTreeMap<(String, String), AccountUse> = BTreeMap::new();
+    let mut payees: BTreeMap<String, PayeeUse> = BTreeMap::new();
+    for record in records {
+        // BICs and the PSP identifier used for payees without an account name
+        // the payee's PSP, so many payees share them by design.
+        let account = record.payee_account.trim();
+        if !account.is_empty() && !record.payee_account_type.eq_ignore_ascii_case("BIC") {
+            let entry = accounts
+                .entry((record.payee_account_type.clone(), account.to_string()))
+                .or_default();
+            entry.psp_ids.insert(record.psp_id.clone());
+            entry.payee_ids.insert(record.payee_id.clone());
+            entry.records += 1;
+        }
+        let entry = payees.entry(record.payee_id.clone()).or_default();
+        entry.names.insert(record.payee_name.trim().to_string());
+        entry.countries.insert(resolve_payee_country(record)?);
+        entry.records += 1;
+    }
+
+    let mut shared_accounts: Vec<SharedAccount> = accounts
+        .into_iter()
+        .filter(|(_, usage)| usage.payee_ids.len() > 1)
+        .map(|((account_type, account), usage)| SharedAccount {
+            account,
+            account_type,
+            psp_ids: usage.psp_ids.into_iter().collect(),
+            payee_ids: usage.payee_ids.into_iter().collect(),
+            records: usage.records,
+        })
+        .collect();
+    shared_accounts.sort_by_key(|shared| std::cmp::Reverse(shared.records));
+    let mut conflicting_payees: Vec<ConflictingPayee> = payees
+        .into_iter()
+        .filter(|(_, usage)| usage.names.len() > 1 || usage.countries.len() > 1)
+        .map(|(payee_id, usage)| ConflictingPayee {
+            payee_id,
+            names: usage.names.into_iter().collect(),
+            countries: usage.countries.into_iter().collect(),
+            records: usage.records,
+        })
+        .collect();
+    conflicting_payees.sort_by_key(|payee| std::cmp::Reverse(payee.records));
+
+    Ok(IdentityCollisions {
+        shared_accounts,
+        conflicting_payees,
+    })
+}
+
+// Cross-border payments only (the same population as the threshold count);
+// refunds are subtracted when they are included.
+pub fn eur_totals(
+    records: &[PaymentRecord],
+    include_refunds: bool,
+    rates: &RateTable,
+) -> Result<EurTotals, Error> {
+    let mut total_eur = 0.0;
+    let mut converted_records = 0usize;
+    let mut unconverted_records = 0usize;
+    let mut unconverted_currencies: BTreeSet<String> = BTreeSet::new();
+    let mut by_payee_ms: BTreeMap<String, f64> = BTreeMap::new();
+    let mut by_payee: HashMap<PayeeKey, (usize, f64)> = HashMap::new();
+
+    for record in records {
+        let payee_country = resolve_payee_country(record)?;
+        if !is_cross_border(record.payer_country.as_str(), &payee_country) {
+            continue;
+        }
+        if record.is_refund && !include_refunds {
+            continue;
+        }
+        let amount = record.amount.trim().parse::<f64>().ok();
+        let period = record_period(&record.execution_time);
+        let converted = amount.and_then(|amount| {
+            period
+                .as_deref()
+                .and_then(|period| rates.to_eur(amount.abs(), &record.currency, period))
+        });
+        let Some(mut value) = converted else {
+            unconverted_records += 1;
+            unconverted_currencies.insert(record.currency.trim().to_uppercase());
+            continue;
+        };
+        if record.is_refund {
+            value = -value;
+        }
+        converted_records += 1;
+        total_eur += value;
+        *by_payee_ms.entry(payee_country.clone()).or_insert(0.0) += value;
+        let entry = by_payee
+            .entry(payee_key(record, &payee_country))
+            .or_insert((0, 0.0));
+        entry.0 += 1;
+        entry.1 += value;
+    }
+
+    let mut by_payee: Vec<PayeeTotal> = by_payee
+        .into_iter()
+        .map(|(key, (records, total))| PayeeTotal {
+            psp_id: key.psp_id,
+            payee_id: key.payee_id,
+            payee_country: key.payee_country,
+            records,
+            total_eur: round_cents(total),
+        })
+        .collect();
+    by_payee.sort_by(|a, b| {
+        b.total_eur
+            .total_cmp(&a.total_eur)
+            .then_with(|| a.payee_id.cmp(&b.payee_id))
+    });
+    for total in by_payee_ms.values_mut() {
+        *total = round_cents(*total);
+    }
+
+    Ok(EurTotals {
+        rate_source: rates.source().to_string(),
+        total_eur: round_cents(total_eur),
+        converted_records,
+        unconverted_records,
+        unconverted_currencies: unconverted_currencies.into_iter().collect(),
+        by_payee_ms,
+        by_payee,
+    })
+}
+
+fn record_period(execution_time: &str) -> Option<String> {
+    let parsed = chrono::DateTime::parse_from_rfc3339(execution_time.trim()).ok()?;
+    let date = parsed.with_timezone(&chrono::Utc).date_naive();
+    Some(format!("{}-Q{}", date.year(), (date.month() - 1) / 3 + 1))
+}
+
+fn round_cents(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
 }
 
 pub fn reportable_payee_keys(
     records: &[PaymentRecord],
     threshold: usize,
     include_refunds: bool,
-) -> Result<HashSet<PayeeKey>, String> {
+) -> Result<HashSet<PayeeKey>, Error> {
     let (payees, _report) = compute_reportability(records, threshold, include_refunds)?;
     Ok(payees)
 }
@@ -56,11 +360,120 @@ pub fn analyze_threshold_records(
     records: &[PaymentRecord],
     threshold: usize,
     include_refunds: bool,
-) -> Result<ThresholdReport, String> {
+) -> Result<ThresholdReport, Error> {
     let (_payees, report) = compute_reportability(records, threshold, include_refunds)?;
     Ok(report)
 }
 
+pub fn payee_threshold_counts(
+    records: &[PaymentRecord],
+    include_refunds: bool,
+) -> Result<HashMap<PayeeKey, usize>, Error> {
+    let multi_identifier_payees = payees_with_multiple_identifiers(records)?;
+    let mut counts: HashMap<IdentifierKey, usize> = HashMap::new();
+    let mut payee_identifiers: HashMap<PayeeKey, IdentifierKey> = HashMap::new();
+
+    for record in records {
+        let payee_country = resolve_payee_country(record)?;
+        if !is_cross_border(record.payer_country.as_str(), &payee_country) {
+            continue;
+        }
+        if record.is_refund && !include_refunds {
+            continue;
+        }
+        let key = identifier_key(record, &payee_country, &multi_identifier_payees);
+        *counts.entry(key.clone()).or_insert(0) += 1;
+        payee_identifiers.insert(payee_key(record, &payee_country), key);
+    }
+
+    Ok(payee_identifiers
+        .into_iter()
+        .map(|(payee, key)| {
+            let count = counts.get(&key).copied().unwrap_or(0);
+            (payee, count)
+        })
+        .collect())
+}
+
+// Threshold counts built one record at a time, for records too many to hold
+// at once. `finish` gives what `reportable_payee_keys` and
+// `payee_threshold_counts` give for the same records.
+#[derive(Debug, Default)]
+pub struct ThresholdCounter {
+    include_refunds: bool,
+    // Counted records per account identifier, for every identifier a payee
+    // uses, counted or not.
+    identifiers: HashMap<PayeeKey, HashMap<String, usize>>,
+}
+
+impl ThresholdCounter {
+    pub fn new(include_refunds: bool) -> Self {
+        Self {
+            include_refunds,
+            identifiers: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, record: &PaymentRecord) -> Result<(), Error> {
+        let payee_country = resolve_payee_country(record)?;
+        let counted = is_cross_border(record.payer_country.as_str(), &payee_country)
+            && (!record.is_refund || self.include_refunds);
+        let count = self
+            .identifiers
+            .entry(payee_key(record, &payee_country))
+            .or_default()
+            .entry(account_identifier(record))
+            .or_insert(0);
+        if counted {
+            *count += 1;
+        }
+        Ok(())
+    }
+
+    // The payees over `threshold`, and the count of every payee with counted
+    // records.
+    pub fn finish(self, threshold: usize) -> (HashSet<PayeeKey>, HashMap<PayeeKey, usize>) {
+        // As in `identifier_key`: payees with several identifiers are counted
+        // by payee_id, the others by their one identifier.
+        let key = |payee: &PayeeKey, identifiers: &HashMap<String, usize>, identifier: &str| {
+            IdentifierKey {
+                psp_id: payee.psp_id.clone(),
+                payee_country: payee.payee_country.clone(),
+                identifier: if identifiers.len() > 1 {
+                    payee.payee_id.clone()
+                } else {
+                    identifier.to_string()
+                },
+            }
+        };
+        let mut counts: HashMap<IdentifierKey, usize> = HashMap::new();
+        for (payee, identifiers) in &self.identifiers {
+            for (identifier, count) in identifiers {
+                *counts
+                    .entry(key(payee, identifiers, identifier))
+                    .or_insert(0) += count;
+            }
+        }
+
+        let mut reportable = HashSet::new();
+        let mut payee_counts = HashMap::new();
+        for (payee, identifiers) in &self.identifiers {
+            let Some((identifier, _)) = identifiers.iter().find(|(_, count)| **count > 0) else {
+                continue;
+            };
+            let count = counts
+                .get(&key(payee, identifiers, identifier))
+                .copied()
+                .unwrap_or(0);
+            if count > threshold {
+                reportable.insert(payee.clone());
+            }
+            payee_counts.insert(payee.clone(), count);
+        }
+        (reportable, payee_counts)
+    }
+}
+
 fn compute_reportability(
     records: &[PaymentRecord],
     threshold: usize,
@@ -118,6 +531,9 @@ fn compute_reportability(
         cross_border_records,
         total_payees,
         payees_over_threshold,
+        eur_totals: None,
+        identity_collisions: None,
+        borderline_payees: None,
     };
 
     Ok((reportable_payees, report))
@@ -0,0 +1,95 @@
+use crate::codes;
+use crate::run_summary::run_id;
+use crate::util::{sha256_file, sha256_hex};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: String,
+    #[serde(default)]
+    pub run_id: String,
+    pub command: String,
+    pub input: String,
+    pub input_sha256: String,
+    pub output: String,
+    pub output_sha256: String,
+    pub seed: u64,
+    pub counts: serde_json::Value,
+    #[serde(default)]
+    pub prev_hash: Option<String>,
+    #[serde(default)]
+    pub entry_hash: String,
+}
+
+pub fn append_audit_entry<C: Serialize>(
+    log_path: &Path,
+    command: &str,
+    input: &Path,
+    output: &Path,
+    seed: u64,
+    counts: &C,
+    chain: bool,
+) -> Result<AuditEntry, String> {
+    let prev_hash = if chain {
+        last_entry_hash(log_path)?
+    } else {
+        None
+    };
+    let mut entry = AuditEntry {
+        timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        run_id: run_id().to_string(),
+        command: command.to_string(),
+        input: input.display().to_string(),
+        input_sha256: sha256_file(input)?,
+        output: output.display().to_string(),
+        output_sha256: sha256_file(output)?,
+        seed,
+        counts: serde_json::to_value(counts).map_err(|err| err.to_string())?,
+        prev_hash,
+        entry_hash: String::new(),
+    };
+    let body = serde_json::to_string(&entry).map_err(|err| err.to_string())?;
+    let chained = format!("{}{}", entry.prev_hash.as_deref().unwrap_or(""), body);
+    entry.entry_hash = sha256_hex(chained.as_bytes());
+
+    if let Some(parent) = log_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)
+        .map_err(|err| err.to_string())?;
+    let line = serde_json::to_string(&entry).map_err(|err| err.to_string())?;
+    writeln!(file, "{}", line).map_err(|err| err.to_string())?;
+    Ok(entry)
+}
+
+fn last_entry_hash(log_path: &Path) -> Result<Option<String>, String> {
+    let file = match std::fs::File::open(log_path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.to_string()),
+    };
+    let mut last = None;
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|err| err.to_string())?;
+        if !line.trim().is_empty() {
+            last = Some(line);
+        }
+    }
+    let Some(line) = last else {
+        return Ok(None);
+    };
+    let entry: AuditEntry = serde_json::from_str(&line).map_err(|err| {
+        codes::IN_FILE_INVALID.err(format!(
+            "invalid audit log entry in {}: {}",
+            log_path.display(),
+            err
+        ))
+    })?;
+    Ok(Some(entry.entry_hash))
+}
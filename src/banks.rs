@@ -0,0 +1,76 @@
+// Fictional but structured bank codes (BIC positions 1-4) and location codes
+// (positions 7-8) per country, so generated BICs look like real ones.
+struct CountryBanks {
+    country: &'static str,
+    banks: &'static [&'static str],
+    locations: &'static [&'static str],
+}
+
+const COUNTRY_BANKS: &[CountryBanks] = &[
+    CountryBanks { country: "AT", banks: &["ALPK", "DNBV", "TIRO", "WIEB"], locations: &["WW", "GZ", "LZ"] },
+    CountryBanks { country: "BE", banks: &["BRXB", "FLAN", "GHNT", "WALO"], locations: &["BB", "AN", "GE"] },
+    CountryBanks { country: "BG", banks: &["BLKB", "SOFB", "VRNA", "PLVD"], locations: &["SF", "VA"] },
+    CountryBanks { country: "HR", banks: &["ADRB", "ZAGB", "DLMT", "SLVB"], locations: &["2X", "ST"] },
+    CountryBanks { country: "CY", banks: &["LMSB", "NICB", "TRDS", "PAFB"], locations: &["2N", "LM"] },
+    CountryBanks { country: "CZ", banks: &["VLTB", "BOHB", "MORV", "PRGB"], locations: &["PP", "BR"] },
+    CountryBanks { country: "DK", banks: &["JYLB", "FYNB", "KBHB", "NORD"], locations: &["KK", "AA"] },
+    CountryBanks { country: "EE", banks: &["BLTB", "TLNB", "TRTB", "HARJ"], locations: &["2X", "TR"] },
+    CountryBanks { country: "FI", banks: &["SUOB", "LAPB", "TMPB", "HELB"], locations: &["HH", "TE"] },
+    CountryBanks { country: "FR", banks: &["LOIR", "RHON", "BRET", "SEIN"], locations: &["PP", "LY", "MA"] },
+    CountryBanks { country: "DE", banks: &["RHEB", "ELBB", "ISAR", "MAIN"], locations: &["FF", "MM", "HH", "BE"] },
+    CountryBanks { country: "GR", banks: &["AEGB", "OLYB", "THSB", "CRTB"], locations: &["AA", "TH"] },
+    CountryBanks { country: "HU", banks: &["DUNB", "BUDB", "TISZ", "BLTN"], locations: &["HB", "DE"] },
+    CountryBanks { country: "IE", banks: &["LIFF", "SHNN", "CORK", "GLWY"], locations: &["2D", "2C"] },
+    CountryBanks { country: "IT", banks: &["TEVB", "ARNO", "POVL", "ETNA"], locations: &["RR", "MM", "TT"] },
+    CountryBanks { country: "LV", banks: &["DAUG", "RIGB", "GAUJ", "KRZM"], locations: &["2X", "RI"] },
+    CountryBanks { country: "LT", banks: &["NEMB", "VLNB", "KAUN", "NERS"], locations: &["2X", "KA"] },
+    CountryBanks { country: "LU", banks: &["ALZB", "MOSL", "SURB", "KIRB"], locations: &["LL", "LU"] },
+    CountryBanks { country: "MT", banks: &["VLTM", "GOZB", "MDNA", "SLMB"], locations: &["MT", "GZ"] },
+    CountryBanks { country: "NL", banks: &["AMST", "RIJN", "MAAS", "VECH"], locations: &["2A", "2R", "2U"] },
+    CountryBanks { country: "PL", banks: &["WISL", "ODRB", "KRKB", "BLTP"], locations: &["PW", "PK", "PP"] },
+    CountryBanks { country: "PT", banks: &["TEJO", "DOUR", "MNDG", "ALGV"], locations: &["PL", "PP"] },
+    CountryBanks { country: "RO", banks: &["DNRB", "CRPT", "BUCB", "MURS"], locations: &["BU", "CJ"] },
+    CountryBanks { country: "SK", banks: &["TATR", "VAHB", "HRNB", "BRTS"], locations: &["BX", "KE"] },
+    CountryBanks { country: "SI", banks: &["SAVB", "LJUB", "TRIG", "KRPB"], locations: &["2X", "MB"] },
+    CountryBanks { country: "ES", banks: &["EBRO", "TAJO", "GUAD", "MSTA"], locations: &["MM", "BB", "VV"] },
+    CountryBanks { country: "SE", banks: &["VASA", "GOTB", "NORL", "MLRN"], locations: &["SS", "GG"] },
+    CountryBanks { country: "GB", banks: &["THMS", "SVRN", "TYNE", "MRSY"], locations: &["2L", "2E", "2M"] },
+    CountryBanks { country: "NO", banks: &["FJRD", "OSLB", "BRGN", "TRND"], locations: &["KK", "BG"] },
+    CountryBanks { country: "CH", banks: &["ALPB", "RHNB", "LMNB", "JURA"], locations: &["ZZ", "GG", "BB"] },
+    CountryBanks { country: "IS", banks: &["HEKL", "REYB", "GEYS", "VTNB"], locations: &["RE", "AK"] },
+    CountryBanks { country: "LI", banks: &["VADB", "RHTB", "SCHB", "ESCH"], locations: &["2X", "22"] },
+    CountryBanks { country: "US", banks: &["HDSN", "MSSP", "CLRD", "PTMC"], locations: &["33", "6S", "44"] },
+    CountryBanks { country: "CA", banks: &["STLW", "FRSR", "YKNB", "ONTB"], locations: &["TT", "MM", "VV"] },
+];
+
+// Each simulated PSP brand keeps the same bank code in every country.
+const PSP_BANK_CODES: &[(&str, &str)] = &[
+    ("Northshore Payments", "NSHP"),
+    ("Atlas Pay", "ATLP"),
+    ("BlueBridge PSP", "BLBR"),
+    ("Harborline Processing", "HBLN"),
+    ("Summit Payments", "SMMT"),
+];
+
+const DEFAULT_LOCATIONS: &[&str] = &["2X"];
+
+fn country_banks(country: &str) -> Option<&'static CountryBanks> {
+    COUNTRY_BANKS.iter().find(|entry| entry.country == country)
+}
+
+pub fn bank_codes(country: &str) -> &'static [&'static str] {
+    country_banks(country).map(|entry| entry.banks).unwrap_or(&[])
+}
+
+pub fn location_codes(country: &str) -> &'static [&'static str] {
+    country_banks(country)
+        .map(|entry| entry.locations)
+        .unwrap_or(DEFAULT_LOCATIONS)
+}
+
+pub fn psp_bank_code(name: &str) -> Option<&'static str> {
+    PSP_BANK_CODES
+        .iter()
+        .find(|(psp, _)| *psp == name)
+        .map(|(_, code)| *code)
+}
@@ -0,0 +1,368 @@
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, Offset, TimeZone, Utc, Weekday,
+};
+
+const HOLIDAY_WEIGHT: f64 = 0.3;
+const SATURDAY_WEIGHT: f64 = 0.85;
+const SUNDAY_WEIGHT: f64 = 0.7;
+// Countries with Sunday trading restrictions see much lower Sunday volume.
+const QUIET_SUNDAY_WEIGHT: f64 = 0.4;
+
+struct Calendar {
+    country: &'static str,
+    fixed: &'static [(u32, u32)],
+    // Days relative to Easter Sunday (Good Friday = -2, Easter Monday = 1, ...).
+    easter: &'static [i64],
+    orthodox: bool,
+    quiet_sunday: bool,
+}
+
+const CALENDARS: &[Calendar] = &[
+    Calendar {
+        country: "AT",
+        fixed: &[(1, 1), (1, 6), (5, 1), (8, 15), (10, 26), (11, 1), (12, 8), (12, 25), (12, 26)],
+        easter: &[1, 39, 50, 60],
+        orthodox: false,
+        quiet_sunday: true,
+    },
+    Calendar {
+        country: "BE",
+        fixed: &[(1, 1), (5, 1), (7, 21), (8, 15), (11, 1), (11, 11), (12, 25)],
+        easter: &[1, 39, 50],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "BG",
+        fixed: &[(1, 1), (3, 3), (5, 1), (5, 6), (5, 24), (9, 6), (9, 22), (12, 24), (12, 25), (12, 26)],
+        easter: &[-2, 1],
+        orthodox: true,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "CY",
+        fixed: &[(1, 1), (1, 6), (3, 25), (4, 1), (5, 1), (8, 15), (10, 1), (10, 28), (12, 25), (12, 26)],
+        easter: &[-48, -2, 1, 50],
+        orthodox: true,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "CZ",
+        fixed: &[(1, 1), (5, 1), (5, 8), (7, 5), (7, 6), (9, 28), (10, 28), (11, 17), (12, 24), (12, 25), (12, 26)],
+        easter: &[-2, 1],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "DE",
+        fixed: &[(1, 1), (5, 1), (10, 3), (12, 25), (12, 26)],
+        easter: &[-2, 1, 39, 50],
+        orthodox: false,
+        quiet_sunday: true,
+    },
+    Calendar {
+        country: "DK",
+        fixed: &[(1, 1), (6, 5), (12, 24), (12, 25), (12, 26)],
+        easter: &[-3, -2, 1, 39, 50],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "EE",
+        fixed: &[(1, 1), (2, 24), (5, 1), (6, 23), (6, 24), (8, 20), (12, 24), (12, 25), (12, 26)],
+        easter: &[-2, 49],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "ES",
+        fixed: &[(1, 1), (1, 6), (5, 1), (8, 15), (10, 12), (11, 1), (12, 6), (12, 8), (12, 25)],
+        easter: &[-2],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "FI",
+        fixed: &[(1, 1), (1, 6), (5, 1), (12, 6), (12, 24), (12, 25), (12, 26)],
+        easter: &[-2, 1, 39],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "FR",
+        fixed: &[(1, 1), (5, 1), (5, 8), (7, 14), (8, 15), (11, 1), (11, 11), (12, 25)],
+        easter: &[1, 39, 50],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "GR",
+        fixed: &[(1, 1), (1, 6), (3, 25), (5, 1), (8, 15), (10, 28), (12, 25), (12, 26)],
+        easter: &[-48, -2, 1, 50],
+        orthodox: true,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "HR",
+        fixed: &[(1, 1), (1, 6), (5, 1), (5, 30), (6, 22), (8, 5), (8, 15), (11, 1), (11, 18), (12, 25), (12, 26)],
+        easter: &[1, 60],
+        orthodox: false,
+        quiet_sunday: true,
+    },
+    Calendar {
+        country: "HU",
+        fixed: &[(1, 1), (3, 15), (5, 1), (8, 20), (10, 23), (11, 1), (12, 25), (12, 26)],
+        easter: &[-2, 1, 50],
+        orthodox: false,
+        quiet_sunday: true,
+    },
+    Calendar {
+        country: "IE",
+        fixed: &[(1, 1), (3, 17), (12, 25), (12, 26)],
+        easter: &[1],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "IT",
+        fixed: &[(1, 1), (1, 6), (4, 25), (5, 1), (6, 2), (8, 15), (11, 1), (12, 8), (12, 25), (12, 26)],
+        easter: &[1],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "LT",
+        fixed: &[(1, 1), (2, 16), (3, 11), (5, 1), (6, 24), (7, 6), (8, 15), (11, 1), (11, 2), (12, 24), (12, 25), (12, 26)],
+        easter: &[1],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "LU",
+        fixed: &[(1, 1), (5, 1), (5, 9), (6, 23), (8, 15), (11, 1), (12, 25), (12, 26)],
+        easter: &[1, 39, 50],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "LV",
+        fixed: &[(1, 1), (5, 1), (5, 4), (6, 23), (6, 24), (11, 18), (12, 24), (12, 25), (12, 26), (12, 31)],
+        easter: &[-2, 1],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "MT",
+        fixed: &[(1, 1), (2, 10), (3, 19), (3, 31), (5, 1), (6, 7), (6, 29), (8, 15), (9, 8), (9, 21), (12, 8), (12, 13), (12, 25)],
+        easter: &[-2],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "NL",
+        fixed: &[(1, 1), (4, 27), (5, 5), (12, 25), (12, 26)],
+        easter: &[1, 39, 50],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "PL",
+        fixed: &[(1, 1), (1, 6), (5, 1), (5, 3), (8, 15), (11, 1), (11, 11), (12, 25), (12, 26)],
+        easter: &[1, 60],
+        orthodox: false,
+        quiet_sunday: true,
+    },
+    Calendar {
+        country: "PT",
+        fixed: &[(1, 1), (4, 25), (5, 1), (6, 10), (8, 15), (10, 5), (11, 1), (12, 1), (12, 8), (12, 25)],
+        easter: &[-2, 60],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "RO",
+        fixed: &[(1, 1), (1, 2), (1, 24), (5, 1), (6, 1), (8, 15), (11, 30), (12, 1), (12, 25), (12, 26)],
+        easter: &[-2, 1, 50],
+        orthodox: true,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "SE",
+        fixed: &[(1, 1), (1, 6), (5, 1), (6, 6), (12, 24), (12, 25), (12, 26), (12, 31)],
+        easter: &[-2, 1, 39],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "SI",
+        fixed: &[(1, 1), (1, 2), (2, 8), (4, 27), (5, 1), (5, 2), (6, 25), (8, 15), (10, 31), (11, 1), (12, 25), (12, 26)],
+        easter: &[1],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "SK",
+        fixed: &[(1, 1), (1, 6), (5, 1), (5, 8), (7, 5), (8, 29), (9, 15), (11, 1), (11, 17), (12, 24), (12, 25), (12, 26)],
+        easter: &[-2, 1],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "GB",
+        fixed: &[(1, 1), (12, 25), (12, 26)],
+        easter: &[-2, 1],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "NO",
+        fixed: &[(1, 1), (5, 1), (5, 17), (12, 25), (12, 26)],
+        easter: &[-3, -2, 1, 39, 50],
+        orthodox: false,
+        quiet_sunday: true,
+    },
+    Calendar {
+        country: "CH",
+        fixed: &[(1, 1), (8, 1), (12, 25), (12, 26)],
+        easter: &[-2, 1, 39, 50],
+        orthodox: false,
+        quiet_sunday: true,
+    },
+    Calendar {
+        country: "IS",
+        fixed: &[(1, 1), (6, 17), (12, 24), (12, 25), (12, 26)],
+        easter: &[-3, -2, 1, 39, 50],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "LI",
+        fixed: &[(1, 1), (1, 6), (8, 15), (12, 25), (12, 26)],
+        easter: &[1, 39, 50],
+        orthodox: false,
+        quiet_sunday: true,
+    },
+    Calendar {
+        country: "US",
+        fixed: &[(1, 1), (7, 4), (11, 11), (12, 25)],
+        easter: &[],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+    Calendar {
+        country: "CA",
+        fixed: &[(1, 1), (7, 1), (12, 25), (12, 26)],
+        easter: &[-2],
+        orthodox: false,
+        quiet_sunday: false,
+    },
+];
+
+fn calendar_for(country: &str) -> Option<&'static Calendar> {
+    CALENDARS
+        .iter()
+        .find(|calendar| calendar.country.eq_ignore_ascii_case(country))
+}
+
+pub fn is_public_holiday(country: &str, date: NaiveDate) -> bool {
+    let Some(calendar) = calendar_for(country) else {
+        return false;
+    };
+    if calendar
+        .fixed
+        .iter()
+        .any(|(month, day)| date.month() == *month && date.day() == *day)
+    {
+        return true;
+    }
+    let easter = if calendar.orthodox {
+        orthodox_easter(date.year())
+    } else {
+        western_easter(date.year())
+    };
+    easter.is_some_and(|easter| {
+        calendar
+            .easter
+            .iter()
+            .any(|offset| easter + Duration::days(*offset) == date)
+    })
+}
+
+// Member States outside Central European Time; every other country uses CET.
+const WESTERN_EUROPEAN_TIME: &[&str] = &["IE", "PT"];
+const EASTERN_EUROPEAN_TIME: &[&str] = &["BG", "CY", "EE", "FI", "GR", "LT", "LV", "RO"];
+
+// The local UTC offset of `country` at `time`. Summer time runs from 01:00 UTC
+// on the last Sunday of March to 01:00 UTC on the last Sunday of October.
+pub fn utc_offset(country: &str, time: DateTime<Utc>) -> FixedOffset {
+    let standard = if WESTERN_EUROPEAN_TIME.contains(&country) {
+        0
+    } else if EASTERN_EUROPEAN_TIME.contains(&country) {
+        2
+    } else {
+        1
+    };
+    let year = time.year();
+    let summer = match (summer_time_switch(year, 3), summer_time_switch(year, 10)) {
+        (Some(start), Some(end)) => time >= start && time < end,
+        _ => false,
+    };
+    let hours = standard + i32::from(summer);
+    FixedOffset::east_opt(hours * 3600).unwrap_or_else(|| Utc.fix())
+}
+
+fn summer_time_switch(year: i32, month: u32) -> Option<DateTime<Utc>> {
+    let last_day = NaiveDate::from_ymd_opt(year, month, 31)?;
+    let back = last_day.weekday().num_days_from_sunday();
+    let sunday = last_day - Duration::days(back.into());
+    Some(Utc.from_utc_datetime(&sunday.and_hms_opt(1, 0, 0)?))
+}
+
+// Relative transaction volume (0..=1) for a payee country on a given day.
+pub fn activity_weight(country: &str, date: NaiveDate) -> f64 {
+    if is_public_holiday(country, date) {
+        return HOLIDAY_WEIGHT;
+    }
+    match date.weekday() {
+        Weekday::Sat => SATURDAY_WEIGHT,
+        Weekday::Sun => {
+            if calendar_for(country).is_some_and(|calendar| calendar.quiet_sunday) {
+                QUIET_SUNDAY_WEIGHT
+            } else {
+                SUNDAY_WEIGHT
+            }
+        }
+        _ => 1.0,
+    }
+}
+
+// Anonymous Gregorian computus.
+fn western_easter(year: i32) -> Option<NaiveDate> {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32)
+}
+
+// Meeus Julian computus shifted to the Gregorian calendar (valid 1900-2099).
+fn orthodox_easter(year: i32) -> Option<NaiveDate> {
+    let a = year % 4;
+    let b = year % 7;
+    let c = year % 19;
+    let d = (19 * c + 15) % 30;
+    let e = (2 * a + 4 * b - d + 34) % 7;
+    let month = (d + e + 114) / 31;
+    let day = (d + e + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32).map(|date| date + Duration::days(13))
+}
@@ -1,15 +1,37 @@
-use crate::analysis::{reportable_payee_keys, PayeeKey};
-use crate::location::{bic_country_code, resolve_payee_country};
+use crate::analysis::{payee_threshold_counts, reportable_payee_keys, PayeeKey, ThresholdCounter};
+use crate::checkpoint::RenderProgress;
+use crate::codes;
+use crate::input::{for_each_payment_row, read_payment_records, CsvReadOptions};
+use crate::location::{
+    account_country_code, bic_country_code, derive_payer_ms, normalize_country_code,
+    resolve_payee_country,
+};
+use crate::manifest::{ManifestFile, PspManifest, RenderManifest, RenderManifestFile};
+use crate::merge;
 use crate::models::PaymentRecord;
-use crate::reference::is_eu_member_state;
-
-use chrono::{Datelike, SecondsFormat, Utc};
+use crate::payment_methods::{map_payment_method, PaymentMethodMode};
+use crate::refids::{registry_path, MessageEntry, PayeeEntry, RefIdRegistry};
+use crate::reference::{banking_country, is_eu_member_state, IBAN_LENGTHS};
+use crate::run_summary::{run_id, PhaseTimings};
+use crate::schema::SchemaVersion;
+use crate::trace::RecordTracer;
+use crate::util::{sha256_file, sha256_hex};
+use crate::Error;
+
+use chrono::{DateTime, Datelike, SecondsFormat, Utc};
 use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::Writer;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
 use std::fs::File;
 use std::io::BufWriter;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
 struct PeriodKey {
@@ -22,10 +44,308 @@ struct ReportKey {
     period: PeriodKey,
     psp_id: String,
     psp_name: String,
+    // Every name the PSP's records carry in the period, when there is more
+    // than one and `--psp-name-policy` chose `psp_name`.
+    psp_names_seen: Vec<String>,
+}
+
+// Which name a reporting PSP is reported under when its records carry more
+// than one: fail, or per period the first name in the input, the name on the
+// latest payment or the longest name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PspNamePolicy {
+    #[default]
+    Error,
+    First,
+    Latest,
+    Longest,
+}
+
+// PSP names seen while grouping records, resolved per period and reporting
+// PSP once every record is read.
+struct PspNames {
+    policy: PspNamePolicy,
+    first: HashMap<String, String>,
+    seen: BTreeMap<(PeriodKey, String), SeenNames>,
+}
+
+#[derive(Default)]
+struct SeenNames {
+    // Distinct names in input order.
+    names: Vec<String>,
+    latest: Option<(i64, String)>,
+}
+
+impl PspNames {
+    fn new(policy: PspNamePolicy) -> Self {
+        Self {
+            policy,
+            first: HashMap::new(),
+            seen: BTreeMap::new(),
+        }
+    }
+
+    // The grouping key of `record`. Under a resolving policy the name is
+    // left empty until `resolve`.
+    fn observe(&mut self, period: PeriodKey, record: &PaymentRecord) -> Result<ReportKey, Error> {
+        let mut key = ReportKey {
+            period,
+            psp_id: record.psp_id.clone(),
+            psp_name: String::new(),
+            psp_names_seen: Vec::new(),
+        };
+        if self.policy == PspNamePolicy::Error {
+            if let Some(existing) = self.first.get(&record.psp_id) {
+                if existing != &record.psp_name {
+                    return Err(codes::RND_MULTIPLE_PSP_NAMES.fail(format!(
+                        "multiple PSP names found for {}: '{}' vs '{}'",
+                        record.psp_id, existing, record.psp_name
+                    )));
+                }
+            } else {
+                self.first
+                    .insert(record.psp_id.clone(), record.psp_name.clone());
+            }
+            key.psp_name = record.psp_name.clone();
+            return Ok(key);
+        }
+
+        let seen = self
+            .seen
+            .entry((period, record.psp_id.clone()))
+            .or_default();
+        if !seen.names.contains(&record.psp_name) {
+            seen.names.push(record.psp_name.clone());
+        }
+        // Later rows win ties, as the most recent extract usually comes last.
+        let time = chrono::DateTime::parse_from_rfc3339(record.execution_time.trim())
+            .map_or(i64::MIN, |time| time.timestamp_millis());
+        if seen.latest.as_ref().is_none_or(|(latest, _)| time >= *latest) {
+            seen.latest = Some((time, record.psp_name.clone()));
+        }
+        Ok(key)
+    }
+
+    // Rekeys `groups` with the chosen names, warning about every PSP and
+    // period whose records carried more than one.
+    fn resolve<V>(&self, groups: BTreeMap<ReportKey, V>) -> BTreeMap<ReportKey, V> {
+        if self.policy == PspNamePolicy::Error {
+            return groups;
+        }
+        groups
+            .into_iter()
+            .map(|(mut key, value)| {
+                if let Some(seen) = self.seen.get(&(key.period, key.psp_id.clone())) {
+                    key.psp_name = match self.policy {
+                        PspNamePolicy::Latest => seen
+                            .latest
+                            .as_ref()
+                            .map(|(_, name)| name.clone())
+                            .unwrap_or_default(),
+                        PspNamePolicy::Longest => seen
+                            .names
+                            .iter()
+                            .rev()
+                            .max_by_key(|name| name.chars().count())
+                            .cloned()
+                            .unwrap_or_default(),
+                        PspNamePolicy::First | PspNamePolicy::Error => {
+                            seen.names.first().cloned().unwrap_or_default()
+                        }
+                    };
+                    if seen.names.len() > 1 {
+                        log::warn!(
+                            "{}",
+                            codes::RND_PSP_NAME_RESOLVED.err(format!(
+                                "{} in {} Q{}: {}; reported as '{}'",
+                                key.psp_id,
+                                key.period.year,
+                                key.period.quarter,
+                                seen.names
+                                    .iter()
+                                    .map(|name| format!("'{}'", name))
+                                    .collect::<Vec<_>>()
+                                    .join(", "),
+                                key.psp_name
+                            ))
+                        );
+                        key.psp_names_seen = seen.names.clone();
+                    }
+                }
+                (key, value)
+            })
+            .collect()
+    }
 }
 
 const REPORTING_THRESHOLD: usize = 25;
 const TRANSMITTING_COUNTRY_AUTO: &str = "auto";
+const SPILL_DIR: &str = ".render-spill";
+const MAX_OPEN_SPILL_FILES: usize = 128;
+// Records of one period and PSP that `render --streaming` sorts in memory at
+// a time.
+const SORT_CHUNK_RECORDS: usize = 20_000;
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub transmitting_country: String,
+    pub licensed_countries: Option<Vec<String>>,
+    pub address_format: AddressFormat,
+    pub ref_seed: Option<String>,
+    // MessageSpec Timestamp of every message instead of the time of writing.
+    pub timestamp: Option<DateTime<Utc>>,
+    pub xml_style: XmlStyle,
+    pub payment_methods: PaymentMethodMode,
+    pub schema: SchemaVersion,
+    pub psp_name_policy: PspNamePolicy,
+    pub payer_ms: PayerMsMode,
+    pub payee_accounts: PayeeAccountMode,
+    pub payee_conflicts: PayeeConflictPolicy,
+    pub csv: CsvReadOptions,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            transmitting_country: TRANSMITTING_COUNTRY_AUTO.to_string(),
+            licensed_countries: None,
+            address_format: AddressFormat::default(),
+            ref_seed: None,
+            timestamp: None,
+            xml_style: XmlStyle::default(),
+            payment_methods: PaymentMethodMode::default(),
+            schema: SchemaVersion::default(),
+            psp_name_policy: PspNamePolicy::default(),
+            payer_ms: PayerMsMode::default(),
+            payee_accounts: PayeeAccountMode::default(),
+            payee_conflicts: PayeeConflictPolicy::default(),
+            csv: CsvReadOptions::default(),
+        }
+    }
+}
+
+// How payee addresses are written: one `AddressFree` string, structured
+// `AddressFix` parts, both, or `AddressFix` whenever the payee has a city or
+// postcode (the format the User Guide prefers) and `AddressFree` otherwise.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum AddressFormat {
+    #[default]
+    Free,
+    Fix,
+    Both,
+    Auto,
+}
+
+// Where PayerMS and PayerMSSource come from: the payer's IBAN or BIC when
+// the record has one (falling back to the columns), or always the
+// `payer_country` and `payer_ms_source` columns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PayerMsMode {
+    #[default]
+    Account,
+    Column,
+}
+
+// Which of a payee's account identifiers are reported: the first IBAN (or
+// else OBAN, or else other identifier) with at most one BIC, or every
+// distinct identifier its reported payments used in the period.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PayeeAccountMode {
+    #[default]
+    First,
+    All,
+}
+
+// Which value a payee attribute (name, tax ids, contact, address) takes when
+// the payee's rows disagree: the first row's, the most common one or the one
+// on the latest payment. Empty values never win over given ones, except
+// under `first`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PayeeConflictPolicy {
+    #[default]
+    First,
+    Majority,
+    Latest,
+}
+
+// Whitespace between elements: none at all, or a line per element indented
+// by N spaces per level (`indent=0` keeps the line breaks only).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XmlStyle {
+    Compact,
+    Indent(usize),
+}
+
+impl Default for XmlStyle {
+    fn default() -> Self {
+        XmlStyle::Indent(2)
+    }
+}
+
+impl XmlStyle {
+    pub fn writer<W: std::io::Write>(self, inner: W) -> Writer<W> {
+        match self {
+            XmlStyle::Compact => Writer::new(inner),
+            XmlStyle::Indent(width) => Writer::new_with_indent(inner, b' ', width),
+        }
+    }
+}
+
+impl std::str::FromStr for XmlStyle {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().to_ascii_lowercase();
+        if value == "compact" {
+            return Ok(XmlStyle::Compact);
+        }
+        value
+            .strip_prefix("indent=")
+            .and_then(|width| width.parse::<usize>().ok())
+            .filter(|width| *width <= 16)
+            .map(XmlStyle::Indent)
+            .ok_or_else(|| {
+                format!(
+                    "invalid XML style {:?}: expected compact or indent=N (0 to 16)",
+                    value
+                )
+            })
+    }
+}
+
+impl std::fmt::Display for XmlStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XmlStyle::Compact => write!(f, "compact"),
+            XmlStyle::Indent(width) => write!(f, "indent={}", width),
+        }
+    }
+}
+
+// Limits above which write_reports splits a report into numbered messages.
+#[derive(Debug, Clone, Copy)]
+pub struct MessageLimits {
+    pub max_bytes: Option<u64>,
+    pub max_payees: Option<usize>,
+}
+
+impl Default for MessageLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: Some(merge::DEFAULT_MAX_SIZE_MB * 1024 * 1024),
+            max_payees: None,
+        }
+    }
+}
+
+// Where write_reports puts the XML files: all in the output directory, or one
+// subdirectory per reporting PSP with its own manifest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputLayout {
+    #[default]
+    Flat,
+    PerPsp,
+}
 
 #[derive(Debug, Clone)]
 pub struct CesopReport {
@@ -35,6 +355,44 @@ pub struct CesopReport {
     pub reporting_psp_name: String,
     pub payees: Vec<PayeeGroup>,
     pub message_type_indic: String,
+    pub address_format: AddressFormat,
+    // With a seed, MessageRefId and DocRefId are derived from it and the
+    // message contents instead of drawn at random.
+    pub ref_seed: Option<String>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub xml_style: XmlStyle,
+    pub schema: SchemaVersion,
+    // Names the reporting PSP's records carried when there was more than one.
+    pub psp_names_seen: Vec<String>,
+}
+
+impl CesopReport {
+    pub fn period(&self) -> (i32, u8) {
+        (self.period.year, self.period.quarter)
+    }
+
+    // The same message header with other payees, e.g. those first reported
+    // after a correction.
+    pub fn with_payees(&self, payees: Vec<PayeeGroup>) -> CesopReport {
+        CesopReport {
+            period: self.period,
+            transmitting_country: self.transmitting_country.clone(),
+            reporting_psp_id: self.reporting_psp_id.clone(),
+            reporting_psp_name: self.reporting_psp_name.clone(),
+            address_format: self.address_format,
+            ref_seed: self.ref_seed.clone(),
+            timestamp: self.timestamp,
+            xml_style: self.xml_style,
+            schema: self.schema,
+            psp_names_seen: self.psp_names_seen.clone(),
+            message_type_indic: if payees.is_empty() {
+                "CESOP102".to_string()
+            } else {
+                "CESOP100".to_string()
+            },
+            payees,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -43,10 +401,33 @@ pub struct PayeeAccount {
     pub account_type: String,
 }
 
+impl PayeeAccount {
+    fn new(id: &str, account_type: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            account_type: account_type.to_string(),
+        }
+    }
+
+    // Country the identifier encodes: an IBAN's prefix or a BIC's country
+    // code. Reported as its CountryCode instead of the payee country.
+    pub fn country(&self) -> Option<String> {
+        match self.account_type.as_str() {
+            "IBAN" => account_country_code("IBAN", &self.id)
+                .filter(|country| IBAN_LENGTHS.iter().any(|(code, _)| code == country)),
+            "BIC" => bic_country_code(&self.id),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Representative {
     pub id: String,
     pub name: Option<String>,
+    // From `payee_psp_country`, else the country of the BIC.
+    pub country: Option<String>,
+    pub address: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -66,139 +447,1904 @@ pub struct PayeeGroup {
     pub transactions: Vec<PaymentRecord>,
 }
 
-pub fn build_reports_from_csv(
-    input: &Path,
-    transmitting_country: &str,
-    licensed_countries: Option<&[String]>,
-) -> Result<Vec<CesopReport>, String> {
-    let records = read_csv(input)?;
-    if records.is_empty() {
-        return Err("no records found in input CSV".to_string());
-    }
-    let mut psp_names: HashMap<String, String> = HashMap::new();
-    let mut period_map: BTreeMap<ReportKey, Vec<PaymentRecord>> = BTreeMap::new();
+type Attribute = fn(&PaymentRecord) -> Option<&String>;
+
+// The payee attributes taken from its rows, by CSV column.
+const PAYEE_ATTRIBUTES: &[(&str, Attribute)] = &[
+    ("payee_name", |tx| Some(&tx.payee_name)),
+    ("payee_tax_id", |tx| tx.payee_tax_id.as_ref()),
+    ("payee_vat_id", |tx| tx.payee_vat_id.as_ref()),
+    ("payee_email", |tx| tx.payee_email.as_ref()),
+    ("payee_web", |tx| tx.payee_web.as_ref()),
+    ("payee_address_line", |tx| tx.payee_address_line.as_ref()),
+    ("payee_city", |tx| tx.payee_city.as_ref()),
+    ("payee_postcode", |tx| tx.payee_postcode.as_ref()),
+];
+
+fn given(value: Option<&String>) -> Option<&String> {
+    value.filter(|value| !value.trim().is_empty())
+}
+
+// Resolves the attributes of one payee from its rows.
+struct PayeeAttributes<'a> {
+    transactions: &'a [PaymentRecord],
+    policy: PayeeConflictPolicy,
+    // Execution times in milliseconds, for `latest`.
+    times: Vec<i64>,
+}
+
+impl<'a> PayeeAttributes<'a> {
+    fn new(transactions: &'a [PaymentRecord], policy: PayeeConflictPolicy) -> Self {
+        let times = if policy == PayeeConflictPolicy::Latest {
+            transactions
+                .iter()
+                .map(|tx| {
+                    chrono::DateTime::parse_from_rfc3339(tx.execution_time.trim())
+                        .map_or(i64::MIN, |time| time.timestamp_millis())
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Self {
+            transactions,
+            policy,
+            times,
+        }
+    }
+
+    fn resolve(&self, get: impl Fn(&'a PaymentRecord) -> Option<&'a String>) -> Option<String> {
+        let first = self.transactions.first().and_then(&get);
+        let chosen = match self.policy {
+            PayeeConflictPolicy::First => first,
+            PayeeConflictPolicy::Majority => {
+                let mut counts: Vec<(&String, usize)> = Vec::new();
+                for value in self.transactions.iter().filter_map(|tx| given(get(tx))) {
+                    match counts.iter_mut().find(|(seen, _)| *seen == value) {
+                        Some((_, count)) => *count += 1,
+                        None => counts.push((value, 1)),
+                    }
+                }
+                // Ties go to the value seen first.
+                counts
+                    .iter()
+                    .rev()
+                    .max_by_key(|(_, count)| *count)
+                    .map(|(value, _)| *value)
+                    .or(first)
+            }
+            // Later rows win ties, as for PSP names.
+            PayeeConflictPolicy::Latest => self
+                .transactions
+                .iter()
+                .zip(&self.times)
+                .filter_map(|(tx, time)| given(get(tx)).map(|value| (*time, value)))
+                .max_by_key(|(time, _)| *time)
+                .map(|(_, value)| value)
+                .or(first),
+        };
+        chosen.cloned()
+    }
+}
+
+impl PayeeGroup {
+    // Columns whose given values differ between the payee's rows.
+    pub fn attribute_conflicts(&self) -> Vec<&'static str> {
+        PAYEE_ATTRIBUTES
+            .iter()
+            .filter(|(_, get)| {
+                let mut values = self.transactions.iter().filter_map(|tx| given(get(tx)));
+                values
+                    .next()
+                    .is_some_and(|first| values.any(|value| value.trim() != first.trim()))
+            })
+            .map(|(column, _)| *column)
+            .collect()
+    }
+
+    // An account whose identifier encodes another country than the
+    // `payee_country` given in the payee's rows, with that country. The VM
+    // flags such inconsistent country codes.
+    pub fn account_country_mismatch(&self) -> Option<(&PayeeAccount, String)> {
+        let provided: BTreeSet<String> = self
+            .transactions
+            .iter()
+            .filter_map(|tx| normalize_country_code(&tx.payee_country))
+            .map(|country| banking_country(&country).to_string())
+            .collect();
+        self.payee_accounts.iter().find_map(|account| {
+            let country = account.country()?;
+            provided
+                .iter()
+                .find(|provided| **provided != country)
+                .map(|provided| (account, provided.clone()))
+        })
+    }
+}
+
+pub fn build_reports_from_csv(
+    input: &Path,
+    options: &RenderOptions,
+    tracer: &mut RecordTracer,
+    timings: &mut PhaseTimings,
+) -> Result<Vec<CesopReport>, Error> {
+    let records = timings.time("csv_read", || read_payment_records(input, &options.csv))?;
+    build_reports(records, options, tracer, timings)
+}
+
+// Groups records into one report per period and reporting PSP; `options.csv`
+// is not used.
+pub fn build_reports(
+    records: Vec<PaymentRecord>,
+    options: &RenderOptions,
+    tracer: &mut RecordTracer,
+    timings: &mut PhaseTimings,
+) -> Result<Vec<CesopReport>, Error> {
+    if records.is_empty() {
+        return Err(codes::RND_NO_RECORDS.fail_summary());
+    }
+    let grouping_start = Instant::now();
+    let mut psp_names = PspNames::new(options.psp_name_policy);
+    let mut period_map: BTreeMap<ReportKey, Vec<PaymentRecord>> = BTreeMap::new();
+
+    for record in records.into_iter() {
+        let period = period_from_timestamp(&record.execution_time)?;
+        let key = psp_names.observe(period, &record)?;
+        if tracer.is_recording() {
+            tracer.include(
+                &record,
+                "period",
+                &format!(
+                    "grouped into {} Q{} for PSP {}",
+                    period.year, period.quarter, record.psp_id
+                ),
+            );
+        }
+        period_map.entry(key).or_default().push(record);
+    }
+    let period_map = psp_names.resolve(period_map);
+
+    timings.add("grouping", grouping_start.elapsed());
+
+    let mut reports = Vec::new();
+    for (key, period_records) in period_map {
+        reports.extend(build_period_reports(
+            key,
+            period_records,
+            options,
+            tracer,
+            timings,
+        )?);
+    }
+
+    Ok(reports)
+}
+
+// The reports of one period and reporting PSP: one, or one per licensed
+// country.
+fn build_period_reports(
+    key: ReportKey,
+    mut period_records: Vec<PaymentRecord>,
+    options: &RenderOptions,
+    tracer: &mut RecordTracer,
+    timings: &mut PhaseTimings,
+) -> Result<Vec<CesopReport>, Error> {
+    let transmitting_country = options.transmitting_country.as_str();
+    let licensed_countries = options.licensed_countries.as_deref();
+    let mut reports = Vec::new();
+    // Before the threshold, which only counts payments from Member States.
+    if options.payer_ms == PayerMsMode::Account {
+        for record in &mut period_records {
+            apply_account_payer_ms(record, tracer);
+        }
+    }
+    let threshold_start = Instant::now();
+    let reportable_payees =
+        reportable_payee_keys(&period_records, REPORTING_THRESHOLD, false)?;
+    let threshold_counts = if tracer.is_recording() {
+        payee_threshold_counts(&period_records, false)?
+    } else {
+        HashMap::new()
+    };
+    timings.add("threshold", threshold_start.elapsed());
+    let grouping_start = Instant::now();
+    let reportable_records: Vec<PaymentRecord> = period_records
+        .into_iter()
+        .filter(|record| reported_by_psp(record, tracer))
+        .map(|mut record| {
+            map_payment_method(&mut record, options.payment_methods)?;
+            Ok(record)
+        })
+        .collect::<Result<_, Error>>()?;
+    let payees = group_payees(
+        reportable_records,
+        &reportable_payees,
+        &threshold_counts,
+        options,
+        tracer,
+    )?;
+    timings.add("grouping", grouping_start.elapsed());
+    if let Some(licensed) = licensed_countries {
+        if !licensed.is_empty() {
+            let assignments =
+                split_payees_by_license(payees, licensed, &key.psp_id, tracer)?;
+            for (country, assigned) in assignments {
+                let message_type_indic = if assigned.is_empty() {
+                    "CESOP102".to_string()
+                } else {
+                    "CESOP100".to_string()
+                };
+                let tx_country = resolve_transmitting_country(&country, &key.psp_id)?;
+
+                reports.push(CesopReport {
+                    period: key.period,
+                    transmitting_country: tx_country,
+                    reporting_psp_id: key.psp_id.clone(),
+                    reporting_psp_name: key.psp_name.clone(),
+                    payees: assigned,
+                    message_type_indic,
+                    address_format: options.address_format,
+                    ref_seed: options.ref_seed.clone(),
+                    timestamp: options.timestamp,
+                    xml_style: options.xml_style,
+                    schema: options.schema,
+                    psp_names_seen: key.psp_names_seen.clone(),
+                });
+            }
+            return Ok(reports);
+        }
+    }
+
+    let message_type_indic = if payees.is_empty() {
+        "CESOP102".to_string()
+    } else {
+        "CESOP100".to_string()
+    };
+    let tx_country = resolve_transmitting_country(transmitting_country, &key.psp_id)?;
+    if tracer.is_recording() {
+        let reason = format!("reported to {}", tx_country);
+        for tx in payees.iter().flat_map(|payee| &payee.transactions) {
+            tracer.include(tx, "transmitting_country", &reason);
+        }
+    }
+
+    reports.push(CesopReport {
+        period: key.period,
+        transmitting_country: tx_country,
+        reporting_psp_id: key.psp_id,
+        reporting_psp_name: key.psp_name,
+        payees,
+        message_type_indic,
+        address_format: options.address_format,
+        ref_seed: options.ref_seed.clone(),
+        timestamp: options.timestamp,
+        xml_style: options.xml_style,
+        schema: options.schema,
+        psp_names_seen: key.psp_names_seen,
+    });
+
+    Ok(reports)
+}
+
+// Standalone CESOP102 nil reports for a reporting PSP that has no payments to
+// report, one per period and transmitting country (`auto` derives it from
+// the PSP BIC), without reading any records.
+pub fn build_nil_reports(
+    psp_id: &str,
+    psp_name: &str,
+    periods: &[(i32, u8)],
+    countries: &[String],
+    options: &RenderOptions,
+) -> Result<Vec<CesopReport>, Error> {
+    if psp_id.trim().is_empty() || psp_name.trim().is_empty() {
+        return Err(codes::RND_NIL_REPORT_PSP.fail_summary());
+    }
+    let mut reports = Vec::new();
+    for &(year, quarter) in periods {
+        for country in countries {
+            reports.push(CesopReport {
+                period: PeriodKey { year, quarter },
+                transmitting_country: resolve_transmitting_country(country, psp_id)?,
+                reporting_psp_id: psp_id.trim().to_string(),
+                reporting_psp_name: psp_name.trim().to_string(),
+                payees: Vec::new(),
+                message_type_indic: "CESOP102".to_string(),
+                address_format: options.address_format,
+                ref_seed: options.ref_seed.clone(),
+                timestamp: options.timestamp,
+                xml_style: options.xml_style,
+                schema: options.schema,
+                psp_names_seen: Vec::new(),
+            });
+        }
+    }
+    Ok(reports)
+}
+
+// Pass one of `render --streaming`: the records of `input` bucketed by period
+// and reporting PSP into spill files under `output_dir`, with the same checks
+// as `build_reports`. Only the PSP names are kept in memory.
+pub fn spill_reports_from_csv(
+    input: &Path,
+    options: &RenderOptions,
+    output_dir: &Path,
+    tracer: &mut RecordTracer,
+    timings: &mut PhaseTimings,
+) -> Result<SpilledReports, Error> {
+    if options.csv.infer_refund_links {
+        return Err(codes::RND_STREAMING_UNSUPPORTED.fail(
+            "refund link inference needs every record in memory; \
+             it cannot be combined with streaming",
+        ));
+    }
+    let dir = output_dir.join(SPILL_DIR);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).map_err(|err| err.to_string())?;
+    }
+    std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    let mut spilled = SpilledReports {
+        dir,
+        buckets: BTreeMap::new(),
+        chunk_records: SORT_CHUNK_RECORDS,
+    };
+
+    let start = Instant::now();
+    let mut psp_names = PspNames::new(options.psp_name_policy);
+    let mut open: BTreeMap<ReportKey, csv::Writer<BufWriter<File>>> = BTreeMap::new();
+    let mut records = 0usize;
+    for_each_payment_row::<Error>(input, &options.csv, |_, record| {
+        records += 1;
+        let period = period_from_timestamp(&record.execution_time)?;
+        let key = psp_names.observe(period, &record)?;
+        if tracer.is_recording() {
+            tracer.include(
+                &record,
+                "period",
+                &format!(
+                    "grouped into {} Q{} for PSP {}",
+                    period.year, period.quarter, record.psp_id
+                ),
+            );
+        }
+        if !open.contains_key(&key) {
+            // Spill files are closed in bulk and reopened for appending, so
+            // inputs with many PSPs and periods stay within the open file
+            // limit.
+            if open.len() >= MAX_OPEN_SPILL_FILES {
+                for (_, mut writer) in std::mem::take(&mut open) {
+                    writer.flush().map_err(|err| err.to_string())?;
+                }
+            }
+            let next = spilled.buckets.len();
+            let path = spilled
+                .buckets
+                .entry(key.clone())
+                .or_insert_with(|| spilled.dir.join(format!("{}.csv", next)));
+            let append = path.exists();
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&*path)
+                .map_err(|err| format!("failed to open {}: {}", path.display(), err))?;
+            let writer = csv::WriterBuilder::new()
+                .has_headers(!append)
+                .from_writer(BufWriter::new(file));
+            open.insert(key.clone(), writer);
+        }
+        if let Some(writer) = open.get_mut(&key) {
+            writer.serialize(&record).map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    })?;
+    for (_, mut writer) in std::mem::take(&mut open) {
+        writer.flush().map_err(|err| err.to_string())?;
+    }
+    timings.add("csv_read", start.elapsed());
+    if records == 0 {
+        return Err(codes::RND_NO_RECORDS.fail_summary());
+    }
+    spilled.buckets = psp_names.resolve(std::mem::take(&mut spilled.buckets));
+    Ok(spilled)
+}
+
+// Records spilled by `spill_reports_from_csv`, one file per period and
+// reporting PSP. The spill directory is removed on drop.
+pub struct SpilledReports {
+    dir: PathBuf,
+    buckets: BTreeMap<ReportKey, PathBuf>,
+    // Records sorted in memory at a time.
+    chunk_records: usize,
+}
+
+// Payees and transactions written by `SpilledReports::write`, or in
+// `reports` (see `render_totals`).
+#[derive(Debug, Clone, Default)]
+pub struct RenderTotals {
+    pub payees: usize,
+    pub transactions: usize,
+    // Periods and reporting PSPs whose name was chosen by the name policy.
+    pub psp_name_conflicts: usize,
+    // Payees with an account identifier from another country than their
+    // `payee_country` column.
+    pub account_country_mismatches: usize,
+    // Payees whose rows disagree on an attribute, by column.
+    pub payee_conflicts: BTreeMap<&'static str, usize>,
+}
+
+impl RenderTotals {
+    fn add_reports(&mut self, reports: &[CesopReport]) {
+        for payee in reports.iter().flat_map(|report| &report.payees) {
+            self.add_payee(payee);
+        }
+    }
+
+    fn add_payee(&mut self, payee: &PayeeGroup) {
+        self.payees += 1;
+        self.transactions += payee.transactions.len();
+        if payee.account_country_mismatch().is_some() {
+            self.account_country_mismatches += 1;
+        }
+        for column in payee.attribute_conflicts() {
+            *self.payee_conflicts.entry(column).or_default() += 1;
+        }
+    }
+}
+
+pub fn render_totals(reports: &[CesopReport]) -> RenderTotals {
+    let mut totals = RenderTotals::default();
+    totals.add_reports(reports);
+    totals.psp_name_conflicts = reports
+        .iter()
+        .filter(|report| !report.psp_names_seen.is_empty())
+        .map(|report| (report.reporting_psp_id.as_str(), report.period))
+        .collect::<BTreeSet<_>>()
+        .len();
+    totals
+}
+
+impl SpilledReports {
+    // Number of periods and reporting PSPs, i.e. of reports unless they are
+    // split by licensed country.
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    // Pass two: the records of one period and PSP at a time are sorted by
+    // payee in chunks of `chunk_records`, then their reports are built one
+    // payee at a time and written in the order `build_reports` returns them.
+    // A report is read back once to plan its parts and once per written
+    // message, so no more than a chunk of records, or one payee's, is held
+    // at once. Jobs only help with licensed countries, which give several
+    // reports per period and PSP.
+    pub fn write(
+        &self,
+        options: &RenderOptions,
+        writer: &mut ReportWriter,
+        tracer: &mut RecordTracer,
+        timings: &mut PhaseTimings,
+    ) -> Result<RenderTotals, Error> {
+        let mut totals = RenderTotals::default();
+        for (key, path) in &self.buckets {
+            if !key.psp_names_seen.is_empty() {
+                totals.psp_name_conflicts += 1;
+            }
+            let grouping_start = Instant::now();
+            let bucket = self.sort_bucket(path, options, tracer)?;
+            let reports = bucket.plan_reports(key, options, &writer.limits, tracer, &mut totals)?;
+            timings.add("grouping", grouping_start.elapsed());
+            let write_start = Instant::now();
+            writer.write_each(&reports)?;
+            timings.add("xml_write", write_start.elapsed());
+            for run in bucket.runs.iter().chain([path]) {
+                std::fs::remove_file(run).map_err(|err| err.to_string())?;
+            }
+        }
+        Ok(totals)
+    }
+
+    // Sorts the records of the period and PSP spilled to `path` by payee, into
+    // runs of at most `chunk_records` records, with the checks and exclusions
+    // of `build_period_reports`.
+    fn sort_bucket(
+        &self,
+        path: &Path,
+        options: &RenderOptions,
+        tracer: &mut RecordTracer,
+    ) -> Result<SortedBucket, Error> {
+        let read_error = |err: csv::Error| format!("failed to read {}: {}", path.display(), err);
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let run_path = |run: usize| self.dir.join(format!("{}.run{}.csv", stem, run));
+        let mut reader = csv::Reader::from_path(path).map_err(read_error)?;
+        let mut counter = ThresholdCounter::new(false);
+        let mut runs = Vec::new();
+        let mut chunk = Vec::new();
+        for record in reader.deserialize::<PaymentRecord>() {
+            let mut record = record.map_err(read_error)?;
+            // Before the threshold, which only counts payments from Member
+            // States.
+            if options.payer_ms == PayerMsMode::Account {
+                apply_account_payer_ms(&mut record, tracer);
+            }
+            counter.add(&record)?;
+            if !reported_by_psp(&record, tracer) {
+                continue;
+            }
+            map_payment_method(&mut record, options.payment_methods)?;
+            if let Some(payee_key) = cross_border_payee(&record, tracer)? {
+                chunk.push((payee_key, record));
+            }
+            if chunk.len() >= self.chunk_records {
+                runs.push(write_run(&mut chunk, &run_path(runs.len()))?);
+            }
+        }
+        if !chunk.is_empty() || runs.is_empty() {
+            runs.push(write_run(&mut chunk, &run_path(runs.len()))?);
+        }
+        // Runs are merged in batches while there are too many to open at once.
+        while runs.len() > MAX_OPEN_SPILL_FILES {
+            let mut merged = Vec::new();
+            for batch in runs.chunks(MAX_OPEN_SPILL_FILES) {
+                let path = run_path(runs.len() + merged.len());
+                let mut merge = RunMerge::open(batch)?;
+                let mut writer = csv::Writer::from_path(&path).map_err(|err| err.to_string())?;
+                while let Some((_, record)) = merge.next_record()? {
+                    writer.serialize(&record).map_err(|err| err.to_string())?;
+                }
+                writer.flush().map_err(|err| err.to_string())?;
+                merged.push(path);
+            }
+            for run in &runs {
+                std::fs::remove_file(run).map_err(|err| err.to_string())?;
+            }
+            runs = merged;
+        }
+        let (reportable, counts) = counter.finish(REPORTING_THRESHOLD);
+        Ok(SortedBucket {
+            runs,
+            reportable,
+            counts,
+        })
+    }
+}
+
+// Sorts `chunk` by payee, keeping the input order of each payee's records,
+// and writes it to `path`.
+fn write_run(chunk: &mut Vec<(PayeeKey, PaymentRecord)>, path: &Path) -> Result<PathBuf, Error> {
+    chunk.sort_by(|left, right| left.0.cmp(&right.0));
+    let mut writer = csv::Writer::from_path(path).map_err(|err| err.to_string())?;
+    for (_, record) in chunk.drain(..) {
+        writer.serialize(&record).map_err(|err| err.to_string())?;
+    }
+    writer.flush().map_err(|err| err.to_string())?;
+    Ok(path.to_path_buf())
+}
+
+// The reported records of one period and PSP in runs sorted by payee, and
+// the threshold decisions over all of its records.
+struct SortedBucket {
+    runs: Vec<PathBuf>,
+    reportable: HashSet<PayeeKey>,
+    counts: HashMap<PayeeKey, usize>,
+}
+
+impl SortedBucket {
+    // The reports of the period and PSP `key`, one or one per licensed
+    // country, with their parts planned. Threshold, license and warnings are
+    // traced and logged here, once per payee.
+    fn plan_reports<'b>(
+        &'b self,
+        key: &ReportKey,
+        options: &'b RenderOptions,
+        limits: &MessageLimits,
+        tracer: &mut RecordTracer,
+        totals: &mut RenderTotals,
+    ) -> Result<Vec<StreamedReport<'b>>, Error> {
+        let licensed = options
+            .licensed_countries
+            .as_deref()
+            .filter(|licensed| !licensed.is_empty());
+        let report = |requested: &str, country: Option<String>| -> Result<_, Error> {
+            Ok(StreamedReport {
+                header: CesopReport {
+                    period: key.period,
+                    transmitting_country: resolve_transmitting_country(requested, &key.psp_id)?,
+                    reporting_psp_id: key.psp_id.clone(),
+                    reporting_psp_name: key.psp_name.clone(),
+                    payees: Vec::new(),
+                    message_type_indic: "CESOP100".to_string(),
+                    address_format: options.address_format,
+                    ref_seed: options.ref_seed.clone(),
+                    timestamp: options.timestamp,
+                    xml_style: options.xml_style,
+                    schema: options.schema,
+                    psp_names_seen: key.psp_names_seen.clone(),
+                },
+                country,
+                licensed,
+                parts: Vec::new(),
+                whole: PartTotals::default(),
+                bucket: self,
+                options,
+            })
+        };
+        let mut reports = match licensed {
+            Some(licensed) => licensed
+                .iter()
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .map(|country| report(country, Some(country.clone())))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => vec![report(&options.transmitting_country, None)?],
+        };
+        let mut planners = reports
+            .iter()
+            .map(|report| PartPlanner::new(&report.header, limits))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut split = licensed.map(|licensed| LicenseSplit::new(licensed, &key.psp_id));
+        let mut merge = RunMerge::open(&self.runs)?;
+        while let Some((payee_key, transactions)) = merge.next_payee()? {
+            let reportable = self.reportable.contains(&payee_key);
+            if tracer.is_recording() {
+                let count = self.counts.get(&payee_key).copied().unwrap_or(0);
+                trace_threshold(tracer, &payee_key, &transactions, count, reportable);
+            }
+            if !reportable {
+                log_below_threshold(&payee_key);
+                continue;
+            }
+            let payee = build_payee(payee_key, transactions, options, true)?;
+            totals.add_payee(&payee);
+            let idx = match split.as_mut() {
+                Some(split) => {
+                    let (country, reason) = split.assign(&payee.payee_country);
+                    if tracer.is_recording() {
+                        let reason = format!("assigned to {} ({})", country, reason);
+                        trace_license(tracer, &payee, &reason);
+                    }
+                    reports
+                        .iter()
+                        .position(|report| report.country.as_deref() == Some(country))
+                        .unwrap_or_default()
+                }
+                None => {
+                    if tracer.is_recording() {
+                        let reason =
+                            format!("reported to {}", reports[0].header.transmitting_country);
+                        for tx in &payee.transactions {
+                            tracer.include(tx, "transmitting_country", &reason);
+                        }
+                    }
+                    0
+                }
+            };
+            planners[idx].add(&payee)?;
+        }
+
+        for (report, planner) in reports.iter_mut().zip(planners) {
+            report.whole = planner.whole.clone();
+            report.parts = planner.finish();
+            if report.whole.payees == 0 {
+                report.header.message_type_indic = "CESOP102".to_string();
+            }
+        }
+        Ok(reports)
+    }
+}
+
+// A report of `render --streaming`, whose payees are read back from the
+// sorted runs of its period and PSP whenever it is written.
+struct StreamedReport<'b> {
+    // The message header, without payees.
+    header: CesopReport,
+    // The licensed country the report is for, when split by license.
+    country: Option<String>,
+    licensed: Option<&'b [String]>,
+    parts: Vec<(PartSlices, PartTotals)>,
+    whole: PartTotals,
+    bucket: &'b SortedBucket,
+    options: &'b RenderOptions,
+}
+
+impl StreamedReport<'_> {
+    fn payees(&self) -> Result<PayeeStream<'_>, Error> {
+        Ok(PayeeStream {
+            merge: RunMerge::open(&self.bucket.runs)?,
+            report: self,
+            split: self
+                .licensed
+                .map(|licensed| LicenseSplit::new(licensed, &self.header.reporting_psp_id)),
+        })
+    }
+
+    fn part_header(&self, slices: &PartSlices) -> CesopReport {
+        let mut part = self.header.with_payees(Vec::new());
+        if !slices.is_empty() {
+            part.message_type_indic = "CESOP100".to_string();
+        }
+        part
+    }
+}
+
+impl Writable for StreamedReport<'_> {
+    fn header(&self) -> &CesopReport {
+        &self.header
+    }
+
+    fn whole(&self) -> Result<(Cow<'_, CesopReport>, PartTotals), Error> {
+        Ok((Cow::Borrowed(&self.header), self.whole.clone()))
+    }
+
+    fn parts(
+        &self,
+        _limits: &MessageLimits,
+    ) -> Result<Vec<(Cow<'_, CesopReport>, PartTotals)>, Error> {
+        Ok(self
+            .parts
+            .iter()
+            .map(|(slices, totals)| (Cow::Owned(self.part_header(slices)), totals.clone()))
+            .collect())
+    }
+
+    fn write_parts(
+        &self,
+        relative: &str,
+        output_dir: &Path,
+        limits: &MessageLimits,
+    ) -> Result<Vec<WrittenPart<'_>>, Error> {
+        if limits.max_payees.is_none_or(|max| self.whole.payees <= max) {
+            let path = output_dir.join(relative);
+            let payees = self.payees()?.map(|payee| payee.map(Cow::Owned));
+            let mut entry = write_report_payees(&self.header, payees, &path)?;
+            let size = std::fs::metadata(&path)
+                .map_err(|err| err.to_string())?
+                .len();
+            if limits.max_bytes.is_none_or(|max| size <= max) {
+                entry.file = relative.to_string();
+                return Ok(vec![WrittenPart {
+                    report: Cow::Borrowed(&self.header),
+                    file: relative.to_string(),
+                    entry: Some(entry),
+                    totals: self.whole.clone(),
+                }]);
+            }
+            std::fs::remove_file(&path).map_err(|err| err.to_string())?;
+        }
+
+        let mut payees = self.payees()?.enumerate();
+        // The payee last read, which the next part may continue.
+        let mut current: Option<(usize, PayeeGroup)> = None;
+        let total = self.parts.len();
+        let mut written = Vec::with_capacity(total);
+        for (idx, (slices, totals)) in self.parts.iter().enumerate() {
+            let file = part_file(relative, idx + 1, total);
+            let part = self.part_header(slices);
+            let part_payees = slices.iter().map(|(payee_idx, range)| {
+                while current.as_ref().is_none_or(|(idx, _)| idx != payee_idx) {
+                    let (idx, payee) = payees
+                        .next()
+                        .ok_or("spilled payees do not match the planned parts")?;
+                    current = Some((idx, payee?));
+                }
+                let (_, payee) = current.as_ref().ok_or("no payee read")?;
+                Ok(Cow::Owned(part_payee(payee, range.clone())))
+            });
+            let mut entry = write_report_payees(&part, part_payees, &output_dir.join(&file))?;
+            entry.file = file.clone();
+            written.push(WrittenPart {
+                report: Cow::Owned(part),
+                file,
+                entry: Some(entry),
+                totals: totals.clone(),
+            });
+        }
+        Ok(written)
+    }
+}
+
+// The payees of a streamed report in report order, built from the merged
+// runs.
+struct PayeeStream<'a> {
+    merge: RunMerge,
+    report: &'a StreamedReport<'a>,
+    split: Option<LicenseSplit<'a>>,
+}
+
+impl PayeeStream<'_> {
+    fn next_payee(&mut self) -> Result<Option<PayeeGroup>, Error> {
+        while let Some((payee_key, transactions)) = self.merge.next_payee()? {
+            if !self.report.bucket.reportable.contains(&payee_key) {
+                continue;
+            }
+            // Every reportable payee is assigned, so the turns of the
+            // round-robin fallback match the planning pass.
+            if let Some(split) = self.split.as_mut() {
+                let (country, _) = split.assign(&payee_key.payee_country);
+                if self.report.country.as_deref() != Some(country) {
+                    continue;
+                }
+            }
+            return build_payee(payee_key, transactions, self.report.options, false).map(Some);
+        }
+        Ok(None)
+    }
+}
+
+impl Iterator for PayeeStream<'_> {
+    type Item = Result<PayeeGroup, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_payee().transpose()
+    }
+}
+
+// Sorted runs merged by payee. Among the records of one payee earlier runs
+// come first, so its transactions keep their input order.
+struct RunMerge {
+    readers: Vec<csv::DeserializeRecordsIntoIter<File, PaymentRecord>>,
+    heads: Vec<Option<PaymentRecord>>,
+    heap: BinaryHeap<Reverse<(PayeeKey, usize)>>,
+}
+
+impl RunMerge {
+    fn open(runs: &[PathBuf]) -> Result<Self, Error> {
+        let mut merge = Self {
+            readers: Vec::with_capacity(runs.len()),
+            heads: Vec::with_capacity(runs.len()),
+            heap: BinaryHeap::with_capacity(runs.len()),
+        };
+        for (run, path) in runs.iter().enumerate() {
+            let reader = csv::Reader::from_path(path)
+                .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+            merge.readers.push(reader.into_deserialize());
+            merge.heads.push(None);
+            merge.advance(run)?;
+        }
+        Ok(merge)
+    }
+
+    fn advance(&mut self, run: usize) -> Result<(), Error> {
+        if let Some(record) = self.readers[run].next() {
+            let record: PaymentRecord = record.map_err(|err| err.to_string())?;
+            let key = PayeeKey {
+                psp_id: record.psp_id.clone(),
+                payee_id: record.payee_id.clone(),
+                payee_country: report_payee_country(&record)?,
+            };
+            self.heads[run] = Some(record);
+            self.heap.push(Reverse((key, run)));
+        }
+        Ok(())
+    }
+
+    fn next_record(&mut self) -> Result<Option<(PayeeKey, PaymentRecord)>, Error> {
+        let Some(Reverse((key, run))) = self.heap.pop() else {
+            return Ok(None);
+        };
+        let record = self.heads[run]
+            .take()
+            .ok_or("spill run read out of order")?;
+        self.advance(run)?;
+        Ok(Some((key, record)))
+    }
+
+    // The next payee with all of its records.
+    fn next_payee(&mut self) -> Result<Option<(PayeeKey, Vec<PaymentRecord>)>, Error> {
+        let Some((key, record)) = self.next_record()? else {
+            return Ok(None);
+        };
+        let mut transactions = vec![record];
+        while self
+            .heap
+            .peek()
+            .is_some_and(|Reverse((next, _))| *next == key)
+        {
+            if let Some((_, record)) = self.next_record()? {
+                transactions.push(record);
+            }
+        }
+        Ok(Some((key, transactions)))
+    }
+}
+
+impl Drop for SpilledReports {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_dir_all(&self.dir) {
+            log::warn!("failed to remove {}: {}", self.dir.display(), err);
+        }
+    }
+}
+
+pub fn write_reports(
+    reports: &[CesopReport],
+    output_dir: &Path,
+    layout: OutputLayout,
+    limits: &MessageLimits,
+    jobs: usize,
+    progress: Option<&mut RenderProgress>,
+) -> Result<Vec<PathBuf>, Error> {
+    let mut writer = ReportWriter::new(output_dir, layout, limits, jobs, progress)?;
+    writer.write_all(reports)?;
+    writer.finish()
+}
+
+// Writes reports, as `write_reports` does for a slice, and the RefId registry
+// and PSP manifests on `finish`. Files, registry entries and manifests are in
+// report order whatever the number of jobs.
+pub struct ReportWriter<'p> {
+    output_dir: PathBuf,
+    layout: OutputLayout,
+    limits: MessageLimits,
+    jobs: usize,
+    progress: Option<&'p mut RenderProgress>,
+    // Registry of an interrupted run, for the entries of skipped reports.
+    previous: Option<RefIdRegistry>,
+    registry: RefIdRegistry,
+    manifests: BTreeMap<String, PspManifest>,
+    // PSP id behind each per-psp directory, so two ids cannot share one.
+    psp_dirs: BTreeMap<String, String>,
+    render_manifest: RenderManifest,
+    outputs: Vec<PathBuf>,
+}
+
+impl<'p> ReportWriter<'p> {
+    pub fn new(
+        output_dir: &Path,
+        layout: OutputLayout,
+        limits: &MessageLimits,
+        jobs: usize,
+        progress: Option<&'p mut RenderProgress>,
+    ) -> Result<Self, Error> {
+        std::fs::create_dir_all(output_dir).map_err(|err| err.to_string())?;
+        let registry_path = registry_path(output_dir);
+        let previous = if progress.is_some() && registry_path.exists() {
+            Some(RefIdRegistry::load(&registry_path)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            output_dir: output_dir.to_path_buf(),
+            layout,
+            limits: *limits,
+            jobs: jobs.max(1),
+            progress,
+            previous,
+            registry: RefIdRegistry::default(),
+            manifests: BTreeMap::new(),
+            psp_dirs: BTreeMap::new(),
+            render_manifest: RenderManifest {
+                run_id: run_id().to_string(),
+                files: Vec::new(),
+            },
+            outputs: Vec::new(),
+        })
+    }
+
+    pub fn write(&mut self, report: &CesopReport) -> Result<(), Error> {
+        self.write_each(std::slice::from_ref(report))
+    }
+
+    // Writes `reports` on up to `jobs` threads. Each thread takes the next
+    // unwritten report; the results are recorded in report order once all
+    // are written. The first error stops threads from taking more reports.
+    pub fn write_all(&mut self, reports: &[CesopReport]) -> Result<(), Error> {
+        self.write_each(reports)
+    }
+
+    fn write_each<T: Writable>(&mut self, reports: &[T]) -> Result<(), Error> {
+        let mut written: Vec<Option<Vec<WrittenPart>>> = Vec::with_capacity(reports.len());
+        let mut pending = Vec::new();
+        for (idx, report) in reports.iter().enumerate() {
+            let relative = self.relative_path(report.header())?;
+            let completed = self.completed_parts(report, &relative)?;
+            if completed.is_none() {
+                pending.push((idx, relative));
+            }
+            written.push(completed);
+        }
+
+        if self.jobs == 1 || pending.len() < 2 {
+            for (idx, relative) in pending {
+                let parts = reports[idx].write_parts(&relative, &self.output_dir, &self.limits)?;
+                mark_written(&parts, &self.output_dir, self.progress.as_deref_mut())?;
+                written[idx] = Some(parts);
+            }
+        } else {
+            let next = AtomicUsize::new(0);
+            let failed = AtomicBool::new(false);
+            let results = Mutex::new(Vec::with_capacity(pending.len()));
+            let progress = Mutex::new(self.progress.as_deref_mut());
+            let (output_dir, limits) = (&self.output_dir, &self.limits);
+            std::thread::scope(|scope| {
+                for _ in 0..self.jobs.min(pending.len()) {
+                    scope.spawn(|| {
+                        while !failed.load(Ordering::Relaxed) {
+                            let task = next.fetch_add(1, Ordering::Relaxed);
+                            let Some((idx, relative)) = pending.get(task) else {
+                                break;
+                            };
+                            let result = reports[*idx]
+                                .write_parts(relative, output_dir, limits)
+                                .and_then(|parts| {
+                                    let mut progress =
+                                        progress.lock().map_err(|err| err.to_string())?;
+                                    mark_written(&parts, output_dir, progress.as_deref_mut())?;
+                                    Ok(parts)
+                                });
+                            if result.is_err() {
+                                failed.store(true, Ordering::Relaxed);
+                            }
+                            if let Ok(mut results) = results.lock() {
+                                results.push((*idx, result));
+                            }
+                        }
+                    });
+                }
+            });
+
+            let mut results = results.into_inner().map_err(|err| err.to_string())?;
+            results.sort_by_key(|(idx, _)| *idx);
+            for (idx, result) in results {
+                written[idx] = Some(result?);
+            }
+        }
+        for parts in written {
+            self.record(parts.ok_or("report was not written")?)?;
+        }
+        Ok(())
+    }
+
+    // File of `report` relative to the output directory; its directory is
+    // created.
+    fn relative_path(&mut self, report: &CesopReport) -> Result<String, Error> {
+        let filename = format!(
+            "cesop_{}_Q{}_{}_{}.xml",
+            report.period.year,
+            report.period.quarter,
+            report.transmitting_country,
+            report.reporting_psp_id
+        );
+        let relative = match self.layout {
+            OutputLayout::Flat => filename,
+            OutputLayout::PerPsp => {
+                let psp_id = report.reporting_psp_id.trim();
+                let dir = psp_dir_name(psp_id);
+                let owner = self
+                    .psp_dirs
+                    .entry(dir.clone())
+                    .or_insert_with(|| psp_id.to_string());
+                if owner != psp_id {
+                    return Err(codes::RND_PSP_DIR_COLLISION.fail(format!(
+                        "PSPs {} and {} would share the directory {}",
+                        owner, psp_id, dir
+                    )));
+                }
+                format!("{}/{}", dir, filename)
+            }
+        };
+        if let Some(parent) = self.output_dir.join(&relative).parent() {
+            std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+        Ok(relative)
+    }
+
+    // The messages an interrupted run completed for `report`, or None when it
+    // has to be written.
+    fn completed_parts<'a, T: Writable>(
+        &self,
+        report: &'a T,
+        relative: &str,
+    ) -> Result<Option<Vec<WrittenPart<'a>>>, Error> {
+        let Some(progress) = self.progress.as_deref() else {
+            return Ok(None);
+        };
+        let path = self.output_dir.join(relative);
+        // A report split in an earlier run is complete once all its parts are.
+        let previous_parts: Vec<&str> = self
+            .previous
+            .iter()
+            .flat_map(|previous| &previous.messages)
+            .map(|message| message.file.as_str())
+            .filter(|file| is_part_file(file, relative))
+            .collect();
+        let skipped = if previous_parts.is_empty() {
+            progress.is_completed(&path)
+        } else {
+            previous_parts
+                .iter()
+                .all(|file| progress.is_completed(&self.output_dir.join(file)))
+        };
+        if !skipped {
+            return Ok(None);
+        }
+
+        log::debug!(file:% = path.display(); "report already completed; skipping");
+        let previous_entry = |file: &str| {
+            self.previous
+                .as_ref()
+                .and_then(|previous| previous.message_for_file(file))
+                .cloned()
+        };
+        if previous_parts.is_empty() {
+            let (report, totals) = report.whole()?;
+            return Ok(Some(vec![WrittenPart {
+                report,
+                file: relative.to_string(),
+                entry: previous_entry(relative),
+                totals,
+            }]));
+        }
+        let parts = report.parts(&self.limits)?;
+        let total = parts.len();
+        Ok(Some(
+            parts
+                .into_iter()
+                .enumerate()
+                .map(|(idx, (report, totals))| {
+                    let file = part_file(relative, idx + 1, total);
+                    let entry = previous_entry(&file);
+                    WrittenPart {
+                        report,
+                        file,
+                        entry,
+                        totals,
+                    }
+                })
+                .collect(),
+        ))
+    }
+
+    fn record(&mut self, written: Vec<WrittenPart>) -> Result<(), String> {
+        for WrittenPart {
+            report: part,
+            file,
+            entry,
+            totals,
+        } in written
+        {
+            let path = self.output_dir.join(&file);
+            let message = ManifestFile {
+                file: file.clone(),
+                period: format!("{}-Q{}", part.period.year, part.period.quarter),
+                transmitting_country: part.transmitting_country.clone(),
+                message_type_indic: part.message_type_indic.clone(),
+                message_ref_id: entry
+                    .as_ref()
+                    .map(|entry| entry.message_ref_id.clone())
+                    .unwrap_or_default(),
+                payees: totals.payees,
+                transactions: totals.transactions,
+                sha256: sha256_file(&path)?,
+            };
+            if self.layout == OutputLayout::PerPsp {
+                let manifest = self
+                    .manifests
+                    .entry(part.reporting_psp_id.clone())
+                    .or_insert_with(|| PspManifest {
+                        run_id: run_id().to_string(),
+                        psp_id: part.reporting_psp_id.clone(),
+                        psp_name: part.reporting_psp_name.clone(),
+                        files: Vec::new(),
+                    });
+                manifest.files.push(ManifestFile {
+                    file: file_name(&path),
+                    ..message.clone()
+                });
+            }
+            self.render_manifest.files.push(RenderManifestFile {
+                psp_id: part.reporting_psp_id.clone(),
+                psp_name: part.reporting_psp_name.clone(),
+                message,
+                total_abs_amounts: totals.abs_amounts(),
+            });
+            if let Some(entry) = entry {
+                self.registry.messages.push(entry);
+            }
+            self.outputs.push(path);
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<Vec<PathBuf>, Error> {
+        for manifest in self.manifests.values() {
+            manifest.write(&self.output_dir.join(psp_dir_name(&manifest.psp_id)))?;
+        }
+        self.registry.write(&registry_path(&self.output_dir))?;
+        self.render_manifest.write(&self.output_dir)?;
+        Ok(self.outputs)
+    }
+}
+
+// Payees, transactions and absolute transaction amounts per currency of a
+// message, for its manifest entries.
+#[derive(Debug, Clone, Default)]
+struct PartTotals {
+    payees: usize,
+    transactions: usize,
+    // In cents as written to the XML, so the totals match the files.
+    abs_cents: BTreeMap<String, i64>,
+}
+
+impl PartTotals {
+    fn of(report: &CesopReport) -> Result<Self, String> {
+        let mut totals = Self::default();
+        for payee in &report.payees {
+            totals.add(payee, 0..payee.transactions.len())?;
+        }
+        Ok(totals)
+    }
+
+    // Adds `payee` with the transactions in `range`, a part of a split payee
+    // counting as one payee.
+    fn add(&mut self, payee: &PayeeGroup, range: Range<usize>) -> Result<(), String> {
+        self.payees += 1;
+        self.transactions += range.len();
+        for tx in &payee.transactions[range] {
+            let amount = format_amount_for_xml(&tx.amount, false)?;
+            let value = amount.parse::<f64>().map_err(|err| err.to_string())?;
+            *self.abs_cents.entry(tx.currency.clone()).or_default() +=
+                (value * 100.0).round() as i64;
+        }
+        Ok(())
+    }
+
+    fn abs_amounts(&self) -> BTreeMap<String, String> {
+        self.abs_cents
+            .iter()
+            .map(|(currency, total)| {
+                (
+                    currency.clone(),
+                    format!("{}.{:02}", total / 100, total % 100),
+                )
+            })
+            .collect()
+    }
+}
+
+// Logs freshly written messages and marks them completed in the checkpoint.
+fn mark_written(
+    written: &[WrittenPart],
+    output_dir: &Path,
+    mut progress: Option<&mut RenderProgress>,
+) -> Result<(), String> {
+    for part in written {
+        let path = output_dir.join(&part.file);
+        log::debug!(
+            file:% = path.display(),
+            psp_id = part.report.reporting_psp_id.as_str(),
+            transmitting_country = part.report.transmitting_country.as_str(),
+            payees = part.totals.payees;
+            "report written"
+        );
+        if let Some(progress) = progress.as_deref_mut() {
+            progress.mark_completed(&path)?;
+        }
+    }
+    Ok(())
+}
+
+// A written message: the report or part of it (only its header for streamed
+// reports), its file relative to the output directory, its registry entry
+// and totals.
+struct WrittenPart<'a> {
+    report: Cow<'a, CesopReport>,
+    file: String,
+    entry: Option<MessageEntry>,
+    totals: PartTotals,
+}
+
+// A report `ReportWriter` can write: held in memory, or streamed from the
+// sorted spill files of `render --streaming`.
+trait Writable: Sync {
+    // The message header; for streamed reports without payees.
+    fn header(&self) -> &CesopReport;
+
+    // The report as one message, for a report an interrupted run completed.
+    fn whole(&self) -> Result<(Cow<'_, CesopReport>, PartTotals), Error>;
+
+    // The parts `write_parts` splits the report into.
+    fn parts(
+        &self,
+        limits: &MessageLimits,
+    ) -> Result<Vec<(Cow<'_, CesopReport>, PartTotals)>, Error>;
+
+    // Writes the report to `relative`, or as numbered parts `<stem>-<x>-<y>.xml`
+    // when it exceeds the payee limit or the written file the size limit.
+    fn write_parts(
+        &self,
+        relative: &str,
+        output_dir: &Path,
+        limits: &MessageLimits,
+    ) -> Result<Vec<WrittenPart<'_>>, Error>;
+}
+
+impl Writable for CesopReport {
+    fn header(&self) -> &CesopReport {
+        self
+    }
+
+    fn whole(&self) -> Result<(Cow<'_, CesopReport>, PartTotals), Error> {
+        Ok((Cow::Borrowed(self), PartTotals::of(self)?))
+    }
+
+    fn parts(
+        &self,
+        limits: &MessageLimits,
+    ) -> Result<Vec<(Cow<'_, CesopReport>, PartTotals)>, Error> {
+        split_report(self, limits)?
+            .into_iter()
+            .map(|part| {
+                let totals = PartTotals::of(&part)?;
+                Ok((Cow::Owned(part), totals))
+            })
+            .collect()
+    }
+
+    fn write_parts(
+        &self,
+        relative: &str,
+        output_dir: &Path,
+        limits: &MessageLimits,
+    ) -> Result<Vec<WrittenPart<'_>>, Error> {
+        write_report_parts(self, relative, output_dir, limits)
+    }
+}
+
+fn write_report_parts<'a>(
+    report: &'a CesopReport,
+    relative: &str,
+    output_dir: &Path,
+    limits: &MessageLimits,
+) -> Result<Vec<WrittenPart<'a>>, Error> {
+    if limits
+        .max_payees
+        .is_none_or(|max| report.payees.len() <= max)
+    {
+        let path = output_dir.join(relative);
+        let mut entry = write_report(report, &path)?;
+        let size = std::fs::metadata(&path)
+            .map_err(|err| err.to_string())?
+            .len();
+        if limits.max_bytes.is_none_or(|max| size <= max) {
+            entry.file = relative.to_string();
+            return Ok(vec![WrittenPart {
+                report: Cow::Borrowed(report),
+                file: relative.to_string(),
+                entry: Some(entry),
+                totals: PartTotals::of(report)?,
+            }]);
+        }
+        std::fs::remove_file(&path).map_err(|err| err.to_string())?;
+    }
+
+    let parts = split_report(report, limits)?;
+    let total = parts.len();
+    let mut written = Vec::with_capacity(total);
+    for (idx, part) in parts.into_iter().enumerate() {
+        let file = part_file(relative, idx + 1, total);
+        let mut entry = write_report(&part, &output_dir.join(&file))?;
+        entry.file = file.clone();
+        written.push(WrittenPart {
+            totals: PartTotals::of(&part)?,
+            report: Cow::Owned(part),
+            file,
+            entry: Some(entry),
+        });
+    }
+    Ok(written)
+}
+
+// Part numbering follows the CESOP "x-y" file naming convention, as for
+// merged reports.
+fn part_file(relative: &str, number: usize, total: usize) -> String {
+    let stem = relative.strip_suffix(".xml").unwrap_or(relative);
+    format!("{}-{}-{}.xml", stem, number, total)
+}
+
+fn is_part_file(file: &str, relative: &str) -> bool {
+    let stem = relative.strip_suffix(".xml").unwrap_or(relative);
+    file.strip_prefix(stem)
+        .and_then(|rest| rest.strip_prefix('-'))
+        .and_then(|rest| rest.strip_suffix(".xml"))
+        .and_then(|rest| rest.split_once('-'))
+        .is_some_and(|(number, total)| {
+            !number.is_empty()
+                && !total.is_empty()
+                && number.chars().all(|ch| ch.is_ascii_digit())
+                && total.chars().all(|ch| ch.is_ascii_digit())
+        })
+}
+
+// Splits the payees of `report` into parts within `limits`, in order. A payee
+// too large for one message is split by transaction and reported in several
+// parts, each with all of its accounts.
+fn split_report(report: &CesopReport, limits: &MessageLimits) -> Result<Vec<CesopReport>, Error> {
+    let mut planner = PartPlanner::new(report, limits)?;
+    for payee in &report.payees {
+        planner.add(payee)?;
+    }
+    Ok(planner
+        .finish()
+        .into_iter()
+        .map(|(slices, _)| {
+            report.with_payees(
+                slices
+                    .into_iter()
+                    .map(|(idx, range)| part_payee(&report.payees[idx], range))
+                    .collect(),
+            )
+        })
+        .collect())
+}
+
+// The payees of a message part, by index in report order, each with the
+// range of its transactions in the part.
+type PartSlices = Vec<(usize, Range<usize>)>;
+
+// Plans the parts `split_report` splits a report into, one payee at a time,
+// so the payees need not be held at once.
+struct PartPlanner<'a> {
+    limits: &'a MessageLimits,
+    style: XmlStyle,
+    address_format: AddressFormat,
+    // Bytes left for payees in a message.
+    budget: Option<u64>,
+    parts: Vec<(PartSlices, PartTotals)>,
+    current: PartSlices,
+    current_totals: PartTotals,
+    current_bytes: u64,
+    // The report as one message.
+    whole: PartTotals,
+}
+
+impl<'a> PartPlanner<'a> {
+    // `report` gives the message header; its payees are not used.
+    fn new(report: &CesopReport, limits: &'a MessageLimits) -> Result<Self, Error> {
+        // Parts are measured unindented and sized as if every line sat as deep
+        // as payees (two levels) or transactions (three), which errs on the
+        // large side.
+        let budget = match limits.max_bytes {
+            Some(max) => {
+                let header = measure(report.xml_style, |writer| {
+                    write_root_start(writer, report.schema)?;
+                    write_message_spec(
+                        writer,
+                        &report.transmitting_country,
+                        &report.message_type_indic,
+                        &uuid::Uuid::nil().to_string(),
+                        None,
+                        report.period,
+                        report.timestamp,
+                    )?;
+                    write_payment_body(
+                        writer,
+                        report,
+                        std::iter::empty(),
+                        &uuid::Uuid::nil().to_string(),
+                    )?;
+                    write_end(writer, "CESOP")
+                })?;
+                Some(max.saturating_sub(header.indented(2)))
+            }
+            None => None,
+        };
+        Ok(Self {
+            limits,
+            style: report.xml_style,
+            address_format: report.address_format,
+            budget,
+            parts: Vec::new(),
+            current: Vec::new(),
+            current_totals: PartTotals::default(),
+            current_bytes: 0,
+            whole: PartTotals::default(),
+        })
+    }
+
+    fn fits(&self, count: usize, bytes: u64) -> bool {
+        self.limits.max_payees.is_none_or(|max| count < max)
+            && self.budget.is_none_or(|budget| bytes <= budget)
+    }
+
+    fn push(&mut self, idx: usize, payee: &PayeeGroup, range: Range<usize>) -> Result<(), Error> {
+        self.current_totals.add(payee, range.clone())?;
+        self.current.push((idx, range));
+        Ok(())
+    }
+
+    fn next_part(&mut self) {
+        let slices = std::mem::take(&mut self.current);
+        let totals = std::mem::take(&mut self.current_totals);
+        self.parts.push((slices, totals));
+        self.current_bytes = 0;
+    }
+
+    // Adds the next payee in report order.
+    fn add(&mut self, payee: &PayeeGroup) -> Result<(), Error> {
+        let idx = self.whole.payees;
+        let all = 0..payee.transactions.len();
+        self.whole.add(payee, all.clone())?;
+        let size = match self.budget {
+            Some(_) => measure(self.style, |writer| {
+                write_reported_payee(
+                    writer,
+                    payee,
+                    self.address_format,
+                    "CESOP1",
+                    &uuid::Uuid::nil().to_string(),
+                    None,
+                )
+            })?,
+            None => Measured::default(),
+        };
+        let payee_bytes = size.indented(2);
+        if self.fits(self.current.len(), self.current_bytes + payee_bytes) {
+            self.push(idx, payee, all)?;
+            self.current_bytes += payee_bytes;
+            return Ok(());
+        }
+        if self.fits(0, payee_bytes) {
+            self.next_part();
+            self.push(idx, payee, all)?;
+            self.current_bytes = payee_bytes;
+            return Ok(());
+        }
+
+        // Fill the current part first, then as many parts as needed.
+        let budget = self.budget.unwrap_or(u64::MAX);
+        let mut transactions = Vec::with_capacity(payee.transactions.len());
+        for tx in &payee.transactions {
+            transactions.push(
+                measure(self.style, |writer| write_reported_transaction(writer, tx))?.indented(3),
+            );
+        }
+        let base = payee_bytes.saturating_sub(transactions.iter().sum());
+        let mut start = 0;
+        let mut chunk_bytes = base;
+        for (idx_tx, (tx, bytes)) in payee.transactions.iter().zip(transactions).enumerate() {
+            if base + bytes > budget {
+                return Err(codes::RND_MESSAGE_LIMIT.fail(format!(
+                    "{} bytes cannot hold payee {} with transaction {}",
+                    self.limits.max_bytes.unwrap_or_default(),
+                    payee.payee_id,
+                    tx.payment_id
+                )));
+            }
+            if !self.fits(self.current.len(), self.current_bytes + chunk_bytes + bytes) {
+                if idx_tx > start {
+                    self.push(idx, payee, start..idx_tx)?;
+                }
+                self.next_part();
+                chunk_bytes = base;
+                start = idx_tx;
+            }
+            chunk_bytes += bytes;
+        }
+        self.push(idx, payee, start..payee.transactions.len())?;
+        self.current_bytes += chunk_bytes;
+        Ok(())
+    }
+
+    // The parts, with their payees and totals.
+    fn finish(mut self) -> Vec<(PartSlices, PartTotals)> {
+        if !self.current.is_empty() || self.parts.is_empty() {
+            self.next_part();
+        }
+        self.parts
+    }
+}
+
+// The transactions in `range` of `payee`, under all of its accounts.
+fn part_payee(payee: &PayeeGroup, range: Range<usize>) -> PayeeGroup {
+    if range.len() == payee.transactions.len() {
+        return payee.clone();
+    }
+    PayeeGroup {
+        transactions: payee.transactions[range].to_vec(),
+        ..payee_header(payee)
+    }
+}
+
+fn payee_header(payee: &PayeeGroup) -> PayeeGroup {
+    PayeeGroup {
+        payee_id: payee.payee_id.clone(),
+        payee_name: payee.payee_name.clone(),
+        payee_country: payee.payee_country.clone(),
+        payee_accounts: payee.payee_accounts.clone(),
+        representative: payee.representative.clone(),
+        payee_tax_id: payee.payee_tax_id.clone(),
+        payee_vat_id: payee.payee_vat_id.clone(),
+        payee_email: payee.payee_email.clone(),
+        payee_web: payee.payee_web.clone(),
+        payee_address_line: payee.payee_address_line.clone(),
+        payee_city: payee.payee_city.clone(),
+        payee_postcode: payee.payee_postcode.clone(),
+        transactions: Vec::new(),
+    }
+}
+
+// Bytes and line breaks written by an XML writer in some style.
+#[derive(Debug, Default, Clone, Copy)]
+struct Measured {
+    bytes: u64,
+    lines: u64,
+    style: Option<XmlStyle>,
+}
 
-    for record in records.into_iter() {
-        let period = period_from_timestamp(&record.execution_time)?;
-        if let Some(existing) = psp_names.get(&record.psp_id) {
-            if existing != &record.psp_name {
-                return Err(format!(
-                    "multiple PSP names found for {}: '{}' vs '{}'",
-                    record.psp_id, existing, record.psp_name
-                ));
+impl Measured {
+    // Size at `depth` levels of indentation, each line preceded by a line
+    // break. Compact output has neither.
+    fn indented(self, depth: u64) -> u64 {
+        match self.style {
+            Some(XmlStyle::Indent(width)) => {
+                self.bytes + (self.lines + 1) * (1 + width as u64 * depth)
             }
-        } else {
-            psp_names.insert(record.psp_id.clone(), record.psp_name.clone());
+            _ => self.bytes,
         }
+    }
+}
 
-        let key = ReportKey {
-            period,
-            psp_id: record.psp_id.clone(),
-            psp_name: record.psp_name.clone(),
-        };
-        period_map.entry(key).or_default().push(record);
+impl std::io::Write for Measured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.bytes += buf.len() as u64;
+        self.lines += buf.iter().filter(|byte| **byte == b'\n').count() as u64;
+        Ok(buf.len())
     }
 
-    let mut reports = Vec::new();
-    for (key, period_records) in period_map {
-        let reportable_payees =
-            reportable_payee_keys(&period_records, REPORTING_THRESHOLD, false)?;
-        let reportable_records: Vec<PaymentRecord> = period_records
-            .into_iter()
-            .filter(|record| reportable_for_psp(record))
-            .collect();
-        let payees = group_payees(reportable_records, &reportable_payees)?;
-        if let Some(licensed) = licensed_countries {
-            if !licensed.is_empty() {
-                let assignments = split_payees_by_license(payees, licensed, &key.psp_id)?;
-                for (country, assigned) in assignments {
-                    let message_type_indic = if assigned.is_empty() {
-                        "CESOP102".to_string()
-                    } else {
-                        "CESOP100".to_string()
-                    };
-                    let tx_country = resolve_transmitting_country(&country, &key.psp_id)?;
-
-                    reports.push(CesopReport {
-                        period: key.period,
-                        transmitting_country: tx_country,
-                        reporting_psp_id: key.psp_id.clone(),
-                        reporting_psp_name: key.psp_name.clone(),
-                        payees: assigned,
-                        message_type_indic,
-                    });
-                }
-                continue;
-            }
-        }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
 
-        let message_type_indic = if payees.is_empty() {
-            "CESOP102".to_string()
-        } else {
-            "CESOP100".to_string()
-        };
-        let tx_country = resolve_transmitting_country(transmitting_country, &key.psp_id)?;
+fn measure(
+    style: XmlStyle,
+    write: impl FnOnce(&mut Writer<&mut Measured>) -> Result<(), String>,
+) -> Result<Measured, String> {
+    let mut measured = Measured {
+        style: Some(style),
+        ..Measured::default()
+    };
+    write(&mut style.writer(&mut measured))?;
+    Ok(measured)
+}
 
-        reports.push(CesopReport {
-            period: key.period,
-            transmitting_country: tx_country,
-            reporting_psp_id: key.psp_id,
-            reporting_psp_name: key.psp_name,
-            payees,
-            message_type_indic,
-        });
-    }
+fn psp_dir_name(psp_id: &str) -> String {
+    psp_id
+        .trim()
+        .chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' })
+        .collect()
+}
 
-    Ok(reports)
+// CESOP101 message deleting previously accepted payees (DocTypeIndic CESOP3),
+// written as `original` was.
+pub fn write_deletion_message(
+    original: &MessageEntry,
+    payees: &[&PayeeEntry],
+    path: &Path,
+) -> Result<MessageEntry, Error> {
+    let format = MessageFormat::of_entry(original)?;
+    write_correction_message(original, &[], payees, &format, path)
 }
 
-pub fn write_reports(reports: &[CesopReport], output_dir: &Path) -> Result<Vec<PathBuf>, String> {
-    std::fs::create_dir_all(output_dir).map_err(|err| err.to_string())?;
-    let mut outputs = Vec::new();
+// How correction and deletion messages are written, taken from the reports
+// they are derived from.
+#[derive(Debug, Clone, Default)]
+pub struct MessageFormat {
+    pub address_format: AddressFormat,
+    pub ref_seed: Option<String>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub xml_style: XmlStyle,
+    pub schema: SchemaVersion,
+}
 
-    for report in reports {
-        let filename = format!(
-            "cesop_{}_Q{}_{}_{}.xml",
-            report.period.year,
-            report.period.quarter,
-            report.transmitting_country,
-            report.reporting_psp_id
-        );
-        let path = output_dir.join(filename);
-        write_report(report, &path)?;
-        outputs.push(path);
+impl MessageFormat {
+    pub fn of(report: &CesopReport) -> Self {
+        Self {
+            address_format: report.address_format,
+            ref_seed: report.ref_seed.clone(),
+            timestamp: report.timestamp,
+            xml_style: report.xml_style,
+            schema: report.schema,
+        }
     }
 
-    Ok(outputs)
+    // The format recorded for a registered message; defaults for what the
+    // registry does not record.
+    pub fn of_entry(entry: &MessageEntry) -> Result<Self, Error> {
+        let schema = match entry.schema_version.as_deref() {
+            Some(version) => SchemaVersion::from_version(version).ok_or_else(|| {
+                codes::RND_REGISTRY_ENTRY_INVALID.fail(format!(
+                    "message {} has unknown schema version {}",
+                    entry.message_ref_id, version
+                ))
+            })?,
+            None => SchemaVersion::default(),
+        };
+        let xml_style = match entry.xml_style.as_deref() {
+            Some(style) => style.parse::<XmlStyle>().map_err(|err| {
+                codes::RND_REGISTRY_ENTRY_INVALID
+                    .fail(format!("message {}: {}", entry.message_ref_id, err))
+            })?,
+            None => XmlStyle::default(),
+        };
+        Ok(Self {
+            ref_seed: entry.ref_seed.clone(),
+            xml_style,
+            schema,
+            ..Self::default()
+        })
+    }
 }
 
-fn read_csv(path: &Path) -> Result<Vec<PaymentRecord>, String> {
-    let mut reader = csv::Reader::from_path(path).map_err(|err| err.to_string())?;
-    let mut records = Vec::new();
-    for row in reader.deserialize() {
-        let record: PaymentRecord = row.map_err(|err| err.to_string())?;
-        records.push(record);
+// CESOP101 message correlated with `original`: corrected payees are reported
+// in full (DocTypeIndic CESOP2), deleted payees only carry the mandatory
+// elements and no transactions (CESOP3).
+pub fn write_correction_message(
+    original: &MessageEntry,
+    corrections: &[(&PayeeEntry, &PayeeGroup)],
+    deletions: &[&PayeeEntry],
+    format: &MessageFormat,
+    path: &Path,
+) -> Result<MessageEntry, Error> {
+    let file = File::create(path).map_err(|err| err.to_string())?;
+    let mut writer = format.xml_style.writer(BufWriter::new(file));
+    let ref_seed = format.ref_seed.as_deref();
+    let message_ref_id = ref_id(
+        ref_seed,
+        &[
+            &original.psp_id,
+            &original.transmitting_country,
+            &original.message_ref_id,
+            &file_name(path),
+        ],
+    );
+    let period = PeriodKey {
+        year: original.year,
+        quarter: original.quarter,
+    };
+
+    write_root_start(&mut writer, format.schema)?;
+    write_message_spec(
+        &mut writer,
+        &original.transmitting_country,
+        "CESOP101",
+        &message_ref_id,
+        Some(&original.message_ref_id),
+        period,
+        format.timestamp,
+    )?;
+    write_start(&mut writer, "PaymentDataBody", &[])?;
+    write_reporting_psp(&mut writer, &original.psp_id, &original.psp_name)?;
+
+    let mut entries = Vec::with_capacity(corrections.len() + deletions.len());
+    for (corrected, payee) in corrections {
+        let doc_ref_id = ref_id(ref_seed, &[&message_ref_id, &corrected.doc_ref_id]);
+        write_reported_payee(
+            &mut writer,
+            payee,
+            format.address_format,
+            "CESOP2",
+            &doc_ref_id,
+            Some(&corrected.doc_ref_id),
+        )?;
+        entries.push(PayeeEntry {
+            payee_id: payee.payee_id.clone(),
+            name: payee.payee_name.clone(),
+            country: payee.payee_country.clone(),
+            doc_type_indic: "CESOP2".to_string(),
+            doc_ref_id,
+            corr_doc_ref_id: Some(corrected.doc_ref_id.clone()),
+            superseded_by: None,
+            digest: Some(payee_digest(payee)),
+            transactions: payee
+                .transactions
+                .iter()
+                .map(|tx| tx.payment_id.clone())
+                .collect(),
+        });
     }
-    Ok(records)
+    for payee in deletions {
+        let doc_ref_id = ref_id(ref_seed, &[&message_ref_id, &payee.doc_ref_id]);
+        write_start(&mut writer, "ReportedPayee", &[])?;
+        write_text_element_with_attrs(
+            &mut writer,
+            "Name",
+            &payee.name,
+            &[("nameType", "BUSINESS")],
+        )?;
+        write_text_element(&mut writer, "Country", &payee.country)?;
+        write_start(&mut writer, "Address", &[])?;
+        write_text_element(&mut writer, "cm:CountryCode", &payee.country)?;
+        write_end(&mut writer, "Address")?;
+        write_start(&mut writer, "TAXIdentification", &[])?;
+        write_end(&mut writer, "TAXIdentification")?;
+        write_text_element(&mut writer, "AccountIdentifier", "")?;
+        write_doc_spec(&mut writer, "CESOP3", &doc_ref_id, Some(&payee.doc_ref_id))?;
+        write_end(&mut writer, "ReportedPayee")?;
+        entries.push(PayeeEntry {
+            payee_id: payee.payee_id.clone(),
+            name: payee.name.clone(),
+            country: payee.country.clone(),
+            doc_type_indic: "CESOP3".to_string(),
+            doc_ref_id,
+            corr_doc_ref_id: Some(payee.doc_ref_id.clone()),
+            superseded_by: None,
+            digest: None,
+            transactions: Vec::new(),
+        });
+    }
+
+    write_end(&mut writer, "PaymentDataBody")?;
+    write_end(&mut writer, "CESOP")?;
+
+    Ok(MessageEntry {
+        file: file_name(path),
+        message_ref_id,
+        corr_message_ref_id: Some(original.message_ref_id.clone()),
+        message_type_indic: "CESOP101".to_string(),
+        transmitting_country: original.transmitting_country.clone(),
+        psp_id: original.psp_id.clone(),
+        psp_name: original.psp_name.clone(),
+        year: original.year,
+        quarter: original.quarter,
+        schema_version: Some(format.schema.profile().version().to_string()),
+        xml_style: Some(format.xml_style.to_string()),
+        ref_seed: format.ref_seed.clone(),
+        payees: entries,
+    })
+}
+
+// Identifies the reported content of a payee: its identification and every
+// transaction, in input order.
+pub fn payee_digest(payee: &PayeeGroup) -> String {
+    sha256_hex(format!("{:?}", payee).as_bytes())
 }
 
-fn resolve_transmitting_country(requested: &str, psp_id: &str) -> Result<String, String> {
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn resolve_transmitting_country(requested: &str, psp_id: &str) -> Result<String, Error> {
     if requested.eq_ignore_ascii_case(TRANSMITTING_COUNTRY_AUTO) {
         return bic_country_code(psp_id).ok_or_else(|| {
-            format!(
+            codes::RND_TRANSMITTING_COUNTRY.fail(format!(
                 "cannot derive transmitting country from PSP identifier {}",
                 psp_id
-            )
+            ))
         });
     }
 
     if requested.trim().is_empty() {
-        return Err("transmitting country cannot be empty".to_string());
+        return Err(codes::RND_TRANSMITTING_COUNTRY.fail("transmitting country cannot be empty"));
     }
 
     Ok(requested.trim().to_uppercase())
 }
 
+// Whether the reporting PSP reports `record`, tracing the exclusion when not.
+fn reported_by_psp(record: &PaymentRecord, tracer: &mut RecordTracer) -> bool {
+    let reportable = reportable_for_psp(record);
+    if !reportable {
+        tracer.exclude(
+            record,
+            "psp_role",
+            &codes::RND_EXCLUDED_PSP_ROLE,
+            "reporting PSP acts for the payer and the payee PSP is in a Member State",
+        );
+    }
+    reportable
+}
+
 fn reportable_for_psp(record: &PaymentRecord) -> bool {
     let role = record.psp_role.as_deref().unwrap_or("PAYEE");
     if !role.eq_ignore_ascii_case("PAYER") {
@@ -213,12 +2359,37 @@ fn reportable_for_psp(record: &PaymentRecord) -> bool {
     !is_eu_member_state(&country)
 }
 
+fn apply_account_payer_ms(record: &mut PaymentRecord, tracer: &mut RecordTracer) {
+    let Some((country, source)) = derive_payer_ms(record) else {
+        return;
+    };
+    if record.payer_country == country && record.payer_ms_source == source {
+        return;
+    }
+    if tracer.is_recording() {
+        tracer.include(
+            record,
+            "payer_ms",
+            &format!(
+                "PayerMS {} ({}) taken from payer_account instead of {} ({})",
+                country, source, record.payer_country, record.payer_ms_source
+            ),
+        );
+    }
+    record.payer_country = country;
+    record.payer_ms_source = source.to_string();
+}
+
 fn is_cross_border(payer_country: &str, payee_country: &str) -> bool {
     is_eu_member_state(payer_country) && payer_country != payee_country
 }
 
-fn period_from_timestamp(ts: &str) -> Result<PeriodKey, String> {
-    let parsed = chrono::DateTime::parse_from_rfc3339(ts).map_err(|err| err.to_string())?;
+fn period_from_timestamp(ts: &str) -> Result<PeriodKey, Error> {
+    // Local offsets can move a timestamp across a quarter boundary; periods
+    // are counted in UTC, as in `analyze`.
+    let parsed = chrono::DateTime::parse_from_rfc3339(ts)
+        .map_err(|err| codes::RND_EXECUTION_TIME.fail(format!("'{}': {}", ts, err)))?
+        .with_timezone(&chrono::Utc);
     let month = parsed.month();
     let quarter = ((month - 1) / 3 + 1) as u8;
     Ok(PeriodKey {
@@ -230,83 +2401,239 @@ fn period_from_timestamp(ts: &str) -> Result<PeriodKey, String> {
 fn group_payees(
     records: Vec<PaymentRecord>,
     reportable_payees: &HashSet<PayeeKey>,
-) -> Result<Vec<PayeeGroup>, String> {
+    threshold_counts: &HashMap<PayeeKey, usize>,
+    options: &RenderOptions,
+    tracer: &mut RecordTracer,
+) -> Result<Vec<PayeeGroup>, Error> {
     let mut groups: BTreeMap<PayeeKey, Vec<PaymentRecord>> = BTreeMap::new();
 
     for record in records {
-        let payee_country = resolve_payee_country(&record)?;
-        if !is_cross_border(record.payer_country.as_str(), &payee_country) {
-            continue;
+        if let Some(key) = cross_border_payee(&record, tracer)? {
+            groups.entry(key).or_default().push(record);
         }
-        let key = PayeeKey {
-            psp_id: record.psp_id.clone(),
-            payee_id: record.payee_id.clone(),
-            payee_country: payee_country.clone(),
-        };
-        groups.entry(key).or_default().push(record);
     }
 
     let mut payees = Vec::new();
     for (payee_key, transactions) in groups {
-        if !reportable_payees.contains(&payee_key) {
+        let reportable = reportable_payees.contains(&payee_key);
+        if tracer.is_recording() {
+            let count = threshold_counts.get(&payee_key).copied().unwrap_or(0);
+            trace_threshold(tracer, &payee_key, &transactions, count, reportable);
+        }
+        if !reportable {
+            log_below_threshold(&payee_key);
             continue;
         }
-        let first = transactions
-            .first()
-            .ok_or_else(|| "missing transactions for payee".to_string())?;
-        let payee_accounts = collect_payee_accounts(&transactions)?;
-        let representative = if payee_accounts.len() == 1 && payee_accounts[0].id.is_empty() {
-            let rep_id = transactions
-                .iter()
-                .find_map(|tx| {
-                    tx.payee_psp_id
-                        .as_deref()
-                        .filter(|value| !value.trim().is_empty())
-                        .map(|value| value.to_string())
-                })
-                .ok_or_else(|| {
-                    "payee PSP identifier required when payee account is missing".to_string()
-                })?;
-            let rep_name = transactions
-                .iter()
-                .find_map(|tx| {
-                    tx.payee_psp_name
-                        .as_deref()
-                        .filter(|value| !value.trim().is_empty())
-                        .map(|value| value.to_string())
-                });
-            Some(Representative {
-                id: rep_id,
-                name: rep_name,
-            })
+        payees.push(build_payee(payee_key, transactions, options, true)?);
+    }
+
+    Ok(payees)
+}
+
+// The payee `record` is grouped under, or None when the payment is not
+// cross-border and so not reported.
+fn cross_border_payee(
+    record: &PaymentRecord,
+    tracer: &mut RecordTracer,
+) -> Result<Option<PayeeKey>, Error> {
+    let payee_country = report_payee_country(record)?;
+    if !is_cross_border(record.payer_country.as_str(), &payee_country) {
+        if tracer.is_recording() {
+            tracer.exclude(
+                record,
+                "cross_border",
+                &codes::RND_EXCLUDED_NOT_CROSS_BORDER,
+                &format!(
+                    "payer country {} is not an EU Member State or equals payee country {}",
+                    record.payer_country, payee_country
+                ),
+            );
+        }
+        return Ok(None);
+    }
+    if tracer.is_recording() {
+        tracer.include(
+            record,
+            "cross_border",
+            &format!(
+                "payer country {} differs from payee country {}",
+                record.payer_country, payee_country
+            ),
+        );
+    }
+    if record.is_refund {
+        tracer.include(
+            record,
+            "refund",
+            "refund is reported with the payee but not counted toward the threshold",
+        );
+    }
+    Ok(Some(PayeeKey {
+        psp_id: record.psp_id.clone(),
+        payee_id: record.payee_id.clone(),
+        payee_country,
+    }))
+}
+
+// XI is not in the CESOP country code lists; Northern Irish payees are
+// reported under GB.
+fn report_payee_country(record: &PaymentRecord) -> Result<String, Error> {
+    resolve_payee_country(record)
+        .map(|country| banking_country(&country).to_string())
+        .map_err(|err| {
+            codes::RND_PAYEE_COUNTRY.fail(format!("payment {}: {}", record.payment_id, err))
+        })
+}
+
+fn log_below_threshold(payee_key: &PayeeKey) {
+    log::trace!(
+        psp_id = payee_key.psp_id.as_str(),
+        payee_id = payee_key.payee_id.as_str(),
+        rule = "threshold";
+        "payee below reporting threshold"
+    );
+}
+
+fn trace_threshold(
+    tracer: &mut RecordTracer,
+    payee_key: &PayeeKey,
+    transactions: &[PaymentRecord],
+    count: usize,
+    reportable: bool,
+) {
+    let reason = format!(
+        "payee has {} counted cross-border payment(s) in {} (threshold >{})",
+        count, payee_key.payee_country, REPORTING_THRESHOLD
+    );
+    for tx in transactions {
+        if reportable {
+            tracer.include(tx, "threshold", &reason);
         } else {
-            None
-        };
+            tracer.exclude(
+                tx,
+                "threshold",
+                &codes::RND_EXCLUDED_BELOW_THRESHOLD,
+                &reason,
+            );
+        }
+    }
+}
 
-        payees.push(PayeeGroup {
-            payee_id: payee_key.payee_id.clone(),
-            payee_name: first.payee_name.clone(),
-            payee_country: payee_key.payee_country.clone(),
-            payee_accounts,
-            representative,
-            payee_tax_id: first.payee_tax_id.clone(),
-            payee_vat_id: first.payee_vat_id.clone(),
-            payee_email: first.payee_email.clone(),
-            payee_web: first.payee_web.clone(),
-            payee_address_line: first.payee_address_line.clone(),
-            payee_city: first.payee_city.clone(),
-            payee_postcode: first.payee_postcode.clone(),
-            transactions,
-        });
+// The reported payee of `payee_key` with its `transactions`. With `warn`,
+// conflicting attributes and foreign accounts are logged; streamed reports
+// build their payees more than once and warn the first time only.
+fn build_payee(
+    payee_key: PayeeKey,
+    transactions: Vec<PaymentRecord>,
+    options: &RenderOptions,
+    warn: bool,
+) -> Result<PayeeGroup, Error> {
+    if transactions.is_empty() {
+        return Err("missing transactions for payee".into());
     }
+    let payee_accounts = collect_payee_accounts(&transactions, options.payee_accounts)?;
+    let representative = if payee_accounts.len() == 1 && payee_accounts[0].id.is_empty() {
+        let rep_id = transactions
+            .iter()
+            .find_map(|tx| {
+                tx.payee_psp_id
+                    .as_deref()
+                    .filter(|value| !value.trim().is_empty())
+                    .map(|value| value.to_string())
+            })
+            .ok_or_else(|| {
+                "payee PSP identifier required when payee account is missing".to_string()
+            })?;
+        let rep_name = transactions
+            .iter()
+            .find_map(|tx| {
+                tx.payee_psp_name
+                    .as_deref()
+                    .filter(|value| !value.trim().is_empty())
+                    .map(|value| value.to_string())
+            });
+        let rep_country = transactions.iter().find_map(|tx| {
+            tx.payee_psp_country
+                .as_deref()
+                .and_then(normalize_country_code)
+        });
+        let bic_country = bic_country_code(&rep_id);
+        if let Some((provided, bic_country)) = rep_country.as_ref().zip(bic_country.as_ref()) {
+            if warn && provided != bic_country {
+                log::warn!(
+                    "{}",
+                    codes::RND_REPRESENTATIVE_COUNTRY.err(format!(
+                        "payee {}: representative {} is from {}, payee_psp_country is {}",
+                        payee_key.payee_id, rep_id, bic_country, provided
+                    ))
+                );
+            }
+        }
+        let rep_address = transactions
+            .iter()
+            .find_map(|tx| given(tx.payee_psp_address.as_ref()))
+            .cloned();
+        Some(Representative {
+            id: rep_id,
+            name: rep_name,
+            country: rep_country.or(bic_country),
+            address: rep_address,
+        })
+    } else {
+        None
+    };
 
-    Ok(payees)
+    let attributes = PayeeAttributes::new(&transactions, options.payee_conflicts);
+    let payee = PayeeGroup {
+        payee_id: payee_key.payee_id,
+        payee_name: attributes.resolve(|tx| Some(&tx.payee_name)).unwrap_or_default(),
+        payee_country: payee_key.payee_country,
+        payee_accounts,
+        representative,
+        payee_tax_id: attributes.resolve(|tx| tx.payee_tax_id.as_ref()),
+        payee_vat_id: attributes.resolve(|tx| tx.payee_vat_id.as_ref()),
+        payee_email: attributes.resolve(|tx| tx.payee_email.as_ref()),
+        payee_web: attributes.resolve(|tx| tx.payee_web.as_ref()),
+        payee_address_line: attributes.resolve(|tx| tx.payee_address_line.as_ref()),
+        payee_city: attributes.resolve(|tx| tx.payee_city.as_ref()),
+        payee_postcode: attributes.resolve(|tx| tx.payee_postcode.as_ref()),
+        transactions,
+    };
+    if !warn {
+        return Ok(payee);
+    }
+    let conflicts = payee.attribute_conflicts();
+    if !conflicts.is_empty() {
+        log::warn!(
+            "{}",
+            codes::RND_PAYEE_ATTRIBUTE_CONFLICT.err(format!(
+                "payee {}: conflicting {}",
+                payee.payee_id,
+                conflicts.join(", ")
+            ))
+        );
+    }
+    if let Some((account, provided)) = payee.account_country_mismatch() {
+        log::warn!(
+            "{}",
+            codes::RND_ACCOUNT_COUNTRY.err(format!(
+                "payee {}: {} {} is from {}, payee_country is {}",
+                payee.payee_id,
+                account.account_type,
+                account.id,
+                account.country().unwrap_or_default(),
+                provided
+            ))
+        );
+    }
+    Ok(payee)
 }
 
 fn split_payees_by_license(
     mut payees: Vec<PayeeGroup>,
     licensed: &[String],
     psp_id: &str,
+    tracer: &mut RecordTracer,
 ) -> Result<BTreeMap<String, Vec<PayeeGroup>>, String> {
     let mut assignments: BTreeMap<String, Vec<PayeeGroup>> = BTreeMap::new();
     for code in licensed {
@@ -316,30 +2643,17 @@ fn split_payees_by_license(
         return Ok(assignments);
     }
 
-    let licensed_set: HashSet<&str> = licensed.iter().map(|code| code.as_str()).collect();
-    let home_country = bic_country_code(psp_id);
-
+    let mut split = LicenseSplit::new(licensed, psp_id);
     payees.sort_by(|left, right| left.payee_id.cmp(&right.payee_id));
-    let mut fallback_idx = 0usize;
     for payee in payees.into_iter() {
-        if licensed_set.contains(payee.payee_country.as_str()) {
-            if let Some(entry) = assignments.get_mut(&payee.payee_country) {
-                entry.push(payee);
-            }
-            continue;
+        let (country, reason) = split.assign(&payee.payee_country);
+        if tracer.is_recording() {
+            trace_license(
+                tracer,
+                &payee,
+                &format!("assigned to {} ({})", country, reason),
+            );
         }
-
-        if let Some(home) = home_country.as_deref() {
-            if licensed_set.contains(home) {
-                if let Some(entry) = assignments.get_mut(home) {
-                    entry.push(payee);
-                    continue;
-                }
-            }
-        }
-
-        let country = &licensed[fallback_idx % licensed.len()];
-        fallback_idx = fallback_idx.saturating_add(1);
         if let Some(entry) = assignments.get_mut(country) {
             entry.push(payee);
         }
@@ -348,7 +2662,52 @@ fn split_payees_by_license(
     Ok(assignments)
 }
 
-fn collect_payee_accounts(transactions: &[PaymentRecord]) -> Result<Vec<PayeeAccount>, String> {
+// Assigns payees, taken in payee_id order, to one of the PSP's licensed
+// countries: the payee's country when licensed, else the PSP's home Member
+// State when licensed, else the next country in turn.
+struct LicenseSplit<'a> {
+    licensed: &'a [String],
+    home: Option<String>,
+    fallback_idx: usize,
+}
+
+impl<'a> LicenseSplit<'a> {
+    fn new(licensed: &'a [String], psp_id: &str) -> Self {
+        Self {
+            licensed,
+            home: bic_country_code(psp_id),
+            fallback_idx: 0,
+        }
+    }
+
+    // The country the payee is reported to, and why.
+    fn assign(&mut self, payee_country: &str) -> (&'a str, &'static str) {
+        if let Some(country) = self.licensed.iter().find(|code| *code == payee_country) {
+            return (country, "payee country is licensed");
+        }
+        if let Some(home) = self.home.as_deref() {
+            if let Some(country) = self.licensed.iter().find(|code| *code == home) {
+                return (country, "PSP home Member State");
+            }
+        }
+        let country = &self.licensed[self.fallback_idx % self.licensed.len()];
+        self.fallback_idx = self.fallback_idx.saturating_add(1);
+        (country, "round-robin fallback")
+    }
+}
+
+fn trace_license(tracer: &mut RecordTracer, payee: &PayeeGroup, reason: &str) {
+    for tx in &payee.transactions {
+        tracer.include(tx, "license_split", reason);
+    }
+}
+
+// A BIC is only reported next to an account; a payee with nothing else is
+// reported through its PSP as representative.
+fn collect_payee_accounts(
+    transactions: &[PaymentRecord],
+    mode: PayeeAccountMode,
+) -> Result<Vec<PayeeAccount>, String> {
     let mut ibans: BTreeMap<String, String> = BTreeMap::new();
     let mut obans: BTreeMap<String, String> = BTreeMap::new();
     let mut others: BTreeMap<String, String> = BTreeMap::new();
@@ -377,118 +2736,220 @@ fn collect_payee_accounts(transactions: &[PaymentRecord]) -> Result<Vec<PayeeAcc
     }
 
     let mut accounts: Vec<PayeeAccount> = Vec::new();
-    if let Some((id, account_type)) = ibans.iter().next() {
-        accounts.push(PayeeAccount {
-            id: id.clone(),
-            account_type: account_type.clone(),
-        });
-    } else if let Some((id, account_type)) = obans.iter().next() {
-        accounts.push(PayeeAccount {
-            id: id.clone(),
-            account_type: account_type.clone(),
-        });
-    } else if let Some((id, account_type)) = others.iter().next() {
-        accounts.push(PayeeAccount {
-            id: id.clone(),
-            account_type: account_type.clone(),
-        });
+    if mode == PayeeAccountMode::All {
+        accounts.extend(
+            ibans
+                .iter()
+                .chain(&obans)
+                .chain(&others)
+                .map(|(id, account_type)| PayeeAccount::new(id, account_type)),
+        );
+    } else if let Some((id, account_type)) = ibans
+        .iter()
+        .next()
+        .or_else(|| obans.iter().next())
+        .or_else(|| others.iter().next())
+    {
+        accounts.push(PayeeAccount::new(id, account_type));
     }
 
     if !accounts.is_empty() {
-        if let Some((id, account_type)) = bics.iter().next() {
-            accounts.push(PayeeAccount {
-                id: id.clone(),
-                account_type: account_type.clone(),
-            });
+        let bics = bics
+            .iter()
+            .map(|(id, account_type)| PayeeAccount::new(id, account_type));
+        match mode {
+            PayeeAccountMode::First => accounts.extend(bics.take(1)),
+            PayeeAccountMode::All => accounts.extend(bics),
         }
     }
 
     if accounts.is_empty() {
-        accounts.push(PayeeAccount {
-            id: String::new(),
-            account_type: String::new(),
-        });
+        accounts.push(PayeeAccount::new("", ""));
     }
 
     Ok(accounts)
 }
 
-fn write_report(report: &CesopReport, path: &Path) -> Result<(), String> {
+pub(crate) fn write_report(report: &CesopReport, path: &Path) -> Result<MessageEntry, String> {
+    let payees = report.payees.iter().map(|payee| Ok(Cow::Borrowed(payee)));
+    Ok(write_report_payees(report, payees, path)?)
+}
+
+// Writes the message header of `report` with `payees`, which are taken one at
+// a time.
+fn write_report_payees<'a>(
+    report: &CesopReport,
+    payees: impl Iterator<Item = Result<Cow<'a, PayeeGroup>, Error>>,
+    path: &Path,
+) -> Result<MessageEntry, Error> {
     let file = File::create(path).map_err(|err| err.to_string())?;
-    let mut writer = Writer::new_with_indent(BufWriter::new(file), b' ', 2);
+    let mut writer = report.xml_style.writer(BufWriter::new(file));
+    let (year, quarter) = report.period();
+    let message_ref_id = ref_id(
+        report.ref_seed.as_deref(),
+        &[
+            &report.reporting_psp_id,
+            &format!("{}Q{}", year, quarter),
+            &report.transmitting_country,
+            &file_name(path),
+        ],
+    );
+
+    write_root_start(&mut writer, report.schema)?;
+    write_message_spec(
+        &mut writer,
+        &report.transmitting_country,
+        &report.message_type_indic,
+        &message_ref_id,
+        None,
+        report.period,
+        report.timestamp,
+    )?;
+    let payees = write_payment_body(&mut writer, report, payees, &message_ref_id)?;
+    write_end(&mut writer, "CESOP")?;
+
+    Ok(MessageEntry {
+        file: file_name(path),
+        message_ref_id,
+        corr_message_ref_id: None,
+        message_type_indic: report.message_type_indic.clone(),
+        transmitting_country: report.transmitting_country.clone(),
+        psp_id: report.reporting_psp_id.clone(),
+        psp_name: report.reporting_psp_name.clone(),
+        year: report.period.year,
+        quarter: report.period.quarter,
+        schema_version: Some(report.schema.profile().version().to_string()),
+        xml_style: Some(report.xml_style.to_string()),
+        ref_seed: report.ref_seed.clone(),
+        payees,
+    })
+}
 
+fn write_root_start<W: std::io::Write>(
+    writer: &mut Writer<W>,
+    schema: SchemaVersion,
+) -> Result<(), String> {
+    let profile = schema.profile();
     let mut root = BytesStart::new("CESOP");
-    root.push_attribute(("xmlns", "urn:ec.europa.eu:taxud:fiscalis:cesop:v1"));
-    root.push_attribute(("xmlns:cm", "urn:eu:taxud:commontypes:v1"));
-    root.push_attribute(("xmlns:iso", "urn:eu:taxud:isotypes:v1"));
-    root.push_attribute(("version", "4.03"));
+    for attr in profile.namespaces() {
+        root.push_attribute(*attr);
+    }
+    root.push_attribute(("version", profile.version()));
     writer
         .write_event(Event::Start(root))
-        .map_err(|err| err.to_string())?;
-
-    write_message_spec(&mut writer, report)?;
-    write_payment_body(&mut writer, report)?;
-
-    writer
-        .write_event(Event::End(BytesEnd::new("CESOP")))
-        .map_err(|err| err.to_string())?;
-
-    Ok(())
+        .map_err(|err| err.to_string())
 }
 
 fn write_message_spec<W: std::io::Write>(
     writer: &mut Writer<W>,
-    report: &CesopReport,
+    transmitting_country: &str,
+    message_type_indic: &str,
+    message_ref_id: &str,
+    corr_message_ref_id: Option<&str>,
+    period: PeriodKey,
+    timestamp: Option<DateTime<Utc>>,
 ) -> Result<(), String> {
     write_start(writer, "MessageSpec", &[])?;
-    write_text_element(writer, "TransmittingCountry", &report.transmitting_country)?;
+    write_text_element(writer, "TransmittingCountry", transmitting_country)?;
     write_text_element(writer, "MessageType", "PMT")?;
-    write_text_element(writer, "MessageTypeIndic", &report.message_type_indic)?;
-    write_text_element(writer, "MessageRefId", &uuid::Uuid::new_v4().to_string())?;
+    write_text_element(writer, "MessageTypeIndic", message_type_indic)?;
+    write_text_element(writer, "MessageRefId", message_ref_id)?;
+    if let Some(corr) = corr_message_ref_id {
+        write_text_element(writer, "CorrMessageRefId", corr)?;
+    }
 
     write_start(writer, "ReportingPeriod", &[])?;
-    write_text_element(writer, "Quarter", &report.period.quarter.to_string())?;
-    write_text_element(writer, "Year", &report.period.year.to_string())?;
+    write_text_element(writer, "Quarter", &period.quarter.to_string())?;
+    write_text_element(writer, "Year", &period.year.to_string())?;
     write_end(writer, "ReportingPeriod")?;
 
-    let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+    let timestamp = timestamp
+        .unwrap_or_else(Utc::now)
+        .to_rfc3339_opts(SecondsFormat::Millis, true);
     write_text_element(writer, "Timestamp", &timestamp)?;
     write_end(writer, "MessageSpec")?;
     Ok(())
 }
 
-fn write_payment_body<W: std::io::Write>(
+fn write_payment_body<'a, W: std::io::Write>(
     writer: &mut Writer<W>,
     report: &CesopReport,
-) -> Result<(), String> {
+    payees: impl Iterator<Item = Result<Cow<'a, PayeeGroup>, Error>>,
+    message_ref_id: &str,
+) -> Result<Vec<PayeeEntry>, Error> {
     write_start(writer, "PaymentDataBody", &[])?;
+    write_reporting_psp(writer, &report.reporting_psp_id, &report.reporting_psp_name)?;
+
+    let mut entries = Vec::new();
+    for payee in payees {
+        let payee = payee?;
+        let payee = payee.as_ref();
+        let doc_ref_id = ref_id(
+            report.ref_seed.as_deref(),
+            &[message_ref_id, &payee.payee_id, &payee.payee_country],
+        );
+        write_reported_payee(
+            writer,
+            payee,
+            report.address_format,
+            "CESOP1",
+            &doc_ref_id,
+            None,
+        )?;
+        entries.push(PayeeEntry {
+            payee_id: payee.payee_id.clone(),
+            name: payee.payee_name.clone(),
+            country: payee.payee_country.clone(),
+            doc_type_indic: "CESOP1".to_string(),
+            doc_ref_id,
+            corr_doc_ref_id: None,
+            superseded_by: None,
+            digest: Some(payee_digest(payee)),
+            transactions: payee
+                .transactions
+                .iter()
+                .map(|tx| tx.payment_id.clone())
+                .collect(),
+        });
+    }
+
+    write_end(writer, "PaymentDataBody")?;
+    Ok(entries)
+}
 
+fn write_reporting_psp<W: std::io::Write>(
+    writer: &mut Writer<W>,
+    psp_id: &str,
+    psp_name: &str,
+) -> Result<(), String> {
     write_start(writer, "ReportingPSP", &[])?;
-    write_text_element_with_attrs(
-        writer,
-        "PSPId",
-        &report.reporting_psp_id,
-        &[("PSPIdType", "BIC")],
-    )?;
-    write_text_element_with_attrs(
-        writer,
-        "Name",
-        &report.reporting_psp_name,
-        &[("nameType", "BUSINESS")],
-    )?;
-    write_end(writer, "ReportingPSP")?;
+    write_text_element_with_attrs(writer, "PSPId", psp_id, &[("PSPIdType", "BIC")])?;
+    write_text_element_with_attrs(writer, "Name", psp_name, &[("nameType", "BUSINESS")])?;
+    write_end(writer, "ReportingPSP")
+}
 
-    for payee in &report.payees {
-        write_reported_payee(writer, payee)?;
+fn write_doc_spec<W: std::io::Write>(
+    writer: &mut Writer<W>,
+    doc_type_indic: &str,
+    doc_ref_id: &str,
+    corr_doc_ref_id: Option<&str>,
+) -> Result<(), String> {
+    write_start(writer, "DocSpec", &[])?;
+    write_text_element(writer, "cm:DocTypeIndic", doc_type_indic)?;
+    write_text_element(writer, "cm:DocRefId", doc_ref_id)?;
+    if let Some(corr) = corr_doc_ref_id {
+        write_text_element(writer, "cm:CorrDocRefId", corr)?;
     }
-
-    write_end(writer, "PaymentDataBody")?;
-    Ok(())
+    write_end(writer, "DocSpec")
 }
 
 fn write_reported_payee<W: std::io::Write>(
     writer: &mut Writer<W>,
     payee: &PayeeGroup,
+    address_format: AddressFormat,
+    doc_type_indic: &str,
+    doc_ref_id: &str,
+    corr_doc_ref_id: Option<&str>,
 ) -> Result<(), String> {
     write_start(writer, "ReportedPayee", &[])?;
     write_text_element_with_attrs(writer, "Name", &payee.payee_name, &[("nameType", "BUSINESS")])?;
@@ -496,8 +2957,24 @@ fn write_reported_payee<W: std::io::Write>(
 
     write_start(writer, "Address", &[])?;
     write_text_element(writer, "cm:CountryCode", &payee.payee_country)?;
-    if let Some(address_free) = build_address_free(payee) {
-        write_text_element(writer, "cm:AddressFree", &address_free)?;
+    let structured = payee.payee_city.as_deref().is_some_and(|city| !city.is_empty())
+        || payee
+            .payee_postcode
+            .as_deref()
+            .is_some_and(|postcode| !postcode.is_empty());
+    let (fix, free) = match address_format {
+        AddressFormat::Free => (false, true),
+        AddressFormat::Fix => (true, false),
+        AddressFormat::Both => (true, true),
+        AddressFormat::Auto => (structured, !structured),
+    };
+    if fix {
+        write_address_fix(writer, payee)?;
+    }
+    if free {
+        if let Some(address_free) = build_address_free(payee) {
+            write_text_element(writer, "cm:AddressFree", &address_free)?;
+        }
     }
     write_end(writer, "Address")?;
 
@@ -535,8 +3012,9 @@ fn write_reported_payee<W: std::io::Write>(
             write_text_element(writer, "AccountIdentifier", "")?;
             continue;
         }
+        let country = account.country();
         let mut attrs = vec![
-            ("CountryCode", payee.payee_country.as_str()),
+            ("CountryCode", country.as_deref().unwrap_or(&payee.payee_country)),
             ("type", account.account_type.as_str()),
         ];
         if account.account_type == "Other" {
@@ -549,6 +3027,7 @@ fn write_reported_payee<W: std::io::Write>(
         write_reported_transaction(writer, tx)?;
     }
 
+    // Representative_Type has no country or address: the BIC locates the PSP.
     if let Some(rep) = payee.representative.as_ref() {
         write_start(writer, "Representative", &[])?;
         write_text_element_with_attrs(
@@ -563,13 +3042,29 @@ fn write_reported_payee<W: std::io::Write>(
         write_end(writer, "Representative")?;
     }
 
-    write_start(writer, "DocSpec", &[])?;
-    write_text_element(writer, "cm:DocTypeIndic", "CESOP1")?;
-    write_text_element(writer, "cm:DocRefId", &uuid::Uuid::new_v4().to_string())?;
-    write_end(writer, "DocSpec")?;
+    write_doc_spec(writer, doc_type_indic, doc_ref_id, corr_doc_ref_id)?;
 
-    write_end(writer, "ReportedPayee")?;
-    Ok(())
+    write_end(writer, "ReportedPayee")
+}
+
+// A random MessageRefId or DocRefId, or with a ref seed one derived from the
+// seed and `inputs`, so that rendering the same data again repeats it. The
+// hash is shaped as a version 4 UUID to satisfy the schema pattern.
+fn ref_id(ref_seed: Option<&str>, inputs: &[&str]) -> String {
+    let Some(seed) = ref_seed else {
+        return uuid::Uuid::new_v4().to_string();
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(seed.as_bytes());
+    for input in inputs {
+        hasher.update([0]);
+        hasher.update(input.as_bytes());
+    }
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hasher.finalize()[..16]);
+    uuid::Builder::from_random_bytes(bytes)
+        .into_uuid()
+        .to_string()
 }
 
 fn write_reported_transaction<W: std::io::Write>(
@@ -608,7 +3103,13 @@ fn write_reported_transaction<W: std::io::Write>(
     write_start(writer, "PaymentMethod", &[])?;
     write_text_element(writer, "cm:PaymentMethodType", &tx.payment_method)?;
     if tx.payment_method == "Other" {
-        write_text_element(writer, "cm:PaymentMethodOther", "Other")?;
+        let other = tx
+            .payment_method_other
+            .as_deref()
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+            .unwrap_or("Other");
+        write_text_element(writer, "cm:PaymentMethodOther", other)?;
     }
     write_end(writer, "PaymentMethod")?;
 
@@ -631,6 +3132,47 @@ fn write_reported_transaction<W: std::io::Write>(
     Ok(())
 }
 
+// Street, house number, postcode and city in `AddressFix` order. A leading
+// number on the address line ("12 Main St") is the BuildingIdentifier.
+fn write_address_fix<W: std::io::Write>(
+    writer: &mut Writer<W>,
+    payee: &PayeeGroup,
+) -> Result<(), String> {
+    let non_empty = |value: Option<&str>| {
+        value
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+    let line = non_empty(payee.payee_address_line.as_deref());
+    let (building, street) = match line.as_deref().and_then(|line| line.split_once(' ')) {
+        Some((number, rest))
+            if number.starts_with(|ch: char| ch.is_ascii_digit()) && !rest.trim().is_empty() =>
+        {
+            (Some(number.to_string()), Some(rest.trim().to_string()))
+        }
+        _ => (None, line),
+    };
+    let postcode = non_empty(payee.payee_postcode.as_deref());
+    let city = non_empty(payee.payee_city.as_deref());
+    if street.is_none() && postcode.is_none() && city.is_none() {
+        return Ok(());
+    }
+
+    write_start(writer, "cm:AddressFix", &[])?;
+    for (name, value) in [
+        ("cm:Street", street),
+        ("cm:BuildingIdentifier", building),
+        ("cm:PostCode", postcode),
+        ("cm:City", city),
+    ] {
+        if let Some(value) = value {
+            write_text_element(writer, name, &value)?;
+        }
+    }
+    write_end(writer, "cm:AddressFix")
+}
+
 fn build_address_free(payee: &PayeeGroup) -> Option<String> {
     let mut parts = Vec::new();
     if let Some(line) = payee.payee_address_line.as_deref() {
@@ -729,3 +3271,140 @@ fn write_text_element_with_attrs<W: std::io::Write>(
         .map_err(|err| err.to_string())?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{generate_records, GeneratorConfig};
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("cesop-xml-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn render_options() -> RenderOptions {
+        RenderOptions {
+            ref_seed: Some("streaming-test".to_string()),
+            timestamp: Some("2025-01-01T00:00:00Z".parse().unwrap()),
+            ..RenderOptions::default()
+        }
+    }
+
+    fn write_input(dir: &Path) -> PathBuf {
+        let data = generate_records(&GeneratorConfig::default(), 7).unwrap();
+        let input = dir.join("payments.csv");
+        crate::compression::write_csv(&input, &data.records).unwrap();
+        input
+    }
+
+    fn xml_files(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+        let mut files = BTreeMap::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(next) = pending.pop() {
+            for entry in std::fs::read_dir(next).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "xml") {
+                    let contents = std::fs::read(&path).unwrap();
+                    files.insert(path.strip_prefix(dir).unwrap().to_path_buf(), contents);
+                }
+            }
+        }
+        files
+    }
+
+    #[test]
+    fn streamed_bucket_is_sorted_in_bounded_runs() {
+        let dir = TempDir::new();
+        let input = write_input(&dir.0);
+        let options = render_options();
+        let mut tracer = RecordTracer::new(&[]);
+        let mut spilled = spill_reports_from_csv(
+            &input,
+            &options,
+            &dir.0,
+            &mut tracer,
+            &mut PhaseTimings::default(),
+        )
+        .unwrap();
+        spilled.chunk_records = 100;
+
+        for path in spilled.buckets.values() {
+            let bucket = spilled.sort_bucket(path, &options, &mut tracer).unwrap();
+            assert!(bucket.runs.len() > 1);
+            for run in &bucket.runs {
+                let records = csv::Reader::from_path(run).unwrap().records().count();
+                assert!(records <= 100, "{} records in one run", records);
+            }
+        }
+    }
+
+    // Renders `input` in memory and streamed into `dir` and compares the
+    // messages.
+    fn assert_streamed_matches(dir: &Path, input: &Path, options: &RenderOptions) {
+        // Some payees are split across messages too.
+        let limits = MessageLimits {
+            max_bytes: Some(24_000),
+            max_payees: Some(2),
+        };
+
+        let in_memory = dir.join("in-memory");
+        let reports = build_reports_from_csv(
+            input,
+            options,
+            &mut RecordTracer::new(&[]),
+            &mut PhaseTimings::default(),
+        )
+        .unwrap();
+        write_reports(&reports, &in_memory, OutputLayout::default(), &limits, 1, None).unwrap();
+
+        let streamed = dir.join("streamed");
+        let mut tracer = RecordTracer::new(&[]);
+        let mut timings = PhaseTimings::default();
+        let mut spilled =
+            spill_reports_from_csv(input, options, &streamed, &mut tracer, &mut timings).unwrap();
+        // More runs than can be opened at once, so they are merged in batches.
+        spilled.chunk_records = 5;
+        let mut writer =
+            ReportWriter::new(&streamed, OutputLayout::default(), &limits, 1, None).unwrap();
+        spilled
+            .write(options, &mut writer, &mut tracer, &mut timings)
+            .unwrap();
+        writer.finish().unwrap();
+        drop(spilled);
+
+        let expected = xml_files(&in_memory);
+        assert!(expected.len() > reports.len());
+        assert_eq!(xml_files(&streamed), expected);
+    }
+
+    #[test]
+    fn streamed_reports_match_in_memory_reports() {
+        let dir = TempDir::new();
+        let input = write_input(&dir.0);
+        assert_streamed_matches(&dir.0, &input, &render_options());
+    }
+
+    #[test]
+    fn streamed_license_split_matches_in_memory_reports() {
+        let dir = TempDir::new();
+        let input = write_input(&dir.0);
+        let options = RenderOptions {
+            licensed_countries: Some(vec!["FR".to_string(), "DE".to_string(), "NL".to_string()]),
+            ..render_options()
+        };
+        assert_streamed_matches(&dir.0, &input, &options);
+    }
+}
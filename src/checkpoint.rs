@@ -0,0 +1,294 @@
+use crate::codes;
+use crate::compression::{self, Compression};
+use crate::models::PaymentRecord;
+use crate::run_summary::run_id;
+use crate::util::sha256_file;
+use chrono::{SecondsFormat, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Seek;
+use std::path::{Path, PathBuf};
+
+const RENDER_CHECKPOINT_FILE: &str = ".render-checkpoint.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerateCheckpoint {
+    pub run_id: String,
+    pub seed: u64,
+    pub fingerprint: String,
+    pub records_total: usize,
+    pub records_written: usize,
+    pub bytes_written: u64,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderCheckpoint {
+    pub run_id: String,
+    pub input_sha256: String,
+    pub reports_total: usize,
+    pub completed: Vec<String>,
+    pub updated_at: String,
+}
+
+pub fn generate_checkpoint_path(output: &Path) -> PathBuf {
+    let file_name = output
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "output".to_string());
+    output.with_file_name(format!("{}.checkpoint.json", file_name))
+}
+
+pub fn load_checkpoint<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(path).map_err(|err| {
+        codes::IN_READ_FAILED.err(format!("failed to read {}: {}", path.display(), err))
+    })?;
+    let checkpoint = serde_json::from_str(&contents).map_err(|err| {
+        codes::IN_FILE_INVALID.err(format!("invalid checkpoint {}: {}", path.display(), err))
+    })?;
+    Ok(Some(checkpoint))
+}
+
+fn save_checkpoint<T: Serialize>(path: &Path, checkpoint: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(checkpoint).map_err(|err| err.to_string())?;
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, json).map_err(|err| err.to_string())?;
+    std::fs::rename(&tmp_path, path).map_err(|err| err.to_string())
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+pub fn write_generated_csv(
+    output: &Path,
+    records: &mut [PaymentRecord],
+    seed: u64,
+    fingerprint: &str,
+    checkpoint_every: usize,
+    resume_from: Option<&GenerateCheckpoint>,
+) -> Result<usize, String> {
+    let checkpoint_path = generate_checkpoint_path(output);
+    // Byte offsets into a compressed stream cannot be truncated and resumed,
+    // so compressed output is written in one pass without checkpoints.
+    if Compression::from_path(output) != Compression::None {
+        if resume_from.is_some() {
+            return Err(codes::GEN_RESUME_MISMATCH.err(format!(
+                "{} is compressed; compressed output cannot be resumed",
+                output.display()
+            )));
+        }
+        compression::write_csv(output, records)?;
+        return Ok(0);
+    }
+    let mut checkpoint = GenerateCheckpoint {
+        run_id: run_id().to_string(),
+        seed,
+        fingerprint: fingerprint.to_string(),
+        records_total: records.len(),
+        records_written: 0,
+        bytes_written: 0,
+        updated_at: now(),
+    };
+
+    let mut file = match resume_from {
+        Some(previous) => {
+            if previous.seed != seed || previous.fingerprint != fingerprint {
+                return Err(codes::GEN_RESUME_MISMATCH.err(format!(
+                    "checkpoint {} was written for a different seed or configuration",
+                    checkpoint_path.display()
+                )));
+            }
+            remap_written_ids(output, records, previous)?;
+            let file = OpenOptions::new()
+                .write(true)
+                .open(output)
+                .map_err(|err| err.to_string())?;
+            file.set_len(previous.bytes_written)
+                .map_err(|err| err.to_string())?;
+            checkpoint.records_written = previous.records_written;
+            checkpoint.bytes_written = previous.bytes_written;
+            file
+        }
+        None => std::fs::File::create(output).map_err(|err| err.to_string())?,
+    };
+    let resumed = checkpoint.records_written;
+    file.seek(std::io::SeekFrom::End(0))
+        .map_err(|err| err.to_string())?;
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(resumed == 0)
+        .from_writer(file);
+    let every = checkpoint_every.max(1);
+    for (idx, record) in records.iter().enumerate().skip(resumed) {
+        writer.serialize(record).map_err(|err| err.to_string())?;
+        let written = idx + 1;
+        if checkpoint_every > 0 && written % every == 0 && written < records.len() {
+            writer.flush().map_err(|err| err.to_string())?;
+            checkpoint.records_written = written;
+            checkpoint.bytes_written = writer
+                .get_ref()
+                .metadata()
+                .map_err(|err| err.to_string())?
+                .len();
+            checkpoint.updated_at = now();
+            save_checkpoint(&checkpoint_path, &checkpoint)?;
+            log::debug!(
+                records_written = written,
+                bytes_written = checkpoint.bytes_written;
+                "generate checkpoint written"
+            );
+        }
+    }
+    writer.flush().map_err(|err| err.to_string())?;
+    if checkpoint_path.exists() {
+        std::fs::remove_file(&checkpoint_path).map_err(|err| err.to_string())?;
+    }
+    Ok(resumed)
+}
+
+// Payment IDs are random per run, so records regenerated from the same seed
+// carry new IDs. Reuse the IDs already on disk (including the originals that
+// written refunds point at) so refund links survive the resume.
+fn remap_written_ids(
+    output: &Path,
+    records: &mut [PaymentRecord],
+    previous: &GenerateCheckpoint,
+) -> Result<(), String> {
+    if previous.records_written > records.len() {
+        return Err(
+            codes::GEN_RESUME_MISMATCH.err("checkpoint is ahead of the regenerated record set")
+        );
+    }
+    let mut reader = csv::Reader::from_path(output).map_err(|err| err.to_string())?;
+    let mut id_map: HashMap<String, String> = HashMap::new();
+    for (idx, row) in reader
+        .deserialize::<PaymentRecord>()
+        .take(previous.records_written)
+        .enumerate()
+    {
+        let written = row.map_err(|err| err.to_string())?;
+        let regenerated = &mut records[idx];
+        if written.payee_id != regenerated.payee_id
+            || written.execution_time != regenerated.execution_time
+            || written.amount != regenerated.amount
+        {
+            return Err(codes::GEN_RESUME_MISMATCH.err(format!(
+                "{} row {} does not match the regenerated data; cannot resume",
+                output.display(),
+                idx + 1
+            )));
+        }
+        id_map.insert(regenerated.payment_id.clone(), written.payment_id.clone());
+        if let (Some(old), Some(new)) = (
+            regenerated.corr_payment_id.clone(),
+            written.corr_payment_id.clone(),
+        ) {
+            id_map.insert(old, new);
+        }
+        regenerated.payment_id = written.payment_id;
+        regenerated.corr_payment_id = written.corr_payment_id;
+    }
+    for record in records.iter_mut().skip(previous.records_written) {
+        if let Some(mapped) = id_map.get(&record.payment_id) {
+            record.payment_id = mapped.clone();
+        }
+        if let Some(corr) = record.corr_payment_id.as_ref() {
+            if let Some(mapped) = id_map.get(corr) {
+                record.corr_payment_id = Some(mapped.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderProgress {
+    path: PathBuf,
+    state: RenderCheckpoint,
+    completed: HashSet<String>,
+}
+
+impl RenderProgress {
+    pub fn start(
+        output_dir: &Path,
+        input: &Path,
+        reports_total: usize,
+        resume: bool,
+    ) -> Result<Self, String> {
+        let path = output_dir.join(RENDER_CHECKPOINT_FILE);
+        let input_sha256 = sha256_file(input)?;
+        let previous: Option<RenderCheckpoint> = if resume {
+            load_checkpoint(&path)?
+        } else {
+            None
+        };
+        let state = match previous {
+            Some(previous) => {
+                if previous.input_sha256 != input_sha256 {
+                    return Err(codes::RND_RESUME_MISMATCH.err(format!(
+                        "checkpoint {} was written for a different input",
+                        path.display()
+                    )));
+                }
+                previous
+            }
+            None => {
+                if resume {
+                    log::warn!(
+                        "no render checkpoint found in {}; starting from scratch",
+                        output_dir.display()
+                    );
+                }
+                RenderCheckpoint {
+                    run_id: run_id().to_string(),
+                    input_sha256,
+                    reports_total,
+                    completed: Vec::new(),
+                    updated_at: now(),
+                }
+            }
+        };
+        let completed = state.completed.iter().cloned().collect();
+        Ok(Self {
+            path,
+            state,
+            completed,
+        })
+    }
+
+    pub fn is_completed(&self, output: &Path) -> bool {
+        output
+            .file_name()
+            .map(|name| self.completed.contains(name.to_string_lossy().as_ref()))
+            .unwrap_or(false)
+            && output.exists()
+    }
+
+    pub fn completed_count(&self) -> usize {
+        self.completed.len()
+    }
+
+    pub fn mark_completed(&mut self, output: &Path) -> Result<(), String> {
+        let name = output
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if self.completed.insert(name.clone()) {
+            self.state.completed.push(name);
+        }
+        self.state.updated_at = now();
+        save_checkpoint(&self.path, &self.state)
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        if self.path.exists() {
+            std::fs::remove_file(&self.path).map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,625 @@
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use CodeCategory::{Generate, Input, Preflight, Render, Validate};
+
+// Stable identifiers for every error and issue the tool reports. Messages may
+// be reworded between releases; codes never change meaning once published.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueLevel {
+    Error,
+    Warning,
+}
+
+impl IssueLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IssueLevel::Error => "error",
+            IssueLevel::Warning => "warning",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeCategory {
+    Generate,
+    Preflight,
+    Render,
+    Validate,
+    Input,
+}
+
+impl CodeCategory {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CodeCategory::Generate => "generate",
+            CodeCategory::Preflight => "preflight",
+            CodeCategory::Render => "render",
+            CodeCategory::Validate => "validate",
+            CodeCategory::Input => "input",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Code {
+    pub code: &'static str,
+    pub category: CodeCategory,
+    pub level: IssueLevel,
+    pub summary: &'static str,
+}
+
+const fn error(code: &'static str, category: CodeCategory, summary: &'static str) -> Code {
+    Code {
+        code,
+        category,
+        level: IssueLevel::Error,
+        summary,
+    }
+}
+
+const fn warning(code: &'static str, category: CodeCategory, summary: &'static str) -> Code {
+    Code {
+        code,
+        category,
+        level: IssueLevel::Warning,
+        summary,
+    }
+}
+
+pub const GEN_PAYEES_ZERO: Code = error("GEN001", Generate, "payees must be greater than 0");
+pub const GEN_PSPS_ZERO: Code = error("GEN002", Generate, "psps must be greater than 0");
+pub const GEN_SEGMENTS_EXCEED_PAYEES: Code = error(
+    "GEN003",
+    Generate,
+    "micro/near/large payees cannot exceed total payees",
+);
+pub const GEN_INVALID_QUARTER: Code = error("GEN004", Generate, "quarter must be 1..4");
+pub const GEN_RATIO_OUT_OF_RANGE: Code = error("GEN005", Generate, "ratio must be 0..1");
+pub const GEN_PSP_COUNTRY_NOT_EU: Code =
+    error("GEN006", Generate, "PSP country is not an EU Member State");
+pub const GEN_RECORDS_OUT_OF_RANGE: Code = error(
+    "GEN007",
+    Generate,
+    "record count is outside what the payee segments can hold",
+);
+pub const GEN_APPEND_CAPACITY: Code = error(
+    "GEN008",
+    Generate,
+    "appended records exceed the remaining payee capacity",
+);
+pub const GEN_IDENTIFIERS_EXHAUSTED: Code =
+    error("GEN009", Generate, "unique identifiers could not be generated");
+pub const GEN_VERIFICATION_FAILED: Code = error(
+    "GEN010",
+    Generate,
+    "generated dataset failed its invariant checks",
+);
+pub const GEN_NO_PAYEE_MIX: Code = error(
+    "GEN011",
+    Generate,
+    "could not derive a valid payee mix for the requested scale",
+);
+pub const GEN_TIME_PROFILE_INVALID: Code = error(
+    "GEN012",
+    Generate,
+    "time profile weights are invalid",
+);
+pub const GEN_REFUND_DELAY_INVALID: Code = error(
+    "GEN013",
+    Generate,
+    "refund delay must be MIN..MAX days",
+);
+pub const GEN_REFUNDS_PER_PAYMENT_ZERO: Code = error(
+    "GEN014",
+    Generate,
+    "max_refunds_per_payment must be at least 1",
+);
+pub const GEN_COUNTRY_WEIGHTS_INVALID: Code = error(
+    "GEN015",
+    Generate,
+    "country weights are invalid",
+);
+pub const GEN_DUPLICATE_PSPS_TOO_FEW: Code = error(
+    "GEN016",
+    Generate,
+    "cross-PSP duplicates need at least two EU PSPs",
+);
+pub const GEN_MULTI_PSP_TOO_FEW: Code = error(
+    "GEN017",
+    Generate,
+    "multi-PSP payees need at least two EU PSPs",
+);
+pub const GEN_FIELD_RATES_INVALID: Code = error(
+    "GEN018",
+    Generate,
+    "field rates must be FIELD=F entries for email, web, vat or tax",
+);
+pub const GEN_RESUME_MISMATCH: Code = error(
+    "GEN019",
+    Generate,
+    "existing output does not match its checkpoint and cannot be resumed",
+);
+pub const GEN_ROSTER_INVALID: Code = error("GEN020", Generate, "PSP roster row is invalid");
+pub const GEN_PORTFOLIO_INVALID: Code = error("GEN021", Generate, "portfolio file is invalid");
+pub const GEN_SCENARIO_INVALID: Code = error("GEN022", Generate, "scenario file is invalid");
+pub const GEN_SAMPLE_EMPTY: Code = error("GEN023", Generate, "sample selects no payees");
+pub const GEN_PSEUDONYMIZE_KEY_EMPTY: Code =
+    error("GEN024", Generate, "pseudonymization key is empty");
+pub const GEN_REQUARTER_SOURCE: Code = error(
+    "GEN025",
+    Generate,
+    "records do not fall in a single source quarter",
+);
+
+pub const PF_ROW_UNPARSEABLE: Code = error("PF001", Preflight, "row could not be parsed");
+pub const PF_DUPLICATE_PAYMENT_ID: Code =
+    error("PF002", Preflight, "duplicate payment_id detected");
+pub const PF_MULTIPLE_PSP_NAMES: Code =
+    error("PF003", Preflight, "multiple PSP names found for one psp_id");
+pub const PF_REFUND_TARGET_MISSING: Code =
+    warning("PF004", Preflight, "refund references missing payment_id");
+pub const PF_PAYMENT_ID_REQUIRED: Code = error("PF005", Preflight, "payment_id is required");
+pub const PF_EXECUTION_TIME_REQUIRED: Code =
+    error("PF006", Preflight, "execution_time is required");
+pub const PF_EXECUTION_TIME_FORMAT: Code = error(
+    "PF007",
+    Preflight,
+    "execution_time must be RFC3339 with timezone",
+);
+pub const PF_AMOUNT_FORMAT: Code =
+    error("PF008", Preflight, "amount must be a decimal with two digits");
+pub const PF_CURRENCY_FORMAT: Code =
+    error("PF009", Preflight, "currency must be ISO-4217 alpha-3");
+pub const PF_PAYER_COUNTRY_FORMAT: Code =
+    error("PF010", Preflight, "payer_country must be ISO-3166 alpha-2");
+pub const PF_PAYER_COUNTRY_NOT_EU: Code =
+    error("PF011", Preflight, "payer_country must be an EU Member State");
+pub const PF_PAYEE_COUNTRY_UNDERIVABLE: Code = error(
+    "PF012",
+    Preflight,
+    "payee country cannot be derived from account identifier or payee PSP BIC",
+);
+pub const PF_PAYEE_COUNTRY_DERIVED: Code = warning(
+    "PF013",
+    Preflight,
+    "payee_country missing; derived from identifier",
+);
+pub const PF_PAYEE_COUNTRY_FORMAT: Code =
+    error("PF014", Preflight, "payee_country must be ISO-3166 alpha-2");
+pub const PF_PAYEE_COUNTRY_MISMATCH: Code = error(
+    "PF015",
+    Preflight,
+    "payee_country does not match derived location",
+);
+pub const PF_POSTCODE_MISMATCH: Code = warning(
+    "PF016",
+    Preflight,
+    "payee_postcode does not match the payee country's postcode format",
+);
+pub const PF_NOT_CROSS_BORDER: Code =
+    warning("PF017", Preflight, "payment is not cross-border (not reportable)");
+pub const PF_PAYEE_ID_REQUIRED: Code = error("PF018", Preflight, "payee_id is required");
+pub const PF_PAYEE_NAME_REQUIRED: Code = error("PF019", Preflight, "payee_name is required");
+pub const PF_REPRESENTATIVE_REPORTED: Code = warning(
+    "PF020",
+    Preflight,
+    "payee_account missing; representative PSP will be reported",
+);
+pub const PF_PAYEE_ACCOUNT_MISSING: Code = error(
+    "PF021",
+    Preflight,
+    "payee_account missing without a valid payee PSP identifier",
+);
+pub const PF_ACCOUNT_TYPE_WITHOUT_ACCOUNT: Code = warning(
+    "PF022",
+    Preflight,
+    "payee_account_type provided without payee_account",
+);
+pub const PF_ACCOUNT_TYPE_INVALID: Code = error(
+    "PF023",
+    Preflight,
+    "payee_account_type must be IBAN/OBAN/BIC/Other",
+);
+pub const PF_IBAN_COUNTRY_MISSING: Code = error("PF024", Preflight, "IBAN country code missing");
+pub const PF_PAYER_MS_SOURCE_INVALID: Code = error(
+    "PF025",
+    Preflight,
+    "payer_ms_source must be IBAN/OBAN/BIC/Other",
+);
+pub const PF_PAYMENT_METHOD_REQUIRED: Code =
+    error("PF026", Preflight, "payment_method is required");
+pub const PF_REFUND_LINK_REQUIRED: Code =
+    error("PF027", Preflight, "refunds must include corr_payment_id");
+pub const PF_LINK_ON_NON_REFUND: Code =
+    warning("PF028", Preflight, "corr_payment_id set on non-refund");
+pub const PF_PSP_ID_REQUIRED: Code = error("PF029", Preflight, "psp_id is required");
+pub const PF_PSP_ID_FORMAT: Code = warning("PF030", Preflight, "psp_id is not a valid BIC format");
+pub const PF_PSP_NAME_REQUIRED: Code = error("PF031", Preflight, "psp_name is required");
+pub const PF_IBAN_TOO_SHORT: Code = error("PF032", Preflight, "IBAN is too short");
+pub const PF_IBAN_NOT_ALPHANUMERIC: Code = error("PF033", Preflight, "IBAN must be alphanumeric");
+pub const PF_IBAN_COUNTRY_MISMATCH: Code = error(
+    "PF034",
+    Preflight,
+    "IBAN country code does not match derived payee country",
+);
+pub const PF_IBAN_LENGTH: Code = error(
+    "PF035",
+    Preflight,
+    "IBAN length does not match country specification",
+);
+pub const PF_IBAN_LENGTH_UNKNOWN: Code =
+    warning("PF036", Preflight, "IBAN length not known for country");
+pub const PF_IBAN_CHECK_DIGITS: Code = error("PF037", Preflight, "IBAN check digits are invalid");
+pub const PF_IBAN_BBAN_FORMAT: Code = warning(
+    "PF038",
+    Preflight,
+    "IBAN BBAN does not match the country's structure",
+);
+pub const PF_PAYER_NORTHERN_IRELAND: Code = warning(
+    "PF039",
+    Preflight,
+    "payer_country XI (Northern Ireland) is outside the Union (not reportable)",
+);
+pub const PF_PAYMENT_METHOD_ALIAS: Code = warning(
+    "PF040",
+    Preflight,
+    "payment_method is not a CESOP PaymentMethodType and is mapped to one",
+);
+pub const PF_PAYMENT_METHOD_UNKNOWN: Code = error(
+    "PF041",
+    Preflight,
+    "payment_method is not in the CESOP PaymentMethodType code list",
+);
+pub const PF_PAYMENT_METHOD_OTHER: Code = warning(
+    "PF042",
+    Preflight,
+    "payment_method is not in the CESOP PaymentMethodType code list; reported as Other",
+);
+pub const PF_PAYER_COUNTRY_ACCOUNT_MISMATCH: Code = warning(
+    "PF043",
+    Preflight,
+    "payer_country does not match the payer account; render reports the account's country",
+);
+pub const PF_PAYER_MS_SOURCE_ACCOUNT_MISMATCH: Code = warning(
+    "PF044",
+    Preflight,
+    "payer_ms_source does not match payer_account_type; render reports the account type",
+);
+pub const PF_AMOUNT_OUT_OF_BOUNDS: Code = warning(
+    "PF045",
+    Preflight,
+    "amount outside the min_amount..max_amount of the rules file",
+);
+pub const PF_CURRENCY_UNKNOWN: Code =
+    error("PF046", Preflight, "currency is not an ISO 4217 code");
+pub const PF_CURRENCY_NOT_CESOP: Code = error(
+    "PF047",
+    Preflight,
+    "currency is not in the CESOP schema's currency list",
+);
+pub const PF_CURRENCY_NOT_ALLOWED: Code = error(
+    "PF048",
+    Preflight,
+    "currency is not in the currencies of the rules file",
+);
+pub const PF_OUTSIDE_PERIOD: Code = error(
+    "PF049",
+    Preflight,
+    "execution_time outside the declared reporting period",
+);
+pub const PF_MIXED_PERIODS: Code = warning(
+    "PF050",
+    Preflight,
+    "execution_time in another quarter than most rows",
+);
+pub const PF_PAYEE_ATTRIBUTE_CONFLICT: Code = warning(
+    "PF051",
+    Preflight,
+    "rows of the same psp_id and payee_id disagree on a payee attribute",
+);
+pub const PF_AMOUNT_NEGATIVE: Code = warning(
+    "PF052",
+    Preflight,
+    "amount is negative; render reports its absolute value",
+);
+pub const PF_AMOUNT_ZERO: Code = warning("PF053", Preflight, "amount is zero");
+pub const PF_BIC_STRUCTURE: Code =
+    error("PF054", Preflight, "BIC does not follow the ISO 9362 structure");
+pub const PF_RULES_INVALID: Code = error("PF055", Preflight, "preflight rules file is invalid");
+pub const PF_RATES_INVALID: Code = error("PF056", Preflight, "exchange rate table is invalid");
+pub const PF_FAILED: Code = error(
+    "PF057",
+    Preflight,
+    "preflight found errors, or warnings under --fail-on warning",
+);
+
+pub const RND_NO_RECORDS: Code = error("RND001", Render, "no records found in input CSV");
+pub const RND_MULTIPLE_PSP_NAMES: Code =
+    error("RND002", Render, "multiple PSP names found for one psp_id");
+pub const RND_TRANSMITTING_COUNTRY: Code = error(
+    "RND003",
+    Render,
+    "transmitting country is empty or cannot be derived from the PSP identifier",
+);
+pub const RND_EXECUTION_TIME: Code =
+    error("RND004", Render, "execution_time is not RFC3339 with timezone");
+pub const RND_PAYEE_COUNTRY: Code = error(
+    "RND005",
+    Render,
+    "payee country cannot be derived from account identifier or payee PSP BIC",
+);
+pub const RND_NO_REPORTS: Code =
+    error("RND006", Render, "no reports generated (no cross-border data)");
+pub const RND_MESSAGE_LIMIT: Code = error(
+    "RND007",
+    Render,
+    "message size limit is too small for a single payee and transaction",
+);
+pub const RND_PAYMENT_METHOD: Code = error(
+    "RND008",
+    Render,
+    "payment_method is not in the CESOP PaymentMethodType code list (--payment-methods strict)",
+);
+pub const RND_RESUME_MISMATCH: Code = error(
+    "RND009",
+    Render,
+    "render checkpoint was written for a different input",
+);
+pub const RND_NIL_REPORT_PSP: Code =
+    error("RND010", Render, "nil reports need a PSP identifier and name");
+pub const RND_STREAMING_UNSUPPORTED: Code = error(
+    "RND011",
+    Render,
+    "option needs every record in memory and cannot be combined with streaming",
+);
+pub const RND_COUNTRY_LIST: Code =
+    error("RND012", Render, "country list holds an invalid country code");
+pub const RND_MESSAGE_UNREADABLE: Code = error(
+    "RND013",
+    Render,
+    "XML message is not a CESOP payment data message the command can read",
+);
+pub const RND_MERGE_MISMATCH: Code = error(
+    "RND014",
+    Render,
+    "merge inputs are not reports of one PSP, period and schema",
+);
+pub const RND_NOTHING_TO_RETRACT: Code =
+    error("RND015", Render, "no payee documents to retract");
+pub const RND_NO_MESSAGES: Code = error("RND016", Render, "no messages to import or package");
+pub const RND_PSP_DIR_COLLISION: Code = error(
+    "RND017",
+    Render,
+    "two reporting PSP ids map to the same per-psp directory",
+);
+pub const RND_REGISTRY_ENTRY_INVALID: Code = error(
+    "RND018",
+    Render,
+    "RefId registry entry has an unknown schema version or XML style",
+);
+pub const RND_EXCLUDED_PSP_ROLE: Code = warning(
+    "RND101",
+    Render,
+    "record excluded: reporting PSP acts for the payer and the payee PSP is in a Member State",
+);
+pub const RND_EXCLUDED_NOT_CROSS_BORDER: Code = warning(
+    "RND102",
+    Render,
+    "record excluded: payment is not cross-border",
+);
+pub const RND_EXCLUDED_BELOW_THRESHOLD: Code = warning(
+    "RND103",
+    Render,
+    "record excluded: payee is at or below the reporting threshold",
+);
+pub const RND_PSP_NAME_RESOLVED: Code = warning(
+    "RND104",
+    Render,
+    "multiple PSP names found for one psp_id; one chosen by --psp-name-policy",
+);
+pub const RND_ACCOUNT_COUNTRY: Code = warning(
+    "RND105",
+    Render,
+    "account identifier encodes another country than the payee's; reported as its CountryCode",
+);
+pub const RND_PAYEE_ATTRIBUTE_CONFLICT: Code = warning(
+    "RND106",
+    Render,
+    "payee rows disagree on an attribute; one value chosen by --payee-conflicts",
+);
+pub const RND_REPRESENTATIVE_COUNTRY: Code = warning(
+    "RND107",
+    Render,
+    "payee_psp_country differs from the country of the representative's BIC",
+);
+
+pub const VM_JAR_NOT_FOUND: Code = error("VM001", Validate, "validation module jar not found");
+pub const VM_INPUT_NOT_FOUND: Code = error("VM002", Validate, "validation input not found");
+pub const VM_JAVA_NOT_FOUND: Code = error("VM003", Validate, "Java runtime not found");
+pub const VM_FAILED: Code = error("VM004", Validate, "validation module exited with an error");
+pub const VM_REJECTED: Code = error(
+    "VM005",
+    Validate,
+    "validation module rejected messages (partially or fully)",
+);
+pub const VM_XSD_INVALID: Code = error(
+    "VM006",
+    Validate,
+    "message does not conform to the bundled CESOP XSD",
+);
+pub const VM_XSD_VERSION: Code = error(
+    "VM007",
+    Validate,
+    "schema version has no bundled XSD to validate against",
+);
+pub const VM_SCHEMA_UNREADABLE: Code =
+    error("VM008", Validate, "bundled CESOP XSD could not be loaded");
+pub const VM_OUTPUT_DIFFERS: Code =
+    error("VM009", Validate, "output differs from the snapshot or report it is checked against");
+
+pub const IN_READ_FAILED: Code = error("IN001", Input, "input file could not be opened or read");
+pub const IN_FILE_INVALID: Code = error("IN002", Input, "input file is malformed");
+pub const IN_ROW_INVALID: Code = error("IN003", Input, "input row could not be parsed");
+
+pub const CATALOG: &[Code] = &[
+    GEN_PAYEES_ZERO,
+    GEN_PSPS_ZERO,
+    GEN_SEGMENTS_EXCEED_PAYEES,
+    GEN_INVALID_QUARTER,
+    GEN_RATIO_OUT_OF_RANGE,
+    GEN_PSP_COUNTRY_NOT_EU,
+    GEN_RECORDS_OUT_OF_RANGE,
+    GEN_APPEND_CAPACITY,
+    GEN_IDENTIFIERS_EXHAUSTED,
+    GEN_VERIFICATION_FAILED,
+    GEN_NO_PAYEE_MIX,
+    GEN_TIME_PROFILE_INVALID,
+    GEN_REFUND_DELAY_INVALID,
+    GEN_REFUNDS_PER_PAYMENT_ZERO,
+    GEN_COUNTRY_WEIGHTS_INVALID,
+    GEN_DUPLICATE_PSPS_TOO_FEW,
+    GEN_MULTI_PSP_TOO_FEW,
+    GEN_FIELD_RATES_INVALID,
+    GEN_RESUME_MISMATCH,
+    GEN_ROSTER_INVALID,
+    GEN_PORTFOLIO_INVALID,
+    GEN_SCENARIO_INVALID,
+    GEN_SAMPLE_EMPTY,
+    GEN_PSEUDONYMIZE_KEY_EMPTY,
+    GEN_REQUARTER_SOURCE,
+    PF_ROW_UNPARSEABLE,
+    PF_DUPLICATE_PAYMENT_ID,
+    PF_MULTIPLE_PSP_NAMES,
+    PF_REFUND_TARGET_MISSING,
+    PF_PAYMENT_ID_REQUIRED,
+    PF_EXECUTION_TIME_REQUIRED,
+    PF_EXECUTION_TIME_FORMAT,
+    PF_AMOUNT_FORMAT,
+    PF_CURRENCY_FORMAT,
+    PF_PAYER_COUNTRY_FORMAT,
+    PF_PAYER_COUNTRY_NOT_EU,
+    PF_PAYEE_COUNTRY_UNDERIVABLE,
+    PF_PAYEE_COUNTRY_DERIVED,
+    PF_PAYEE_COUNTRY_FORMAT,
+    PF_PAYEE_COUNTRY_MISMATCH,
+    PF_POSTCODE_MISMATCH,
+    PF_NOT_CROSS_BORDER,
+    PF_PAYEE_ID_REQUIRED,
+    PF_PAYEE_NAME_REQUIRED,
+    PF_REPRESENTATIVE_REPORTED,
+    PF_PAYEE_ACCOUNT_MISSING,
+    PF_ACCOUNT_TYPE_WITHOUT_ACCOUNT,
+    PF_ACCOUNT_TYPE_INVALID,
+    PF_IBAN_COUNTRY_MISSING,
+    PF_PAYER_MS_SOURCE_INVALID,
+    PF_PAYMENT_METHOD_REQUIRED,
+    PF_REFUND_LINK_REQUIRED,
+    PF_LINK_ON_NON_REFUND,
+    PF_PSP_ID_REQUIRED,
+    PF_PSP_ID_FORMAT,
+    PF_PSP_NAME_REQUIRED,
+    PF_IBAN_TOO_SHORT,
+    PF_IBAN_NOT_ALPHANUMERIC,
+    PF_IBAN_COUNTRY_MISMATCH,
+    PF_IBAN_LENGTH,
+    PF_IBAN_LENGTH_UNKNOWN,
+    PF_IBAN_CHECK_DIGITS,
+    PF_IBAN_BBAN_FORMAT,
+    PF_PAYER_NORTHERN_IRELAND,
+    PF_PAYMENT_METHOD_ALIAS,
+    PF_PAYMENT_METHOD_UNKNOWN,
+    PF_PAYMENT_METHOD_OTHER,
+    PF_PAYER_COUNTRY_ACCOUNT_MISMATCH,
+    PF_PAYER_MS_SOURCE_ACCOUNT_MISMATCH,
+    PF_AMOUNT_OUT_OF_BOUNDS,
+    PF_CURRENCY_UNKNOWN,
+    PF_CURRENCY_NOT_CESOP,
+    PF_CURRENCY_NOT_ALLOWED,
+    PF_OUTSIDE_PERIOD,
+    PF_MIXED_PERIODS,
+    PF_PAYEE_ATTRIBUTE_CONFLICT,
+    PF_AMOUNT_NEGATIVE,
+    PF_AMOUNT_ZERO,
+    PF_BIC_STRUCTURE,
+    PF_RULES_INVALID,
+    PF_RATES_INVALID,
+    PF_FAILED,
+    RND_NO_RECORDS,
+    RND_MULTIPLE_PSP_NAMES,
+    RND_TRANSMITTING_COUNTRY,
+    RND_EXECUTION_TIME,
+    RND_PAYEE_COUNTRY,
+    RND_NO_REPORTS,
+    RND_MESSAGE_LIMIT,
+    RND_PAYMENT_METHOD,
+    RND_RESUME_MISMATCH,
+    RND_NIL_REPORT_PSP,
+    RND_STREAMING_UNSUPPORTED,
+    RND_COUNTRY_LIST,
+    RND_MESSAGE_UNREADABLE,
+    RND_MERGE_MISMATCH,
+    RND_NOTHING_TO_RETRACT,
+    RND_NO_MESSAGES,
+    RND_PSP_DIR_COLLISION,
+    RND_REGISTRY_ENTRY_INVALID,
+    RND_EXCLUDED_PSP_ROLE,
+    RND_EXCLUDED_NOT_CROSS_BORDER,
+    RND_EXCLUDED_BELOW_THRESHOLD,
+    RND_PSP_NAME_RESOLVED,
+    RND_ACCOUNT_COUNTRY,
+    RND_PAYEE_ATTRIBUTE_CONFLICT,
+    RND_REPRESENTATIVE_COUNTRY,
+    VM_JAR_NOT_FOUND,
+    VM_INPUT_NOT_FOUND,
+    VM_JAVA_NOT_FOUND,
+    VM_FAILED,
+    VM_REJECTED,
+    VM_XSD_INVALID,
+    VM_XSD_VERSION,
+    VM_SCHEMA_UNREADABLE,
+    VM_OUTPUT_DIFFERS,
+    IN_READ_FAILED,
+    IN_FILE_INVALID,
+    IN_ROW_INVALID,
+];
+
+impl Code {
+    // Error string carrying the code, e.g. "[GEN004] quarter must be 1..4".
+    pub fn err(&self, message: impl std::fmt::Display) -> String {
+        format!("[{}] {}", self.code, message)
+    }
+
+    pub fn err_summary(&self) -> String {
+        self.err(self.summary)
+    }
+
+    // The library error for a failure with this code, of the variant its
+    // category gives.
+    pub fn fail(&self, message: impl std::fmt::Display) -> Error {
+        Error::coded(self, self.err(message))
+    }
+
+    pub fn fail_summary(&self) -> Error {
+        self.fail(self.summary)
+    }
+}
+
+pub fn lookup(code: &str) -> Option<&'static Code> {
+    CATALOG.iter().find(|entry| entry.code == code)
+}
+
+// Finds the first catalog code embedded in an error string. Errors are often
+// wrapped with context ("tenant acme: [GEN004] ..."), so the tag may not lead.
+pub fn code_in(message: &str) -> Option<&'static str> {
+    let mut rest = message;
+    while let Some(start) = rest.find('[') {
+        let tail = &rest[start + 1..];
+        if let Some(code) = tail.find(']').and_then(|end| lookup(&tail[..end])) {
+            return Some(code.code);
+        }
+        rest = tail;
+    }
+    None
+}
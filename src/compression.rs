@@ -0,0 +1,145 @@
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    // Output compression is chosen by extension: `.gz` or `.zst`.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("gz") => Compression::Gzip,
+            Some(ext) if ext.eq_ignore_ascii_case("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gz"),
+            Compression::Zstd => Some("zst"),
+        }
+    }
+}
+
+// `payments.csv.gz` -> `payments.csv`, so sidecar files are named after the
+// uncompressed dataset.
+pub fn uncompressed_path(path: &Path) -> PathBuf {
+    match Compression::from_path(path) {
+        Compression::None => path.to_path_buf(),
+        _ => path.with_extension(""),
+    }
+}
+
+// Opens a file for reading, decompressing gzip or zstd content detected from
+// its leading bytes. Concatenated members (from `--append`) are read through.
+pub fn open_input(path: &Path) -> Result<Box<dyn Read>, String> {
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    let mut reader = BufReader::new(file);
+    let head = reader.fill_buf().map_err(|err| err.to_string())?;
+    if head.starts_with(GZIP_MAGIC) {
+        Ok(Box::new(MultiGzDecoder::new(reader)))
+    } else if head.starts_with(ZSTD_MAGIC) {
+        let decoder = zstd::Decoder::with_buffer(reader).map_err(|err| err.to_string())?;
+        Ok(Box::new(decoder))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+pub fn csv_reader(path: &Path) -> Result<csv::Reader<Box<dyn Read>>, String> {
+    Ok(csv::Reader::from_reader(open_input(path)?))
+}
+
+pub enum OutputFile {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl OutputFile {
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path).map_err(|err| err.to_string())?;
+        Self::wrap(file, Compression::from_path(path))
+    }
+
+    // Compressed output gets a new gzip member or zstd frame, which readers
+    // decode as one continuous stream.
+    pub fn append(path: &Path) -> Result<Self, String> {
+        let file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(|err| err.to_string())?;
+        Self::wrap(file, Compression::from_path(path))
+    }
+
+    fn wrap(file: File, compression: Compression) -> Result<Self, String> {
+        let inner = BufWriter::new(file);
+        Ok(match compression {
+            Compression::None => OutputFile::Plain(inner),
+            Compression::Gzip => {
+                OutputFile::Gzip(GzEncoder::new(inner, flate2::Compression::default()))
+            }
+            Compression::Zstd => OutputFile::Zstd(
+                zstd::Encoder::new(inner, ZSTD_LEVEL).map_err(|err| err.to_string())?,
+            ),
+        })
+    }
+
+    // Writes the compression trailer; dropping an unfinished encoder would
+    // swallow its errors.
+    pub fn finish(self) -> Result<(), String> {
+        let mut inner = match self {
+            OutputFile::Plain(inner) => inner,
+            OutputFile::Gzip(encoder) => encoder.finish().map_err(|err| err.to_string())?,
+            OutputFile::Zstd(encoder) => encoder.finish().map_err(|err| err.to_string())?,
+        };
+        inner.flush().map_err(|err| err.to_string())
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            OutputFile::Plain(inner) => inner.write(buf),
+            OutputFile::Gzip(inner) => inner.write(buf),
+            OutputFile::Zstd(inner) => inner.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            OutputFile::Plain(inner) => inner.flush(),
+            OutputFile::Gzip(inner) => inner.flush(),
+            OutputFile::Zstd(inner) => inner.flush(),
+        }
+    }
+}
+
+// Serializes `records` to `path`, compressed according to its extension.
+pub fn write_csv<T: serde::Serialize>(path: &Path, records: &[T]) -> Result<(), String> {
+    let mut writer = csv::Writer::from_writer(OutputFile::create(path)?);
+    for record in records {
+        writer.serialize(record).map_err(|err| err.to_string())?;
+    }
+    finish_csv(writer)
+}
+
+pub fn finish_csv(writer: csv::Writer<OutputFile>) -> Result<(), String> {
+    writer
+        .into_inner()
+        .map_err(|err| err.to_string())?
+        .finish()
+}
@@ -1,18 +1,22 @@
+use crate::generator::generate_account_identifier;
+use crate::input::{read_payment_records, CsvReadOptions};
 use crate::location::{account_country_code, bic_country_code, normalize_country_code};
 use crate::models::PaymentRecord;
 use crate::reference::{
     currency_for_country, iban_length, is_eu_member_state, ACCOUNT_IDENTIFIER_TYPES, EU_MEMBER_STATES,
 };
-use crate::util::{iban_check_digits, random_alphanum_upper, random_digits};
+use crate::util::iban_check_digits;
+use crate::Error;
 use chrono::Utc;
 use rand::seq::SliceRandom;
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
 use std::collections::{BTreeSet, HashMap};
 use std::path::Path;
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct CorrectSummary {
     pub total_records: usize,
     pub corrected_records: usize,
@@ -39,13 +43,13 @@ struct PayeePlan {
     account_id: Option<String>,
 }
 
-pub fn correct_csv(input: &Path, output: &Path, seed: u64) -> Result<CorrectSummary, String> {
-    let mut reader = csv::Reader::from_path(input).map_err(|err| err.to_string())?;
-    let mut records: Vec<PaymentRecord> = Vec::new();
-    for result in reader.deserialize() {
-        let record: PaymentRecord = result.map_err(|err| err.to_string())?;
-        records.push(record);
-    }
+pub fn correct_csv(
+    input: &Path,
+    output: &Path,
+    seed: u64,
+    csv_options: &CsvReadOptions,
+) -> Result<CorrectSummary, Error> {
+    let mut records = read_payment_records(input, csv_options)?;
 
     let mut rng = ChaCha8Rng::seed_from_u64(seed);
     let mut summary = CorrectSummary::default();
@@ -270,28 +274,18 @@ fn select_payee_account<R: Rng + ?Sized>(
         saw_account = true;
         let account_type = canonical_account_type(&record.payee_account_type);
         match account_type {
-            Some("IBAN") => {
-                if is_valid_iban(account_id, country) {
-                    ibans.insert(account_id.to_string());
-                }
+            Some("IBAN") if is_valid_iban(account_id, country) => {
+                ibans.insert(account_id.to_string());
             }
-            Some("OBAN") => {
-                if account_country_code("OBAN", account_id)
-                    .as_deref()
-                    .map(|code| code == country)
-                    .unwrap_or(false)
-                {
-                    obans.insert(account_id.to_string());
-                }
+            Some("OBAN")
+                if account_country_code("OBAN", account_id).as_deref() == Some(country) =>
+            {
+                obans.insert(account_id.to_string());
             }
-            Some("Other") => {
-                if account_country_code("Other", account_id)
-                    .as_deref()
-                    .map(|code| code == country)
-                    .unwrap_or(false)
-                {
-                    others.insert(account_id.to_string());
-                }
+            Some("Other")
+                if account_country_code("Other", account_id).as_deref() == Some(country) =>
+            {
+                others.insert(account_id.to_string());
             }
             _ => {}
         }
@@ -335,15 +329,8 @@ fn has_valid_payee_psp(records: &[&PaymentRecord]) -> bool {
 }
 
 fn generate_account_for_country<R: Rng + ?Sized>(country: &str, rng: &mut R) -> (String, String) {
-    if let Some(len) = iban_length(country) {
-        let bban_len = len.saturating_sub(4);
-        let bban = random_digits(rng, bban_len);
-        let check = iban_check_digits(country, &bban).unwrap_or_else(|_| "00".to_string());
-        return ("IBAN".to_string(), format!("{}{}{}", country, check, bban));
-    }
-
-    let suffix = random_alphanum_upper(rng, 12);
-    ("Other".to_string(), format!("{}{}", country, suffix))
+    let (account_id, account_type) = generate_account_identifier(rng, country);
+    (account_type, account_id)
 }
 
 fn is_valid_iban(iban: &str, country: &str) -> bool {
@@ -353,7 +340,7 @@ fn is_valid_iban(iban: &str, country: &str) -> bool {
     if !iban.chars().all(|ch| ch.is_ascii_alphanumeric()) {
         return false;
     }
-    if &iban[0..2].to_uppercase() != country {
+    if iban[0..2].to_uppercase() != country {
         return false;
     }
     if let Some(expected) = iban_length(country) {
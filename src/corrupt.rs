@@ -1,11 +1,15 @@
+use crate::codes;
+use crate::input::{read_payment_records, CsvReadOptions};
 use crate::models::PaymentRecord;
+use crate::Error;
 use rand::seq::SliceRandom;
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
 use std::collections::HashMap;
 use std::path::Path;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct CorruptSummary {
     pub payees_targeted: usize,
     pub payee_name_missing: usize,
@@ -32,6 +36,33 @@ impl CorruptSummary {
     }
 }
 
+// Error rates for `generate --messy`; `light` matches the `corrupt` defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum MessyProfile {
+    Light,
+    Moderate,
+    Heavy,
+}
+
+impl MessyProfile {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MessyProfile::Light => "light",
+            MessyProfile::Moderate => "moderate",
+            MessyProfile::Heavy => "heavy",
+        }
+    }
+
+    // (payee_error_rate, tx_error_rate)
+    pub fn rates(self) -> (f64, f64) {
+        match self {
+            MessyProfile::Light => (0.02, 0.01),
+            MessyProfile::Moderate => (0.05, 0.03),
+            MessyProfile::Heavy => (0.15, 0.08),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 enum PayeeCorruption {
     MissingName,
@@ -41,6 +72,7 @@ enum PayeeCorruption {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[allow(clippy::enum_variant_names)]
 enum TxCorruption {
     InvalidCurrency,
     InvalidPayerCountry,
@@ -53,19 +85,31 @@ pub fn corrupt_csv(
     payee_error_rate: f64,
     tx_error_rate: f64,
     seed: u64,
-) -> Result<CorruptSummary, String> {
+    csv_options: &CsvReadOptions,
+) -> Result<CorruptSummary, Error> {
+    let mut records = read_payment_records(input, csv_options)?;
+    let summary = corrupt_records(&mut records, payee_error_rate, tx_error_rate, seed)?;
+
+    let mut writer = csv::Writer::from_path(output).map_err(|err| err.to_string())?;
+    for record in records {
+        writer.serialize(record).map_err(|err| err.to_string())?;
+    }
+    writer.flush().map_err(|err| err.to_string())?;
+
+    Ok(summary)
+}
+
+pub fn corrupt_records(
+    records: &mut [PaymentRecord],
+    payee_error_rate: f64,
+    tx_error_rate: f64,
+    seed: u64,
+) -> Result<CorruptSummary, Error> {
     if !(0.0..=1.0).contains(&payee_error_rate) {
-        return Err("payee_error_rate must be 0..1".to_string());
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("payee_error_rate must be 0..1"));
     }
     if !(0.0..=1.0).contains(&tx_error_rate) {
-        return Err("tx_error_rate must be 0..1".to_string());
-    }
-
-    let mut reader = csv::Reader::from_path(input).map_err(|err| err.to_string())?;
-    let mut records: Vec<PaymentRecord> = Vec::new();
-    for result in reader.deserialize() {
-        let record: PaymentRecord = result.map_err(|err| err.to_string())?;
-        records.push(record);
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("tx_error_rate must be 0..1"));
     }
 
     let mut rng = ChaCha8Rng::seed_from_u64(seed);
@@ -89,22 +133,16 @@ pub fn corrupt_csv(
         };
         summary.payees_targeted += 1;
         let corruption = pick_payee_corruption(&mut rng);
-        apply_payee_corruption(&mut records, indices, corruption, &mut summary, &mut rng);
+        apply_payee_corruption(records, indices, corruption, &mut summary, &mut rng);
     }
 
-    for record in &mut records {
+    for record in records.iter_mut() {
         if rng.gen_bool(tx_error_rate) {
             let corruption = pick_tx_corruption(&mut rng);
             apply_tx_corruption(record, corruption, &mut summary);
         }
     }
 
-    let mut writer = csv::Writer::from_path(output).map_err(|err| err.to_string())?;
-    for record in records {
-        writer.serialize(record).map_err(|err| err.to_string())?;
-    }
-    writer.flush().map_err(|err| err.to_string())?;
-
     Ok(summary)
 }
 
@@ -0,0 +1,113 @@
+use crate::codes::{code_in, Code, CodeCategory};
+
+// Error returned by the library entry points. Coded failures are built where
+// they happen with `Code::fail`, which picks the variant from the catalog
+// category of the code; input failures and errors converted from a message
+// are `Input`, whose code is the one in its message, if any. The message is
+// the text shown to users, led by the code in brackets for coded errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Generate { code: &'static str, message: String },
+    Preflight { code: &'static str, message: String },
+    Render { code: &'static str, message: String },
+    Validate { code: &'static str, message: String },
+    Input(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub(crate) fn coded(code: &Code, message: String) -> Self {
+        let code_id = code.code;
+        match code.category {
+            CodeCategory::Generate => Error::Generate {
+                code: code_id,
+                message,
+            },
+            CodeCategory::Preflight => Error::Preflight {
+                code: code_id,
+                message,
+            },
+            CodeCategory::Render => Error::Render {
+                code: code_id,
+                message,
+            },
+            CodeCategory::Validate => Error::Validate {
+                code: code_id,
+                message,
+            },
+            CodeCategory::Input => Error::Input(message),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Error::Generate { message, .. }
+            | Error::Preflight { message, .. }
+            | Error::Render { message, .. }
+            | Error::Validate { message, .. }
+            | Error::Input(message) => message,
+        }
+    }
+
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            Error::Generate { code, .. }
+            | Error::Preflight { code, .. }
+            | Error::Render { code, .. }
+            | Error::Validate { code, .. } => Some(code),
+            Error::Input(message) => code_in(message),
+        }
+    }
+
+    // The same error with `context` in front of its message, e.g. the tenant
+    // or file it happened for.
+    pub fn context(self, context: impl std::fmt::Display) -> Self {
+        let prefix = |message: String| format!("{}: {}", context, message);
+        match self {
+            Error::Generate { code, message } => Error::Generate {
+                code,
+                message: prefix(message),
+            },
+            Error::Preflight { code, message } => Error::Preflight {
+                code,
+                message: prefix(message),
+            },
+            Error::Render { code, message } => Error::Render {
+                code,
+                message: prefix(message),
+            },
+            Error::Validate { code, message } => Error::Validate {
+                code,
+                message: prefix(message),
+            },
+            Error::Input(message) => Error::Input(prefix(message)),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Input(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::from(message.to_string())
+    }
+}
+
+impl From<Error> for String {
+    fn from(err: Error) -> Self {
+        err.to_string()
+    }
+}
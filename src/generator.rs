@@ -1,17 +1,34 @@
+use crate::analysis::payee_threshold_counts;
+use crate::banks::{bank_codes, location_codes, psp_bank_code};
+use crate::calendar::{activity_weight, utc_offset};
+use crate::codes;
+use crate::compression::uncompressed_path;
 use crate::location::bic_country_code;
 use crate::models::PaymentRecord;
-use crate::reference::{currency_for_country, iban_length, is_eu_member_state, EU_MEMBER_STATES};
+use crate::reference::{
+    banking_country, bban_pattern, city_postcodes, currency_for_country, iban_length,
+    is_eu_member_state, postcode_patterns, EU_COUNTRY_WEIGHTS, EU_MEMBER_STATES,
+    NORTHERN_IRELAND,
+};
+use crate::roster::RosterPsp;
+use crate::run_summary::run_id;
+use crate::seasonality::TimeProfile;
 use crate::util::{
     format_amount, iban_check_digits, random_alphanum_upper, random_digits, random_upper_letters,
-    slugify,
+    sha256_file, sha256_hex, slugify,
 };
+use crate::Error;
 
-use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use chrono::{DateTime, Datelike, Duration, SecondsFormat, TimeZone, Utc};
+use rand::distributions::WeightedIndex;
 use rand::seq::SliceRandom;
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
+#[derive(Clone, Serialize)]
 pub struct GeneratorConfig {
     pub records: usize,
     pub payees: usize,
@@ -21,11 +38,391 @@ pub struct GeneratorConfig {
     pub psps: usize,
     pub cross_border_ratio: f64,
     pub refund_ratio: f64,
+    // Share of payments settled in a non-EU currency (USD, GBP, CHF).
+    pub foreign_currency_ratio: f64,
+    // Share of payments carrying the payer's IBAN, BIC or other account.
+    pub payer_account_ratio: f64,
+    // Share of payments from payers outside the EU, which are never reportable.
+    pub non_eu_payer_ratio: f64,
+    // Share of execution times written with the reporting PSP's local UTC
+    // offset instead of `Z`.
+    pub local_time_ratio: f64,
+    // Shares of payees and of payments from payers in Northern Ireland (`XI`).
+    pub xi_payee_ratio: f64,
+    pub xi_payer_ratio: f64,
+    // Share of payees whose payments are split between two reporting PSPs.
+    pub multi_psp_ratio: f64,
     pub multi_account_ratio: f64,
     pub non_eu_payee_ratio: f64,
     pub no_account_payee_ratio: f64,
+    // Share of payees that are marketplaces selling for sub-merchants.
+    pub marketplace_ratio: f64,
+    // Share of payees with diacritics, non-Latin scripts or `&`/`'` in the name.
+    pub unicode_name_ratio: f64,
     pub year: i32,
     pub quarter: u8,
+    pub holiday_calendars: bool,
+    // Brand used for every EU PSP instead of a random one.
+    pub psp_name: Option<String>,
+    // One EU PSP per listed country instead of `psps` random countries.
+    pub psp_countries: Vec<String>,
+    // Institutions from `--psp-roster`; replaces invented PSPs when set.
+    pub psp_roster: Vec<RosterPsp>,
+    // Derive payment ids from the seed instead of drawing random UUIDs.
+    pub deterministic_ids: bool,
+    pub time_profile: TimeProfile,
+    // Relative volume of EU payee and payer countries.
+    pub country_weights: CountryWeights,
+    // Payee churn applied by `generate_quarter`.
+    pub churn: Churn,
+    // Refunds follow their original payment by this many days (inclusive).
+    pub refund_delay_days: (u32, u32),
+    // Share of refunds that return only part of the remaining amount.
+    pub partial_refund_ratio: f64,
+    pub max_refunds_per_payment: u32,
+    pub perspective: Perspective,
+    // Payer perspective: share of payees with an account whose PSP the
+    // reporting PSP does not know.
+    pub unknown_payee_psp_ratio: f64,
+    // Give every payee a merchant category code, which scales its amounts and
+    // transaction volume.
+    pub mcc: bool,
+    // Presence of the optional payee email, web, VAT and TAX fields.
+    pub field_rates: FieldRates,
+}
+
+// The `generate` defaults at the default scale of 1200 records, for the
+// current year's first quarter.
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self {
+            records: 1200,
+            payees: 50,
+            micro_payees: 13,
+            near_threshold_payees: 5,
+            large_payees: 3,
+            psps: 1,
+            cross_border_ratio: 0.8,
+            refund_ratio: 0.02,
+            foreign_currency_ratio: 0.0,
+            payer_account_ratio: 0.0,
+            non_eu_payer_ratio: 0.0,
+            local_time_ratio: 0.0,
+            xi_payee_ratio: 0.0,
+            xi_payer_ratio: 0.0,
+            multi_psp_ratio: 0.0,
+            multi_account_ratio: 0.0,
+            non_eu_payee_ratio: 0.0,
+            no_account_payee_ratio: 0.0,
+            marketplace_ratio: 0.0,
+            unicode_name_ratio: 0.0,
+            year: Utc::now().year(),
+            quarter: 1,
+            holiday_calendars: false,
+            psp_name: None,
+            psp_countries: Vec::new(),
+            psp_roster: Vec::new(),
+            deterministic_ids: false,
+            time_profile: TimeProfile::default(),
+            country_weights: CountryWeights::default(),
+            churn: Churn::default(),
+            refund_delay_days: DEFAULT_REFUND_DELAY_DAYS,
+            partial_refund_ratio: 0.0,
+            max_refunds_per_payment: 1,
+            perspective: Perspective::Payee,
+            unknown_payee_psp_ratio: 0.0,
+            mcc: false,
+            field_rates: FieldRates::default(),
+        }
+    }
+}
+
+// The side of the payment the reporting PSPs act for. From the payer side,
+// EU payees bank with PSPs of their own country, so their payments are left
+// to those PSPs and only payments to non-EU or unknown payee PSPs report.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Perspective {
+    #[default]
+    Payee,
+    Payer,
+}
+
+impl Perspective {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Perspective::Payee => "payee",
+            Perspective::Payer => "payer",
+        }
+    }
+}
+
+// Relative weight per EU Member State; unlisted countries weigh 1, so the
+// empty default draws countries uniformly.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CountryWeights(BTreeMap<String, f64>);
+
+impl CountryWeights {
+    // `uniform`, `realistic` (approximate e-commerce volume) and/or `CC=W`
+    // entries, comma separated; later entries win, e.g. `realistic,MT=5`.
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        let mut weights = BTreeMap::new();
+        for item in value.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            match item.to_ascii_lowercase().as_str() {
+                "uniform" => weights.clear(),
+                "realistic" => weights.extend(
+                    EU_COUNTRY_WEIGHTS
+                        .iter()
+                        .map(|(country, weight)| (country.to_string(), *weight)),
+                ),
+                _ => {
+                    let invalid = || {
+                        codes::GEN_COUNTRY_WEIGHTS_INVALID
+                            .fail(format!("invalid country weight '{}': expected CC=W", item))
+                    };
+                    let (country, weight) = item.split_once('=').ok_or_else(invalid)?;
+                    let weight = weight.trim().parse::<f64>().map_err(|_| invalid())?;
+                    weights.insert(country.trim().to_uppercase(), weight);
+                }
+            }
+        }
+        let weights = CountryWeights(weights);
+        weights.validate()?;
+        Ok(weights)
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = &codes::GEN_COUNTRY_WEIGHTS_INVALID;
+        for (country, weight) in &self.0 {
+            if !is_eu_member_state(country) {
+                return Err(invalid.fail(format!(
+                    "country weight {} is not an EU Member State",
+                    country
+                )));
+            }
+            if !weight.is_finite() || *weight < 0.0 {
+                return Err(invalid.fail(format!(
+                    "country weight {} must be finite and non-negative",
+                    country
+                )));
+            }
+        }
+        if EU_MEMBER_STATES.iter().all(|country| self.weight(country) == 0.0) {
+            return Err(invalid.fail("country weights cannot all be zero"));
+        }
+        Ok(())
+    }
+
+    pub fn is_uniform(&self) -> bool {
+        self.0.values().all(|weight| *weight == 1.0)
+    }
+
+    pub fn describe(&self) -> String {
+        if self.is_uniform() {
+            return "uniform".to_string();
+        }
+        self.0
+            .iter()
+            .map(|(country, weight)| format!("{}={}", country, weight))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    fn weight(&self, country: &str) -> f64 {
+        self.0.get(country).copied().unwrap_or(1.0)
+    }
+
+    // An EU Member State other than `exclude`; falls back to a uniform draw
+    // when every remaining country weighs 0.
+    fn pick<R: Rng + ?Sized>(&self, rng: &mut R, exclude: Option<&str>) -> &'static str {
+        let candidates: Vec<&'static str> = EU_MEMBER_STATES
+            .iter()
+            .copied()
+            .filter(|country| Some(*country) != exclude)
+            .collect();
+        if !self.is_uniform() {
+            if let Ok(country) = candidates.choose_weighted(rng, |country| self.weight(country)) {
+                return country;
+            }
+        }
+        candidates.choose(rng).copied().unwrap_or("DE")
+    }
+}
+
+// Share of payees carrying each optional field. Unset fields keep the built-in
+// rates: email and web always, TAX and VAT by segment. VAT ids are only given
+// to EU payees.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldRates {
+    pub email: Option<f64>,
+    pub web: Option<f64>,
+    pub vat: Option<f64>,
+    pub tax: Option<f64>,
+}
+
+impl FieldRates {
+    // `field=F` entries, comma separated, e.g. `email=0,vat=1`.
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        let mut rates = FieldRates::default();
+        for item in value.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let invalid = || {
+                codes::GEN_FIELD_RATES_INVALID
+                    .fail(format!("invalid field rate '{}': expected FIELD=F", item))
+            };
+            let (field, rate) = item.split_once('=').ok_or_else(invalid)?;
+            let rate = rate.trim().parse::<f64>().map_err(|_| invalid())?;
+            let slot = match field.trim().to_ascii_lowercase().as_str() {
+                "email" => &mut rates.email,
+                "web" => &mut rates.web,
+                "vat" => &mut rates.vat,
+                "tax" => &mut rates.tax,
+                other => {
+                    return Err(codes::GEN_FIELD_RATES_INVALID.fail(format!(
+                        "unknown field '{}': expected email, web, vat or tax",
+                        other
+                    )))
+                }
+            };
+            *slot = Some(rate);
+        }
+        rates.validate()?;
+        Ok(rates)
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        for (field, rate) in self.entries() {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(codes::GEN_RATIO_OUT_OF_RANGE
+                    .fail(format!("{} field rate must be 0..1", field)));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn describe(&self) -> String {
+        let entries: Vec<String> = self
+            .entries()
+            .map(|(field, rate)| format!("{}={}", field, rate))
+            .collect();
+        if entries.is_empty() {
+            "default".to_string()
+        } else {
+            entries.join(",")
+        }
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (&'static str, f64)> {
+        [
+            ("email", self.email),
+            ("web", self.web),
+            ("vat", self.vat),
+            ("tax", self.tax),
+        ]
+        .into_iter()
+        .filter_map(|(field, rate)| rate.map(|rate| (field, rate)))
+    }
+}
+
+// Issues payment ids and remembers payments per payee, so refunds can point at
+// an earlier payment. Seeded ids come from their own ChaCha stream, so switching
+// modes leaves every other field of a seeded run unchanged.
+struct PaymentIds {
+    seeded: Option<ChaCha8Rng>,
+    by_payee: HashMap<String, Vec<EarlierPayment>>,
+}
+
+// A payment that can still be refunded. `time` is the latest event (the
+// payment or its last refund), so chained refunds stay in order.
+struct EarlierPayment {
+    id: String,
+    psp_id: String,
+    time: DateTime<Utc>,
+    cents: i64,
+    currency: String,
+    refunded_cents: i64,
+    refunds: u32,
+}
+
+impl PaymentIds {
+    fn new(deterministic: bool, seed: u64) -> Self {
+        let seeded = deterministic.then(|| {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            rng.set_stream(1);
+            rng
+        });
+        Self {
+            seeded,
+            by_payee: HashMap::new(),
+        }
+    }
+
+    fn issue(&mut self) -> String {
+        match &mut self.seeded {
+            Some(rng) => uuid::Builder::from_random_bytes(rng.gen()).into_uuid(),
+            None => uuid::Uuid::new_v4(),
+        }
+        .to_string()
+    }
+
+    fn remember(
+        &mut self,
+        payee_id: &str,
+        id: String,
+        psp_id: String,
+        time: DateTime<Utc>,
+        cents: i64,
+        currency: String,
+    ) {
+        self.by_payee
+            .entry(payee_id.to_string())
+            .or_default()
+            .push(EarlierPayment {
+                id,
+                psp_id,
+                time,
+                cents,
+                currency,
+                refunded_cents: 0,
+                refunds: 0,
+            });
+    }
+
+    // Applies an existing refund to its original payment, if remembered.
+    fn remember_refund(&mut self, payee_id: &str, corr_id: &str, time: DateTime<Utc>, cents: i64) {
+        let original = self
+            .by_payee
+            .get_mut(payee_id)
+            .and_then(|earlier| earlier.iter_mut().find(|payment| payment.id == corr_id));
+        if let Some(original) = original {
+            original.time = original.time.max(time);
+            original.refunded_cents += cents;
+            original.refunds += 1;
+        }
+    }
+
+    // Indexes of the payee's payments with a last event before `latest`, an
+    // amount left to refund and fewer than `max_refunds` refunds.
+    fn refundable(&self, payee_id: &str, latest: DateTime<Utc>, max_refunds: u32) -> Vec<usize> {
+        self.by_payee
+            .get(payee_id)
+            .map(|earlier| {
+                earlier
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, payment)| {
+                        payment.time < latest
+                            && payment.refunds < max_refunds
+                            && payment.refunded_cents < payment.cents
+                    })
+                    .map(|(index, _)| index)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Clone)]
@@ -39,21 +436,109 @@ struct PayeeSegment {
 
 struct PayeePlan {
     segment: PayeeSegment,
+    sector: Option<&'static MerchantSector>,
 }
 
-#[derive(Clone)]
-struct PayeeAccount {
-    id: String,
-    account_type: String,
+// A merchant category: `weight` is its share of payees, and amounts and
+// extra transactions (within the segment bounds) scale by the factors.
+pub struct MerchantSector {
+    pub mcc: &'static str,
+    pub label: &'static str,
+    weight: f64,
+    amount_factor: f64,
+    volume_factor: f64,
 }
 
-struct PayeeProfile {
-    id: String,
-    name: String,
+pub const MERCHANT_SECTORS: &[MerchantSector] = &[
+    MerchantSector {
+        mcc: "5411",
+        label: "grocery",
+        weight: 3.0,
+        amount_factor: 0.6,
+        volume_factor: 1.6,
+    },
+    MerchantSector {
+        mcc: "5812",
+        label: "restaurants",
+        weight: 2.0,
+        amount_factor: 0.4,
+        volume_factor: 1.4,
+    },
+    MerchantSector {
+        mcc: "5691",
+        label: "clothing",
+        weight: 3.0,
+        amount_factor: 1.0,
+        volume_factor: 1.0,
+    },
+    MerchantSector {
+        mcc: "5732",
+        label: "electronics",
+        weight: 2.0,
+        amount_factor: 2.5,
+        volume_factor: 0.7,
+    },
+    MerchantSector {
+        mcc: "5815",
+        label: "digital_goods",
+        weight: 2.0,
+        amount_factor: 0.3,
+        volume_factor: 1.8,
+    },
+    MerchantSector {
+        mcc: "4722",
+        label: "travel",
+        weight: 1.0,
+        amount_factor: 3.0,
+        volume_factor: 0.5,
+    },
+    MerchantSector {
+        mcc: "7011",
+        label: "lodging",
+        weight: 1.0,
+        amount_factor: 2.2,
+        volume_factor: 0.6,
+    },
+    MerchantSector {
+        mcc: "5999",
+        label: "retail_other",
+        weight: 2.0,
+        amount_factor: 1.0,
+        volume_factor: 1.0,
+    },
+];
+
+pub fn merchant_sector(mcc: &str) -> Option<&'static MerchantSector> {
+    MERCHANT_SECTORS.iter().find(|sector| sector.mcc == mcc)
+}
+
+fn assign_sectors<R: Rng + ?Sized>(rng: &mut R, plans: &mut [PayeePlan]) {
+    for plan in plans {
+        plan.sector = MERCHANT_SECTORS
+            .choose_weighted(rng, |sector| sector.weight)
+            .ok();
+    }
+}
+
+fn volume_factor(mcc: Option<&str>) -> f64 {
+    mcc.and_then(merchant_sector)
+        .map_or(1.0, |sector| sector.volume_factor)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PayeeAccount {
+    pub(crate) id: String,
+    pub(crate) account_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PayeeProfile {
+    pub(crate) id: String,
+    pub(crate) name: String,
     amount_min: f64,
     amount_max: f64,
-    country: String,
-    accounts: Vec<PayeeAccount>,
+    pub(crate) country: String,
+    pub(crate) accounts: Vec<PayeeAccount>,
     tax_id: Option<String>,
     vat_id: Option<String>,
     email: Option<String>,
@@ -63,18 +548,50 @@ struct PayeeProfile {
     postcode: Option<String>,
     payee_psp_id: String,
     payee_psp_name: String,
-    reporting_psp_id: String,
+    pub(crate) reporting_psp_id: String,
     reporting_psp_name: String,
-    psp_role: String,
+    pub(crate) psp_role: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    sub_merchants: Vec<SubMerchant>,
+    // Marketplace funds are collected by an intermediary PSP (`payee_psp_id`)
+    // instead of a payee account.
+    #[serde(default)]
+    via_intermediary: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mcc: Option<String>,
+    // A second EU PSP reporting about half of the payee's payments in the
+    // same role as `reporting_psp_id`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    second_psp: Option<PspProfile>,
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SubMerchant {
+    id: String,
+    name: String,
+    country: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct PspProfile {
     id: String,
     name: String,
 }
 
-const NON_EU_PAYEE_COUNTRIES: &[&str] = &["GB", "NO", "CH", "IS", "LI", "US", "CA"];
+// A refund more than a quarter after its payment could never land in the
+// payment's own quarter.
+pub const MAX_REFUND_DELAY_DAYS: u32 = 92;
+pub const DEFAULT_REFUND_DELAY_DAYS: (u32, u32) = (1, 30);
+
+fn default_refund_delay_days() -> (u32, u32) {
+    DEFAULT_REFUND_DELAY_DAYS
+}
+
+fn default_max_refunds_per_payment() -> u32 {
+    1
+}
+
+const NON_EU_COUNTRIES: &[&str] = &["GB", "NO", "CH", "IS", "LI", "US", "CA"];
 
 const COMPANY_PREFIX: &[&str] = &[
     "Silver", "North", "Blue", "Cobalt", "Summit", "Urban", "Prime", "Atlas", "Green", "Nova",
@@ -143,162 +660,1561 @@ const PSP_NAMES: &[&str] = &[
     "Summit Payments",
 ];
 
+// Names with diacritics, apostrophes and ampersands, by payee country.
+const UNICODE_NAMES: &[(&str, &str)] = &[
+    ("AT", "Grünwald Ökotechnik GmbH"),
+    ("BE", "Boulangerie Dupré & Fils SRL"),
+    ("BG", "Българска Търговия ЕООД"),
+    ("CY", "Κυπριακή Ενέργεια Λτδ"),
+    ("CZ", "Řemeslná Dílna Čechy s.r.o."),
+    ("DE", "Müller & Söhne GmbH"),
+    ("DE", "Bäckerei Weiß KG"),
+    ("DK", "Ærø Smørrebrød ApS"),
+    ("EE", "Põltsamaa Õun OÜ"),
+    ("ES", "Añejo Diseño S.L."),
+    ("FI", "Äänekosken Öljy Oy"),
+    ("FR", "Crème & Château SARL"),
+    ("FR", "L'Atelier d'Éloïse SAS"),
+    ("GR", "Ελληνικά Τρόφιμα Α.Ε."),
+    ("HR", "Đurđević Obrt d.o.o."),
+    ("HU", "Győri Ékszer Kft."),
+    ("IE", "O'Sullivan & Ó Briain Ltd"),
+    ("IT", "Caffè dell'Università S.r.l."),
+    ("LT", "Šiaulių Ąžuolas UAB"),
+    ("LU", "Bäckerei Schäfer S.à r.l."),
+    ("LV", "Rīgas Ēdnīca SIA"),
+    ("MT", "Ħal Qormi Ħobż Ltd"),
+    ("NL", "Van 't Hoff & Zn. B.V."),
+    ("PL", "Łódzka Spółka Żywności Sp. z o.o."),
+    ("PT", "Conceição & Irmãos Lda"),
+    ("RO", "Țesătoria Ștefănești SRL"),
+    ("SE", "Åkerö Möbler AB"),
+    ("SI", "Žabjek Čevlji d.o.o."),
+    ("SK", "Ďurčo & Šťastný s.r.o."),
+    ("CA", "Boulangerie Québécoise Inc."),
+    ("CH", "Zürcher Uhren AG"),
+    ("GB", "Marks & O'Brien Ltd"),
+    ("LI", "Vaduzer Wäscherei AG"),
+    ("NO", "Bjørnstad & Sønn AS"),
+    ("US", "Joe's Café & Grill LLC"),
+];
+
+// Non-Latin names that can appear for any country (online sellers).
+const NON_LATIN_NAMES: &[&str] = &[
+    "株式会社さくら商事",
+    "北京星辰贸易有限公司",
+    "شركة النخيل للتجارة",
+    "Торговый Дом «Север»",
+    "חברת אור בע\"מ",
+];
+
+const TRANSLITERATIONS: &[(char, &str)] = &[
+    ('ä', "ae"), ('ö', "oe"), ('ü', "ue"), ('ß', "ss"), ('æ', "ae"), ('ø', "o"), ('å', "a"),
+    ('á', "a"), ('à', "a"), ('â', "a"), ('ã', "a"), ('ą', "a"), ('ā', "a"), ('č', "c"),
+    ('ç', "c"), ('ď', "d"), ('đ', "d"), ('é', "e"), ('è', "e"), ('ê', "e"), ('ë', "e"),
+    ('ē', "e"), ('ě', "e"), ('ħ', "h"), ('í', "i"), ('ì', "i"), ('ï', "i"), ('ī', "i"),
+    ('ł', "l"), ('ñ', "n"), ('ó', "o"), ('ô', "o"), ('õ', "o"), ('ő', "o"), ('ř', "r"),
+    ('š', "s"), ('ș', "s"), ('ť', "t"), ('ț', "t"), ('ú', "u"), ('ų', "u"), ('ž', "z"),
+    ('ż', "z"),
+];
+
+const INTERMEDIARY_PSP_NAMES: &[&str] = &[
+    "Relay Payments",
+    "Bridgeway Pay",
+    "Conduit Money",
+    "Clearpath Settlement",
+];
+
+// `PaymentMethodOther` texts for marketplace payments outside the CESOP types.
+const MARKETPLACE_OTHER_METHODS: &[&str] = &[
+    "Marketplace wallet balance",
+    "Buy now, pay later",
+    "Marketplace gift card",
+];
+
+const CA_INSTITUTION_NUMBERS: &[&str] = &["001", "002", "003", "004", "006", "010", "016"];
+
+const FOREIGN_CURRENCIES: &[&str] = &["USD", "GBP", "CHF"];
+
 const PSP_ROLE_PAYEE: &str = "PAYEE";
 const PSP_ROLE_PAYER: &str = "PAYER";
 
-pub fn generate_records(
+pub struct GeneratedData {
+    pub records: Vec<PaymentRecord>,
+    pub registry: PayeeRegistry,
+    pub churn: ChurnSummary,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayeeRegistry {
+    year: i32,
+    quarter: u8,
+    cross_border_ratio: f64,
+    refund_ratio: f64,
+    #[serde(default = "default_refund_delay_days")]
+    refund_delay_days: (u32, u32),
+    #[serde(default)]
+    partial_refund_ratio: f64,
+    #[serde(default = "default_max_refunds_per_payment")]
+    max_refunds_per_payment: u32,
+    #[serde(default)]
+    foreign_currency_ratio: f64,
+    #[serde(default)]
+    payer_account_ratio: f64,
+    #[serde(default)]
+    non_eu_payer_ratio: f64,
+    #[serde(default)]
+    local_time_ratio: f64,
+    #[serde(default)]
+    xi_payer_ratio: f64,
+    #[serde(default)]
+    country_weights: CountryWeights,
+    #[serde(default)]
+    holiday_calendars: bool,
+    #[serde(default)]
+    time_profile: TimeProfile,
+    #[serde(default)]
+    psps: Vec<PspProfile>,
+    // Highest payee number handed out so far, including payees that left.
+    #[serde(default)]
+    issued_payees: usize,
+    // Payees joining later quarters get a merchant category too.
+    #[serde(default)]
+    mcc: bool,
+    // Appended records keep seeded payment ids when the dataset had them.
+    #[serde(default)]
+    deterministic_ids: bool,
+    payees: Vec<RegistryPayee>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RegistryPayee {
+    segment: String,
+    min_tx: usize,
+    max_tx: usize,
+    tx_count: usize,
+    #[serde(flatten)]
+    profile: PayeeProfile,
+}
+
+impl PayeeRegistry {
+    pub fn payees(&self) -> usize {
+        self.payees.len()
+    }
+
+    pub fn period(&self) -> (i32, u8) {
+        (self.year, self.quarter)
+    }
+
+    pub fn set_period(&mut self, year: i32, quarter: u8) {
+        self.year = year;
+        self.quarter = quarter;
+    }
+
+    pub fn records(&self) -> usize {
+        self.payees.iter().map(|payee| payee.tx_count).sum()
+    }
+
+    pub fn remaining_capacity(&self) -> usize {
+        self.payees
+            .iter()
+            .map(|payee| payee.max_tx.saturating_sub(payee.tx_count))
+            .sum()
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let contents = std::fs::read_to_string(path).map_err(|err| {
+            codes::IN_READ_FAILED.err(format!(
+                "failed to read payee registry {}: {}",
+                path.display(),
+                err
+            ))
+        })?;
+        serde_json::from_str(&contents).map_err(|err| {
+            codes::IN_FILE_INVALID.fail(format!(
+                "invalid payee registry {}: {}",
+                path.display(),
+                err
+            ))
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        std::fs::write(path, json).map_err(|err| err.to_string().into())
+    }
+}
+
+pub fn registry_path(output: &Path) -> PathBuf {
+    sibling_path(output, "payees.json")
+}
+
+pub fn manifest_path(output: &Path) -> PathBuf {
+    sibling_path(output, "manifest.json")
+}
+
+fn sibling_path(output: &Path, suffix: &str) -> PathBuf {
+    let output = uncompressed_path(output);
+    let stem = output
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "payments".to_string());
+    output.with_file_name(format!("{}.{}", stem, suffix))
+}
+
+// Seed, resolved config and output digest of one generated CSV, so the run
+// can be audited and reproduced.
+#[derive(Clone, Serialize)]
+pub struct GenerateManifest {
+    pub run_id: String,
+    pub args: Vec<String>,
+    pub seed: u64,
+    pub config: GeneratorConfig,
+    pub records: usize,
+    pub payees: usize,
+    pub payees_per_segment: BTreeMap<String, usize>,
+    pub output: String,
+    pub sha256: String,
+}
+
+impl GenerateManifest {
+    // `records` is the number of rows in `output`, which can differ from
+    // `config.records` once messy rows, duplicates or edge payees are added.
+    pub fn new(
+        seed: u64,
+        config: &GeneratorConfig,
+        registry: &PayeeRegistry,
+        records: usize,
+        output: &Path,
+    ) -> Result<Self, Error> {
+        let mut payees_per_segment = BTreeMap::new();
+        for payee in &registry.payees {
+            *payees_per_segment.entry(payee.segment.clone()).or_insert(0) += 1;
+        }
+        Ok(Self {
+            run_id: run_id().to_string(),
+            args: std::env::args().skip(1).collect(),
+            seed,
+            config: config.clone(),
+            records,
+            payees: registry.payees(),
+            payees_per_segment,
+            output: output.display().to_string(),
+            sha256: sha256_file(output)?,
+        })
+    }
+
+    pub fn write(&self) -> Result<PathBuf, Error> {
+        let path = manifest_path(Path::new(&self.output));
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        std::fs::write(&path, json).map_err(|err| err.to_string())?;
+        Ok(path)
+    }
+}
+
+pub fn generate_records(config: &GeneratorConfig, seed: u64) -> Result<GeneratedData, Error> {
+    let mut records = Vec::with_capacity(config.records);
+    let (registry, mut rng) = generate_payees(config, seed, |chunk| {
+        records.extend(chunk);
+        Ok(())
+    })?;
+    records.shuffle(&mut rng);
+    Ok(GeneratedData {
+        records,
+        registry,
+        churn: ChurnSummary::default(),
+    })
+}
+
+// Hands each payee's records to `sink` as soon as they are built instead of
+// collecting the whole dataset. The records equal those of `generate_records`
+// for the same seed, grouped by payee rather than shuffled.
+pub fn generate_streaming<F>(config: &GeneratorConfig, seed: u64, sink: F) -> Result<PayeeRegistry, Error>
+where
+    F: FnMut(Vec<PaymentRecord>) -> Result<(), Error>,
+{
+    generate_payees(config, seed, sink).map(|(registry, _)| registry)
+}
+
+fn generate_payees<F>(
+    config: &GeneratorConfig,
+    seed: u64,
+    mut sink: F,
+) -> Result<(PayeeRegistry, ChaCha8Rng), Error>
+where
+    F: FnMut(Vec<PaymentRecord>) -> Result<(), Error>,
+{
+    validate_config(config)?;
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let mut plans = build_payee_plans(config)?;
+    plans.shuffle(&mut rng);
+    // Sectors come from their own stream, so the rest of the dataset keeps
+    // its shape when `mcc` is switched on.
+    if config.mcc {
+        let mut sector_rng = ChaCha8Rng::seed_from_u64(seed);
+        sector_rng.set_stream(5);
+        assign_sectors(&mut sector_rng, &mut plans);
+    }
+    let counts = allocate_counts(&mut rng, &plans, config.records)?;
+
+    let mut company_cores = load_company_cores().unwrap_or_default();
+    if company_cores.is_empty() {
+        company_cores = default_company_cores();
+    }
+
+    let (psps, non_eu_psps) = build_config_psps(&mut rng, config)?;
+    if config.multi_psp_ratio > 0.0 && psps.len() < 2 {
+        return Err(codes::GEN_MULTI_PSP_TOO_FEW.fail(format!(
+            "multi-PSP payees need at least two EU PSPs, found {}",
+            psps.len()
+        )));
+    }
+    let payees = build_payees(
+        &mut rng,
+        &plans,
+        &mut company_cores,
+        &psps,
+        &non_eu_psps,
+        config,
+        0,
+    );
+
+    let mix = config.record_mix();
+    let timing = Timing {
+        period: quarter_bounds(config.year, config.quarter)?,
+        holiday_calendars: config.holiday_calendars,
+        profile: &config.time_profile,
+    };
+    // Refunds only link within a payee, so payment ids are dropped once the
+    // payee is done.
+    let mut ids = PaymentIds::new(config.deterministic_ids, seed);
+
+    for (idx, payee) in payees.iter().enumerate() {
+        let mut chunk = Vec::with_capacity(counts[idx]);
+        for _ in 0..counts[idx] {
+            chunk.push(build_record(
+                &mut rng,
+                payee,
+                &mix,
+                &timing,
+                &mut ids,
+            ));
+        }
+        ids.by_payee.remove(&payee.id);
+        sink(chunk)?;
+    }
+
+    let registry = PayeeRegistry {
+        year: config.year,
+        quarter: config.quarter,
+        cross_border_ratio: config.cross_border_ratio,
+        refund_ratio: config.refund_ratio,
+        refund_delay_days: config.refund_delay_days,
+        partial_refund_ratio: config.partial_refund_ratio,
+        max_refunds_per_payment: config.max_refunds_per_payment,
+        foreign_currency_ratio: config.foreign_currency_ratio,
+        payer_account_ratio: config.payer_account_ratio,
+        non_eu_payer_ratio: config.non_eu_payer_ratio,
+        local_time_ratio: config.local_time_ratio,
+        xi_payer_ratio: config.xi_payer_ratio,
+        country_weights: config.country_weights.clone(),
+        holiday_calendars: config.holiday_calendars,
+        time_profile: config.time_profile.clone(),
+        psps: psps.into_iter().chain(non_eu_psps).collect(),
+        issued_payees: plans.len(),
+        mcc: config.mcc,
+        deterministic_ids: config.deterministic_ids,
+        payees: plans
+            .iter()
+            .zip(payees)
+            .zip(counts)
+            .map(|((plan, profile), tx_count)| RegistryPayee {
+                segment: plan.segment.label.to_string(),
+                min_tx: plan.segment.min_tx,
+                max_tx: plan.segment.max_tx,
+                tx_count,
+                profile,
+            })
+            .collect(),
+    };
+    Ok((registry, rng))
+}
+
+#[derive(Debug, Clone)]
+pub struct DatasetCheck {
+    pub name: &'static str,
+    pub expected: String,
+    pub actual: String,
+    pub passed: bool,
+}
+
+// Ratio checks allow this many standard deviations of sampling noise (plus a
+// small absolute slack) before flagging drift.
+const CHECK_SIGMAS: f64 = 4.0;
+
+pub fn verify_generated(
+    config: &GeneratorConfig,
+    data: &GeneratedData,
+    threshold: usize,
+) -> Result<Vec<DatasetCheck>, Error> {
+    let mut stats = DatasetStats::new(config)?;
+    for record in &data.records {
+        stats.observe(record);
+    }
+    Ok(stats.checks(config, &data.registry, threshold))
+}
+
+// Tries `seed`, `seed + 1`, ... until `analyze` would find exactly `target`
+// payees over `threshold`, and returns the data with the seed that produced it.
+pub fn generate_reportable(
+    config: &GeneratorConfig,
+    seed: u64,
+    target: usize,
+    threshold: usize,
+) -> Result<(GeneratedData, u64), Error> {
+    const ATTEMPTS: u64 = 32;
+    let mut closest = usize::MAX;
+    for attempt in 0..ATTEMPTS {
+        let seed = seed.wrapping_add(attempt);
+        let data = generate_records(config, seed)?;
+        let reportable = payee_threshold_counts(&data.records, false)?
+            .values()
+            .filter(|count| **count > threshold)
+            .count();
+        if reportable == target {
+            return Ok((data, seed));
+        }
+        if reportable.abs_diff(target) < closest.abs_diff(target) {
+            closest = reportable;
+        }
+    }
+    Err(codes::GEN_NO_PAYEE_MIX.fail(format!(
+        "no seed from {} to {} gives exactly {} reportable payees (closest: {})",
+        seed,
+        seed.wrapping_add(ATTEMPTS - 1),
+        target,
+        closest
+    )))
+}
+
+// Running totals behind the dataset checks, so streamed datasets can be
+// verified without keeping their records.
+pub struct DatasetStats {
+    period: (DateTime<Utc>, DateTime<Utc>),
+    records: usize,
+    refunds: usize,
+    cross_border: usize,
+    outside_period: usize,
+    outside_examples: Vec<String>,
+    // Per payee: all records, non-refunds, and cross-border non-refunds.
+    per_payee: HashMap<String, (usize, usize, usize)>,
+}
+
+impl DatasetStats {
+    pub fn new(config: &GeneratorConfig) -> Result<Self, Error> {
+        Ok(Self {
+            period: quarter_bounds(config.year, config.quarter)?,
+            records: 0,
+            refunds: 0,
+            cross_border: 0,
+            outside_period: 0,
+            outside_examples: Vec::new(),
+            per_payee: HashMap::new(),
+        })
+    }
+
+    pub fn observe(&mut self, record: &PaymentRecord) {
+        self.records += 1;
+        let (start, end) = self.period;
+        // Every execution_time must parse and fall inside the requested quarter.
+        let inside = DateTime::parse_from_rfc3339(&record.execution_time)
+            .map(|time| time.with_timezone(&Utc))
+            .is_ok_and(|time| time >= start && time < end);
+        if !inside {
+            self.outside_period += 1;
+            if self.outside_examples.len() < 3 {
+                self.outside_examples.push(record.payment_id.clone());
+            }
+        }
+        let cross_border = is_cross_border(record);
+        if cross_border {
+            self.cross_border += 1;
+        }
+        let entry = match self.per_payee.get_mut(record.payee_id.as_str()) {
+            Some(entry) => entry,
+            None => self.per_payee.entry(record.payee_id.clone()).or_default(),
+        };
+        entry.0 += 1;
+        if record.is_refund {
+            self.refunds += 1;
+        } else {
+            entry.1 += 1;
+            if cross_border {
+                entry.2 += 1;
+            }
+        }
+    }
+
+    pub fn checks(
+        &self,
+        config: &GeneratorConfig,
+        registry: &PayeeRegistry,
+        threshold: usize,
+    ) -> Vec<DatasetCheck> {
+        let mut checks = Vec::new();
+
+        checks.push(DatasetCheck {
+            name: "record_count",
+            expected: config.records.to_string(),
+            actual: self.records.to_string(),
+            passed: self.records == config.records,
+        });
+
+        checks.push(DatasetCheck {
+            name: "period_bounds",
+            expected: format!("every execution_time in {}-Q{}", config.year, config.quarter),
+            actual: if self.outside_period == 0 {
+                "all within the quarter".to_string()
+            } else {
+                format!(
+                    "{} record(s) outside the quarter (e.g. {})",
+                    self.outside_period,
+                    self.outside_examples.join(", ")
+                )
+            },
+            passed: self.outside_period == 0,
+        });
+
+        let per_payee = &self.per_payee;
+        checks.push(DatasetCheck {
+            name: "payee_count",
+            expected: config.payees.to_string(),
+            actual: per_payee.len().to_string(),
+            passed: per_payee.len() == config.payees,
+        });
+
+        let out_of_segment: Vec<&str> = registry
+            .payees
+            .iter()
+            .filter(|payee| {
+                let count = per_payee.get(payee.profile.id.as_str()).map_or(0, |c| c.0);
+                count != payee.tx_count || count < payee.min_tx || count > payee.max_tx
+            })
+            .map(|payee| payee.profile.id.as_str())
+            .collect();
+        checks.push(DatasetCheck {
+            name: "segment_tx_counts",
+            expected: "every payee within its segment bounds".to_string(),
+            actual: if out_of_segment.is_empty() {
+                "all within bounds".to_string()
+            } else {
+                format!(
+                    "{} payee(s) outside bounds (e.g. {})",
+                    out_of_segment.len(),
+                    out_of_segment.iter().take(3).copied().collect::<Vec<_>>().join(", ")
+                )
+            },
+            passed: out_of_segment.is_empty(),
+        });
+
+        let total = self.records.max(1) as f64;
+        checks.push(ratio_check(
+            "refund_ratio",
+            config.refund_ratio,
+            self.refunds as f64 / total,
+            (config.refund_ratio * (1.0 - config.refund_ratio) / total).sqrt(),
+        ));
+
+        // Payments from EU payers to non-EU payees are always cross-border;
+        // non-EU payers never are.
+        let mut expected_cross_border = 0.0;
+        let mut cross_border_variance = 0.0;
+        let mut expected_over = 0.0;
+        let mut over_variance = 0.0;
+        for payee in &registry.payees {
+            let (count, non_refunds, _) = per_payee
+                .get(payee.profile.id.as_str())
+                .copied()
+                .unwrap_or((0, 0, 0));
+            let p = if is_eu_member_state(&payee.profile.country) {
+                config.cross_border_ratio
+            } else {
+                1.0
+            } * (1.0 - config.non_eu_payer_ratio)
+                * (1.0 - config.xi_payer_ratio);
+            expected_cross_border += count as f64 * p;
+            cross_border_variance += count as f64 * p * (1.0 - p);
+            let q = binomial_tail(non_refunds, p, threshold);
+            expected_over += q;
+            over_variance += q * (1.0 - q);
+        }
+        checks.push(ratio_check(
+            "cross_border_ratio",
+            expected_cross_border / total,
+            self.cross_border as f64 / total,
+            cross_border_variance.sqrt() / total,
+        ));
+
+        // Counted per payee on the generated country, without the identifier
+        // grouping that analyze applies to PSP-represented payees.
+        let over = self.payees_over(threshold);
+        let tolerance = CHECK_SIGMAS * over_variance.sqrt() + 1.0;
+        checks.push(DatasetCheck {
+            name: "payees_over_threshold",
+            expected: format!("{:.1} ± {:.1}", expected_over, tolerance),
+            actual: over.to_string(),
+            passed: (over as f64 - expected_over).abs() <= tolerance,
+        });
+
+        checks
+    }
+
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    pub fn cross_border(&self) -> usize {
+        self.cross_border
+    }
+
+    pub fn payees_over(&self, threshold: usize) -> usize {
+        self.per_payee
+            .values()
+            .filter(|(_, _, cross_border)| *cross_border > threshold)
+            .count()
+    }
+}
+
+fn is_cross_border(record: &PaymentRecord) -> bool {
+    is_eu_member_state(&record.payer_country) && record.payer_country != record.payee_country
+}
+
+fn ratio_check(name: &'static str, expected: f64, actual: f64, std_dev: f64) -> DatasetCheck {
+    let tolerance = CHECK_SIGMAS * std_dev + 0.005;
+    DatasetCheck {
+        name,
+        expected: format!("{:.4} ± {:.4}", expected, tolerance),
+        actual: format!("{:.4}", actual),
+        passed: (actual - expected).abs() <= tolerance,
+    }
+}
+
+// P(X > threshold) for X ~ Binomial(n, p).
+fn binomial_tail(n: usize, p: f64, threshold: usize) -> f64 {
+    if n <= threshold || p <= 0.0 {
+        return 0.0;
+    }
+    if p >= 1.0 {
+        return 1.0;
+    }
+    let mut pmf = (1.0 - p).powi(n as i32);
+    let mut below = 0.0;
+    for k in 0..=threshold {
+        below += pmf;
+        pmf *= (n - k) as f64 / (k + 1) as f64 * p / (1.0 - p);
+    }
+    (1.0 - below).clamp(0.0, 1.0)
+}
+
+pub fn append_records(
+    registry: &mut PayeeRegistry,
+    existing: &[PaymentRecord],
+    additional: usize,
+    seed: u64,
+) -> Result<Vec<PaymentRecord>, Error> {
+    let capacity = registry.remaining_capacity();
+    if additional > capacity {
+        return Err(codes::GEN_APPEND_CAPACITY.fail(format!(
+            "cannot append {} records: payee segments only allow {} more",
+            additional, capacity
+        )));
+    }
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mix = registry.record_mix();
+    let timing = Timing {
+        period: quarter_bounds(registry.year, registry.quarter)?,
+        holiday_calendars: registry.holiday_calendars,
+        profile: &registry.time_profile,
+    };
+
+    let mut ids = PaymentIds::new(registry.deterministic_ids, seed);
+    for record in existing.iter().filter(|record| !record.is_refund) {
+        if let (Some(time), Some(cents)) = (record_time(record), record_cents(record)) {
+            ids.remember(
+                &record.payee_id,
+                record.payment_id.clone(),
+                record.psp_id.clone(),
+                time,
+                cents,
+                record.currency.clone(),
+            );
+        }
+    }
+    for record in existing.iter().filter(|record| record.is_refund) {
+        if let (Some(corr_id), Some(time), Some(cents)) = (
+            record.corr_payment_id.as_deref(),
+            record_time(record),
+            record_cents(record),
+        ) {
+            ids.remember_refund(&record.payee_id, corr_id, time, cents);
+        }
+    }
+    let existing_ids: HashSet<&str> = existing
+        .iter()
+        .map(|record| record.payment_id.as_str())
+        .collect();
+
+    let mut counts = vec![0usize; registry.payees.len()];
+    let mut remaining = additional;
+    while remaining > 0 {
+        let idx = rng.gen_range(0..registry.payees.len());
+        let payee = &registry.payees[idx];
+        if payee.tx_count + counts[idx] < payee.max_tx {
+            counts[idx] += 1;
+            remaining -= 1;
+        }
+    }
+
+    let mut records = Vec::with_capacity(additional);
+    for (idx, payee) in registry.payees.iter_mut().enumerate() {
+        for _ in 0..counts[idx] {
+            let record = build_record(
+                &mut rng,
+                &payee.profile,
+                &mix,
+                &timing,
+                &mut ids,
+            );
+            if existing_ids.contains(record.payment_id.as_str()) {
+                return Err(codes::GEN_IDENTIFIERS_EXHAUSTED
+                    .fail(format!("payment_id collision: {}", record.payment_id)));
+            }
+            records.push(record);
+        }
+        payee.tx_count += counts[idx];
+    }
+
+    records.shuffle(&mut rng);
+    Ok(records)
+}
+
+fn record_time(record: &PaymentRecord) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(record.execution_time.trim())
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+fn record_cents(record: &PaymentRecord) -> Option<i64> {
+    let value = record.amount.trim().parse::<f64>().ok()?;
+    Some((value.abs() * 100.0).round() as i64)
+}
+
+// Generates another quarter for the payees, accounts and PSPs of `registry`.
+// Every payee gets a fresh transaction count within its segment bounds and
+// the total volume matches the registry's quarter; refunds only link to
+// payments of the same quarter. `config` supplies the period, id mode and
+// churn; new payees follow its payee ratios.
+pub fn generate_quarter(
+    registry: &PayeeRegistry,
     config: &GeneratorConfig,
     seed: u64,
-) -> Result<Vec<PaymentRecord>, String> {
+) -> Result<GeneratedData, Error> {
     validate_config(config)?;
     let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut ids = PaymentIds::new(config.deterministic_ids, seed);
+    let mix = registry.record_mix();
+    let timing = Timing {
+        period: quarter_bounds(config.year, config.quarter)?,
+        holiday_calendars: registry.holiday_calendars,
+        profile: &registry.time_profile,
+    };
+    let total = registry.records();
+
+    let mut next = registry.clone();
+    next.set_period(config.year, config.quarter);
+    let churn = if config.churn.is_active() {
+        apply_churn(&mut rng, &mut next, config)
+    } else {
+        ChurnSummary::default()
+    };
+
+    let mut counts: Vec<usize> = next.payees.iter().map(|payee| payee.min_tx).collect();
+    let max: Vec<usize> = next.payees.iter().map(|payee| payee.max_tx).collect();
+    let capacity: usize = max.iter().sum();
+    let remaining = total.min(capacity).saturating_sub(counts.iter().sum());
+    let volume: Vec<f64> = if next.mcc {
+        next.payees
+            .iter()
+            .map(|payee| volume_factor(payee.profile.mcc.as_deref()))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    distribute_counts(&mut rng, &mut counts, &max, &volume, remaining)?;
+
+    let mut records = Vec::with_capacity(total);
+    for (payee, count) in next.payees.iter_mut().zip(counts) {
+        for _ in 0..count {
+            records.push(build_record(
+                &mut rng,
+                &payee.profile,
+                &mix,
+                &timing,
+                &mut ids,
+            ));
+        }
+        payee.tx_count = count;
+    }
+
+    records.shuffle(&mut rng);
+    Ok(GeneratedData {
+        records,
+        registry: next,
+        churn,
+    })
+}
+
+// Payees leaving, joining and switching accounts between two quarters.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Churn {
+    pub leave_ratio: f64,
+    pub join_ratio: f64,
+    pub account_change_ratio: f64,
+}
+
+impl Churn {
+    pub fn is_active(&self) -> bool {
+        self.leave_ratio > 0.0 || self.join_ratio > 0.0 || self.account_change_ratio > 0.0
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ChurnSummary {
+    pub left: usize,
+    pub joined: usize,
+    pub changed_accounts: usize,
+}
+
+// Removes leaving payees (at least one payee stays), gives some payees a new
+// primary account and adds new payees with the segments of the previous
+// roster.
+fn apply_churn<R: Rng + ?Sized>(
+    rng: &mut R,
+    registry: &mut PayeeRegistry,
+    config: &GeneratorConfig,
+) -> ChurnSummary {
+    let churn = &config.churn;
+    let mut summary = ChurnSummary::default();
+    let previous = registry.payees.len();
+    let segments: Vec<String> = registry
+        .payees
+        .iter()
+        .map(|payee| payee.segment.clone())
+        .collect();
+
+    if churn.leave_ratio > 0.0 {
+        let mut leaving: Vec<bool> = (0..previous)
+            .map(|_| rng.gen_bool(churn.leave_ratio))
+            .collect();
+        if leaving.iter().all(|leaves| *leaves) {
+            leaving[rng.gen_range(0..previous)] = false;
+        }
+        let mut leaving = leaving.into_iter();
+        registry
+            .payees
+            .retain(|_| !leaving.next().unwrap_or(false));
+        summary.left = previous - registry.payees.len();
+    }
+
+    if churn.account_change_ratio > 0.0 {
+        for payee in &mut registry.payees {
+            let profile = &mut payee.profile;
+            let primary = profile
+                .accounts
+                .iter_mut()
+                .find(|account| account.account_type != "BIC");
+            if let Some(account) = primary {
+                if rng.gen_bool(churn.account_change_ratio) {
+                    let (id, account_type) = generate_account_identifier(rng, &profile.country);
+                    *account = PayeeAccount { id, account_type };
+                    summary.changed_accounts += 1;
+                }
+            }
+        }
+    }
 
-    let mut plans = build_payee_plans(config)?;
-    plans.shuffle(&mut rng);
-    let counts = allocate_counts(&mut rng, &plans, config.records)?;
+    let joins = (previous as f64 * churn.join_ratio).round() as usize;
+    if joins > 0 {
+        summary.joined = add_payees(rng, registry, config, &segments, joins);
+    }
+    summary
+}
 
+// New payees get ids after every id the registry has issued, so a payee that
+// left is never confused with a newcomer.
+fn add_payees<R: Rng + ?Sized>(
+    rng: &mut R,
+    registry: &mut PayeeRegistry,
+    config: &GeneratorConfig,
+    segments: &[String],
+    count: usize,
+) -> usize {
+    let mut plans: Vec<PayeePlan> = (0..count)
+        .filter_map(|_| segments.choose(rng).and_then(|label| segment_for_label(label)))
+        .map(|segment| PayeePlan {
+            segment,
+            sector: None,
+        })
+        .collect();
+    if registry.mcc {
+        assign_sectors(rng, &mut plans);
+    }
+    let (mut psps, non_eu_psps): (Vec<PspProfile>, Vec<PspProfile>) =
+        registry.psps.iter().cloned().partition(|psp| {
+            bic_country_code(&psp.id).is_some_and(|country| is_eu_member_state(&country))
+        });
+    if psps.is_empty() {
+        psps = registry
+            .payees
+            .iter()
+            .take(1)
+            .map(|payee| PspProfile {
+                id: payee.profile.reporting_psp_id.clone(),
+                name: payee.profile.reporting_psp_name.clone(),
+            })
+            .collect();
+    }
     let mut company_cores = load_company_cores().unwrap_or_default();
     if company_cores.is_empty() {
         company_cores = default_company_cores();
     }
+    company_cores.retain(|core| {
+        !registry
+            .payees
+            .iter()
+            .any(|payee| payee.profile.name.starts_with(core.as_str()))
+    });
+
+    let issued = registry.issued_payees.max(
+        registry
+            .payees
+            .iter()
+            .filter_map(|payee| payee.profile.id.strip_prefix("MER")?.parse::<usize>().ok())
+            .max()
+            .unwrap_or(0),
+    );
+    let payees = build_payees(
+        rng,
+        &plans,
+        &mut company_cores,
+        &psps,
+        &non_eu_psps,
+        config,
+        issued,
+    );
+    registry.issued_payees = issued + payees.len();
+    let joined = payees.len();
+    registry
+        .payees
+        .extend(plans.iter().zip(payees).map(|(plan, profile)| RegistryPayee {
+            segment: plan.segment.label.to_string(),
+            min_tx: plan.segment.min_tx,
+            max_tx: plan.segment.max_tx,
+            tx_count: 0,
+            profile,
+        }));
+    joined
+}
 
-    let psps = build_psps(&mut rng, config.psps)?;
-    let non_eu_psps = build_non_eu_psps(&mut rng, (config.psps / 2).max(1))?;
+fn segment_for_label(label: &str) -> Option<PayeeSegment> {
+    [
+        segment_micro(),
+        segment_small(),
+        segment_mid(),
+        segment_near_below(),
+        segment_near_above(),
+        segment_large(),
+    ]
+    .into_iter()
+    .find(|segment| segment.label == label)
+}
+
+// Payees whose cross-border payment count sits on the >25 reporting threshold:
+// (payee id, cross-border payments, cross-border refunds). Refund variants
+// cross the threshold only when refunds are counted.
+pub const THRESHOLD_EDGE_PAYEES: &[(&str, usize, usize)] = &[
+    ("EDGE-24", 24, 0),
+    ("EDGE-25", 25, 0),
+    ("EDGE-26", 26, 0),
+    ("EDGE-24-R2", 24, 2),
+    ("EDGE-25-R1", 25, 1),
+    ("EDGE-26-R1", 26, 1),
+];
+// Domestic payments per edge payee, which must never count.
+const THRESHOLD_EDGE_DOMESTIC: usize = 3;
+
+// Records of the threshold edge payees: EU payees with one account, paid
+// through the PSPs of `registry`. They are kept out of the registry so
+// appends and later quarters do not inherit exact counts.
+pub fn threshold_edge_records(
+    registry: &PayeeRegistry,
+    config: &GeneratorConfig,
+    seed: u64,
+) -> Result<Vec<PaymentRecord>, Error> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(2);
+    let mut ids = PaymentIds::new(config.deterministic_ids, seed.wrapping_add(1));
+    let timing = Timing {
+        period: quarter_bounds(config.year, config.quarter)?,
+        holiday_calendars: registry.holiday_calendars,
+        profile: &registry.time_profile,
+    };
+    let edge_config = GeneratorConfig {
+        perspective: Perspective::Payee,
+        unknown_payee_psp_ratio: 0.0,
+        multi_account_ratio: 0.0,
+        non_eu_payee_ratio: 0.0,
+        xi_payee_ratio: 0.0,
+        multi_psp_ratio: 0.0,
+        no_account_payee_ratio: 0.0,
+        marketplace_ratio: 0.0,
+        unicode_name_ratio: 0.0,
+        ..config.clone()
+    };
+    let psps: Vec<PspProfile> = registry
+        .psps
+        .iter()
+        .filter(|psp| bic_country_code(&psp.id).is_some_and(|country| is_eu_member_state(&country)))
+        .cloned()
+        .collect();
+    if psps.is_empty() {
+        return Err(codes::GEN_PSP_COUNTRY_NOT_EU
+            .fail("threshold edge payees need an EU PSP in the registry"));
+    }
+    let mut plans: Vec<PayeePlan> = THRESHOLD_EDGE_PAYEES
+        .iter()
+        .map(|(_, payments, refunds)| PayeePlan {
+            segment: PayeeSegment {
+                label: "threshold_edge",
+                min_tx: payments + refunds + THRESHOLD_EDGE_DOMESTIC,
+                max_tx: payments + refunds + THRESHOLD_EDGE_DOMESTIC,
+                amount_min: 20.0,
+                amount_max: 300.0,
+            },
+            sector: None,
+        })
+        .collect();
+    if config.mcc {
+        assign_sectors(&mut rng, &mut plans);
+    }
+    let mut company_cores = default_company_cores();
     let payees = build_payees(
         &mut rng,
         &plans,
         &mut company_cores,
         &psps,
+        &[],
+        &edge_config,
+        0,
+    );
+
+    let mut mix = registry.record_mix();
+    mix.refund = 0.0;
+    mix.non_eu_payer = 0.0;
+    mix.xi_payer = 0.0;
+    let mut records = Vec::new();
+    for (mut payee, (id, payments, refunds)) in payees.into_iter().zip(THRESHOLD_EDGE_PAYEES) {
+        payee.id = id.to_string();
+        mix.cross_border = 1.0;
+        for _ in 0..*payments {
+            records.push(build_record(&mut rng, &payee, &mix, &timing, &mut ids));
+        }
+        mix.cross_border = 0.0;
+        for _ in 0..THRESHOLD_EDGE_DOMESTIC {
+            records.push(build_record(&mut rng, &payee, &mix, &timing, &mut ids));
+        }
+        mix.cross_border = 1.0;
+        mix.refund = 1.0;
+        for _ in 0..*refunds {
+            let record = build_record(&mut rng, &payee, &mix, &timing, &mut ids);
+            if !record.is_refund {
+                return Err(codes::GEN_REFUND_DELAY_INVALID
+                    .fail("threshold edge refunds need a refund delay that fits the quarter"));
+            }
+            records.push(record);
+        }
+        mix.refund = 0.0;
+    }
+    records.shuffle(&mut rng);
+    Ok(records)
+}
+
+// The same payments reported a second time by the payer's PSP: for a `ratio`
+// share of the payments reported by the payee's PSP, a copy with a new
+// payment id, reported by another EU PSP of `registry` in the payer role.
+// Amount, time, parties and method stay identical, so the pairs differ only in
+// payment id and reporting PSP. Refunds are never duplicated.
+pub fn cross_psp_duplicates(
+    records: &[PaymentRecord],
+    registry: &PayeeRegistry,
+    config: &GeneratorConfig,
+    ratio: f64,
+    seed: u64,
+) -> Result<Vec<PaymentRecord>, Error> {
+    if !(0.0..=1.0).contains(&ratio) {
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("cross_psp_duplicate_ratio must be 0..1"));
+    }
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(3);
+    let mut ids = PaymentIds::new(config.deterministic_ids, seed.wrapping_add(2));
+    let psps: Vec<&PspProfile> = registry
+        .psps
+        .iter()
+        .filter(|psp| bic_country_code(&psp.id).is_some_and(|country| is_eu_member_state(&country)))
+        .collect();
+    if ratio > 0.0 && psps.len() < 2 {
+        return Err(codes::GEN_DUPLICATE_PSPS_TOO_FEW.fail(format!(
+            "cross-PSP duplicates need at least two EU PSPs, found {}",
+            psps.len()
+        )));
+    }
+    let mut duplicates = Vec::new();
+    for record in records {
+        if record.is_refund
+            || record.psp_role.as_deref() != Some(PSP_ROLE_PAYEE)
+            || !rng.gen_bool(ratio)
+        {
+            continue;
+        }
+        let payer_psps: Vec<&&PspProfile> =
+            psps.iter().filter(|psp| psp.id != record.psp_id).collect();
+        let Some(payer_psp) = payer_psps.choose(&mut rng) else {
+            continue;
+        };
+        duplicates.push(PaymentRecord {
+            payment_id: ids.issue(),
+            psp_role: Some(PSP_ROLE_PAYER.to_string()),
+            psp_id: payer_psp.id.clone(),
+            psp_name: payer_psp.name.clone(),
+            ..record.clone()
+        });
+    }
+    Ok(duplicates)
+}
+
+// One payee for `synth`: a segment drawn from the config's mix, fresh PSPs
+// and an id from `rng`, so repeated calls rarely collide.
+pub(crate) fn synth_payee<R: Rng + ?Sized>(
+    rng: &mut R,
+    config: &GeneratorConfig,
+) -> Result<PayeeProfile, Error> {
+    validate_config(config)?;
+    let mut plans = build_payee_plans(config)?;
+    let index = rng.gen_range(0..plans.len());
+    let mut plan = plans.swap_remove(index);
+    if config.mcc {
+        assign_sectors(rng, std::slice::from_mut(&mut plan));
+    }
+    let (psps, non_eu_psps) = build_config_psps(rng, config)?;
+    let issued = rng.gen_range(0..999_999);
+    let payees = build_payees(
+        rng,
+        &[plan],
+        &mut default_company_cores(),
+        &psps,
         &non_eu_psps,
-        config.multi_account_ratio,
-        config.non_eu_payee_ratio,
-        config.no_account_payee_ratio,
+        config,
+        issued,
     );
+    payees
+        .into_iter()
+        .next()
+        .ok_or_else(|| codes::GEN_PAYEES_ZERO.fail_summary())
+}
+
+// One payment (never a refund) to `payee` in the config's quarter, with a
+// payment id drawn from `rng`.
+pub(crate) fn synth_payment<R: Rng + ?Sized>(
+    rng: &mut R,
+    payee: &PayeeProfile,
+    config: &GeneratorConfig,
+) -> Result<PaymentRecord, Error> {
+    let mut mix = config.record_mix();
+    mix.refund = 0.0;
+    let timing = Timing {
+        period: quarter_bounds(config.year, config.quarter)?,
+        holiday_calendars: config.holiday_calendars,
+        profile: &config.time_profile,
+    };
+    let mut ids = PaymentIds::new(true, rng.gen());
+    Ok(build_record(rng, payee, &mix, &timing, &mut ids))
+}
 
-    let (period_start, period_end) = quarter_bounds(config.year, config.quarter)?;
-    let mut seen_by_payee: HashMap<String, Vec<String>> = HashMap::new();
+// Per-record ratios, taken from the config for fresh runs and from the
+// registry for appends and later quarters.
+struct RecordMix {
+    cross_border: f64,
+    refund: f64,
+    refund_delay_days: (u32, u32),
+    partial_refund: f64,
+    max_refunds_per_payment: u32,
+    foreign_currency: f64,
+    payer_account: f64,
+    non_eu_payer: f64,
+    local_time: f64,
+    xi_payer: f64,
+    country_weights: CountryWeights,
+}
 
-    let mut records = Vec::with_capacity(config.records);
-    for (idx, payee) in payees.iter().enumerate() {
-        let count = counts[idx];
-        for _ in 0..count {
-            let is_refund = rng.gen_bool(config.refund_ratio);
-            let corr_payment_id = if is_refund {
-                seen_by_payee
-                    .get(&payee.id)
-                    .and_then(|ids| ids.choose(&mut rng).cloned())
-            } else {
-                None
-            };
-            let is_refund = is_refund && corr_payment_id.is_some();
-
-            let payment_id = uuid::Uuid::new_v4().to_string();
-            seen_by_payee
-                .entry(payee.id.clone())
-                .or_default()
-                .push(payment_id.clone());
-
-            let payer_country =
-                pick_payer_country(&mut rng, &payee.country, config.cross_border_ratio);
-            let amount_value = rng.gen_range(payee.amount_min..payee.amount_max);
-            let currency = currency_for_country(&payer_country).to_string();
-            let execution_time = random_datetime(&mut rng, period_start, period_end)
-                .to_rfc3339_opts(SecondsFormat::Millis, true);
-
-            let payment_method = PAYMENT_METHODS
-                .choose(&mut rng)
-                .unwrap_or(&"Card payment")
-                .to_string();
-
-            let initiated_at_pos = if payment_method == "Card payment" {
-                rng.gen_bool(0.7)
-            } else {
-                rng.gen_bool(0.2)
-            };
-            let payer_ms_source = pick_payer_ms_source(&mut rng).to_string();
-            let (payee_account, payee_account_type) = if let Some(account) =
-                payee.accounts.choose(&mut rng)
+impl GeneratorConfig {
+    fn record_mix(&self) -> RecordMix {
+        RecordMix {
+            cross_border: self.cross_border_ratio,
+            refund: self.refund_ratio,
+            refund_delay_days: self.refund_delay_days,
+            partial_refund: self.partial_refund_ratio,
+            max_refunds_per_payment: self.max_refunds_per_payment,
+            foreign_currency: self.foreign_currency_ratio,
+            payer_account: self.payer_account_ratio,
+            non_eu_payer: self.non_eu_payer_ratio,
+            local_time: self.local_time_ratio,
+            xi_payer: self.xi_payer_ratio,
+            country_weights: self.country_weights.clone(),
+        }
+    }
+}
+
+impl PayeeRegistry {
+    fn record_mix(&self) -> RecordMix {
+        RecordMix {
+            cross_border: self.cross_border_ratio,
+            refund: self.refund_ratio,
+            refund_delay_days: self.refund_delay_days,
+            partial_refund: self.partial_refund_ratio,
+            max_refunds_per_payment: self.max_refunds_per_payment,
+            foreign_currency: self.foreign_currency_ratio,
+            payer_account: self.payer_account_ratio,
+            non_eu_payer: self.non_eu_payer_ratio,
+            local_time: self.local_time_ratio,
+            xi_payer: self.xi_payer_ratio,
+            country_weights: self.country_weights.clone(),
+        }
+    }
+}
+
+fn build_record<R: Rng + ?Sized>(
+    rng: &mut R,
+    payee: &PayeeProfile,
+    mix: &RecordMix,
+    timing: &Timing,
+    ids: &mut PaymentIds,
+) -> PaymentRecord {
+    let (min_delay, max_delay) = mix.refund_delay_days;
+    let (_, period_end) = timing.period;
+    let refund_of = if rng.gen_bool(mix.refund) {
+        ids.refundable(
+            &payee.id,
+            period_end - Duration::days(min_delay.into()),
+            mix.max_refunds_per_payment,
+        )
+        .choose(rng)
+        .copied()
+    } else {
+        None
+    };
+    let is_refund = refund_of.is_some();
+
+    let payment_id = ids.issue();
+
+    let payer_country = if mix.non_eu_payer > 0.0 && rng.gen_bool(mix.non_eu_payer) {
+        pick_non_eu_payer_country(rng, &payee.country)
+    } else if mix.xi_payer > 0.0 && rng.gen_bool(mix.xi_payer) {
+        NORTHERN_IRELAND.to_string()
+    } else {
+        pick_payer_country(rng, &payee.country, mix.cross_border, &mix.country_weights)
+    };
+    let amount_value = rng.gen_range(payee.amount_min..payee.amount_max);
+    let currency = if mix.foreign_currency > 0.0 && rng.gen_bool(mix.foreign_currency) {
+        FOREIGN_CURRENCIES.choose(rng).unwrap_or(&"USD")
+    } else {
+        currency_for_country(&payer_country)
+    }
+    .to_string();
+    let refunded = refund_of.and_then(|index| {
+        ids.by_payee
+            .get_mut(&payee.id)
+            .and_then(|earlier| earlier.get_mut(index))
+    });
+    let (corr_payment_id, psp_id, execution_time, cents, currency) = match refunded {
+        Some(original) => {
+            let earliest = original.time + Duration::days(min_delay.into());
+            let latest = (original.time + Duration::days(max_delay.into()))
+                .min(period_end - Duration::milliseconds(1));
+            let time = random_datetime_inclusive(rng, earliest, latest);
+            let remaining = original.cents - original.refunded_cents;
+            // Partial refunds return 10-90% of what is left of the payment.
+            let cents = if mix.partial_refund > 0.0
+                && remaining > 1
+                && rng.gen_bool(mix.partial_refund)
             {
-                (account.id.clone(), account.account_type.clone())
+                rng.gen_range((remaining / 10).max(1)..=(remaining * 9 / 10).max(1))
             } else {
-                (String::new(), String::new())
+                remaining
             };
+            original.time = time;
+            original.refunded_cents += cents;
+            original.refunds += 1;
+            let psp_id = original.psp_id.clone();
+            (Some(original.id.clone()), psp_id, time, cents, original.currency.clone())
+        }
+        None => {
+            let time = timing.sample(rng, &payee.country);
+            let cents = (amount_value * 100.0).round() as i64;
+            let psp_id = match &payee.second_psp {
+                Some(second) if rng.gen_bool(0.5) => second.id.clone(),
+                _ => payee.reporting_psp_id.clone(),
+            };
+            ids.remember(
+                &payee.id,
+                payment_id.clone(),
+                psp_id.clone(),
+                time,
+                cents,
+                currency.clone(),
+            );
+            (None, psp_id, time, cents, currency)
+        }
+    };
+    // Refunds go through the PSP that reported their payment.
+    let second_psp = payee
+        .second_psp
+        .as_ref()
+        .filter(|second| second.id == psp_id);
+    let psp_name = second_psp.map_or(&payee.reporting_psp_name, |second| &second.name);
+    let execution_time = if mix.local_time > 0.0 && rng.gen_bool(mix.local_time) {
+        let country = bic_country_code(&psp_id).unwrap_or_default();
+        execution_time
+            .with_timezone(&utc_offset(&country, execution_time))
+            .to_rfc3339_opts(SecondsFormat::Millis, false)
+    } else {
+        execution_time.to_rfc3339_opts(SecondsFormat::Millis, true)
+    };
 
-            records.push(PaymentRecord {
-                payment_id,
-                execution_time,
-                amount: format_amount(amount_value),
-                currency,
-                payer_country,
-                payer_ms_source,
-                payee_country: payee.country.clone(),
-                payee_id: payee.id.clone(),
-                payee_name: payee.name.clone(),
-                payee_account,
-                payee_account_type,
-                payee_tax_id: payee.tax_id.clone(),
-                payee_vat_id: payee.vat_id.clone(),
-                payee_email: payee.email.clone(),
-                payee_web: payee.web.clone(),
-                payee_address_line: payee.address_line.clone(),
-                payee_city: payee.city.clone(),
-                payee_postcode: payee.postcode.clone(),
-                payment_method,
-                initiated_at_pos,
-                is_refund,
-                corr_payment_id,
-                psp_role: Some(payee.psp_role.clone()),
-                payee_psp_id: Some(payee.payee_psp_id.clone()),
-                payee_psp_name: Some(payee.payee_psp_name.clone()),
-                psp_id: payee.reporting_psp_id.clone(),
-                psp_name: payee.reporting_psp_name.clone(),
-            });
+    let (payment_method, payment_method_other) = if payee.sub_merchants.is_empty() {
+        let method = PAYMENT_METHODS.choose(rng).unwrap_or(&"Card payment");
+        (method.to_string(), None)
+    } else {
+        pick_marketplace_method(rng, payee.via_intermediary)
+    };
+    let sub_merchant = payee.sub_merchants.choose(rng);
+
+    let initiated_at_pos = if payment_method == "Card payment" {
+        rng.gen_bool(0.7)
+    } else {
+        rng.gen_bool(0.2)
+    };
+    let payer_ms_source = pick_payer_ms_source(rng).to_string();
+    let (payer_account, payer_account_type) =
+        if mix.payer_account > 0.0 && rng.gen_bool(mix.payer_account) {
+            let (account, account_type) =
+                generate_payer_account(rng, &payer_country, &payer_ms_source);
+            (Some(account), Some(account_type))
+        } else {
+            (None, None)
+        };
+    let (payee_account, payee_account_type) = if let Some(account) = payee.accounts.choose(rng) {
+        (account.id.clone(), account.account_type.clone())
+    } else {
+        (String::new(), String::new())
+    };
+    // Reporting as the payee's PSP, the second PSP also holds the account.
+    let (payee_psp_id, payee_psp_name) = match second_psp {
+        Some(second) if payee.psp_role == PSP_ROLE_PAYEE => {
+            (second.id.clone(), second.name.clone())
         }
+        _ => (payee.payee_psp_id.clone(), payee.payee_psp_name.clone()),
+    };
+    let payee_psp_id = Some(payee_psp_id).filter(|id| !id.is_empty());
+    let payee_psp_name = Some(payee_psp_name).filter(|name| !name.is_empty());
+    // Paid without an account, the payee is reported with its PSP as
+    // Representative, which is located where its BIC says.
+    let payee_psp_country = payee_psp_id
+        .as_deref()
+        .filter(|_| payee.accounts.is_empty())
+        .and_then(bic_country_code);
+    let payee_psp_address = payee_psp_id
+        .as_deref()
+        .zip(payee_psp_country.as_deref())
+        .map(|(id, country)| representative_address(id, country));
+
+    PaymentRecord {
+        payment_id,
+        execution_time,
+        amount: format_amount(cents as f64 / 100.0),
+        currency,
+        payer_country,
+        payer_ms_source,
+        payee_country: payee.country.clone(),
+        payee_id: payee.id.clone(),
+        payee_name: payee.name.clone(),
+        payee_account,
+        payee_account_type,
+        payee_tax_id: payee.tax_id.clone(),
+        payee_vat_id: payee.vat_id.clone(),
+        payee_email: payee.email.clone(),
+        payee_web: payee.web.clone(),
+        payee_address_line: payee.address_line.clone(),
+        payee_city: payee.city.clone(),
+        payee_postcode: payee.postcode.clone(),
+        payment_method,
+        initiated_at_pos,
+        is_refund,
+        corr_payment_id,
+        psp_role: Some(payee.psp_role.clone()),
+        payee_psp_id,
+        payee_psp_name,
+        psp_id,
+        psp_name: psp_name.clone(),
+        payment_method_other,
+        sub_merchant_id: sub_merchant.map(|merchant| merchant.id.clone()),
+        sub_merchant_name: sub_merchant.map(|merchant| merchant.name.clone()),
+        sub_merchant_country: sub_merchant.map(|merchant| merchant.country.clone()),
+        payer_account,
+        payer_account_type,
+        payee_mcc: payee.mcc.clone(),
+        payee_psp_country,
+        payee_psp_address,
     }
+}
 
-    records.shuffle(&mut rng);
-    Ok(records)
+// The office of a PSP acting for payees, the same on all their payments. It
+// is drawn from a stream seeded by the PSP id, so the records' own stream is
+// unchanged.
+fn representative_address(psp_id: &str, country: &str) -> String {
+    let digest = sha256_hex(psp_id.as_bytes());
+    let mut rng = ChaCha8Rng::seed_from_u64(u64::from_str_radix(&digest[..16], 16).unwrap_or(0));
+    let street_num = rng.gen_range(1..250);
+    let street = STREET_NAMES.choose(&mut rng).unwrap_or(&"Market");
+    let (city, postcode_prefix) = city_postcodes(country)
+        .and_then(|cities| cities.choose(&mut rng))
+        .copied()
+        .unwrap_or_else(|| (CITIES.choose(&mut rng).unwrap_or(&"Berlin"), ""));
+    let postcode = generate_postcode(&mut rng, country, postcode_prefix);
+    format!("{} {} St, {} {}", street_num, street, postcode, city)
 }
 
-fn validate_config(config: &GeneratorConfig) -> Result<(), String> {
+pub fn validate_config(config: &GeneratorConfig) -> Result<(), Error> {
     if config.payees == 0 {
-        return Err("payees must be greater than 0".to_string());
+        return Err(codes::GEN_PAYEES_ZERO.fail_summary());
     }
     if config.psps == 0 {
-        return Err("psps must be greater than 0".to_string());
+        return Err(codes::GEN_PSPS_ZERO.fail_summary());
     }
     if config.micro_payees + config.near_threshold_payees + config.large_payees > config.payees {
-        return Err("micro/near/large payees cannot exceed total payees".to_string());
+        return Err(codes::GEN_SEGMENTS_EXCEED_PAYEES.fail_summary());
     }
     if !(1..=4).contains(&config.quarter) {
-        return Err("quarter must be 1..4".to_string());
+        return Err(codes::GEN_INVALID_QUARTER.fail_summary());
     }
     if !(0.0..=1.0).contains(&config.cross_border_ratio) {
-        return Err("cross_border_ratio must be 0..1".to_string());
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("cross_border_ratio must be 0..1"));
     }
     if !(0.0..=1.0).contains(&config.refund_ratio) {
-        return Err("refund_ratio must be 0..1".to_string());
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("refund_ratio must be 0..1"));
+    }
+    let (min_delay, max_delay) = config.refund_delay_days;
+    if min_delay > max_delay || max_delay > MAX_REFUND_DELAY_DAYS {
+        return Err(codes::GEN_REFUND_DELAY_INVALID.fail(format!(
+            "refund_delay_days must be MIN..MAX with MIN <= MAX <= {}",
+            MAX_REFUND_DELAY_DAYS
+        )));
+    }
+    if !(0.0..=1.0).contains(&config.partial_refund_ratio) {
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("partial_refund_ratio must be 0..1"));
+    }
+    if config.max_refunds_per_payment == 0 {
+        return Err(codes::GEN_REFUNDS_PER_PAYMENT_ZERO.fail_summary());
+    }
+    for (name, ratio) in [
+        ("churn leave_ratio", config.churn.leave_ratio),
+        ("churn join_ratio", config.churn.join_ratio),
+        ("churn account_change_ratio", config.churn.account_change_ratio),
+    ] {
+        if !(0.0..=1.0).contains(&ratio) {
+            return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail(format!("{} must be 0..1", name)));
+        }
+    }
+    if !(0.0..=1.0).contains(&config.unknown_payee_psp_ratio) {
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("unknown_payee_psp_ratio must be 0..1"));
+    }
+    if config.unknown_payee_psp_ratio > 0.0 && config.perspective != Perspective::Payer {
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE
+            .fail("unknown_payee_psp_ratio needs the payer perspective"));
+    }
+    if !(0.0..=1.0).contains(&config.xi_payee_ratio) {
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("xi_payee_ratio must be 0..1"));
+    }
+    if !(0.0..=1.0).contains(&config.xi_payer_ratio) {
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("xi_payer_ratio must be 0..1"));
+    }
+    if !(0.0..=1.0).contains(&config.multi_psp_ratio) {
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("multi_psp_ratio must be 0..1"));
+    }
+    if !(0.0..=1.0).contains(&config.local_time_ratio) {
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("local_time_ratio must be 0..1"));
+    }
+    if !(0.0..=1.0).contains(&config.non_eu_payer_ratio) {
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("non_eu_payer_ratio must be 0..1"));
+    }
+    if !(0.0..=1.0).contains(&config.payer_account_ratio) {
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("payer_account_ratio must be 0..1"));
+    }
+    if !(0.0..=1.0).contains(&config.foreign_currency_ratio) {
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("foreign_currency_ratio must be 0..1"));
     }
     if !(0.0..=1.0).contains(&config.multi_account_ratio) {
-        return Err("multi_account_ratio must be 0..1".to_string());
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("multi_account_ratio must be 0..1"));
     }
     if !(0.0..=1.0).contains(&config.non_eu_payee_ratio) {
-        return Err("non_eu_payee_ratio must be 0..1".to_string());
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("non_eu_payee_ratio must be 0..1"));
     }
     if !(0.0..=1.0).contains(&config.no_account_payee_ratio) {
-        return Err("no_account_payee_ratio must be 0..1".to_string());
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("no_account_payee_ratio must be 0..1"));
+    }
+    if !(0.0..=1.0).contains(&config.marketplace_ratio) {
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("marketplace_ratio must be 0..1"));
+    }
+    if !(0.0..=1.0).contains(&config.unicode_name_ratio) {
+        return Err(codes::GEN_RATIO_OUT_OF_RANGE.fail("unicode_name_ratio must be 0..1"));
+    }
+    config.country_weights.validate()?;
+    config.field_rates.validate()?;
+    config.time_profile.validate()?;
+    if let Some(country) = config
+        .psp_countries
+        .iter()
+        .find(|country| !is_eu_member_state(country))
+    {
+        return Err(codes::GEN_PSP_COUNTRY_NOT_EU
+            .fail(format!("PSP country {} is not an EU Member State", country)));
+    }
+    if !config.psp_roster.is_empty() && !config.psp_roster.iter().any(RosterPsp::is_eu) {
+        return Err(
+            codes::GEN_PSP_COUNTRY_NOT_EU.fail("PSP roster lists no PSP in an EU Member State")
+        );
     }
     Ok(())
 }
 
-fn build_payee_plans(config: &GeneratorConfig) -> Result<Vec<PayeePlan>, String> {
+fn build_payee_plans(config: &GeneratorConfig) -> Result<Vec<PayeePlan>, Error> {
     let payees = config.payees;
     let micro = config.micro_payees;
     let near = config.near_threshold_payees;
     let large = config.large_payees;
 
     if micro + near + large > payees {
-        return Err("micro/near/large payees cannot exceed total payees".to_string());
+        return Err(codes::GEN_SEGMENTS_EXCEED_PAYEES.fail_summary());
     }
 
     let remaining = payees - (micro + near + large);
@@ -312,29 +2228,37 @@ fn build_payee_plans(config: &GeneratorConfig) -> Result<Vec<PayeePlan>, String>
     for _ in 0..micro {
         plans.push(PayeePlan {
             segment: segment_micro(),
+            sector: None,
         });
     }
     for _ in 0..small {
         plans.push(PayeePlan {
             segment: segment_small(),
+            sector: None,
         });
     }
     for _ in 0..mid {
-        plans.push(PayeePlan { segment: segment_mid() });
+        plans.push(PayeePlan {
+            segment: segment_mid(),
+            sector: None,
+        });
     }
     for _ in 0..near_below {
         plans.push(PayeePlan {
             segment: segment_near_below(),
+            sector: None,
         });
     }
     for _ in 0..near_above {
         plans.push(PayeePlan {
             segment: segment_near_above(),
+            sector: None,
         });
     }
     for _ in 0..large {
         plans.push(PayeePlan {
             segment: segment_large(),
+            sector: None,
         });
     }
 
@@ -345,29 +2269,57 @@ fn allocate_counts<R: Rng + ?Sized>(
     rng: &mut R,
     plans: &[PayeePlan],
     total_records: usize,
-) -> Result<Vec<usize>, String> {
+) -> Result<Vec<usize>, Error> {
     let min_total: usize = plans.iter().map(|plan| plan.segment.min_tx).sum();
     let max_total: usize = plans.iter().map(|plan| plan.segment.max_tx).sum();
 
     if total_records < min_total || total_records > max_total {
-        return Err(format!(
+        return Err(codes::GEN_RECORDS_OUT_OF_RANGE.fail(format!(
             "records must be between {} and {} for the chosen parameters",
             min_total, max_total
-        ));
+        )));
     }
 
     let mut counts: Vec<usize> = plans.iter().map(|plan| plan.segment.min_tx).collect();
-    let mut remaining = total_records - min_total;
+    let max: Vec<usize> = plans.iter().map(|plan| plan.segment.max_tx).collect();
+    let volume: Vec<f64> = if plans.iter().any(|plan| plan.sector.is_some()) {
+        plans
+            .iter()
+            .map(|plan| plan.sector.map_or(1.0, |sector| sector.volume_factor))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    distribute_counts(rng, &mut counts, &max, &volume, total_records - min_total)?;
 
+    Ok(counts)
+}
+
+// Hands out `remaining` transactions one at a time to payees below their
+// maximum. Payees are drawn uniformly, or in proportion to `volume` when set.
+fn distribute_counts<R: Rng + ?Sized>(
+    rng: &mut R,
+    counts: &mut [usize],
+    max: &[usize],
+    volume: &[f64],
+    mut remaining: usize,
+) -> Result<(), String> {
+    let weighted = if volume.is_empty() {
+        None
+    } else {
+        Some(WeightedIndex::new(volume).map_err(|err| err.to_string())?)
+    };
     while remaining > 0 {
-        let idx = rng.gen_range(0..plans.len());
-        if counts[idx] < plans[idx].segment.max_tx {
+        let idx = match &weighted {
+            Some(weighted) => rng.sample(weighted),
+            None => rng.gen_range(0..counts.len()),
+        };
+        if counts[idx] < max[idx] {
             counts[idx] += 1;
             remaining -= 1;
         }
     }
-
-    Ok(counts)
+    Ok(())
 }
 
 fn build_payees<R: Rng + ?Sized>(
@@ -376,18 +2328,47 @@ fn build_payees<R: Rng + ?Sized>(
     company_cores: &mut Vec<String>,
     psps: &[PspProfile],
     non_eu_psps: &[PspProfile],
-    multi_account_ratio: f64,
-    non_eu_payee_ratio: f64,
-    no_account_payee_ratio: f64,
+    config: &GeneratorConfig,
+    issued: usize,
 ) -> Vec<PayeeProfile> {
     let mut payees = Vec::with_capacity(plans.len());
+    let mut unicode_names_used: HashSet<&str> = HashSet::new();
     for (idx, plan) in plans.iter().enumerate() {
-        let mut country = pick_payee_country(rng, non_eu_payee_ratio);
+        let id = format!("MER{:06}", issued + idx + 1);
+        let mut country = if config.xi_payee_ratio > 0.0 && rng.gen_bool(config.xi_payee_ratio) {
+            NORTHERN_IRELAND.to_string()
+        } else {
+            pick_payee_country(rng, config.non_eu_payee_ratio, &config.country_weights)
+        };
         let core = pick_company_core(rng, company_cores);
-        let name = build_company_name(rng, &core);
-        let slug = slugify(&name);
-        let payee_psp_is_eu = is_eu_member_state(&country) || non_eu_psps.is_empty();
-        let payee_psp = if payee_psp_is_eu {
+        let marketplace = config.marketplace_ratio > 0.0 && rng.gen_bool(config.marketplace_ratio);
+        let unicode_name = (!marketplace
+            && config.unicode_name_ratio > 0.0
+            && rng.gen_bool(config.unicode_name_ratio))
+        .then(|| pick_unicode_name(rng, &country, &unicode_names_used))
+        .flatten();
+        let name = if marketplace {
+            format!("{} Marketplace", core)
+        } else if let Some(name) = unicode_name {
+            unicode_names_used.insert(name);
+            name.to_string()
+        } else {
+            build_company_name(rng, &core)
+        };
+        // Non-Latin names have no ASCII form, so their domains use the payee id.
+        let slug = Some(slugify(&transliterate(&name)))
+            .filter(|slug| !slug.is_empty())
+            .unwrap_or_else(|| id.to_lowercase());
+        // Northern Irish payees always bank with a UK PSP.
+        let payee_psp_is_eu = country != NORTHERN_IRELAND
+            && (is_eu_member_state(&country) || non_eu_psps.is_empty());
+        let payer_perspective = config.perspective == Perspective::Payer;
+        let local_psp = ((payer_perspective && is_eu_member_state(&country))
+            || country == NORTHERN_IRELAND)
+            .then(|| build_psp_for_country(rng, banking_country(&country), None));
+        let payee_psp = if let Some(local_psp) = &local_psp {
+            local_psp
+        } else if payee_psp_is_eu {
             psps.choose(rng).unwrap_or_else(|| &psps[0])
         } else {
             non_eu_psps
@@ -396,21 +2377,42 @@ fn build_payees<R: Rng + ?Sized>(
         };
         let payee_psp_country =
             bic_country_code(&payee_psp.id).unwrap_or_else(|| country.clone());
-        let has_account = !rng.gen_bool(no_account_payee_ratio);
-        let accounts = if has_account {
-            build_payee_accounts(rng, &country, multi_account_ratio)
+        let has_account = !rng.gen_bool(config.no_account_payee_ratio);
+        let mut accounts = if has_account {
+            build_payee_accounts(rng, &country, config.multi_account_ratio)
         } else {
             Vec::new()
         };
         if !has_account {
             country = payee_psp_country;
         }
-        let (reporting_psp, psp_role) = if payee_psp_is_eu {
+        let intermediary = (marketplace && payee_psp_is_eu && rng.gen_bool(0.5)).then(|| {
+            let name = INTERMEDIARY_PSP_NAMES.choose(rng).unwrap_or(&"Relay Payments");
+            build_psp(rng, Some(name))
+        });
+        if let Some(intermediary) = &intermediary {
+            accounts.clear();
+            country = bic_country_code(&intermediary.id).unwrap_or(country);
+        }
+        let (reporting_psp, psp_role) = if payee_psp_is_eu && !payer_perspective {
             (payee_psp, PSP_ROLE_PAYEE)
         } else {
             let payer_psp = psps.choose(rng).unwrap_or(payee_psp);
             (payer_psp, PSP_ROLE_PAYER)
         };
+        let second_psp = (intermediary.is_none()
+            && config.multi_psp_ratio > 0.0
+            && rng.gen_bool(config.multi_psp_ratio))
+        .then(|| {
+            let others: Vec<&PspProfile> =
+                psps.iter().filter(|psp| psp.id != reporting_psp.id).collect();
+            others.choose(rng).map(|psp| (*psp).clone())
+        })
+        .flatten();
+        let payee_psp_unknown = payer_perspective
+            && !accounts.is_empty()
+            && config.unknown_payee_psp_ratio > 0.0
+            && rng.gen_bool(config.unknown_payee_psp_ratio);
 
         let (tax_chance, vat_chance) = match plan.segment.label {
             "micro" => (0.2, 0.4),
@@ -421,6 +2423,9 @@ fn build_payees<R: Rng + ?Sized>(
             "large" => (0.8, 0.95),
             _ => (0.4, 0.6),
         };
+        let rates = &config.field_rates;
+        let tax_chance = rates.tax.unwrap_or(tax_chance);
+        let vat_chance = rates.vat.unwrap_or(vat_chance);
 
         let tax_id = if rng.gen_bool(tax_chance) {
             Some(format!("TAX{}{}", country, random_digits(rng, 8)))
@@ -436,96 +2441,247 @@ fn build_payees<R: Rng + ?Sized>(
         let street_num = rng.gen_range(1..250);
         let street = STREET_NAMES.choose(rng).unwrap_or(&"Market");
         let address_line = Some(format!("{} {} St", street_num, street));
-        let city = CITIES.choose(rng).unwrap_or(&"Berlin").to_string();
-        let postcode = random_digits(rng, 5);
+        let (city, postcode_prefix) = city_postcodes(&country)
+            .and_then(|cities| cities.choose(rng))
+            .copied()
+            .unwrap_or_else(|| (CITIES.choose(rng).unwrap_or(&"Berlin"), ""));
+        let city = city.to_string();
+        let postcode = generate_postcode(rng, &country, postcode_prefix);
+        let email = rates
+            .email
+            .is_none_or(|rate| rng.gen_bool(rate))
+            .then(|| format!("billing@{}.example", slug));
+        let web = rates
+            .web
+            .is_none_or(|rate| rng.gen_bool(rate))
+            .then(|| format!("https://{}.example", slug));
+        let sub_merchants = if marketplace {
+            build_sub_merchants(rng, &id)
+        } else {
+            Vec::new()
+        };
+        let payee_psp = intermediary.as_ref().unwrap_or(payee_psp);
+
+        let amount_factor = plan.sector.map_or(1.0, |sector| sector.amount_factor);
 
         payees.push(PayeeProfile {
-            id: format!("MER{:06}", idx + 1),
+            id,
             name,
-            amount_min: plan.segment.amount_min,
-            amount_max: plan.segment.amount_max,
+            amount_min: plan.segment.amount_min * amount_factor,
+            amount_max: plan.segment.amount_max * amount_factor,
             country,
             accounts,
             tax_id,
             vat_id,
-            email: Some(format!("billing@{}.example", slug)),
-            web: Some(format!("https://{}.example", slug)),
+            email,
+            web,
             address_line,
             city: Some(city),
             postcode: Some(postcode),
-            payee_psp_id: payee_psp.id.clone(),
-            payee_psp_name: payee_psp.name.clone(),
+            payee_psp_id: if payee_psp_unknown {
+                String::new()
+            } else {
+                payee_psp.id.clone()
+            },
+            payee_psp_name: if payee_psp_unknown {
+                String::new()
+            } else {
+                payee_psp.name.clone()
+            },
             reporting_psp_id: reporting_psp.id.clone(),
             reporting_psp_name: reporting_psp.name.clone(),
             psp_role: psp_role.to_string(),
+            via_intermediary: intermediary.is_some(),
+            mcc: plan.sector.map(|sector| sector.mcc.to_string()),
+            second_psp,
+            sub_merchants,
         });
     }
 
     payees
 }
 
-fn build_psp<R: Rng + ?Sized>(rng: &mut R) -> PspProfile {
-    let name = PSP_NAMES.choose(rng).unwrap_or(&"Atlas Pay");
-    let bic = generate_bic(rng);
-    PspProfile {
-        id: bic,
-        name: name.to_string(),
+// A country-specific name most of the time, otherwise a non-Latin one. Each
+// name is used once; `None` falls back to a generated ASCII name.
+fn pick_unicode_name<R: Rng + ?Sized>(
+    rng: &mut R,
+    country: &str,
+    used: &HashSet<&str>,
+) -> Option<&'static str> {
+    let local: Vec<&'static str> = UNICODE_NAMES
+        .iter()
+        .filter(|(name_country, name)| *name_country == country && !used.contains(name))
+        .map(|(_, name)| *name)
+        .collect();
+    if !local.is_empty() && rng.gen_bool(0.8) {
+        return local.choose(rng).copied();
     }
+    let non_latin: Vec<&'static str> = NON_LATIN_NAMES
+        .iter()
+        .copied()
+        .filter(|name| !used.contains(name))
+        .collect();
+    non_latin.choose(rng).or(local.choose(rng)).copied()
 }
 
-fn build_psp_for_country<R: Rng + ?Sized>(rng: &mut R, country: &str) -> PspProfile {
-    let name = PSP_NAMES.choose(rng).unwrap_or(&"Atlas Pay");
-    let bic = generate_bic_for_country(rng, country);
-    PspProfile {
-        id: bic,
-        name: name.to_string(),
+fn transliterate(name: &str) -> String {
+    name.chars()
+        .flat_map(|ch| ch.to_lowercase())
+        .map(|ch| {
+            TRANSLITERATIONS
+                .iter()
+                .find(|(from, _)| *from == ch)
+                .map_or_else(|| ch.to_string(), |(_, to)| to.to_string())
+        })
+        .collect()
+}
+
+fn build_sub_merchants<R: Rng + ?Sized>(rng: &mut R, payee_id: &str) -> Vec<SubMerchant> {
+    (1..=rng.gen_range(3..=8))
+        .map(|idx| {
+            let core = random_company_core(rng);
+            SubMerchant {
+                id: format!("{}-S{:02}", payee_id, idx),
+                name: build_company_name(rng, &core),
+                country: EU_MEMBER_STATES.choose(rng).unwrap_or(&"DE").to_string(),
+            }
+        })
+        .collect()
+}
+
+// Marketplace payments are mostly `Marketplace`, `Intermediary` when an
+// intermediary PSP collects the funds, and otherwise `Other` with a
+// `PaymentMethodOther` text.
+fn pick_marketplace_method<R: Rng + ?Sized>(
+    rng: &mut R,
+    via_intermediary: bool,
+) -> (String, Option<String>) {
+    let roll = rng.gen_range(0..100);
+    if roll < 60 {
+        ("Marketplace".to_string(), None)
+    } else if roll < 80 && via_intermediary {
+        ("Intermediary".to_string(), None)
+    } else if roll < 80 {
+        ("Marketplace".to_string(), None)
+    } else {
+        let other = MARKETPLACE_OTHER_METHODS
+            .choose(rng)
+            .unwrap_or(&"Marketplace wallet balance");
+        ("Other".to_string(), Some(other.to_string()))
     }
 }
 
-fn build_non_eu_psp<R: Rng + ?Sized>(rng: &mut R) -> PspProfile {
-    let name = PSP_NAMES.choose(rng).unwrap_or(&"Atlas Pay");
-    let country = NON_EU_PAYEE_COUNTRIES
-        .choose(rng)
-        .unwrap_or(&"GB")
-        .to_string();
-    let bic = generate_bic_for_country(rng, &country);
+fn build_psp<R: Rng + ?Sized>(rng: &mut R, name: Option<&str>) -> PspProfile {
+    let country = EU_MEMBER_STATES.choose(rng).unwrap_or(&"DE");
+    build_psp_for_country(rng, country, name)
+}
+
+fn build_psp_for_country<R: Rng + ?Sized>(
+    rng: &mut R,
+    country: &str,
+    name: Option<&str>,
+) -> PspProfile {
+    let name = name.unwrap_or_else(|| PSP_NAMES.choose(rng).unwrap_or(&"Atlas Pay"));
+    let bank = psp_bank_code(name)
+        .map(str::to_string)
+        .unwrap_or_else(|| brand_bank_code(rng, name));
     PspProfile {
-        id: bic,
+        id: compose_bic(rng, &bank, country),
         name: name.to_string(),
     }
 }
 
-fn build_psps<R: Rng + ?Sized>(rng: &mut R, count: usize) -> Result<Vec<PspProfile>, String> {
+// Bank code for brands outside PSP_BANK_CODES: the first letters of the
+// name, padded with random letters.
+fn brand_bank_code<R: Rng + ?Sized>(rng: &mut R, name: &str) -> String {
+    let mut code: String = name
+        .chars()
+        .filter(|ch| ch.is_ascii_alphabetic())
+        .map(|ch| ch.to_ascii_uppercase())
+        .take(4)
+        .collect();
+    let missing = 4 - code.len();
+    code.push_str(&random_upper_letters(rng, missing));
+    code
+}
+
+fn build_non_eu_psp<R: Rng + ?Sized>(rng: &mut R) -> PspProfile {
+    let country = NON_EU_COUNTRIES.choose(rng).unwrap_or(&"GB");
+    build_psp_for_country(rng, country, None)
+}
+
+fn build_psps<R: Rng + ?Sized>(
+    rng: &mut R,
+    count: usize,
+    name: Option<&str>,
+) -> Result<Vec<PspProfile>, Error> {
     let mut psps = Vec::with_capacity(count);
     let mut seen = HashSet::new();
     let unique_targets = count.min(EU_MEMBER_STATES.len());
-    let mut countries: Vec<&str> = EU_MEMBER_STATES.iter().copied().collect();
+    let mut countries: Vec<&str> = EU_MEMBER_STATES.to_vec();
     countries.shuffle(rng);
 
     for country in countries.into_iter().take(unique_targets) {
-        let psp = build_psp_for_country(rng, country);
+        let psp = build_psp_for_country(rng, country, name);
         if seen.insert(psp.id.clone()) {
             psps.push(psp);
         }
     }
 
     while psps.len() < count {
-        let psp = build_psp(rng);
+        let psp = build_psp(rng, name);
         if seen.insert(psp.id.clone()) {
             psps.push(psp);
         }
         if seen.len() > count * 10 {
-            return Err("failed to generate unique PSP identifiers".to_string());
+            return Err(
+                codes::GEN_IDENTIFIERS_EXHAUSTED.fail("failed to generate unique PSP identifiers")
+            );
         }
     }
 
     Ok(psps)
 }
 
+// The EU and non-EU PSPs of a fresh run: the roster, one per listed country,
+// or `psps` invented ones.
+fn build_config_psps<R: Rng + ?Sized>(
+    rng: &mut R,
+    config: &GeneratorConfig,
+) -> Result<(Vec<PspProfile>, Vec<PspProfile>), Error> {
+    let psps = if !config.psp_roster.is_empty() {
+        roster_psps(&config.psp_roster, true)
+    } else if config.psp_countries.is_empty() {
+        build_psps(rng, config.psps, config.psp_name.as_deref())?
+    } else {
+        config
+            .psp_countries
+            .iter()
+            .map(|country| build_psp_for_country(rng, country, config.psp_name.as_deref()))
+            .collect()
+    };
+    let non_eu_psps = match roster_psps(&config.psp_roster, false) {
+        roster if roster.is_empty() => build_non_eu_psps(rng, (config.psps / 2).max(1))?,
+        roster => roster,
+    };
+    Ok((psps, non_eu_psps))
+}
+
+fn roster_psps(roster: &[RosterPsp], eu: bool) -> Vec<PspProfile> {
+    roster
+        .iter()
+        .filter(|psp| psp.is_eu() == eu)
+        .map(|psp| PspProfile {
+            id: psp.bic.clone(),
+            name: psp.name.clone(),
+        })
+        .collect()
+}
+
 fn build_non_eu_psps<R: Rng + ?Sized>(
     rng: &mut R,
     count: usize,
-) -> Result<Vec<PspProfile>, String> {
+) -> Result<Vec<PspProfile>, Error> {
     let mut psps = Vec::with_capacity(count);
     let mut seen = HashSet::new();
 
@@ -535,7 +2691,8 @@ fn build_non_eu_psps<R: Rng + ?Sized>(
             psps.push(psp);
         }
         if seen.len() > count * 10 {
-            return Err("failed to generate unique non-eu PSP identifiers".to_string());
+            return Err(codes::GEN_IDENTIFIERS_EXHAUSTED
+                .fail("failed to generate unique non-eu PSP identifiers"));
         }
     }
 
@@ -652,46 +2809,103 @@ fn build_company_name<R: Rng + ?Sized>(rng: &mut R, core: &str) -> String {
     format!("{} {} {}", core, suffix, legal)
 }
 
-fn generate_iban<R: Rng + ?Sized>(rng: &mut R, country: &str) -> String {
-    let length = iban_length(country).unwrap_or(22);
-    let bban_len = length.saturating_sub(4);
-    let bban = random_digits(rng, bban_len);
+// `prefix` holds the city's leading postcode characters; the first format
+// that accepts it is used.
+fn generate_postcode<R: Rng + ?Sized>(rng: &mut R, country: &str, prefix: &str) -> String {
+    let Some(pattern) = postcode_patterns(country).and_then(|patterns| {
+        patterns
+            .iter()
+            .find(|pattern| {
+                pattern.len() >= prefix.len()
+                    && pattern.chars().zip(prefix.chars()).all(|(p, c)| match p {
+                        '9' => c.is_ascii_digit(),
+                        'A' => c.is_ascii_uppercase(),
+                        'X' => c.is_ascii_digit() || c.is_ascii_uppercase(),
+                        literal => literal == c,
+                    })
+            })
+            .or(patterns.first())
+    }) else {
+        return random_digits(rng, 5);
+    };
+    fill_pattern(rng, pattern, prefix)
+}
+
+// Fills a reference pattern (see `POSTCODE_PATTERNS`), keeping `prefix` as the
+// leading characters.
+fn fill_pattern<R: Rng + ?Sized>(rng: &mut R, pattern: &str, prefix: &str) -> String {
+    // Leading zeros are rare (and invalid in most countries), so avoid them.
+    let prefix: Vec<char> = prefix.chars().collect();
+    pattern
+        .chars()
+        .enumerate()
+        .map(|(idx, ch)| match ch {
+            _ if idx < prefix.len() => prefix[idx],
+            '9' if idx == 0 => char::from(b'0' + rng.gen_range(1..10u8)),
+            '9' => char::from(b'0' + rng.gen_range(0..10u8)),
+            'A' => char::from(b'A' + rng.gen_range(0..26u8)),
+            'X' => random_alphanum_upper(rng, 1).chars().next().unwrap_or('0'),
+            literal => literal,
+        })
+        .collect()
+}
+
+// The BBAN follows the country's registry structure; letter bank codes are
+// taken from the country's BIC bank codes.
+pub(crate) fn generate_iban<R: Rng + ?Sized>(rng: &mut R, country: &str) -> String {
+    let bban = match bban_pattern(country) {
+        Some(pattern) => {
+            let bank = pattern
+                .starts_with("AAAA")
+                .then(|| bank_codes(country).choose(rng).copied())
+                .flatten()
+                .unwrap_or("");
+            fill_pattern(rng, pattern, bank)
+        }
+        None => random_digits(rng, iban_length(country).unwrap_or(22).saturating_sub(4)),
+    };
     let check = iban_check_digits(country, &bban).unwrap_or_else(|_| "00".to_string());
     format!("{}{}{}", country, check, bban)
 }
 
 fn generate_bic_for_country<R: Rng + ?Sized>(rng: &mut R, country: &str) -> String {
-    let bank = random_upper_letters(rng, 4);
-    let location = random_alphanum_upper(rng, 2);
-    let branch = if rng.gen_bool(0.7) {
-        Some(random_alphanum_upper(rng, 3))
-    } else {
-        None
-    };
-
-    match branch {
-        Some(branch) => format!("{}{}{}{}", bank, country, location, branch),
-        None => format!("{}{}{}", bank, country, location),
-    }
+    let country = banking_country(country);
+    let bank = bank_codes(country)
+        .choose(rng)
+        .map(|code| code.to_string())
+        .unwrap_or_else(|| random_upper_letters(rng, 4));
+    compose_bic(rng, &bank, country)
 }
 
-fn generate_bic<R: Rng + ?Sized>(rng: &mut R) -> String {
-    let country = EU_MEMBER_STATES
-        .choose(rng)
-        .unwrap_or(&"DE")
-        .to_string();
-    generate_bic_for_country(rng, &country)
+// Head offices use the 8-character form or the XXX branch; other branches get
+// a numeric branch code.
+fn compose_bic<R: Rng + ?Sized>(rng: &mut R, bank: &str, country: &str) -> String {
+    let location = location_codes(country).choose(rng).unwrap_or(&"2X");
+    let roll = rng.gen::<f64>();
+    if roll < 0.4 {
+        format!("{}{}{}", bank, country, location)
+    } else if roll < 0.7 {
+        format!("{}{}{}XXX", bank, country, location)
+    } else {
+        format!("{}{}{}{}", bank, country, location, rng.gen_range(100..1000))
+    }
 }
 
 
-fn pick_payee_country<R: Rng + ?Sized>(rng: &mut R, non_eu_ratio: f64) -> String {
+fn pick_payee_country<R: Rng + ?Sized>(
+    rng: &mut R,
+    non_eu_ratio: f64,
+    weights: &CountryWeights,
+) -> String {
     if rng.gen_bool(non_eu_ratio) {
-        NON_EU_PAYEE_COUNTRIES
+        NON_EU_COUNTRIES
             .choose(rng)
             .unwrap_or(&"GB")
             .to_string()
-    } else {
+    } else if weights.is_uniform() {
         EU_MEMBER_STATES.choose(rng).unwrap_or(&"DE").to_string()
+    } else {
+        weights.pick(rng, None).to_string()
     }
 }
 
@@ -714,9 +2928,15 @@ fn build_payee_accounts<R: Rng + ?Sized>(
     accounts
 }
 
-fn generate_account_identifier<R: Rng + ?Sized>(rng: &mut R, country: &str) -> (String, String) {
+pub(crate) fn generate_account_identifier<R: Rng + ?Sized>(
+    rng: &mut R,
+    country: &str,
+) -> (String, String) {
+    let country = banking_country(country);
     if iban_length(country).is_some() {
         (generate_iban(rng, country), "IBAN".to_string())
+    } else if let Some(oban) = generate_oban(rng, country) {
+        (oban, "OBAN".to_string())
     } else {
         (
             format!("{}{}", country, random_alphanum_upper(rng, 12)),
@@ -725,6 +2945,57 @@ fn generate_account_identifier<R: Rng + ?Sized>(rng: &mut R, country: &str) -> (
     }
 }
 
+// The identifier the payer MS source points at: an IBAN or BIC from the payer
+// country, or an other-type account number.
+// National account numbers of countries without IBANs, prefixed with the
+// country code so the payee country can be read from them.
+fn generate_oban<R: Rng + ?Sized>(rng: &mut R, country: &str) -> Option<String> {
+    match country {
+        // ABA routing number (Federal Reserve district 01-12, 3-7-1 weighted
+        // check digit) followed by a 10-digit account number.
+        "US" => {
+            let district = rng.gen_range(1..=12u32);
+            let mut digits = vec![district / 10, district % 10];
+            digits.extend((0..6).map(|_| rng.gen_range(0..10u32)));
+            let weighted: u32 = digits
+                .iter()
+                .zip([3, 7, 1, 3, 7, 1, 3, 7])
+                .map(|(digit, weight)| digit * weight)
+                .sum();
+            digits.push((10 - weighted % 10) % 10);
+            let routing: String = digits.iter().map(|digit| digit.to_string()).collect();
+            Some(format!("US{}{}", routing, random_digits(rng, 10)))
+        }
+        // EFT format: `0`, 3-digit institution, 5-digit transit, then a
+        // 7-digit account number.
+        "CA" => {
+            let institution = CA_INSTITUTION_NUMBERS.choose(rng).unwrap_or(&"001");
+            Some(format!(
+                "CA0{}{}{}",
+                institution,
+                random_digits(rng, 5),
+                random_digits(rng, 7)
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn generate_payer_account<R: Rng + ?Sized>(
+    rng: &mut R,
+    country: &str,
+    source: &str,
+) -> (String, String) {
+    match source {
+        "BIC" => (generate_bic_for_country(rng, country), "BIC".to_string()),
+        "IBAN" => generate_account_identifier(rng, country),
+        _ => (
+            format!("{}{}", country, random_alphanum_upper(rng, 12)),
+            "Other".to_string(),
+        ),
+    }
+}
+
 fn pick_payer_ms_source<R: Rng + ?Sized>(rng: &mut R) -> &'static str {
     let roll = rng.gen::<f64>();
     if roll < 0.8 {
@@ -736,18 +3007,34 @@ fn pick_payer_ms_source<R: Rng + ?Sized>(rng: &mut R) -> &'static str {
     }
 }
 
+fn pick_non_eu_payer_country<R: Rng + ?Sized>(rng: &mut R, payee_country: &str) -> String {
+    let candidates: Vec<&str> = NON_EU_COUNTRIES
+        .iter()
+        .copied()
+        .filter(|country| *country != payee_country)
+        .collect();
+    candidates.choose(rng).unwrap_or(&"US").to_string()
+}
+
 fn pick_payer_country<R: Rng + ?Sized>(
     rng: &mut R,
     payee_country: &str,
     cross_border_ratio: f64,
+    weights: &CountryWeights,
 ) -> String {
     if !is_eu_member_state(payee_country) {
+        if !weights.is_uniform() {
+            return weights.pick(rng, None).to_string();
+        }
         return EU_MEMBER_STATES
             .choose(rng)
             .unwrap_or(&"FR")
             .to_string();
     }
     if rng.gen_bool(cross_border_ratio) {
+        if !weights.is_uniform() {
+            return weights.pick(rng, Some(payee_country)).to_string();
+        }
         loop {
             let candidate = EU_MEMBER_STATES.choose(rng).unwrap_or(&"FR");
             if *candidate != payee_country {
@@ -759,13 +3046,13 @@ fn pick_payer_country<R: Rng + ?Sized>(
     }
 }
 
-fn quarter_bounds(year: i32, quarter: u8) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
+pub fn quarter_bounds(year: i32, quarter: u8) -> Result<(DateTime<Utc>, DateTime<Utc>), Error> {
     let (start_month, next_year, next_month) = match quarter {
         1 => (1, year, 4),
         2 => (4, year, 7),
         3 => (7, year, 10),
         4 => (10, year + 1, 1),
-        _ => return Err("quarter must be 1..4".to_string()),
+        _ => return Err(codes::GEN_INVALID_QUARTER.fail_summary()),
     };
 
     let start = Utc
@@ -793,3 +3080,41 @@ fn random_datetime<R: Rng + ?Sized>(
         .single()
         .unwrap_or(start)
 }
+
+fn random_datetime_inclusive<R: Rng + ?Sized>(
+    rng: &mut R,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> DateTime<Utc> {
+    let span = (end - start).num_milliseconds().max(0);
+    start + Duration::milliseconds(rng.gen_range(0..=span))
+}
+
+// When payments may happen: the quarter, shaped by the payee country's
+// calendar and by the volume profile.
+struct Timing<'a> {
+    period: (DateTime<Utc>, DateTime<Utc>),
+    holiday_calendars: bool,
+    profile: &'a TimeProfile,
+}
+
+impl Timing<'_> {
+    // Rejection-sample a timestamp so volume follows the profile and, with
+    // holiday calendars, the country's weekend pattern and public holidays.
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R, country: &str) -> DateTime<Utc> {
+        let (start, end) = self.period;
+        if !self.holiday_calendars && self.profile.is_uniform() {
+            return random_datetime(rng, start, end);
+        }
+        loop {
+            let candidate = random_datetime(rng, start, end);
+            let mut weight = self.profile.weight(candidate);
+            if self.holiday_calendars {
+                weight *= activity_weight(country, candidate.date_naive());
+            }
+            if rng.gen_bool(weight) {
+                return candidate;
+            }
+        }
+    }
+}
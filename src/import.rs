@@ -0,0 +1,316 @@
+use crate::codes;
+use crate::compression;
+use crate::models::PaymentRecord;
+use crate::refids::{registry_path, RefIdRegistry};
+use crate::xml_tree::{parse_file, XmlNode};
+use crate::xsd::collect_xml_files;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportSummary {
+    pub files: usize,
+    // Messages read per MessageTypeIndic.
+    pub messages: BTreeMap<String, usize>,
+    pub payees: usize,
+    pub deleted_payees: usize,
+    pub transactions: usize,
+    // Payees whose payee_id came from a RefId registry rather than their
+    // account or tax identifiers.
+    pub registry_payee_ids: usize,
+    pub registries: Vec<PathBuf>,
+}
+
+// A reported payee read back from a message, with its transactions as
+// payment rows.
+#[derive(Debug, Clone)]
+pub struct ImportedPayee {
+    pub doc_type_indic: String,
+    pub doc_ref_id: String,
+    pub records: Vec<PaymentRecord>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportedMessage {
+    pub message_type_indic: String,
+    pub transmitting_country: String,
+    pub period: (i32, u8),
+    pub psp_id: String,
+    pub psp_name: String,
+    pub payees: Vec<ImportedPayee>,
+}
+
+// Reads every CESOP message in `inputs` (files, or directories searched for
+// .xml files) back into payment rows and writes them to `output`, compressed
+// by extension. Payees deleted by a CESOP3 document carry no transactions
+// and give no rows. The payee_id is not part of the message: it is looked up
+// by DocRefId in `registry`, or in a refid_registry.json next to the file or
+// one directory up, and otherwise taken from the payee's first account
+// identifier, VAT id, tax id or name.
+pub fn import_xml(
+    inputs: &[PathBuf],
+    output: &Path,
+    registry: Option<&Path>,
+) -> Result<ImportSummary, String> {
+    let mut files = Vec::new();
+    for input in inputs {
+        files.extend(collect_xml_files(input)?);
+    }
+    if files.is_empty() {
+        return Err(codes::RND_NO_MESSAGES.err("no XML files to import"));
+    }
+
+    let mut summary = ImportSummary::default();
+    let mut registries: HashMap<PathBuf, HashMap<String, String>> = HashMap::new();
+    let mut records = Vec::new();
+    for path in &files {
+        let registry_file = match registry {
+            Some(path) => Some(path.to_path_buf()),
+            None => path
+                .ancestors()
+                .skip(1)
+                .take(2)
+                .map(registry_path)
+                .find(|candidate| candidate.exists()),
+        };
+        let payee_ids = match registry_file {
+            Some(registry_file) => {
+                if !registries.contains_key(&registry_file) {
+                    let loaded = RefIdRegistry::load(&registry_file)?;
+                    let ids = loaded
+                        .messages
+                        .iter()
+                        .flat_map(|message| &message.payees)
+                        .map(|payee| (payee.doc_ref_id.clone(), payee.payee_id.clone()))
+                        .collect();
+                    summary.registries.push(registry_file.clone());
+                    registries.insert(registry_file.clone(), ids);
+                }
+                registries.get(&registry_file)
+            }
+            None => None,
+        };
+
+        let message = read_message(path)?;
+        *summary
+            .messages
+            .entry(message.message_type_indic.clone())
+            .or_default() += 1;
+        for payee in message.payees {
+            if payee.doc_type_indic == "CESOP3" {
+                summary.deleted_payees += 1;
+                continue;
+            }
+            summary.payees += 1;
+            let payee_id = payee_ids.and_then(|ids| ids.get(&payee.doc_ref_id));
+            if payee_id.is_some() {
+                summary.registry_payee_ids += 1;
+            }
+            for mut record in payee.records {
+                if let Some(payee_id) = payee_id {
+                    record.payee_id = payee_id.clone();
+                }
+                summary.transactions += 1;
+                records.push(record);
+            }
+        }
+        log::debug!(
+            file:% = path.display(),
+            message_type_indic = message.message_type_indic.as_str();
+            "message imported"
+        );
+    }
+    summary.files = files.len();
+    compression::write_csv(output, &records)?;
+    Ok(summary)
+}
+
+// Parses one CESOP payment data message. Element prefixes are ignored, so
+// messages written with other namespace prefixes read the same.
+pub fn read_message(path: &Path) -> Result<ImportedMessage, String> {
+    let mut document = parse_file(path)?;
+    strip_prefixes(&mut document);
+    message_from_document(&document)
+        .map_err(|err| codes::RND_MESSAGE_UNREADABLE.err(format!("{}: {}", path.display(), err)))
+}
+
+fn message_from_document(document: &XmlNode) -> Result<ImportedMessage, String> {
+    if document.name != "CESOP" {
+        return Err(format!("expected a CESOP message, found <{}>", document.name));
+    }
+    let spec = document
+        .child("MessageSpec")
+        .ok_or_else(|| "missing MessageSpec".to_string())?;
+    let text = |node: &XmlNode, names: &[&str]| {
+        node.path_text(names)
+            .map(|value| value.trim().to_string())
+            .ok_or_else(|| format!("missing {}", names.join("/")))
+    };
+    let year = text(spec, &["ReportingPeriod", "Year"])?
+        .parse::<i32>()
+        .map_err(|err| format!("invalid ReportingPeriod/Year: {}", err))?;
+    let quarter = text(spec, &["ReportingPeriod", "Quarter"])?
+        .parse::<u8>()
+        .map_err(|err| format!("invalid ReportingPeriod/Quarter: {}", err))?;
+    let body = document
+        .child("PaymentDataBody")
+        .ok_or_else(|| "missing PaymentDataBody".to_string())?;
+    let psp_id = text(body, &["ReportingPSP", "PSPId"])?;
+    let psp_name = text(body, &["ReportingPSP", "Name"])?;
+
+    let mut payees = Vec::new();
+    for payee in body.children_named("ReportedPayee") {
+        let doc_ref_id = text(payee, &["DocSpec", "DocRefId"])?;
+        let records = payee_records(payee, &psp_id, &psp_name)
+            .map_err(|err| format!("payee {}: {}", doc_ref_id, err))?;
+        payees.push(ImportedPayee {
+            doc_type_indic: text(payee, &["DocSpec", "DocTypeIndic"])?,
+            doc_ref_id,
+            records,
+        });
+    }
+    Ok(ImportedMessage {
+        message_type_indic: text(spec, &["MessageTypeIndic"])?,
+        transmitting_country: text(spec, &["TransmittingCountry"])?,
+        period: (year, quarter),
+        psp_id,
+        psp_name,
+        payees,
+    })
+}
+
+fn payee_records(
+    payee: &XmlNode,
+    psp_id: &str,
+    psp_name: &str,
+) -> Result<Vec<PaymentRecord>, String> {
+    let optional = |node: &XmlNode, names: &[&str]| {
+        node.path_text(names)
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+    let name = optional(payee, &["Name"]).ok_or_else(|| "missing Name".to_string())?;
+    let country = optional(payee, &["Country"]).ok_or_else(|| "missing Country".to_string())?;
+    let vat_id = optional(payee, &["TAXIdentification", "VATId"]);
+    let tax_id = optional(payee, &["TAXIdentification", "TAXId"]);
+
+    // A payment row has room for one account. Every transaction gets the
+    // first that is not a BIC, which the renderer would pick again, except
+    // that further accounts (BICs last) go one each on the last transactions,
+    // so rendering the rows again reports them too.
+    let mut accounts: Vec<(String, String)> = payee
+        .children_named("AccountIdentifier")
+        .filter(|node| !node.text.trim().is_empty())
+        .map(|node| (node.text.trim().to_string(), attr(node, "type").unwrap_or_default()))
+        .collect();
+    accounts.sort_by_key(|(_, account_type)| account_type == "BIC");
+    let mut accounts = accounts.into_iter();
+    let (account, account_type) = accounts.next().unwrap_or_default();
+    let further: Vec<(String, String)> = accounts.collect();
+    let payee_id = [Some(account.clone()), vat_id.clone(), tax_id.clone()]
+        .into_iter()
+        .flatten()
+        .find(|value| !value.is_empty())
+        .unwrap_or_else(|| name.clone());
+
+    let (address_line, postcode, city) = match payee.child("Address") {
+        Some(address) => match address.child("AddressFix") {
+            Some(fix) => {
+                let street = [
+                    optional(fix, &["BuildingIdentifier"]),
+                    optional(fix, &["Street"]),
+                ]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" ");
+                (
+                    Some(street).filter(|line| !line.is_empty()),
+                    optional(fix, &["PostCode"]),
+                    optional(fix, &["City"]),
+                )
+            }
+            None => (optional(address, &["AddressFree"]), None, None),
+        },
+        None => (None, None, None),
+    };
+    let representative_id = optional(payee, &["Representative", "RepresentativeId"]);
+    let representative_name = optional(payee, &["Representative", "Name"]);
+
+    let mut records = Vec::new();
+    for tx in payee.children_named("ReportedTransaction") {
+        let payment_id = optional(tx, &["TransactionIdentifier"])
+            .ok_or_else(|| "transaction without TransactionIdentifier".to_string())?;
+        let amount = tx
+            .child("Amount")
+            .ok_or_else(|| format!("transaction {} has no Amount", payment_id))?;
+        let payer_ms = tx
+            .child("PayerMS")
+            .ok_or_else(|| format!("transaction {} has no PayerMS", payment_id))?;
+        let is_refund = attr(tx, "IsRefund").is_some_and(|value| value == "true" || value == "1");
+        let payment_method = optional(tx, &["PaymentMethod", "PaymentMethodType"]).unwrap_or_default();
+        records.push(PaymentRecord {
+            payment_id: payment_id.clone(),
+            execution_time: optional(tx, &["DateTime"]).unwrap_or_default(),
+            amount: amount.text.trim().to_string(),
+            currency: attr(amount, "currency").unwrap_or_default(),
+            payer_country: payer_ms.text.trim().to_string(),
+            payer_ms_source: attr(payer_ms, "PayerMSSource").unwrap_or_default(),
+            payee_country: country.clone(),
+            payee_id: payee_id.clone(),
+            payee_name: name.clone(),
+            payee_account: account.clone(),
+            payee_account_type: account_type.clone(),
+            payee_tax_id: tax_id.clone(),
+            payee_vat_id: vat_id.clone(),
+            payee_email: optional(payee, &["EmailAddress"]),
+            payee_web: optional(payee, &["WebPage"]),
+            payee_address_line: address_line.clone(),
+            payee_city: city.clone(),
+            payee_postcode: postcode.clone(),
+            payment_method_other: optional(tx, &["PaymentMethod", "PaymentMethodOther"])
+                .filter(|_| payment_method == "Other"),
+            payment_method,
+            initiated_at_pos: optional(tx, &["InitiatedAtPhysicalPremisesOfMerchant"])
+                .is_some_and(|value| value == "true" || value == "1"),
+            is_refund,
+            corr_payment_id: optional(tx, &["CorrTransactionIdentifier"]),
+            psp_role: None,
+            payee_psp_id: representative_id.clone(),
+            payee_psp_name: representative_name.clone(),
+            psp_id: psp_id.to_string(),
+            psp_name: psp_name.to_string(),
+            sub_merchant_id: None,
+            sub_merchant_name: None,
+            sub_merchant_country: None,
+            payer_account: None,
+            payer_account_type: None,
+            payee_mcc: None,
+            payee_psp_country: None,
+            payee_psp_address: None,
+        });
+    }
+    let first_further = records.len().saturating_sub(further.len()).max(1);
+    for (record, (id, id_type)) in records.iter_mut().skip(first_further).zip(further) {
+        record.payee_account = id;
+        record.payee_account_type = id_type;
+    }
+    Ok(records)
+}
+
+fn attr(node: &XmlNode, name: &str) -> Option<String> {
+    node.attrs
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.trim().to_string())
+}
+
+fn strip_prefixes(node: &mut XmlNode) {
+    if let Some((_, local)) = node.name.split_once(':') {
+        node.name = local.to_string();
+    }
+    for child in &mut node.children {
+        strip_prefixes(child);
+    }
+}
@@ -0,0 +1,283 @@
+use crate::codes;
+use crate::compression::{open_input, uncompressed_path};
+use crate::linkage::infer_refund_links;
+use crate::models::PaymentRecord;
+use serde::Serialize;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BadRowPolicy {
+    #[default]
+    Fail,
+    Skip,
+    Collect,
+}
+
+// Payment record file format. `auto` reads `.jsonl` and `.ndjson` files, also
+// compressed (`.jsonl.gz`), as JSON Lines and anything else as CSV.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum InputFormat {
+    #[default]
+    Auto,
+    Csv,
+    Jsonl,
+}
+
+impl InputFormat {
+    pub fn resolve(self, path: &Path) -> InputFormat {
+        if self != InputFormat::Auto {
+            return self;
+        }
+        let path = uncompressed_path(path);
+        let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        if ext.eq_ignore_ascii_case("jsonl") || ext.eq_ignore_ascii_case("ndjson") {
+            InputFormat::Jsonl
+        } else {
+            InputFormat::Csv
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CsvReadOptions {
+    pub format: InputFormat,
+    pub on_bad_row: BadRowPolicy,
+    pub rejects: Option<PathBuf>,
+    pub infer_refund_links: bool,
+    pub min_link_confidence: f64,
+    pub linkage_report: Option<PathBuf>,
+}
+
+impl Default for CsvReadOptions {
+    fn default() -> Self {
+        Self {
+            format: InputFormat::default(),
+            on_bad_row: BadRowPolicy::default(),
+            rejects: None,
+            infer_refund_links: false,
+            min_link_confidence: 0.5,
+            linkage_report: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BadRow {
+    pub row: usize,
+    pub line: u64,
+    pub error: String,
+    pub raw: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CsvLoad {
+    pub rows: Vec<(usize, PaymentRecord)>,
+    pub bad_rows: Vec<BadRow>,
+}
+
+pub fn read_payment_records(
+    path: &Path,
+    options: &CsvReadOptions,
+) -> Result<Vec<PaymentRecord>, String> {
+    let load = load_payment_rows(path, options)?;
+    Ok(load.rows.into_iter().map(|(_, record)| record).collect())
+}
+
+pub fn load_payment_rows(path: &Path, options: &CsvReadOptions) -> Result<CsvLoad, String> {
+    let mut rows = Vec::new();
+    let bad_rows = for_each_payment_row::<String>(path, options, |row, record| {
+        rows.push((row, record));
+        Ok(())
+    })?;
+    let mut load = CsvLoad { rows, bad_rows };
+
+    if options.infer_refund_links {
+        let (rows, mut records): (Vec<usize>, Vec<PaymentRecord>) =
+            load.rows.into_iter().unzip();
+        let summary = infer_refund_links(&mut records, options.min_link_confidence);
+        log::info!(
+            "Refund linkage: candidates={} linked={} (high={} medium={} low={}) unlinked={}",
+            summary.refund_candidates,
+            summary.linked,
+            summary.high_confidence,
+            summary.medium_confidence,
+            summary.low_confidence,
+            summary.unlinked
+        );
+        if let Some(report) = options.linkage_report.as_deref() {
+            summary.write_json(report)?;
+            log::info!("Refund linkage report written to {}", report.display());
+        }
+        load.rows = rows.into_iter().zip(records).collect();
+    }
+
+    Ok(load)
+}
+
+// Parses `path` row by row without holding the rows, calling `visit` with the
+// row number and record of every good row. Bad rows are handled per
+// `options.on_bad_row` and returned; refund links are not inferred.
+pub fn for_each_payment_row<E: From<String>>(
+    path: &Path,
+    options: &CsvReadOptions,
+    mut visit: impl FnMut(usize, PaymentRecord) -> Result<(), E>,
+) -> Result<Vec<BadRow>, E> {
+    let mut bad_rows = Vec::new();
+    let mut handle = |row: usize, parsed: Result<PaymentRecord, BadRow>| match parsed {
+        Ok(record) => visit(row, record),
+        Err(bad_row) => {
+            if options.on_bad_row == BadRowPolicy::Fail {
+                return Err(codes::IN_ROW_INVALID
+                    .err(format!(
+                        "{}:{}: {}",
+                        path.display(),
+                        bad_row.line,
+                        bad_row.error
+                    ))
+                    .into());
+            }
+            log::warn!(
+                file:% = path.display(),
+                row = row,
+                line = bad_row.line;
+                "skipping bad row: {}",
+                bad_row.error
+            );
+            bad_rows.push(bad_row);
+            Ok(())
+        }
+    };
+    match options.format.resolve(path) {
+        InputFormat::Jsonl => read_jsonl_rows(path, &mut handle)?,
+        _ => read_csv_rows(path, &mut handle)?,
+    }
+
+    if !bad_rows.is_empty() {
+        if options.on_bad_row == BadRowPolicy::Collect {
+            let rejects = options
+                .rejects
+                .clone()
+                .unwrap_or_else(|| rejects_path(path));
+            write_rejects(&rejects, &bad_rows)?;
+            log::warn!(
+                "{} bad row(s) in {} written to {}",
+                bad_rows.len(),
+                path.display(),
+                rejects.display()
+            );
+        } else {
+            log::warn!(
+                "{} bad row(s) skipped in {}",
+                bad_rows.len(),
+                path.display()
+            );
+        }
+    }
+
+    Ok(bad_rows)
+}
+
+fn read_csv_rows<E: From<String>>(
+    path: &Path,
+    handle: &mut impl FnMut(usize, Result<PaymentRecord, BadRow>) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut reader = csv::Reader::from_reader(open_input(path).map_err(|err| {
+        codes::IN_READ_FAILED.err(format!("failed to open {}: {}", path.display(), err))
+    })?);
+    let headers = reader
+        .byte_headers()
+        .map_err(|err| {
+            codes::IN_FILE_INVALID.err(format!("{}: invalid header: {}", path.display(), err))
+        })?
+        .clone();
+    let mut raw = csv::ByteRecord::new();
+    let mut row = 0;
+
+    loop {
+        row += 1;
+        let parsed = match reader.read_byte_record(&mut raw) {
+            Ok(false) => break,
+            Ok(true) => raw
+                .deserialize::<PaymentRecord>(Some(&headers))
+                .map_err(|err| err.to_string()),
+            Err(err) if matches!(err.kind(), csv::ErrorKind::Io(_)) => {
+                return Err(codes::IN_READ_FAILED
+                    .err(format!("failed to read {}: {}", path.display(), err))
+                    .into());
+            }
+            Err(err) => Err(err.to_string()),
+        };
+        let parsed = parsed.map_err(|error| BadRow {
+            row,
+            line: raw
+                .position()
+                .map(|pos| pos.line())
+                .unwrap_or_else(|| reader.position().line()),
+            error,
+            raw: raw_row(&raw),
+        });
+        handle(row, parsed)?;
+    }
+    Ok(())
+}
+
+// One JSON object per line; blank lines are skipped and not counted as rows.
+fn read_jsonl_rows<E: From<String>>(
+    path: &Path,
+    handle: &mut impl FnMut(usize, Result<PaymentRecord, BadRow>) -> Result<(), E>,
+) -> Result<(), E> {
+    let reader = BufReader::new(open_input(path).map_err(|err| {
+        codes::IN_READ_FAILED.err(format!("failed to open {}: {}", path.display(), err))
+    })?);
+    let mut row = 0;
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| {
+            codes::IN_READ_FAILED.err(format!("failed to read {}: {}", path.display(), err))
+        })?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        row += 1;
+        let parsed = serde_json::from_str::<PaymentRecord>(&line).map_err(|err| BadRow {
+            row,
+            line: idx as u64 + 1,
+            error: err.to_string(),
+            raw: line.clone(),
+        });
+        handle(row, parsed)?;
+    }
+    Ok(())
+}
+
+pub fn rejects_path(input: &Path) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "input".to_string());
+    input.with_file_name(format!("{}.rejects.csv", stem))
+}
+
+fn write_rejects(path: &Path, bad_rows: &[BadRow]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+    }
+    let mut writer = csv::Writer::from_path(path).map_err(|err| err.to_string())?;
+    for bad_row in bad_rows {
+        writer.serialize(bad_row).map_err(|err| err.to_string())?;
+    }
+    writer.flush().map_err(|err| err.to_string())
+}
+
+fn raw_row(record: &csv::ByteRecord) -> String {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    if writer.write_byte_record(record).is_err() {
+        return String::new();
+    }
+    writer
+        .into_inner()
+        .map(|bytes| String::from_utf8_lossy(&bytes).trim_end().to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,56 @@
+//! CESOP synthetic data generator and report renderer.
+//!
+//! The public functions and methods of `generator`, `synth`, `preflight`,
+//! `analysis`, `cesop_xml`, `correct`, `corrupt` and `validation` that can fail
+//! return [`Result`] with the crate [`Error`], whose `code` is the catalog code
+//! of the failure, if any. The remaining public modules back the `cesop-demo`
+//! binary and report failures as strings led by the code.
+
+pub mod amend;
+pub mod analysis;
+pub mod audit;
+mod banks;
+mod calendar;
+pub mod cesop_xml;
+pub mod checkpoint;
+pub mod codes;
+pub mod compression;
+pub mod correct;
+pub mod corrupt;
+mod error;
+pub mod generator;
+pub mod import;
+pub mod input;
+mod linkage;
+mod location;
+pub mod logging;
+mod manifest;
+pub mod merge;
+pub mod models;
+pub mod package;
+pub mod payment_methods;
+pub mod portfolio;
+pub mod preflight;
+pub mod pseudonymize;
+pub mod rates;
+mod reference;
+pub mod schema;
+pub mod refids;
+pub mod requarter;
+pub mod resubmit;
+pub mod retract;
+pub mod roster;
+pub mod run_summary;
+pub mod sample;
+pub mod scenario;
+pub mod seasonality;
+pub mod snapshot;
+pub mod synth;
+pub mod trace;
+pub mod util;
+pub mod validation;
+pub mod xml_diff;
+mod xml_tree;
+pub mod xsd;
+
+pub use error::{Error, Result};
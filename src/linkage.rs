@@ -0,0 +1,168 @@
+use crate::models::PaymentRecord;
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+const AMOUNT_WEIGHT: f64 = 0.6;
+const TIME_WEIGHT: f64 = 0.4;
+const PARTIAL_AMOUNT_SCORE: f64 = 0.6;
+const REUSED_ORIGINAL_PENALTY: f64 = 0.8;
+const TIME_DECAY_DAYS: f64 = 30.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RefundLink {
+    pub refund_payment_id: String,
+    pub original_payment_id: Option<String>,
+    pub payee_id: String,
+    pub confidence: f64,
+    pub candidates: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LinkageSummary {
+    pub refund_candidates: usize,
+    pub linked: usize,
+    pub unlinked: usize,
+    pub high_confidence: usize,
+    pub medium_confidence: usize,
+    pub low_confidence: usize,
+    pub links: Vec<RefundLink>,
+}
+
+impl LinkageSummary {
+    pub fn write_json(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        std::fs::write(path, json).map_err(|err| err.to_string())
+    }
+}
+
+// Refunds without corr_payment_id (is_refund set, or a negative amount) are
+// matched to an earlier non-refund payment of the same payee, PSP and currency.
+// Candidates are scored on amount (exact beats partial) and time proximity; the
+// margin over the runner-up discounts ambiguous matches.
+pub fn infer_refund_links(records: &mut [PaymentRecord], min_confidence: f64) -> LinkageSummary {
+    let mut originals: HashMap<(&str, &str), Vec<usize>> = HashMap::new();
+    let mut refunds = Vec::new();
+    for (idx, record) in records.iter().enumerate() {
+        let negative = record.amount.trim().starts_with('-');
+        if record.is_refund || negative {
+            let missing_link = record
+                .corr_payment_id
+                .as_deref()
+                .map(|corr| corr.trim().is_empty())
+                .unwrap_or(true);
+            if missing_link {
+                refunds.push(idx);
+            }
+        } else {
+            originals
+                .entry((record.payee_id.as_str(), record.psp_id.as_str()))
+                .or_default()
+                .push(idx);
+        }
+    }
+
+    let mut summary = LinkageSummary {
+        refund_candidates: refunds.len(),
+        ..LinkageSummary::default()
+    };
+    let mut used: HashSet<usize> = HashSet::new();
+    let mut assignments = Vec::new();
+    for refund_idx in refunds {
+        let refund = &records[refund_idx];
+        let candidates = originals
+            .get(&(refund.payee_id.as_str(), refund.psp_id.as_str()))
+            .map(|list| list.as_slice())
+            .unwrap_or(&[]);
+        let mut scored: Vec<(usize, f64)> = candidates
+            .iter()
+            .filter_map(|&original_idx| {
+                score_candidate(refund, &records[original_idx]).map(|score| {
+                    if used.contains(&original_idx) {
+                        (original_idx, score * REUSED_ORIGINAL_PENALTY)
+                    } else {
+                        (original_idx, score)
+                    }
+                })
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let best = scored.first().copied();
+        let confidence = match (best, scored.get(1)) {
+            (Some((_, best)), Some((_, second))) if best > 0.0 => {
+                best * (0.5 + 0.5 * (best - second) / best)
+            }
+            (Some((_, best)), _) => best,
+            (None, _) => 0.0,
+        };
+        let confidence = (confidence * 100.0).round() / 100.0;
+        let original = best
+            .filter(|_| confidence >= min_confidence)
+            .map(|(original_idx, _)| original_idx);
+
+        if let Some(original_idx) = original {
+            used.insert(original_idx);
+            summary.linked += 1;
+            if confidence >= 0.8 {
+                summary.high_confidence += 1;
+            } else if confidence >= 0.6 {
+                summary.medium_confidence += 1;
+            } else {
+                summary.low_confidence += 1;
+            }
+            assignments.push((refund_idx, original_idx));
+        } else {
+            summary.unlinked += 1;
+        }
+        summary.links.push(RefundLink {
+            refund_payment_id: refund.payment_id.clone(),
+            original_payment_id: original.map(|idx| records[idx].payment_id.clone()),
+            payee_id: refund.payee_id.clone(),
+            confidence,
+            candidates: scored.len(),
+        });
+    }
+
+    for (refund_idx, original_idx) in assignments {
+        let original_id = records[original_idx].payment_id.clone();
+        let refund = &mut records[refund_idx];
+        refund.corr_payment_id = Some(original_id);
+        refund.is_refund = true;
+    }
+    summary
+}
+
+fn score_candidate(refund: &PaymentRecord, original: &PaymentRecord) -> Option<f64> {
+    if !refund.currency.eq_ignore_ascii_case(&original.currency) {
+        return None;
+    }
+    let refund_amount = parse_amount(&refund.amount)?.abs();
+    let original_amount = parse_amount(&original.amount)?;
+    let amount_score = if (refund_amount - original_amount).abs() < 0.005 {
+        1.0
+    } else if refund_amount < original_amount {
+        PARTIAL_AMOUNT_SCORE
+    } else {
+        return None;
+    };
+
+    let refund_time = parse_time(&refund.execution_time)?;
+    let original_time = parse_time(&original.execution_time)?;
+    if original_time > refund_time {
+        return None;
+    }
+    let days = (refund_time - original_time).num_seconds() as f64 / 86_400.0;
+    let time_score = 1.0 / (1.0 + days / TIME_DECAY_DAYS);
+
+    Some(AMOUNT_WEIGHT * amount_score + TIME_WEIGHT * time_score)
+}
+
+fn parse_amount(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok()
+}
+
+fn parse_time(value: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(value.trim()).ok()
+}
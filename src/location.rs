@@ -1,4 +1,5 @@
 use crate::models::PaymentRecord;
+use crate::reference::{banking_country, NORTHERN_IRELAND};
 
 pub fn normalize_country_code(code: &str) -> Option<String> {
     let trimmed = code.trim();
@@ -35,7 +36,32 @@ pub fn account_country_code(account_type: &str, account_id: &str) -> Option<Stri
     }
 }
 
+// PayerMS and its PayerMSSource as the payer's account identifier gives them:
+// the IBAN's country prefix or the BIC's country code. OBAN and other
+// identifiers do not reliably encode a country, so nothing is derived.
+pub fn derive_payer_ms(record: &PaymentRecord) -> Option<(String, &'static str)> {
+    let account = record.payer_account.as_deref()?;
+    let account_type = record.payer_account_type.as_deref()?.trim().to_uppercase();
+    match account_type.as_str() {
+        "IBAN" => account_country_code("IBAN", account).map(|country| (country, "IBAN")),
+        "BIC" => bic_country_code(account).map(|country| (country, "BIC")),
+        _ => None,
+    }
+}
+
+// Northern Irish payees hold UK accounts; a provided `XI` is kept as the
+// location when the identifiers point to GB.
 pub fn resolve_payee_country(record: &PaymentRecord) -> Result<String, String> {
+    let derived = derive_payee_country(record)?;
+    let northern_ireland = normalize_country_code(&record.payee_country)
+        .is_some_and(|provided| provided == NORTHERN_IRELAND);
+    if northern_ireland && derived == banking_country(NORTHERN_IRELAND) {
+        return Ok(NORTHERN_IRELAND.to_string());
+    }
+    Ok(derived)
+}
+
+fn derive_payee_country(record: &PaymentRecord) -> Result<String, String> {
     if !record.payee_account.trim().is_empty() {
         if let Some(country) =
             account_country_code(&record.payee_account_type, &record.payee_account)
@@ -1,20 +1,33 @@
+use crate::run_summary::{run_id, run_id_is_external};
 use chrono::Local;
+use log::kv::{Error as KvError, Key, Value, VisitSource};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Once;
 
 static INIT: Once = Once::new();
+// Set from CESOP_LOG_REDACT when logging is initialized.
+static REDACT: AtomicBool = AtomicBool::new(false);
 
-pub fn init_logging(app_name: &str) -> Result<(), String> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+// Console lines go to stderr instead of stdout when `stderr` is set, e.g.
+// because stdout carries a command's JSON result.
+pub fn init_logging(app_name: &str, stderr: bool) -> Result<(), String> {
     let mut init_result: Result<(), String> = Ok(());
     INIT.call_once(|| {
-        if let Err(err) = init_logging_inner(app_name) {
+        if let Err(err) = init_logging_inner(app_name, stderr) {
             init_result = Err(err);
         }
     });
     init_result
 }
 
-fn init_logging_inner(app_name: &str) -> Result<(), String> {
+fn init_logging_inner(app_name: &str, stderr: bool) -> Result<(), String> {
     let level = std::env::var("CESOP_LOG_LEVEL")
         .or_else(|_| std::env::var("RUST_LOG"))
         .unwrap_or_else(|_| "info".to_string());
@@ -22,6 +35,16 @@ fn init_logging_inner(app_name: &str) -> Result<(), String> {
         .parse::<log::LevelFilter>()
         .unwrap_or(log::LevelFilter::Info);
 
+    let format = match std::env::var("CESOP_LOG_FORMAT").as_deref() {
+        Ok("json") | Ok("JSON") => LogFormat::Json,
+        _ => LogFormat::Text,
+    };
+    let redact = matches!(
+        std::env::var("CESOP_LOG_REDACT").as_deref(),
+        Ok("1") | Ok("true") | Ok("yes")
+    );
+    REDACT.store(redact, Ordering::Relaxed);
+
     let log_dir = std::env::var("CESOP_LOG_DIR").ok();
     let log_dir = match log_dir.as_deref() {
         Some("off") | Some("none") | Some("") => None,
@@ -29,25 +52,262 @@ fn init_logging_inner(app_name: &str) -> Result<(), String> {
         None => Some(PathBuf::from("logs")),
     };
 
+    let tag_text_lines = run_id_is_external();
     let mut dispatch = fern::Dispatch::new()
         .level(level)
-        .format(|out, message, record| {
-            out.finish(format_args!(
-                "{} | {:<5} | {} | {}",
-                Local::now().format("%Y-%m-%d %H:%M:%S"),
-                record.level(),
-                record.target(),
-                message
-            ))
+        .format(move |out, message, record| {
+            let mut fields = collect_fields(record);
+            if format == LogFormat::Json || tag_text_lines {
+                fields.push((
+                    "run_id".to_string(),
+                    serde_json::Value::String(run_id().to_string()),
+                ));
+            }
+            let mut message = message.to_string();
+            if redact {
+                message = redact_text(&message);
+                redact_fields(&mut fields);
+            }
+            match format {
+                LogFormat::Text => out.finish(format_args!(
+                    "{} | {:<5} | {} | {}{}",
+                    Local::now().format("%Y-%m-%d %H:%M:%S"),
+                    record.level(),
+                    record.target(),
+                    message,
+                    format_text_fields(&fields)
+                )),
+                LogFormat::Json => out.finish(format_args!(
+                    "{}",
+                    format_json_line(record, &message, fields)
+                )),
+            }
         })
-        .chain(std::io::stdout());
+        .chain(if stderr {
+            fern::Output::from(std::io::stderr())
+        } else {
+            fern::Output::from(std::io::stdout())
+        });
 
     if let Some(dir) = log_dir {
         std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
         let date = Local::now().format("%Y_%m_%d");
-        let file_path = dir.join(format!("{app_name}-{date}.log"));
+        let extension = match format {
+            LogFormat::Text => "log",
+            LogFormat::Json => "jsonl",
+        };
+        let file_path = dir.join(format!("{app_name}-{date}.{extension}"));
         dispatch = dispatch.chain(fern::log_file(file_path).map_err(|err| err.to_string())?);
     }
 
     dispatch.apply().map_err(|err| err.to_string())
 }
+
+struct FieldCollector {
+    fields: Vec<(String, serde_json::Value)>,
+}
+
+impl<'kvs> VisitSource<'kvs> for FieldCollector {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), KvError> {
+        self.fields.push((key.to_string(), field_value(&value)));
+        Ok(())
+    }
+}
+
+fn field_value(value: &Value<'_>) -> serde_json::Value {
+    if let Some(flag) = value.to_bool() {
+        return serde_json::Value::Bool(flag);
+    }
+    if let Some(number) = value.to_u64() {
+        return serde_json::Value::from(number);
+    }
+    if let Some(number) = value.to_i64() {
+        return serde_json::Value::from(number);
+    }
+    if let Some(number) = value.to_f64() {
+        return serde_json::Value::from(number);
+    }
+    serde_json::Value::String(value.to_string())
+}
+
+fn collect_fields(record: &log::Record) -> Vec<(String, serde_json::Value)> {
+    let mut collector = FieldCollector { fields: Vec::new() };
+    let _ = record.key_values().visit(&mut collector);
+    collector.fields
+}
+
+fn format_text_fields(fields: &[(String, serde_json::Value)]) -> String {
+    let mut out = String::new();
+    for (key, value) in fields {
+        match value {
+            serde_json::Value::String(text) if !text.contains(' ') => {
+                out.push_str(&format!(" {}={}", key, text));
+            }
+            _ => out.push_str(&format!(" {}={}", key, value)),
+        }
+    }
+    out
+}
+
+fn format_json_line(
+    record: &log::Record,
+    message: &str,
+    fields: Vec<(String, serde_json::Value)>,
+) -> String {
+    let mut line = serde_json::Map::new();
+    line.insert(
+        "ts".to_string(),
+        serde_json::Value::String(Local::now().to_rfc3339()),
+    );
+    line.insert(
+        "level".to_string(),
+        serde_json::Value::String(record.level().to_string()),
+    );
+    line.insert(
+        "target".to_string(),
+        serde_json::Value::String(record.target().to_string()),
+    );
+    line.insert(
+        "message".to_string(),
+        serde_json::Value::String(message.to_string()),
+    );
+    for (key, value) in fields {
+        line.insert(key, value);
+    }
+    serde_json::Value::Object(line).to_string()
+}
+
+const REDACTED: &str = "[REDACTED]";
+const PERSONAL_FIELDS: &[&str] = &[
+    "payee_name",
+    "psp_name",
+    "payee_psp_name",
+    "payee_psp_address",
+    "name",
+    "payee_address_line",
+    "payee_city",
+    "payee_postcode",
+    "address",
+];
+
+fn redact_fields(fields: &mut [(String, serde_json::Value)]) {
+    for (key, value) in fields.iter_mut() {
+        if PERSONAL_FIELDS.contains(&key.as_str()) {
+            *value = serde_json::Value::String(REDACTED.to_string());
+        } else if let serde_json::Value::String(text) = value {
+            *text = redact_text(text);
+        }
+    }
+}
+
+// `value` of the CSV column or log field `field`, redacted as a log field
+// would be when CESOP_LOG_REDACT is set. For values formatted into a message,
+// where the redaction of the line can no longer tell them apart from text.
+pub fn redact_field(field: &str, value: &str) -> String {
+    if !REDACT.load(Ordering::Relaxed) {
+        return value.to_string();
+    }
+    redact_value(field, value)
+}
+
+fn redact_value(field: &str, value: &str) -> String {
+    if PERSONAL_FIELDS.contains(&field) {
+        REDACTED.to_string()
+    } else {
+        redact_text(value)
+    }
+}
+
+fn redact_text(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut word = String::new();
+    let mut in_quote = false;
+    let mut prev: Option<char> = None;
+    for ch in input.chars() {
+        let opens_quote = !prev.map(|p| p.is_alphanumeric()).unwrap_or(false);
+        prev = Some(ch);
+        if ch == '\'' && (in_quote || opens_quote) {
+            flush_word(&mut out, &mut word, in_quote);
+            if in_quote {
+                out.push_str(REDACTED);
+            }
+            in_quote = !in_quote;
+            out.push(ch);
+            continue;
+        }
+        if ch.is_ascii_alphanumeric() || matches!(ch, '@' | '.' | '_' | '+' | '-') {
+            word.push(ch);
+        } else {
+            flush_word(&mut out, &mut word, in_quote);
+            if !in_quote {
+                out.push(ch);
+            }
+        }
+    }
+    flush_word(&mut out, &mut word, in_quote);
+    out
+}
+
+fn flush_word(out: &mut String, word: &mut String, in_quote: bool) {
+    if word.is_empty() {
+        return;
+    }
+    if !in_quote {
+        let trimmed = word.trim_end_matches('.');
+        let trailing = &word[trimmed.len()..];
+        out.push_str(&redact_word(trimmed));
+        out.push_str(trailing);
+    }
+    word.clear();
+}
+
+fn redact_word(word: &str) -> String {
+    if let Some((_, domain)) = word.split_once('@') {
+        if domain.contains('.') {
+            return format!("***@{}", domain);
+        }
+    }
+    if looks_like_account(word) {
+        let visible_tail = &word[word.len() - 4..];
+        return format!("{}{}{}", &word[..4], "*".repeat(word.len() - 8), visible_tail);
+    }
+    word.to_string()
+}
+
+fn looks_like_account(word: &str) -> bool {
+    let bytes = word.as_bytes();
+    if !(12..=34).contains(&bytes.len()) {
+        return false;
+    }
+    bytes[0].is_ascii_uppercase()
+        && bytes[1].is_ascii_uppercase()
+        && bytes[2].is_ascii_digit()
+        && bytes[3].is_ascii_digit()
+        && bytes.iter().all(|b| b.is_ascii_digit() || b.is_ascii_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payee_name_in_a_message_is_redacted() {
+        let name = "Bäckerei \"Zur Mühle\" O'Brien";
+        let message = format!(" payee_name={:?}", redact_value("payee_name", name));
+        let line = redact_text(&message);
+        assert_eq!(line, format!(" payee_name={:?}", REDACTED));
+        assert!(!line.contains("Mühle") && !line.contains("Brien"));
+    }
+
+    #[test]
+    fn account_in_a_message_is_masked() {
+        let message = format!(
+            " payee_account={:?}",
+            redact_value("payee_account", "DE89370400440532013000")
+        );
+        assert_eq!(
+            redact_text(&message),
+            " payee_account=\"DE89**************3000\""
+        );
+    }
+}
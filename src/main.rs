@@ -1,45 +1,118 @@
-mod analysis;
-mod cesop_xml;
-mod correct;
-mod corrupt;
-mod generator;
-mod location;
-mod logging;
-mod models;
-mod preflight;
-mod reference;
-mod util;
-mod validation;
-
-use analysis::{analyze_threshold_csv, ThresholdReport};
-use clap::{Parser, Subcommand};
+use cesop_demo::amend::{self, AmendmentLabels};
+use cesop_demo::analysis::{
+    analyze_threshold_csv, AnalyzeOptions, BorderlinePayee, EurTotals, IdentityCollisions,
+    ThresholdReport,
+};
+use cesop_demo::cesop_xml::{
+    build_nil_reports, build_reports, build_reports_from_csv, render_totals, spill_reports_from_csv,
+    write_reports, AddressFormat, MessageLimits, OutputLayout, PayeeAccountMode,
+    PayeeConflictPolicy, PayerMsMode, PspNamePolicy, RenderOptions, RenderTotals, ReportWriter,
+    XmlStyle,
+};
+use cesop_demo::checkpoint::{GenerateCheckpoint, RenderProgress};
+use cesop_demo::compression::{self, OutputFile};
+use cesop_demo::corrupt::{CorruptSummary, MessyProfile};
+use cesop_demo::generator::{
+    generate_records, generate_streaming, verify_generated, DatasetCheck, DatasetStats,
+    Churn, CountryWeights, FieldRates, GenerateManifest, GeneratedData, GeneratorConfig, PayeeRegistry,
+    Perspective,
+};
+use cesop_demo::input::{BadRowPolicy, CsvReadOptions, InputFormat};
+use cesop_demo::models::PaymentRecord;
+use cesop_demo::payment_methods::PaymentMethodMode;
+use cesop_demo::preflight::{
+    BicValidation, FailOn, PreflightFormat, PreflightOptions, PreflightRules,
+};
+use cesop_demo::portfolio::{Portfolio, PortfolioManifest, TenantResult};
+use cesop_demo::rates::RateTable;
+use cesop_demo::roster::PspRoster;
+use cesop_demo::run_summary::RunSummary;
+use cesop_demo::schema::SchemaVersion;
+use cesop_demo::scenario::Scenario;
+use cesop_demo::seasonality::{TimePreset, TimeProfile};
+use cesop_demo::snapshot::SnapshotScenario;
+use cesop_demo::trace::RecordTracer;
+use cesop_demo::validation::validate_with_vm;
+use cesop_demo::{
+    audit, checkpoint, codes, correct, corrupt, generator, import, input, logging, merge,
+    package, portfolio, preflight, pseudonymize, refids, requarter, resubmit, retract, run_summary, sample,
+    snapshot, util, xml_diff, xsd,
+};
 use chrono::Datelike;
-use cesop_xml::{build_reports_from_csv, write_reports};
-use generator::{generate_records, GeneratorConfig};
-use models::PaymentRecord;
+use clap::{Parser, Subcommand, ValueEnum};
 use rand::Rng;
+use serde::Serialize;
 use std::fs::create_dir_all;
 use std::path::{Path, PathBuf};
 use std::time::Instant;
-use validation::validate_with_vm;
 
 #[derive(Parser)]
 #[command(name = "cesop-demo")]
 #[command(about = "CESOP synthetic data generator", long_about = None)]
 struct Cli {
+    #[arg(long, global = true)]
+    run_id: Option<String>,
     #[command(subcommand)]
     command: Command,
 }
 
 #[derive(Subcommand)]
 enum Command {
-    Generate(GenerateArgs),
+    Generate(Box<GenerateArgs>),
     Analyze(AnalyzeArgs),
     Render(RenderArgs),
     Correct(CorrectArgs),
     Corrupt(CorruptArgs),
     Preflight(PreflightArgs),
     Validate(ValidateArgs),
+    Sample(SampleArgs),
+    Pseudonymize(PseudonymizeArgs),
+    Snapshot(SnapshotArgs),
+    Retract(RetractArgs),
+    Resubmit(ResubmitArgs),
+    Refids(RefidsArgs),
+    Requarter(RequarterArgs),
+    Merge(MergeArgs),
+    Import(ImportArgs),
+    XmlDiff(XmlDiffArgs),
+    Package(PackageArgs),
+    Codes(CodesArgs),
+}
+
+#[derive(clap::Args)]
+struct CsvInputArgs {
+    #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+    input_format: InputFormat,
+    #[arg(long, value_enum, default_value_t = BadRowPolicy::Fail)]
+    on_bad_row: BadRowPolicy,
+    #[arg(long)]
+    rejects: Option<PathBuf>,
+    #[arg(long, default_value_t = false)]
+    infer_refund_links: bool,
+    #[arg(long, default_value_t = 0.5)]
+    min_link_confidence: f64,
+    #[arg(long)]
+    linkage_report: Option<PathBuf>,
+}
+
+impl CsvInputArgs {
+    fn options(&self) -> CsvReadOptions {
+        // A rejects file only makes sense when collecting, so --rejects
+        // implies it.
+        let on_bad_row = if self.rejects.is_some() {
+            BadRowPolicy::Collect
+        } else {
+            self.on_bad_row
+        };
+        CsvReadOptions {
+            format: self.input_format,
+            on_bad_row,
+            rejects: self.rejects.clone(),
+            infer_refund_links: self.infer_refund_links,
+            min_link_confidence: self.min_link_confidence,
+            linkage_report: self.linkage_report.clone(),
+        }
+    }
 }
 
 #[derive(Parser)]
@@ -56,8 +129,176 @@ struct GenerateArgs {
     non_eu_payee_ratio: f64,
     #[arg(long, default_value_t = 0.02)]
     no_account_payee_ratio: f64,
+    #[arg(long, default_value_t = 0.0)]
+    marketplace_ratio: f64,
+    #[arg(long, default_value_t = 0.0)]
+    foreign_currency_ratio: f64,
+    #[arg(long, default_value_t = 0.0)]
+    payer_account_ratio: f64,
+    #[arg(long, default_value_t = 0.0)]
+    non_eu_payer_ratio: f64,
+    #[arg(long, default_value_t = 0.0)]
+    local_time_ratio: f64,
+    #[arg(long, default_value_t = 0.0)]
+    xi_payee_ratio: f64,
+    #[arg(long, default_value_t = 0.0)]
+    xi_payer_ratio: f64,
+    #[arg(long, default_value_t = 0.0)]
+    multi_psp_ratio: f64,
+    #[arg(long, value_enum, default_value_t = Perspective::Payee)]
+    perspective: Perspective,
+    #[arg(long, default_value_t = 0.0)]
+    unknown_payee_psp_ratio: f64,
+    #[arg(long, default_value_t = false)]
+    mcc: bool,
+    #[arg(long, default_value = "", value_parser = FieldRates::parse)]
+    field_rates: FieldRates,
+    #[arg(long, default_value = "uniform", value_parser = CountryWeights::parse)]
+    country_weights: CountryWeights,
+    #[arg(long, default_value_t = 0.0)]
+    unicode_name_ratio: f64,
+    #[arg(long, default_value = "1..30", value_parser = util::parse_day_range)]
+    refund_delay_days: (u32, u32),
+    #[arg(long, default_value_t = 0.0)]
+    partial_refund_ratio: f64,
+    #[arg(long, default_value_t = 1)]
+    max_refunds_per_payment: u32,
     #[arg(long, default_value = "data/synthetic/payments.csv")]
     output: PathBuf,
+    #[arg(long, default_value_t = 100_000)]
+    checkpoint_every: usize,
+    #[arg(long, default_value_t = false)]
+    resume: bool,
+    #[arg(long, default_value_t = false, conflicts_with = "resume")]
+    append: bool,
+    #[arg(long, default_value_t = false)]
+    holiday_calendars: bool,
+    #[arg(long, value_enum, default_value_t = TimePreset::Uniform, conflicts_with = "append")]
+    time_profile: TimePreset,
+    #[arg(long, conflicts_with = "append")]
+    year: Option<i32>,
+    #[arg(long, conflicts_with = "append", value_parser = clap::value_parser!(u8).range(1..=4))]
+    quarter: Option<u8>,
+    #[arg(
+        long,
+        conflicts_with_all = ["year", "quarter", "append", "resume", "portfolio"]
+    )]
+    quarters: Option<String>,
+    #[arg(long, default_value_t = false, requires = "quarters")]
+    split_quarters: bool,
+    #[arg(long, default_value_t = 0.0, requires = "quarters")]
+    churn_leave_ratio: f64,
+    #[arg(long, default_value_t = 0.0, requires = "quarters")]
+    churn_join_ratio: f64,
+    #[arg(long, default_value_t = 0.0, requires = "quarters")]
+    churn_account_change_ratio: f64,
+    #[arg(long, default_value_t = false)]
+    no_verify: bool,
+    #[arg(long, default_value_t = false, conflicts_with = "append")]
+    deterministic_ids: bool,
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "light",
+        conflicts_with_all = ["append", "quarters", "portfolio", "stream"]
+    )]
+    messy: Option<MessyProfile>,
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["resume", "append", "quarters", "portfolio"]
+    )]
+    stream: bool,
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["append", "quarters", "portfolio", "stream"]
+    )]
+    threshold_edge: bool,
+    #[arg(
+        long,
+        default_value_t = 0.0,
+        conflicts_with_all = ["append", "quarters", "portfolio", "stream"]
+    )]
+    cross_psp_duplicate_ratio: f64,
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "0.1",
+        conflicts_with_all = ["append", "quarters", "portfolio", "stream"]
+    )]
+    with_corrections: Option<f64>,
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "resume",
+            "append",
+            "quarters",
+            "portfolio",
+            "stream",
+            "with_corrections"
+        ]
+    )]
+    render: Option<PathBuf>,
+    #[arg(
+        long,
+        conflicts_with_all = ["append", "quarters", "portfolio", "stream", "config"]
+    )]
+    target_reportable: Option<usize>,
+    #[arg(
+        long,
+        value_parser = util::parse_byte_size,
+        conflicts_with_all = [
+            "scale",
+            "target_reportable",
+            "threshold_edge",
+            "cross_psp_duplicate_ratio",
+            "append",
+            "quarters",
+            "portfolio"
+        ]
+    )]
+    max_output_size: Option<u64>,
+    #[arg(long, conflicts_with_all = ["resume", "append"])]
+    portfolio: Option<PathBuf>,
+    #[arg(long, conflicts_with_all = ["portfolio", "append", "psps"])]
+    psp_roster: Option<PathBuf>,
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "portfolio",
+            "append",
+            "scale",
+            "psps",
+            "multi_account_ratio",
+            "non_eu_payee_ratio",
+            "no_account_payee_ratio",
+            "marketplace_ratio",
+            "unicode_name_ratio",
+            "foreign_currency_ratio",
+            "payer_account_ratio",
+            "non_eu_payer_ratio",
+            "local_time_ratio",
+            "xi_payee_ratio",
+            "xi_payer_ratio",
+            "multi_psp_ratio",
+            "perspective",
+            "unknown_payee_psp_ratio",
+            "mcc",
+            "field_rates",
+            "country_weights",
+            "churn_leave_ratio",
+            "churn_join_ratio",
+            "churn_account_change_ratio",
+            "refund_delay_days",
+            "partial_refund_ratio",
+            "max_refunds_per_payment",
+            "holiday_calendars",
+            "time_profile",
+        ]
+    )]
+    config: Option<PathBuf>,
 }
 
 #[derive(Parser)]
@@ -68,6 +309,18 @@ struct AnalyzeArgs {
     threshold: usize,
     #[arg(long, default_value_t = false)]
     include_refunds: bool,
+    #[arg(long, default_value_t = false, requires = "rates")]
+    eur_totals: bool,
+    #[arg(long)]
+    rates: Option<PathBuf>,
+    #[arg(long, default_value_t = 10)]
+    top_payees: usize,
+    #[arg(long, default_value_t = false)]
+    identity_collisions: bool,
+    #[arg(long)]
+    proximity: Option<usize>,
+    #[command(flatten)]
+    csv: CsvInputArgs,
 }
 
 #[derive(Parser)]
@@ -80,6 +333,69 @@ struct RenderArgs {
     transmitting_country: String,
     #[arg(long)]
     licensed_countries: Option<String>,
+    #[arg(long, value_enum, default_value_t = AddressFormat::Free)]
+    address_format: AddressFormat,
+    #[arg(long)]
+    ref_seed: Option<String>,
+    #[arg(long)]
+    timestamp: Option<String>,
+    #[arg(long, default_value_t = XmlStyle::default())]
+    xml_style: XmlStyle,
+    #[arg(long, value_enum, default_value_t = PaymentMethodMode::Lenient)]
+    payment_methods: PaymentMethodMode,
+    #[arg(long, value_enum, default_value_t = SchemaVersion::V4_03)]
+    schema_version: SchemaVersion,
+    #[arg(long, value_enum, default_value_t = PspNamePolicy::Error)]
+    psp_name_policy: PspNamePolicy,
+    #[arg(long, value_enum, default_value_t = PayerMsMode::Account)]
+    payer_ms: PayerMsMode,
+    #[arg(long, value_enum, default_value_t = PayeeAccountMode::First)]
+    payee_accounts: PayeeAccountMode,
+    #[arg(long, value_enum, default_value_t = PayeeConflictPolicy::First)]
+    payee_conflicts: PayeeConflictPolicy,
+    #[arg(long)]
+    trace_records: Option<String>,
+    #[arg(long)]
+    trace_output: Option<PathBuf>,
+    #[arg(long, default_value_t = false)]
+    resume: bool,
+    #[arg(long, value_enum, default_value_t = OutputLayout::Flat)]
+    layout: OutputLayout,
+    #[arg(long)]
+    quarantine: Option<PathBuf>,
+    #[arg(
+        long,
+        default_value_t = merge::DEFAULT_MAX_SIZE_MB,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    max_size_mb: u64,
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    max_payees: Option<u64>,
+    #[arg(long, default_value_t = false)]
+    validate: bool,
+    #[arg(long, default_value_t = false)]
+    package: bool,
+    #[arg(long, default_value_t = false)]
+    streaming: bool,
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    jobs: u64,
+    #[arg(
+        long,
+        default_value_t = false,
+        requires_all = ["psp", "psp_name", "period"],
+        conflicts_with_all = ["streaming", "resume", "trace_records", "quarantine"]
+    )]
+    nil_report: bool,
+    #[arg(long, requires = "nil_report")]
+    psp: Option<String>,
+    #[arg(long, requires = "nil_report")]
+    psp_name: Option<String>,
+    #[arg(long, requires = "nil_report")]
+    period: Option<String>,
+    #[arg(long, requires = "nil_report")]
+    country: Option<String>,
+    #[command(flatten)]
+    csv: CsvInputArgs,
 }
 
 #[derive(Parser)]
@@ -90,6 +406,12 @@ struct CorrectArgs {
     output: PathBuf,
     #[arg(long)]
     seed: Option<u64>,
+    #[arg(long, default_value = "data/audit/audit.jsonl")]
+    audit_log: PathBuf,
+    #[arg(long, default_value_t = false)]
+    audit_chain: bool,
+    #[command(flatten)]
+    csv: CsvInputArgs,
 }
 
 #[derive(Parser)]
@@ -104,6 +426,12 @@ struct CorruptArgs {
     tx_error_rate: f64,
     #[arg(long)]
     seed: Option<u64>,
+    #[arg(long, default_value = "data/audit/audit.jsonl")]
+    audit_log: PathBuf,
+    #[arg(long, default_value_t = false)]
+    audit_chain: bool,
+    #[command(flatten)]
+    csv: CsvInputArgs,
 }
 
 #[derive(Parser)]
@@ -114,6 +442,26 @@ struct PreflightArgs {
     threshold: usize,
     #[arg(long, default_value_t = false)]
     include_refunds: bool,
+    #[arg(long, default_value_t = 5)]
+    max_examples: usize,
+    #[arg(long, value_enum, default_value_t = PaymentMethodMode::Lenient)]
+    payment_methods: PaymentMethodMode,
+    #[arg(long, value_enum, default_value_t = PreflightFormat::Text)]
+    format: PreflightFormat,
+    #[arg(long)]
+    rules: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t = FailOn::Error)]
+    fail_on: FailOn,
+    #[arg(long, requires = "quarter")]
+    year: Option<i32>,
+    #[arg(long, requires = "year", value_parser = clap::value_parser!(u8).range(1..=4))]
+    quarter: Option<u8>,
+    #[arg(long, value_enum, default_value_t = BicValidation::Basic)]
+    bic_validation: BicValidation,
+    #[arg(long)]
+    proximity: Option<usize>,
+    #[command(flatten)]
+    csv: CsvInputArgs,
 }
 
 #[derive(Parser)]
@@ -129,64 +477,397 @@ struct ValidateArgs {
     jar: PathBuf,
     #[arg(long, default_value = "java")]
     java: String,
+    #[arg(long, default_value_t = false)]
+    xsd: bool,
+}
+
+#[derive(Parser)]
+struct SampleArgs {
+    #[arg(long, default_value = "data/synthetic/payments.csv")]
+    input: PathBuf,
+    #[arg(long, default_value = "data/synthetic/payments_sample.csv")]
+    output: PathBuf,
+    #[arg(long, conflicts_with = "fraction", required_unless_present = "fraction")]
+    payees: Option<usize>,
+    #[arg(long)]
+    fraction: Option<f64>,
+    #[arg(long)]
+    seed: Option<u64>,
+    #[command(flatten)]
+    csv: CsvInputArgs,
+}
+
+#[derive(Parser)]
+struct PseudonymizeArgs {
+    #[arg(long, default_value = "data/synthetic/payments.csv")]
+    input: PathBuf,
+    #[arg(long, default_value = "data/synthetic/payments_pseudonymized.csv")]
+    output: PathBuf,
+    #[arg(long)]
+    key_file: Option<PathBuf>,
+    #[command(flatten)]
+    csv: CsvInputArgs,
+}
+
+#[derive(Parser)]
+struct RequarterArgs {
+    #[arg(long, default_value = "data/synthetic/payments.csv")]
+    input: PathBuf,
+    #[arg(long)]
+    output: PathBuf,
+    #[arg(long)]
+    period: String,
+    #[arg(long)]
+    from_period: Option<String>,
+    #[command(flatten)]
+    csv: CsvInputArgs,
+}
+
+#[derive(Parser)]
+struct MergeArgs {
+    #[arg(required = true, num_args = 2..)]
+    inputs: Vec<PathBuf>,
+    #[arg(long)]
+    output: PathBuf,
+    #[arg(
+        long,
+        default_value_t = merge::DEFAULT_MAX_SIZE_MB,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    max_size_mb: u64,
+}
+
+#[derive(Parser)]
+struct ImportArgs {
+    #[arg(required = true, num_args = 1..)]
+    inputs: Vec<PathBuf>,
+    #[arg(long)]
+    output: PathBuf,
+    #[arg(long)]
+    registry: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+struct XmlDiffArgs {
+    left: PathBuf,
+    right: PathBuf,
+    #[arg(long, default_value_t = false)]
+    json: bool,
+    #[arg(long, default_value_t = false)]
+    check: bool,
+}
+
+#[derive(Parser)]
+struct PackageArgs {
+    #[arg(long, default_value = "data/output")]
+    output_dir: PathBuf,
+    #[arg(long)]
+    registry: Option<PathBuf>,
+    #[arg(long)]
+    recipient_key: PathBuf,
+    #[arg(long)]
+    package_dir: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+struct CodesArgs {
+    #[arg(long, value_enum)]
+    category: Option<codes::CodeCategory>,
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(Parser)]
+struct RetractArgs {
+    #[arg(long, default_value = "data/output/refid_registry.json")]
+    registry: PathBuf,
+    #[arg(long)]
+    payees: Option<String>,
+    #[arg(long)]
+    payees_file: Option<PathBuf>,
+    #[arg(long)]
+    periods: Option<String>,
+    #[arg(long)]
+    psp: Option<String>,
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+struct ResubmitArgs {
+    #[arg(long, default_value = "data/synthetic/payments.csv")]
+    input: PathBuf,
+    #[arg(long, default_value = "data/output/refid_registry.json")]
+    registry: PathBuf,
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
+    #[arg(long, default_value = "auto")]
+    transmitting_country: String,
+    #[arg(long)]
+    licensed_countries: Option<String>,
+    #[arg(long, value_enum, default_value_t = AddressFormat::Free)]
+    address_format: AddressFormat,
+    #[arg(long)]
+    ref_seed: Option<String>,
+    #[arg(long)]
+    timestamp: Option<String>,
+    #[arg(long, default_value_t = XmlStyle::default())]
+    xml_style: XmlStyle,
+    #[arg(long, value_enum, default_value_t = PaymentMethodMode::Lenient)]
+    payment_methods: PaymentMethodMode,
+    #[arg(long, value_enum, default_value_t = SchemaVersion::V4_03)]
+    schema_version: SchemaVersion,
+    #[arg(long, value_enum, default_value_t = PspNamePolicy::Error)]
+    psp_name_policy: PspNamePolicy,
+    #[arg(long, value_enum, default_value_t = PayerMsMode::Account)]
+    payer_ms: PayerMsMode,
+    #[arg(long, value_enum, default_value_t = PayeeAccountMode::First)]
+    payee_accounts: PayeeAccountMode,
+    #[arg(long, value_enum, default_value_t = PayeeConflictPolicy::First)]
+    payee_conflicts: PayeeConflictPolicy,
+    #[command(flatten)]
+    csv: CsvInputArgs,
+}
+
+#[derive(Parser)]
+struct RefidsArgs {
+    #[arg(long, default_value = "data/output/refid_registry.json")]
+    registry: PathBuf,
+    #[arg(long)]
+    psp: Option<String>,
+    #[arg(long)]
+    period: Option<String>,
+    #[arg(long)]
+    payee: Option<String>,
+    #[arg(long)]
+    transaction: Option<String>,
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(Parser)]
+struct SnapshotArgs {
+    #[command(subcommand)]
+    action: SnapshotAction,
+}
+
+#[derive(Subcommand)]
+enum SnapshotAction {
+    Record(SnapshotRecordArgs),
+    Check(SnapshotCheckArgs),
+}
+
+#[derive(Parser)]
+struct SnapshotRecordArgs {
+    #[arg(long)]
+    name: String,
+    #[arg(long, default_value = "data/synthetic/payments.csv")]
+    input: PathBuf,
+    #[arg(long, default_value_t = 25)]
+    threshold: usize,
+    #[arg(long, default_value_t = false)]
+    include_refunds: bool,
+    #[arg(long, default_value = "auto")]
+    transmitting_country: String,
+    #[arg(long)]
+    licensed_countries: Option<String>,
+    #[arg(long, default_value = "snapshots")]
+    dir: PathBuf,
+}
+
+#[derive(Parser)]
+struct SnapshotCheckArgs {
+    #[arg(long)]
+    name: Option<String>,
+    #[arg(long, default_value = "snapshots")]
+    dir: PathBuf,
+}
+
+// A failed run: its error and the exit code the process ends with.
+struct Failure {
+    message: String,
+    exit_code: i32,
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            exit_code: 1,
+        }
+    }
 }
 
 fn main() {
-    if let Err(err) = run() {
-        eprintln!("error: {err}");
-        std::process::exit(1);
+    if let Err(failure) = run() {
+        eprintln!("error: {}", failure.message);
+        std::process::exit(failure.exit_code);
     }
 }
 
-fn run() -> Result<(), String> {
-    logging::init_logging("cesop-demo")?;
+fn run() -> Result<(), Failure> {
     let cli = Cli::parse();
-    match cli.command {
-        Command::Generate(args) => run_generate(args),
-        Command::Analyze(args) => run_analyze(args),
-        Command::Render(args) => run_render(args),
-        Command::Correct(args) => run_correct(args),
-        Command::Corrupt(args) => run_corrupt(args),
-        Command::Preflight(args) => run_preflight(args),
-        Command::Validate(args) => run_validate(args),
+    run_summary::init_run_id(cli.run_id.as_deref());
+    logging::init_logging("cesop-demo", prints_json(&cli.command))?;
+    let mut summary = RunSummary::new(command_name(&cli.command));
+    let result = match cli.command {
+        Command::Generate(args) => run_generate(*args, &mut summary),
+        Command::Analyze(args) => run_analyze(args, &mut summary),
+        Command::Render(args) => run_render(args, &mut summary),
+        Command::Correct(args) => run_correct(args, &mut summary),
+        Command::Corrupt(args) => run_corrupt(args, &mut summary),
+        Command::Preflight(args) => run_preflight(args, &mut summary),
+        Command::Validate(args) => run_validate(args, &mut summary),
+        Command::Sample(args) => run_sample(args, &mut summary),
+        Command::Pseudonymize(args) => run_pseudonymize(args, &mut summary),
+        Command::Snapshot(args) => run_snapshot(args, &mut summary),
+        Command::Retract(args) => run_retract(args, &mut summary),
+        Command::Resubmit(args) => run_resubmit(args, &mut summary),
+        Command::Refids(args) => run_refids(args, &mut summary),
+        Command::Requarter(args) => run_requarter(args, &mut summary),
+        Command::Merge(args) => run_merge(args, &mut summary),
+        Command::Import(args) => run_import(args, &mut summary),
+        Command::XmlDiff(args) => run_xml_diff(args, &mut summary),
+        Command::Package(args) => run_package(args, &mut summary),
+        Command::Codes(args) => run_codes(args, &mut summary),
+    };
+    summary.finish(&result);
+    if let Some(peak_kb) = summary.peak_rss_kb {
+        emit_info_line(&format!(
+            "Peak memory: {:.1} MiB",
+            peak_kb as f64 / 1024.0
+        ));
+    }
+    if let Some(dir) = run_summary::runs_dir() {
+        match summary.write(&dir) {
+            Ok(path) => log::debug!(
+                "run summary for run {} written to {}",
+                summary.run_id,
+                path.display()
+            ),
+            Err(err) => log::warn!("failed to write run summary: {}", err),
+        }
     }
+    result.map_err(|message| Failure {
+        message,
+        exit_code: summary.exit_code,
+    })
 }
 
-fn run_generate(args: GenerateArgs) -> Result<(), String> {
-    let (year, quarter) = resolve_year_quarter()?;
-    let seed = args.seed.unwrap_or_else(random_seed);
+// Commands whose result on stdout is JSON, so logs must not interleave.
+fn prints_json(command: &Command) -> bool {
+    match command {
+        Command::Preflight(args) => args.format == PreflightFormat::Json,
+        Command::Refids(args) => args.json,
+        Command::XmlDiff(args) => args.json,
+        Command::Codes(args) => args.json,
+        _ => false,
+    }
+}
 
-    let derived = derive_scaled_generation(args.scale)?;
-    let config = GeneratorConfig {
-        records: derived.records,
-        payees: derived.payees,
-        micro_payees: derived.micro_payees,
-        near_threshold_payees: derived.near_threshold_payees,
-        large_payees: derived.large_payees,
-        psps: args.psps,
-        cross_border_ratio: 0.8,
-        refund_ratio: 0.02,
-        multi_account_ratio: args.multi_account_ratio,
-        non_eu_payee_ratio: args.non_eu_payee_ratio,
-        no_account_payee_ratio: args.no_account_payee_ratio,
-        year,
-        quarter,
+fn command_name(command: &Command) -> &'static str {
+    match command {
+        Command::Generate(_) => "generate",
+        Command::Analyze(_) => "analyze",
+        Command::Render(_) => "render",
+        Command::Correct(_) => "correct",
+        Command::Corrupt(_) => "corrupt",
+        Command::Preflight(_) => "preflight",
+        Command::Validate(_) => "validate",
+        Command::Sample(_) => "sample",
+        Command::Pseudonymize(_) => "pseudonymize",
+        Command::Snapshot(_) => "snapshot",
+        Command::Retract(_) => "retract",
+        Command::Resubmit(_) => "resubmit",
+        Command::Refids(_) => "refids",
+        Command::Requarter(_) => "requarter",
+        Command::Merge(_) => "merge",
+        Command::Import(_) => "import",
+        Command::XmlDiff(_) => "xml-diff",
+        Command::Package(_) => "package",
+        Command::Codes(_) => "codes",
+    }
+}
+
+fn run_generate(args: GenerateArgs, summary: &mut RunSummary) -> Result<(), String> {
+    if let Some(path) = &args.portfolio {
+        return run_generate_portfolio(path, &args, summary);
+    }
+    if args.append {
+        return run_generate_append(args, summary);
+    }
+    if let Some(quarters) = &args.quarters {
+        return run_generate_quarters(quarters, &args, summary);
+    }
+    let scenario = args.config.as_deref().map(Scenario::load).transpose()?;
+    let (year, quarter) =
+        generate_period(&args, scenario.as_ref().map(Scenario::period).transpose()?.flatten())?;
+    let output = scenario
+        .as_ref()
+        .and_then(|scenario| scenario.output.clone())
+        .unwrap_or_else(|| args.output.clone());
+    let scale = match args.max_output_size {
+        Some(_) => OUTPUT_SIZE_SAMPLE_RECORDS,
+        None => scenario
+            .as_ref()
+            .and_then(|scenario| scenario.scale)
+            .unwrap_or(args.scale),
+    };
+    let checkpoint_path = checkpoint::generate_checkpoint_path(&output);
+    let resume_from: Option<GenerateCheckpoint> = if args.resume {
+        let loaded = checkpoint::load_checkpoint(&checkpoint_path)?;
+        if loaded.is_none() {
+            log::warn!(
+                "no checkpoint found at {}; starting from scratch",
+                checkpoint_path.display()
+            );
+        }
+        loaded
+    } else {
+        None
+    };
+    let seed = args
+        .seed
+        .or(scenario.as_ref().and_then(|scenario| scenario.seed))
+        .or(resume_from.as_ref().map(|checkpoint| checkpoint.seed))
+        .unwrap_or_else(random_seed);
+
+    let mut config =
+        build_generate_config(&args, scenario.as_ref(), scale, year, quarter, summary)?;
+    let scale = match args.max_output_size {
+        Some(max_bytes) => {
+            let scale = records_for_output_size(&config, seed, max_bytes)?;
+            let derived = derive_scaled_generation(scale, None)?;
+            config.records = derived.records;
+            config.payees = derived.payees;
+            config.micro_payees = derived.micro_payees;
+            config.near_threshold_payees = derived.near_threshold_payees;
+            config.large_payees = derived.large_payees;
+            emit_info_line(&format!(
+                "Max output size: {} bytes -> {} records",
+                max_bytes, scale
+            ));
+            summary.counter("max_output_size", max_bytes);
+            scale
+        }
+        None => scale,
     };
 
     log::info!(
         "Using scale {} -> payees={}, micro={}, near={}, large={}",
-        args.scale,
+        scale,
         config.payees,
         config.micro_payees,
         config.near_threshold_payees,
         config.large_payees
     );
     log::info!(
-        "Generator options: psps={}, multi_account_ratio(account+BIC)={}, non_eu_payee_ratio={}, no_account_payee_ratio={}",
+        "Generator options: psps={}, multi_account_ratio(account+BIC)={}, non_eu_payee_ratio={}, no_account_payee_ratio={}, holiday_calendars={}",
         config.psps,
         config.multi_account_ratio,
         config.non_eu_payee_ratio,
-        config.no_account_payee_ratio
+        config.no_account_payee_ratio,
+        config.holiday_calendars
     );
 
     log::info!(
@@ -195,10 +876,125 @@ fn run_generate(args: GenerateArgs) -> Result<(), String> {
         config.payees,
         seed
     );
+    if args.stream {
+        return run_generate_streaming(&args, &config, seed, &output, summary);
+    }
+    let roster_fingerprint = args
+        .psp_roster
+        .as_deref()
+        .map(util::sha256_file)
+        .transpose()?
+        .unwrap_or_default();
+    let fingerprint = generate_fingerprint(&args, &config, &roster_fingerprint)?;
     let gen_start = Instant::now();
-    let records = generate_records(&config, seed)?;
+    let (generated, seed) = match args.target_reportable {
+        Some(target) => {
+            let (generated, used_seed) =
+                generator::generate_reportable(&config, seed, target, 25)?;
+            emit_info_line(&format!(
+                "Target reportable payees: {} (seed {})",
+                target, used_seed
+            ));
+            summary.counter("target_reportable", target);
+            (generated, used_seed)
+        }
+        None => (generate_records(&config, seed)?, seed),
+    };
     let gen_elapsed = gen_start.elapsed();
-    write_csv(&args.output, &records)?;
+    summary.phases.add("generation", gen_elapsed);
+    if args.no_verify {
+        log::warn!("dataset verification skipped (--no-verify)");
+    } else {
+        let checks = summary
+            .phases
+            .time("verification", || verify_dataset(&config, &generated, seed))?;
+        summary.counter("dataset_checks", checks);
+        emit_info_line(&format!("Dataset checks passed: {}", checks));
+    }
+    let mut records = generated.records;
+    if let Some(profile) = args.messy {
+        let (payee_error_rate, tx_error_rate) = profile.rates();
+        let result = corrupt::corrupt_records(&mut records, payee_error_rate, tx_error_rate, seed)?;
+        emit_info_line(&format!("Messy profile: {}", profile.as_str()));
+        summary.counter("messy", profile.as_str());
+        record_corrupt_summary(&result, summary);
+    }
+    if args.cross_psp_duplicate_ratio != 0.0 {
+        let duplicates = generator::cross_psp_duplicates(
+            &records,
+            &generated.registry,
+            &config,
+            args.cross_psp_duplicate_ratio,
+            seed,
+        )?;
+        emit_info_line(&format!(
+            "Cross-PSP duplicates: {} payment(s) reported again by a payer PSP",
+            duplicates.len()
+        ));
+        summary.counter("cross_psp_duplicates", duplicates.len());
+        records.extend(duplicates);
+    }
+    if args.threshold_edge {
+        let edge = generator::threshold_edge_records(&generated.registry, &config, seed)?;
+        emit_info_line(&format!(
+            "Threshold edge: {} payees with {} records ({})",
+            generator::THRESHOLD_EDGE_PAYEES.len(),
+            edge.len(),
+            generator::THRESHOLD_EDGE_PAYEES
+                .iter()
+                .map(|(id, _, _)| *id)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        summary.counter("threshold_edge_records", edge.len());
+        records.extend(edge);
+    }
+    if let Some(output_dir) = &args.render {
+        emit_info_line(&format!(
+            "Generated {} records for Q{} {} (seed {}); generation time: {} ms",
+            records.len(),
+            quarter,
+            year,
+            seed,
+            gen_elapsed.as_millis()
+        ));
+        summary.counter("seed", seed);
+        summary.counter("year", year);
+        summary.counter("quarter", quarter);
+        summary.counter("records", records.len());
+        summary.counter("payees", config.payees);
+        summary.counter("generation_ms", gen_elapsed.as_millis() as u64);
+        return render_generated(records, output_dir, summary);
+    }
+    let resumed = summary.phases.time("csv_write", || {
+        checkpoint::write_generated_csv(
+            &output,
+            &mut records,
+            seed,
+            &fingerprint,
+            args.checkpoint_every,
+            resume_from.as_ref(),
+        )
+    })?;
+    if resumed > 0 {
+        emit_info_line(&format!(
+            "Resumed from checkpoint: {} record(s) already written, {} appended",
+            resumed,
+            records.len() - resumed
+        ));
+        summary.counter("resumed_records", resumed);
+    }
+    if let Some(rate) = args.with_corrections {
+        write_correction_pair(&output, &records, rate, seed, summary)?;
+    }
+    let registry_path = generator::registry_path(&output);
+    generated.registry.write(&registry_path)?;
+    log::debug!("payee registry written to {}", registry_path.display());
+    let manifest_path =
+        GenerateManifest::new(seed, &config, &generated.registry, records.len(), &output)?
+            .write()?;
+    log::debug!("generate manifest written to {}", manifest_path.display());
+    summary.counter("manifest", manifest_path.display().to_string());
 
     log::info!(
         "generated {} records for Q{} {}, seed {}, output {}",
@@ -206,73 +1002,1096 @@ fn run_generate(args: GenerateArgs) -> Result<(), String> {
         quarter,
         year,
         seed,
-        args.output.display()
+        output.display()
+    );
+    emit_info_line(&format!(
+        "Generation time: {} ms",
+        gen_elapsed.as_millis()
+    ));
+    summary.counter("seed", seed);
+    summary.counter("year", year);
+    summary.counter("quarter", quarter);
+    summary.counter("records", records.len());
+    summary.counter("payees", config.payees);
+    summary.counter("output", output.display().to_string());
+    summary.counter("generation_ms", gen_elapsed.as_millis() as u64);
+
+    analyze_generated(&output, summary)
+}
+
+// What a checkpoint must match for `--resume` to append to its CSV: the
+// resolved generator config and the options applied after generation.
+#[derive(Serialize)]
+struct GenerateFingerprint<'a> {
+    config: &'a GeneratorConfig,
+    messy: Option<&'static str>,
+    threshold_edge: bool,
+    cross_psp_duplicate_ratio: f64,
+    target_reportable: Option<usize>,
+    max_output_size: Option<u64>,
+    psp_roster_sha256: &'a str,
+}
+
+fn generate_fingerprint(
+    args: &GenerateArgs,
+    config: &GeneratorConfig,
+    roster_fingerprint: &str,
+) -> Result<String, String> {
+    let fingerprint = GenerateFingerprint {
+        config,
+        messy: args.messy.map(MessyProfile::as_str),
+        threshold_edge: args.threshold_edge,
+        cross_psp_duplicate_ratio: args.cross_psp_duplicate_ratio,
+        target_reportable: args.target_reportable,
+        max_output_size: args.max_output_size,
+        psp_roster_sha256: roster_fingerprint,
+    };
+    let json = serde_json::to_string(&fingerprint).map_err(|err| err.to_string())?;
+    Ok(util::sha256_hex(json.as_bytes()))
+}
+
+// `generate --render`: groups the generated records into CESOP reports and
+// writes the XML without an intermediate CSV, payee registry or manifest.
+fn render_generated(
+    records: Vec<PaymentRecord>,
+    output_dir: &Path,
+    summary: &mut RunSummary,
+) -> Result<(), String> {
+    let mut tracer = RecordTracer::new(&[]);
+    let reports = build_reports(
+        records,
+        &RenderOptions::default(),
+        &mut tracer,
+        &mut summary.phases,
+    )?;
+    if reports.is_empty() {
+        return Err(codes::RND_NO_REPORTS.err_summary());
+    }
+    let outputs = summary.phases.time("xml_write", || {
+        write_reports(
+            &reports,
+            output_dir,
+            OutputLayout::Flat,
+            &MessageLimits::default(),
+            1,
+            None,
+        )
+    })?;
+    emit_info_line(&format!(
+        "Rendered {} report(s) to {}",
+        outputs.len(),
+        output_dir.display()
+    ));
+    summary.counter("reports", outputs.len());
+    summary.counter("output_dir", output_dir.display().to_string());
+    for path in &outputs {
+        emit_info_line(&format!("XML output: {}", path.display()));
+    }
+    emit_phase_timings(summary);
+    Ok(())
+}
+
+// Writes the amended copy of a generated CSV and the labels naming the payees
+// that changed.
+fn write_correction_pair(
+    output: &Path,
+    records: &[PaymentRecord],
+    rate: f64,
+    seed: u64,
+    summary: &mut RunSummary,
+) -> Result<(), String> {
+    let (amended, amendments) = amend::amend_records(records, rate, seed)?;
+    let amended_path = amend::amended_path(output);
+    compression::write_csv(&amended_path, &amended)?;
+    let labels_path = amend::labels_path(output);
+    let labels = AmendmentLabels {
+        seed,
+        original: output.display().to_string(),
+        amended: amended_path.display().to_string(),
+        payees_amended: amendments.len(),
+        payees_reported: amendments
+            .iter()
+            .filter(|amendment| amendment.reported)
+            .count(),
+        amendments,
+    };
+    labels.write(&labels_path)?;
+    let name_fixes = labels
+        .amendments
+        .iter()
+        .filter(|amendment| amendment.kind == "name_fix")
+        .count();
+    emit_info_line(&format!(
+        "Corrections: {} payee(s) amended ({} reported; {} name fixes, {} account changes) -> {}, labels {}",
+        labels.payees_amended,
+        labels.payees_reported,
+        name_fixes,
+        labels.payees_amended - name_fixes,
+        amended_path.display(),
+        labels_path.display()
+    ));
+    summary.counter("corrections_payees", labels.payees_amended);
+    summary.counter("corrections_reported_payees", labels.payees_reported);
+    summary.counter("corrections_output", amended_path.display().to_string());
+    Ok(())
+}
+
+// Writes each payee's records as soon as they are generated, so memory stays
+// bounded by the largest payee. Verification runs on running totals once the
+// file is complete, and the in-memory analysis pass is skipped.
+fn run_generate_streaming(
+    args: &GenerateArgs,
+    config: &GeneratorConfig,
+    seed: u64,
+    output: &Path,
+    summary: &mut RunSummary,
+) -> Result<(), String> {
+    let mut writer = csv::Writer::from_writer(OutputFile::create(output)?);
+    let mut stats = DatasetStats::new(config)?;
+    let gen_start = Instant::now();
+    let registry = generate_streaming(config, seed, |chunk| {
+        for record in &chunk {
+            stats.observe(record);
+            writer.serialize(record).map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    })?;
+    compression::finish_csv(writer)?;
+    let gen_elapsed = gen_start.elapsed();
+    summary.phases.add("generation", gen_elapsed);
+    if args.no_verify {
+        log::warn!("dataset verification skipped (--no-verify)");
+    } else {
+        let checks = summary.phases.time("verification", || {
+            report_dataset_checks(stats.checks(config, &registry, 25), seed)
+        })?;
+        summary.counter("dataset_checks", checks);
+        emit_info_line(&format!("Dataset checks passed: {}", checks));
+    }
+    let registry_path = generator::registry_path(output);
+    registry.write(&registry_path)?;
+    log::debug!("payee registry written to {}", registry_path.display());
+    let manifest_path =
+        GenerateManifest::new(seed, config, &registry, stats.records(), output)?.write()?;
+    log::debug!("generate manifest written to {}", manifest_path.display());
+    summary.counter("manifest", manifest_path.display().to_string());
+
+    log::info!(
+        "streamed {} records for Q{} {}, seed {}, output {}",
+        stats.records(),
+        config.quarter,
+        config.year,
+        seed,
+        output.display()
+    );
+    emit_info_line(&format!(
+        "Generation time: {} ms",
+        gen_elapsed.as_millis()
+    ));
+    emit_info_line(&format!(
+        "Cross-border records: {}; payees over threshold (generated country): {}",
+        stats.cross_border(),
+        stats.payees_over(25)
+    ));
+    summary.counter("seed", seed);
+    summary.counter("year", config.year);
+    summary.counter("quarter", config.quarter);
+    summary.counter("records", stats.records());
+    summary.counter("payees", config.payees);
+    summary.counter("output", output.display().to_string());
+    summary.counter("generation_ms", gen_elapsed.as_millis() as u64);
+    summary.counter("streamed", true);
+    emit_phase_timings(summary);
+    Ok(())
+}
+
+fn build_generate_config(
+    args: &GenerateArgs,
+    scenario: Option<&Scenario>,
+    scale: usize,
+    year: i32,
+    quarter: u8,
+    summary: &mut RunSummary,
+) -> Result<GeneratorConfig, String> {
+    let derived = derive_scaled_generation(scale, args.target_reportable)?;
+    let mut config = GeneratorConfig {
+        records: derived.records,
+        payees: derived.payees,
+        micro_payees: derived.micro_payees,
+        near_threshold_payees: derived.near_threshold_payees,
+        large_payees: derived.large_payees,
+        psps: args.psps,
+        cross_border_ratio: 0.8,
+        refund_ratio: 0.02,
+        foreign_currency_ratio: args.foreign_currency_ratio,
+        payer_account_ratio: args.payer_account_ratio,
+        non_eu_payer_ratio: args.non_eu_payer_ratio,
+        local_time_ratio: args.local_time_ratio,
+        xi_payee_ratio: args.xi_payee_ratio,
+        xi_payer_ratio: args.xi_payer_ratio,
+        multi_psp_ratio: args.multi_psp_ratio,
+        country_weights: args.country_weights.clone(),
+        multi_account_ratio: args.multi_account_ratio,
+        non_eu_payee_ratio: args.non_eu_payee_ratio,
+        no_account_payee_ratio: args.no_account_payee_ratio,
+        marketplace_ratio: args.marketplace_ratio,
+        unicode_name_ratio: args.unicode_name_ratio,
+        year,
+        quarter,
+        holiday_calendars: args.holiday_calendars,
+        psp_name: None,
+        psp_countries: Vec::new(),
+        psp_roster: Vec::new(),
+        deterministic_ids: args.deterministic_ids,
+        time_profile: TimeProfile::preset(args.time_profile),
+        churn: Churn {
+            leave_ratio: args.churn_leave_ratio,
+            join_ratio: args.churn_join_ratio,
+            account_change_ratio: args.churn_account_change_ratio,
+        },
+        refund_delay_days: args.refund_delay_days,
+        partial_refund_ratio: args.partial_refund_ratio,
+        max_refunds_per_payment: args.max_refunds_per_payment,
+        perspective: args.perspective,
+        unknown_payee_psp_ratio: args.unknown_payee_psp_ratio,
+        mcc: args.mcc,
+        field_rates: args.field_rates,
+    };
+    if let (Some(scenario), Some(path)) = (scenario, &args.config) {
+        scenario
+            .apply(&mut config)
+            .map_err(|err| format!("scenario {}: {}", path.display(), err))?;
+        emit_info_line(&format!("Scenario: {}", path.display()));
+        summary.counter("scenario", path.display().to_string());
+    }
+    // Payees without an account count together under their PSP's identifier,
+    // which would add reportable payees the target cannot control.
+    if args.target_reportable.is_some() {
+        config.no_account_payee_ratio = 0.0;
+    }
+    if let Some(path) = &args.psp_roster {
+        let roster = PspRoster::from_csv(path)?;
+        config.psp_roster = roster.psps().to_vec();
+        config.psps = roster.psps().iter().filter(|psp| psp.is_eu()).count();
+        emit_info_line(&format!(
+            "PSP roster: {} ({} PSPs, licensed in {})",
+            roster.source(),
+            roster.psps().len(),
+            roster.licensed_countries().join(",")
+        ));
+        summary.counter("psp_roster", roster.source().to_string());
+    }
+    Ok(config)
+}
+
+// One dataset per quarter for the same payees, accounts and PSPs: the first
+// quarter is generated as usual, later quarters reuse its payee registry.
+fn run_generate_quarters(
+    spec: &str,
+    args: &GenerateArgs,
+    summary: &mut RunSummary,
+) -> Result<(), String> {
+    let periods = util::parse_period_range(spec)?;
+    let scenario = args.config.as_deref().map(Scenario::load).transpose()?;
+    let output = scenario
+        .as_ref()
+        .and_then(|scenario| scenario.output.clone())
+        .unwrap_or_else(|| args.output.clone());
+    let scale = scenario
+        .as_ref()
+        .and_then(|scenario| scenario.scale)
+        .unwrap_or(args.scale);
+    let seed = args
+        .seed
+        .or(scenario.as_ref().and_then(|scenario| scenario.seed))
+        .unwrap_or_else(random_seed);
+    let (first_year, first_quarter) = periods[0];
+    let mut config = build_generate_config(
+        args,
+        scenario.as_ref(),
+        scale,
+        first_year,
+        first_quarter,
+        summary,
+    )?;
+    emit_info_line(&format!(
+        "Generating {} quarter(s) from {}-Q{}: {} records per quarter across {} payees (seed {})",
+        periods.len(),
+        first_year,
+        first_quarter,
+        config.records,
+        config.payees,
+        seed
+    ));
+
+    let gen_start = Instant::now();
+    let mut combined: Vec<PaymentRecord> = Vec::new();
+    let mut registry: Option<PayeeRegistry> = None;
+    let mut outputs = Vec::new();
+    for (idx, &(year, quarter)) in periods.iter().enumerate() {
+        let quarter_seed = seed.wrapping_add(idx as u64);
+        config.year = year;
+        config.quarter = quarter;
+        let generated = match &registry {
+            None => generate_records(&config, quarter_seed)?,
+            Some(previous) => generator::generate_quarter(previous, &config, quarter_seed)?,
+        };
+        // Churn changes the roster, so later quarters are checked against it.
+        config.payees = generated.registry.payees();
+        config.records = generated.registry.records();
+        if !args.no_verify {
+            verify_dataset(&config, &generated, quarter_seed)?;
+        }
+        emit_info_line(&format!(
+            "Quarter {}-Q{}: {} records (seed {})",
+            year,
+            quarter,
+            generated.records.len(),
+            quarter_seed
+        ));
+        if registry.is_some() && config.churn.is_active() {
+            let churn = &generated.churn;
+            emit_info_line(&format!(
+                "Quarter {}-Q{} churn: {} payees left, {} joined, {} changed accounts",
+                year, quarter, churn.left, churn.joined, churn.changed_accounts
+            ));
+        }
+        let mut records = generated.records;
+        if args.split_quarters {
+            let stem = output
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| "payments".to_string());
+            let path = output.with_file_name(format!("{}-{}Q{}.csv", stem, year, quarter));
+            checkpoint::write_generated_csv(&path, &mut records, quarter_seed, "", 0, None)?;
+            generated.registry.write(&generator::registry_path(&path))?;
+            GenerateManifest::new(quarter_seed, &config, &generated.registry, records.len(), &path)?
+                .write()?;
+            outputs.push(path);
+        } else {
+            combined.append(&mut records);
+        }
+        registry = Some(generated.registry);
+    }
+    if !args.split_quarters {
+        checkpoint::write_generated_csv(&output, &mut combined, seed, "", 0, None)?;
+        // The registry of the last quarter, so `--append` extends that quarter.
+        if let Some(registry) = &registry {
+            registry.write(&generator::registry_path(&output))?;
+        }
+        outputs.push(output);
+    }
+    let gen_elapsed = gen_start.elapsed();
+    summary.phases.add("generation", gen_elapsed);
+
+    for path in &outputs {
+        emit_info_line(&format!("Wrote {}", path.display()));
+    }
+    summary.counter("seed", seed);
+    summary.counter("quarters", periods.len());
+    summary.counter("records_per_quarter", config.records);
+    summary.counter("payees", config.payees);
+    summary.counter(
+        "outputs",
+        outputs
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>(),
+    );
+    summary.counter("generation_ms", gen_elapsed.as_millis() as u64);
+    emit_phase_timings(summary);
+    Ok(())
+}
+
+// Runs the post-generation checks; returns the number of checks passed or an
+// error naming the failed ones.
+fn verify_dataset(
+    config: &GeneratorConfig,
+    generated: &GeneratedData,
+    seed: u64,
+) -> Result<usize, String> {
+    report_dataset_checks(verify_generated(config, generated, 25)?, seed)
+}
+
+fn report_dataset_checks(checks: Vec<DatasetCheck>, seed: u64) -> Result<usize, String> {
+    for check in &checks {
+        log::debug!(
+            check = check.name,
+            expected = check.expected.as_str(),
+            actual = check.actual.as_str(),
+            passed = check.passed;
+            "dataset check"
+        );
+    }
+    let failed: Vec<_> = checks.iter().filter(|check| !check.passed).collect();
+    for check in &failed {
+        log::error!(
+            "dataset check {} failed: expected {}, got {}",
+            check.name,
+            check.expected,
+            check.actual
+        );
+    }
+    if !failed.is_empty() {
+        return Err(codes::GEN_VERIFICATION_FAILED.err(format!(
+            "generated dataset failed {} of {} check(s) (seed {}): {}",
+            failed.len(),
+            checks.len(),
+            seed,
+            failed
+                .iter()
+                .map(|check| check.name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    }
+    Ok(checks.len())
+}
+
+fn run_generate_portfolio(
+    path: &Path,
+    args: &GenerateArgs,
+    summary: &mut RunSummary,
+) -> Result<(), String> {
+    let portfolio = Portfolio::load(path)?;
+    let (year, quarter) = generate_period(
+        args,
+        portfolio.period.as_deref().map(util::parse_period).transpose()?,
+    )?;
+    let portfolio_seed = args.seed.or(portfolio.seed).unwrap_or_else(random_seed);
+    emit_info_line(&format!(
+        "Portfolio {}: {} tenant(s) for Q{} {} (seed {})",
+        path.display(),
+        portfolio.tenants.len(),
+        quarter,
+        year,
+        portfolio_seed
+    ));
+
+    let mut results = Vec::with_capacity(portfolio.tenants.len());
+    let gen_start = Instant::now();
+    for (idx, tenant) in portfolio.tenants.iter().enumerate() {
+        let seed = portfolio::tenant_seed(portfolio_seed, idx, tenant);
+        let derived = derive_scaled_generation(tenant.scale, None)?;
+        let config = GeneratorConfig {
+            records: derived.records,
+            payees: derived.payees,
+            micro_payees: derived.micro_payees,
+            near_threshold_payees: derived.near_threshold_payees,
+            large_payees: derived.large_payees,
+            psps: if tenant.licensed_countries.is_empty() {
+                tenant.psps
+            } else {
+                tenant.licensed_countries.len()
+            },
+            cross_border_ratio: tenant.cross_border_ratio,
+            refund_ratio: tenant.refund_ratio,
+            foreign_currency_ratio: tenant.foreign_currency_ratio,
+            payer_account_ratio: tenant.payer_account_ratio,
+            non_eu_payer_ratio: tenant.non_eu_payer_ratio,
+            local_time_ratio: tenant.local_time_ratio,
+            xi_payee_ratio: tenant.xi_payee_ratio,
+            xi_payer_ratio: tenant.xi_payer_ratio,
+            multi_psp_ratio: tenant.multi_psp_ratio,
+            country_weights: tenant
+                .country_weights
+                .as_deref()
+                .map(CountryWeights::parse)
+                .transpose()
+                .map_err(|err| format!("tenant {}: {}", tenant.psp_name, err))?
+                .unwrap_or_default(),
+            multi_account_ratio: tenant.multi_account_ratio,
+            non_eu_payee_ratio: tenant.non_eu_payee_ratio,
+            no_account_payee_ratio: tenant.no_account_payee_ratio,
+            marketplace_ratio: tenant.marketplace_ratio,
+            unicode_name_ratio: tenant.unicode_name_ratio,
+            year,
+            quarter,
+            holiday_calendars: tenant.holiday_calendars,
+            psp_name: Some(tenant.psp_name.clone()),
+            psp_countries: tenant.licensed_countries.clone(),
+            psp_roster: Vec::new(),
+            deterministic_ids: args.deterministic_ids,
+            time_profile: TimeProfile::preset(tenant.time_profile),
+            churn: Churn::default(),
+            refund_delay_days: tenant.refund_delay_days,
+            partial_refund_ratio: tenant.partial_refund_ratio,
+            max_refunds_per_payment: tenant.max_refunds_per_payment,
+            perspective: tenant.perspective,
+            unknown_payee_psp_ratio: tenant.unknown_payee_psp_ratio,
+            mcc: tenant.mcc,
+            field_rates: tenant
+                .field_rates
+                .as_deref()
+                .map(FieldRates::parse)
+                .transpose()
+                .map_err(|err| format!("tenant {}: {}", tenant.psp_name, err))?
+                .unwrap_or_default(),
+        };
+        let context = |err: String| format!("tenant {}: {}", tenant.psp_name, err);
+        let generated = generate_records(&config, seed).map_err(|err| context(err.into()))?;
+        if !args.no_verify {
+            verify_dataset(&config, &generated, seed).map_err(context)?;
+        }
+
+        let output = portfolio.tenant_output(tenant);
+        if let Some(parent) = output.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+        let mut records = generated.records;
+        checkpoint::write_generated_csv(&output, &mut records, seed, "", 0, None)?;
+        let registry_path = generator::registry_path(&output);
+        generated.registry.write(&registry_path)?;
+        GenerateManifest::new(seed, &config, &generated.registry, records.len(), &output)?
+            .write()?;
+
+        let mut psp_ids: Vec<String> = records.iter().map(|record| record.psp_id.clone()).collect();
+        psp_ids.sort();
+        psp_ids.dedup();
+        emit_info_line(&format!(
+            "Tenant {}: {} records across {} payees, PSPs {} (seed {}) -> {}",
+            tenant.psp_name,
+            records.len(),
+            config.payees,
+            psp_ids.join(","),
+            seed,
+            output.display()
+        ));
+        results.push(TenantResult {
+            psp_name: tenant.psp_name.clone(),
+            psp_ids,
+            licensed_countries: tenant.licensed_countries.clone(),
+            seed,
+            records: records.len(),
+            payees: config.payees,
+            output: output.display().to_string(),
+            registry: registry_path.display().to_string(),
+        });
+    }
+    let gen_elapsed = gen_start.elapsed();
+    summary.phases.add("generation", gen_elapsed);
+
+    create_dir_all(&portfolio.output_dir).map_err(|err| err.to_string())?;
+    let manifest = PortfolioManifest {
+        run_id: run_summary::run_id().to_string(),
+        seed: portfolio_seed,
+        year,
+        quarter,
+        tenants: results,
+    };
+    let manifest_path = manifest.write(&portfolio.output_dir)?;
+    emit_info_line(&format!("Portfolio manifest: {}", manifest_path.display()));
+    summary.counter("portfolio", path.display().to_string());
+    summary.counter("seed", portfolio_seed);
+    summary.counter("year", year);
+    summary.counter("quarter", quarter);
+    summary.counter("tenants", manifest.tenants.len());
+    summary.counter(
+        "records",
+        manifest.tenants.iter().map(|tenant| tenant.records).sum::<usize>(),
+    );
+    summary.counter("generation_ms", gen_elapsed.as_millis() as u64);
+    emit_phase_timings(summary);
+    Ok(())
+}
+
+fn run_generate_append(args: GenerateArgs, summary: &mut RunSummary) -> Result<(), String> {
+    let registry_path = generator::registry_path(&args.output);
+    let mut registry = PayeeRegistry::load(&registry_path)?;
+    let seed = args.seed.unwrap_or_else(random_seed);
+    let existing = input::read_payment_records(&args.output, &CsvReadOptions::default())?;
+    let (year, quarter) = registry.period();
+
+    log::info!(
+        "Appending {} records for {} payees in {} (seed {})",
+        args.scale,
+        registry.payees(),
+        args.output.display(),
+        seed
     );
+    let gen_start = Instant::now();
+    let records = generator::append_records(&mut registry, &existing, args.scale, seed)?;
+    let gen_elapsed = gen_start.elapsed();
+    summary.phases.add("generation", gen_elapsed);
+    summary
+        .phases
+        .time("csv_write", || append_csv(&args.output, &records))?;
+    registry.write(&registry_path)?;
+    // The manifest describes the original run, and its digest no longer matches.
+    let manifest_path = generator::manifest_path(&args.output);
+    if manifest_path.exists() {
+        std::fs::remove_file(&manifest_path).map_err(|err| err.to_string())?;
+        log::warn!(
+            "removed {}: it no longer matches the appended CSV",
+            manifest_path.display()
+        );
+    }
+
+    emit_info_line(&format!(
+        "Appended {} records to {} ({} total, {} more allowed by payee segments)",
+        records.len(),
+        args.output.display(),
+        registry.records(),
+        registry.remaining_capacity()
+    ));
     emit_info_line(&format!(
         "Generation time: {} ms",
         gen_elapsed.as_millis()
     ));
+    summary.counter("seed", seed);
+    summary.counter("year", year);
+    summary.counter("quarter", quarter);
+    summary.counter("appended_records", records.len());
+    summary.counter("records", existing.len() + records.len());
+    summary.counter("payees", registry.payees());
+    summary.counter("output", args.output.display().to_string());
+    summary.counter("generation_ms", gen_elapsed.as_millis() as u64);
+
+    analyze_generated(&args.output, summary)
+}
+
+fn append_csv(output: &Path, records: &[PaymentRecord]) -> Result<(), String> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(OutputFile::append(output)?);
+    for record in records {
+        writer.serialize(record).map_err(|err| err.to_string())?;
+    }
+    compression::finish_csv(writer)
+}
 
+fn analyze_generated(output: &Path, summary: &mut RunSummary) -> Result<(), String> {
     let analysis_start = Instant::now();
-    let report = analyze_threshold_csv(&args.output, 25, false)?;
+    let report = analyze_threshold_csv(output, &AnalyzeOptions::default(), &mut summary.phases)?;
     let analysis_elapsed = analysis_start.elapsed();
     log_threshold_report(&report);
     emit_info_line(&format!(
         "Analysis time: {} ms",
         analysis_elapsed.as_millis()
     ));
+    record_threshold_report(summary, &report);
+    summary.counter("analysis_ms", analysis_elapsed.as_millis() as u64);
+    emit_phase_timings(summary);
 
     Ok(())
 }
 
-fn run_analyze(args: AnalyzeArgs) -> Result<(), String> {
+fn run_analyze(args: AnalyzeArgs, summary: &mut RunSummary) -> Result<(), String> {
+    let rates = match &args.rates {
+        Some(path) => Some(RateTable::from_csv(path)?),
+        None => None,
+    };
     let analysis_start = Instant::now();
-    let report = analyze_threshold_csv(&args.input, args.threshold, args.include_refunds)?;
+    let options = AnalyzeOptions {
+        threshold: args.threshold,
+        include_refunds: args.include_refunds,
+        rates: rates.as_ref(),
+        identity_collisions: args.identity_collisions,
+        proximity: args.proximity,
+        csv: args.csv.options(),
+    };
+    let report = analyze_threshold_csv(&args.input, &options, &mut summary.phases)?;
     let analysis_elapsed = analysis_start.elapsed();
     log_threshold_report(&report);
     emit_info_line(&format!(
         "Analysis time: {} ms",
         analysis_elapsed.as_millis()
     ));
+    record_threshold_report(summary, &report);
+    if let Some(totals) = &report.eur_totals {
+        log_eur_totals(totals, args.top_payees);
+        summary.counter("rate_source", totals.rate_source.clone());
+        summary.counter("total_eur", totals.total_eur);
+        summary.counter("eur_unconverted_records", totals.unconverted_records);
+    }
+    if let Some(collisions) = &report.identity_collisions {
+        log_identity_collisions(collisions, args.top_payees);
+        summary.counter("shared_accounts", collisions.shared_accounts.len());
+        summary.counter("conflicting_payees", collisions.conflicting_payees.len());
+    }
+    if let Some(payees) = &report.borderline_payees {
+        log_borderline_payees(payees, report.threshold, args.top_payees);
+        record_borderline_payees(summary, payees);
+    }
+    summary.counter("analysis_ms", analysis_elapsed.as_millis() as u64);
+    emit_phase_timings(summary);
     Ok(())
 }
 
-fn run_render(args: RenderArgs) -> Result<(), String> {
-    let licensed_list = match args.licensed_countries {
+fn parse_licensed_countries(value: Option<&str>) -> Result<Option<Vec<String>>, String> {
+    match value {
         Some(value) => {
-            let parsed = parse_country_list(&value)?;
+            let parsed = parse_country_list(value)?;
             if parsed.is_empty() {
-                None
+                Ok(None)
             } else {
-                Some(parsed)
+                Ok(Some(parsed))
             }
         }
-        None => None,
+        None => Ok(None),
+    }
+}
+
+fn run_render(args: RenderArgs, summary: &mut RunSummary) -> Result<(), String> {
+    let options = RenderOptions {
+        transmitting_country: args.transmitting_country.clone(),
+        licensed_countries: parse_licensed_countries(args.licensed_countries.as_deref())?,
+        address_format: args.address_format,
+        ref_seed: args.ref_seed.clone(),
+        timestamp: util::fixed_timestamp(args.timestamp.as_deref())?,
+        xml_style: args.xml_style,
+        payment_methods: args.payment_methods,
+        schema: args.schema_version,
+        psp_name_policy: args.psp_name_policy,
+        payer_ms: args.payer_ms,
+        payee_accounts: args.payee_accounts,
+        payee_conflicts: args.payee_conflicts,
+        csv: args.csv.options(),
     };
-    let reports = build_reports_from_csv(
+    let trace_targets = args
+        .trace_records
+        .as_deref()
+        .map(parse_id_list)
+        .unwrap_or_default();
+    let mut tracer = RecordTracer::new(&trace_targets);
+    if args.quarantine.is_some() {
+        tracer = tracer.with_quarantine();
+    }
+    if args.validate && !args.schema_version.profile().bundled_xsd() {
+        return Err(codes::VM_XSD_VERSION.err(format!(
+            "--validate checks against the bundled CESOP v{} XSD, not v{}",
+            xsd::XSD_VERSION,
+            args.schema_version.profile().version()
+        )));
+    }
+    let limits = MessageLimits {
+        max_bytes: Some(args.max_size_mb * 1024 * 1024),
+        max_payees: args.max_payees.map(|max| max as usize),
+    };
+
+    if args.nil_report {
+        let periods = util::parse_period_range(args.period.as_deref().unwrap_or_default())?;
+        let countries = match args.country.as_deref() {
+            Some(value) => parse_country_list(value)?,
+            None => vec![args.transmitting_country.clone()],
+        };
+        let reports = build_nil_reports(
+            args.psp.as_deref().unwrap_or_default(),
+            args.psp_name.as_deref().unwrap_or_default(),
+            &periods,
+            &countries,
+            &options,
+        )?;
+        let outputs = summary.phases.time("xml_write", || {
+            write_reports(
+                &reports,
+                &args.output_dir,
+                args.layout,
+                &limits,
+                args.jobs as usize,
+                None,
+            )
+        })?;
+        return finish_render(&args, &outputs, 0, 0, summary);
+    }
+
+    if args.streaming {
+        let rendered = render_streamed(&args, &options, &limits, &mut tracer, summary);
+        write_render_trace(&args, &tracer, trace_targets.len(), summary)?;
+        let (outputs, totals, resumed) = rendered?;
+        if let Some(path) = &args.quarantine {
+            write_render_quarantine(path, &tracer, totals.transactions, summary)?;
+        }
+        emit_render_conflicts(&args, &totals, summary);
+        return finish_render(&args, &outputs, totals.payees, resumed, summary);
+    }
+
+    let build_result =
+        build_reports_from_csv(&args.input, &options, &mut tracer, &mut summary.phases);
+    write_render_trace(&args, &tracer, trace_targets.len(), summary)?;
+    let reports = build_result?;
+    if let Some(path) = &args.quarantine {
+        let reported: usize = reports
+            .iter()
+            .flat_map(|report| &report.payees)
+            .map(|payee| payee.transactions.len())
+            .sum();
+        write_render_quarantine(path, &tracer, reported, summary)?;
+    }
+    if reports.is_empty() {
+        return Err(codes::RND_NO_REPORTS.err_summary());
+    }
+
+    let mut progress =
+        RenderProgress::start(&args.output_dir, &args.input, reports.len(), args.resume)?;
+    let resumed = progress.completed_count();
+    let outputs = summary.phases.time("xml_write", || {
+        write_reports(
+            &reports,
+            &args.output_dir,
+            args.layout,
+            &limits,
+            args.jobs as usize,
+            Some(&mut progress),
+        )
+    })?;
+    progress.finish()?;
+    let totals = render_totals(&reports);
+    emit_render_conflicts(&args, &totals, summary);
+    finish_render(&args, &outputs, totals.payees, resumed, summary)
+}
+
+// Input conflicts render resolved on its own, each also logged with its code:
+// PSP-periods whose PSP ID came with more than one name (RND104), payees
+// whose account identifiers contradict their `payee_country` (RND105) and
+// payees whose rows disagree on an attribute (RND106).
+fn emit_render_conflicts(args: &RenderArgs, totals: &RenderTotals, summary: &mut RunSummary) {
+    if totals.psp_name_conflicts > 0 {
+        let policy = args
+            .psp_name_policy
+            .to_possible_value()
+            .map(|value| value.get_name().to_string());
+        emit_info_line(&format!(
+            "PSP name conflicts resolved by --psp-name-policy {}: {} PSP-period(s)",
+            policy.unwrap_or_default(),
+            totals.psp_name_conflicts
+        ));
+        summary.counter("psp_name_conflicts", totals.psp_name_conflicts);
+    }
+    if totals.account_country_mismatches > 0 {
+        emit_info_line(&format!(
+            "Account CountryCode taken from the identifier, not payee_country: {} payee(s)",
+            totals.account_country_mismatches
+        ));
+        summary.counter("account_country_mismatches", totals.account_country_mismatches);
+    }
+    if !totals.payee_conflicts.is_empty() {
+        let policy = args
+            .payee_conflicts
+            .to_possible_value()
+            .map(|value| value.get_name().to_string());
+        let columns = totals
+            .payee_conflicts
+            .iter()
+            .map(|(column, count)| format!("{}={}", column, count))
+            .collect::<Vec<_>>()
+            .join(" ");
+        emit_info_line(&format!(
+            "Payee attribute conflicts resolved by --payee-conflicts {}: {}",
+            policy.unwrap_or_default(),
+            columns
+        ));
+        summary.counter("payee_conflicts", serde_json::json!(totals.payee_conflicts));
+    }
+}
+
+// `render --streaming`: spills the input by period and reporting PSP, then
+// builds and writes the reports of one PSP-period at a time.
+fn render_streamed(
+    args: &RenderArgs,
+    options: &RenderOptions,
+    limits: &MessageLimits,
+    tracer: &mut RecordTracer,
+    summary: &mut RunSummary,
+) -> Result<(Vec<PathBuf>, RenderTotals, usize), String> {
+    let spilled = spill_reports_from_csv(
         &args.input,
-        &args.transmitting_country,
-        licensed_list.as_deref(),
+        options,
+        &args.output_dir,
+        tracer,
+        &mut summary.phases,
     )?;
-    if reports.is_empty() {
-        return Err("no reports generated (no cross-border data)".to_string());
+    let countries = options
+        .licensed_countries
+        .as_ref()
+        .map_or(1, |licensed| licensed.len().max(1));
+    let mut progress = RenderProgress::start(
+        &args.output_dir,
+        &args.input,
+        spilled.len() * countries,
+        args.resume,
+    )?;
+    let resumed = progress.completed_count();
+    let mut writer = ReportWriter::new(
+        &args.output_dir,
+        args.layout,
+        limits,
+        args.jobs as usize,
+        Some(&mut progress),
+    )?;
+    let totals = spilled.write(options, &mut writer, tracer, &mut summary.phases)?;
+    let outputs = writer.finish()?;
+    progress.finish()?;
+    Ok((outputs, totals, resumed))
+}
+
+fn write_render_trace(
+    args: &RenderArgs,
+    tracer: &RecordTracer,
+    targets: usize,
+    summary: &mut RunSummary,
+) -> Result<(), String> {
+    if !tracer.is_enabled() {
+        return Ok(());
+    }
+    let trace_path = args
+        .trace_output
+        .clone()
+        .unwrap_or_else(|| args.output_dir.join("trace.jsonl"));
+    tracer.write_jsonl(&trace_path)?;
+    emit_info_line(&format!(
+        "Trace: {} decision(s) for {} target(s) written to {}",
+        tracer.events().len(),
+        targets,
+        trace_path.display()
+    ));
+    summary.counter("trace_events", tracer.events().len());
+    Ok(())
+}
+
+fn write_render_quarantine(
+    path: &Path,
+    tracer: &RecordTracer,
+    reported: usize,
+    summary: &mut RunSummary,
+) -> Result<(), String> {
+    let quarantined = tracer.write_quarantine_csv(path)?;
+    let counts = tracer.quarantine_counts();
+    emit_info_line(&format!(
+        "Quarantine: {} excluded record(s) written to {} ({}); {} record(s) reported",
+        quarantined,
+        path.display(),
+        counts
+            .iter()
+            .map(|(stage, count)| format!("{}={}", stage, count))
+            .collect::<Vec<_>>()
+            .join(", "),
+        reported
+    ));
+    summary.counter("quarantined_records", quarantined);
+    summary.counter("reported_records", reported);
+    for (stage, count) in counts {
+        summary.counter(&format!("quarantined_{}", stage), count);
     }
+    Ok(())
+}
 
-    let outputs = write_reports(&reports, &args.output_dir)?;
+fn finish_render(
+    args: &RenderArgs,
+    outputs: &[PathBuf],
+    payees: usize,
+    resumed: usize,
+    summary: &mut RunSummary,
+) -> Result<(), String> {
+    if resumed > 0 {
+        emit_info_line(&format!(
+            "Resumed from checkpoint: {} report(s) already completed",
+            resumed
+        ));
+        summary.counter("resumed_reports", resumed);
+    }
     emit_info_line(&format!(
         "Rendered {} report(s) to {}",
         outputs.len(),
         args.output_dir.display()
     ));
+    summary.counter("reports", outputs.len());
+    summary.counter("payees", payees);
+    summary.counter("output_dir", args.output_dir.display().to_string());
     for path in outputs {
         emit_info_line(&format!("XML output: {}", path.display()));
     }
+    summary.counter(
+        "outputs",
+        outputs
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>(),
+    );
+    if args.validate {
+        check_xsd(outputs, None, summary)?;
+    }
+    if args.package {
+        let registry = refids::RefIdRegistry::load(&refids::registry_path(&args.output_dir))?;
+        let packages = summary.phases.time("package", || {
+            package::package_messages(&registry, &args.output_dir)
+        })?;
+        for path in &packages {
+            emit_info_line(&format!("Package: {}", path.display()));
+        }
+        summary.counter("packages", packages.len());
+    }
+    emit_phase_timings(summary);
     Ok(())
 }
 
-fn run_correct(args: CorrectArgs) -> Result<(), String> {
+// Validates `files` against the bundled XSDs. Up to 20 violations per file are
+// printed; `output` receives all of them, one `file: path: message` per line.
+fn check_xsd(
+    files: &[PathBuf],
+    output: Option<&Path>,
+    summary: &mut RunSummary,
+) -> Result<(), String> {
+    let results = summary
+        .phases
+        .time("xsd_validate", || xsd::validate_files(files))?;
+    let mut lines = Vec::new();
+    let mut invalid_files = 0;
+    for (path, violations) in &results {
+        if violations.is_empty() {
+            continue;
+        }
+        invalid_files += 1;
+        emit_info_line(&format!(
+            "XSD: {} violation(s) in {}",
+            violations.len(),
+            path.display()
+        ));
+        for violation in violations.iter().take(20) {
+            emit_info_line(&format!("  {}: {}", violation.path, violation.message));
+        }
+        lines.extend(violations.iter().map(|violation| {
+            format!("{}: {}: {}", path.display(), violation.path, violation.message)
+        }));
+    }
+    if let Some(output) = output {
+        if let Some(parent) = output.parent() {
+            create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+        let mut contents = lines.join("\n");
+        if !contents.is_empty() {
+            contents.push('\n');
+        }
+        std::fs::write(output, contents).map_err(|err| err.to_string())?;
+        emit_info_line(&format!("XSD violations written to {}", output.display()));
+    }
+    summary.counter("xsd_files", results.len());
+    summary.counter("xsd_invalid_files", invalid_files);
+    summary.counter("xsd_violations", lines.len());
+    if invalid_files > 0 {
+        return Err(codes::VM_XSD_INVALID.err(format!(
+            "{} of {} file(s) do not conform to the CESOP v{} XSD",
+            invalid_files,
+            results.len(),
+            xsd::XSD_VERSION
+        )));
+    }
+    emit_info_line(&format!(
+        "XSD: {} file(s) valid against the CESOP v{} XSD",
+        results.len(),
+        xsd::XSD_VERSION
+    ));
+    Ok(())
+}
+
+fn run_correct(args: CorrectArgs, summary: &mut RunSummary) -> Result<(), String> {
     let seed = args.seed.unwrap_or_else(random_seed);
-    let summary = correct::correct_csv(&args.input, &args.output, seed)?;
+    let result = correct::correct_csv(
+        &args.input,
+        &args.output,
+        seed,
+        &args.csv.options(),
+    )?;
 
     emit_info_line(&format!(
         "Correct: input={} output={} seed={}",
@@ -282,18 +2101,57 @@ fn run_correct(args: CorrectArgs) -> Result<(), String> {
     ));
     emit_info_line(&format!(
         "Corrected records: {} / {}",
-        summary.corrected_records, summary.total_records
+        result.corrected_records, result.total_records
+    ));
+    emit_info_line(&format!(
+        "Corrections: payee_name={} payee_country={} account_type={} account_value={} payer_country={} payer_source={} currency={} execution_time={}",
+        result.payee_name_fixed,
+        result.payee_country_fixed,
+        result.payee_account_type_fixed,
+        result.payee_account_value_fixed,
+        result.payer_country_fixed,
+        result.payer_source_fixed,
+        result.currency_fixed,
+        result.execution_time_fixed
     ));
+    summary.counter("seed", seed);
+    summary.counter("total_records", result.total_records);
+    summary.counter("corrected_records", result.corrected_records);
+    summary.counter("payee_name_fixed", result.payee_name_fixed);
+    summary.counter("payee_country_fixed", result.payee_country_fixed);
+    summary.counter("payee_account_type_fixed", result.payee_account_type_fixed);
+    summary.counter("payee_account_value_fixed", result.payee_account_value_fixed);
+    summary.counter("payer_country_fixed", result.payer_country_fixed);
+    summary.counter("payer_source_fixed", result.payer_source_fixed);
+    summary.counter("currency_fixed", result.currency_fixed);
+    summary.counter("execution_time_fixed", result.execution_time_fixed);
+    write_audit_entry(
+        "correct",
+        &args.input,
+        &args.output,
+        seed,
+        &result,
+        &args.audit_log,
+        args.audit_chain,
+    )?;
+    Ok(())
+}
+
+fn write_audit_entry<C: serde::Serialize>(
+    command: &str,
+    input: &Path,
+    output: &Path,
+    seed: u64,
+    counts: &C,
+    audit_log: &Path,
+    chain: bool,
+) -> Result<(), String> {
+    let entry =
+        audit::append_audit_entry(audit_log, command, input, output, seed, counts, chain)?;
     emit_info_line(&format!(
-        "Corrections: payee_name={} payee_country={} account_type={} account_value={} payer_country={} payer_source={} currency={} execution_time={}",
-        summary.payee_name_fixed,
-        summary.payee_country_fixed,
-        summary.payee_account_type_fixed,
-        summary.payee_account_value_fixed,
-        summary.payer_country_fixed,
-        summary.payer_source_fixed,
-        summary.currency_fixed,
-        summary.execution_time_fixed
+        "Audit entry appended to {} (entry_hash={})",
+        audit_log.display(),
+        entry.entry_hash
     ));
     Ok(())
 }
@@ -307,10 +2165,7 @@ fn parse_country_list(input: &str) -> Result<Vec<String>, String> {
         }
         let code = trimmed.to_uppercase();
         if code.len() != 2 || !code.chars().all(|ch| ch.is_ascii_alphabetic()) {
-            return Err(format!(
-                "invalid country code in --licensed-countries: {}",
-                trimmed
-            ));
+            return Err(codes::RND_COUNTRY_LIST.err(format!("invalid country code: {}", trimmed)));
         }
         if !countries.contains(&code) {
             countries.push(code);
@@ -319,14 +2174,51 @@ fn parse_country_list(input: &str) -> Result<Vec<String>, String> {
     Ok(countries)
 }
 
-fn run_corrupt(args: CorruptArgs) -> Result<(), String> {
+fn parse_id_list(input: &str) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::new();
+    for raw in input.split(',') {
+        let trimmed = raw.trim();
+        if !trimmed.is_empty() && !ids.iter().any(|id| id == trimmed) {
+            ids.push(trimmed.to_string());
+        }
+    }
+    ids
+}
+
+fn record_corrupt_summary(result: &CorruptSummary, summary: &mut RunSummary) {
+    emit_info_line(&format!(
+        "Corrupt payee errors: targeted={} name_missing={} country_invalid={} account_type_invalid={} account_value_invalid={}",
+        result.payees_targeted,
+        result.payee_name_missing,
+        result.payee_country_invalid,
+        result.account_type_invalid,
+        result.account_value_invalid
+    ));
+    emit_info_line(&format!(
+        "Corrupt tx errors: currency_invalid={} payer_country_invalid={} payer_source_invalid={}",
+        result.tx_currency_invalid,
+        result.tx_payer_country_invalid,
+        result.tx_payer_source_invalid
+    ));
+    summary.counter("payees_targeted", result.payees_targeted);
+    summary.counter("payee_name_missing", result.payee_name_missing);
+    summary.counter("payee_country_invalid", result.payee_country_invalid);
+    summary.counter("account_type_invalid", result.account_type_invalid);
+    summary.counter("account_value_invalid", result.account_value_invalid);
+    summary.counter("tx_currency_invalid", result.tx_currency_invalid);
+    summary.counter("tx_payer_country_invalid", result.tx_payer_country_invalid);
+    summary.counter("tx_payer_source_invalid", result.tx_payer_source_invalid);
+}
+
+fn run_corrupt(args: CorruptArgs, summary: &mut RunSummary) -> Result<(), String> {
     let seed = args.seed.unwrap_or_else(random_seed);
-    let summary = corrupt::corrupt_csv(
+    let result = corrupt::corrupt_csv(
         &args.input,
         &args.output,
         args.payee_error_rate,
         args.tx_error_rate,
         seed,
+        &args.csv.options(),
     )?;
 
     emit_info_line(&format!(
@@ -335,73 +2227,179 @@ fn run_corrupt(args: CorruptArgs) -> Result<(), String> {
         args.output.display(),
         seed
     ));
-    emit_info_line(&format!(
-        "Corrupt payee errors: targeted={} name_missing={} country_invalid={} account_type_invalid={} account_value_invalid={}",
-        summary.payees_targeted,
-        summary.payee_name_missing,
-        summary.payee_country_invalid,
-        summary.account_type_invalid,
-        summary.account_value_invalid
-    ));
-    emit_info_line(&format!(
-        "Corrupt tx errors: currency_invalid={} payer_country_invalid={} payer_source_invalid={}",
-        summary.tx_currency_invalid,
-        summary.tx_payer_country_invalid,
-        summary.tx_payer_source_invalid
-    ));
+    record_corrupt_summary(&result, summary);
+    summary.counter("seed", seed);
+    write_audit_entry(
+        "corrupt",
+        &args.input,
+        &args.output,
+        seed,
+        &result,
+        &args.audit_log,
+        args.audit_chain,
+    )?;
 
     Ok(())
 }
 
-fn run_preflight(args: PreflightArgs) -> Result<(), String> {
-    let report = preflight::preflight_csv(&args.input, args.threshold, args.include_refunds)?;
+// Exit codes of a failed preflight, so scripts can tell errors from warnings.
+// Clear of 1 (any other failure) and 2 (command-line usage errors).
+const EXIT_PREFLIGHT_ERRORS: i32 = 10;
+const EXIT_PREFLIGHT_WARNINGS: i32 = 11;
 
-    emit_info_line(&format!(
-        "Preflight (threshold >{}): records={} cross_border={} payees={} payees_over_threshold={}",
-        report.threshold,
-        report.total_records,
-        report.cross_border_records,
-        report.total_payees,
-        report.payees_over_threshold
-    ));
-    emit_info_line(&format!(
-        "Preflight issues: errors={} warnings={}",
-        report.error_count(),
-        report.warning_count()
-    ));
+fn run_preflight(args: PreflightArgs, summary: &mut RunSummary) -> Result<(), String> {
+    let rules = match args.rules.as_deref() {
+        Some(path) => PreflightRules::load(path)?,
+        None => PreflightRules::default(),
+    };
+    let options = PreflightOptions {
+        threshold: args.threshold,
+        include_refunds: args.include_refunds,
+        max_examples: args.max_examples,
+        payment_methods: args.payment_methods,
+        rules,
+        period: args.year.zip(args.quarter),
+        bic_validation: args.bic_validation,
+        proximity: args.proximity,
+        csv: args.csv.options(),
+    };
+    let report = preflight::preflight_csv(&args.input, &options, &mut summary.phases)?;
+
+    summary.counter("threshold", report.threshold);
+    summary.counter("total_records", report.total_records);
+    summary.counter("cross_border_records", report.cross_border_records);
+    summary.counter("total_payees", report.total_payees);
+    summary.counter("payees_over_threshold", report.payees_over_threshold);
+    summary.counter("periods", serde_json::json!(report.periods));
+    summary.counter("payee_conflicts", report.payee_conflict_count);
+    if let Some(payees) = &report.borderline_payees {
+        record_borderline_payees(summary, payees);
+    }
+    summary.counter("errors", report.error_count());
+    summary.counter("warnings", report.warning_count());
+    summary.counter(
+        "rules",
+        serde_json::to_value(&report.rules).map_err(|err| err.to_string())?,
+    );
 
-    emit_issue_summary("error", &report.issues, preflight::IssueLevel::Error);
-    emit_issue_summary("warning", &report.issues, preflight::IssueLevel::Warning);
+    match args.format {
+        PreflightFormat::Json => {
+            let json =
+                serde_json::to_string_pretty(&report.to_json()).map_err(|err| err.to_string())?;
+            println!("{json}");
+        }
+        PreflightFormat::Text => {
+            emit_info_line(&format!(
+                "Preflight (threshold >{}): records={} cross_border={} payees={} payees_over_threshold={}",
+                report.threshold,
+                report.total_records,
+                report.cross_border_records,
+                report.total_payees,
+                report.payees_over_threshold
+            ));
+            let periods: Vec<String> = report
+                .periods
+                .iter()
+                .map(|(period, count)| format!("{}={}", period, count))
+                .collect();
+            emit_info_line(&format!("Preflight periods: {}", periods.join(" ")));
+            emit_info_line(&format!(
+                "Preflight issues: errors={} warnings={}",
+                report.error_count(),
+                report.warning_count()
+            ));
+            emit_phase_timings(summary);
+            emit_issue_summary("error", &report.rules, preflight::IssueLevel::Error);
+            emit_issue_summary("warning", &report.rules, preflight::IssueLevel::Warning);
+            for conflict in &report.payee_conflicts {
+                emit_info_line(&format!(
+                    "Preflight payee conflict: psp {} payee {} {} {:?} (row {}) vs {:?} (row {})",
+                    conflict.psp_id,
+                    conflict.payee_id,
+                    conflict.field,
+                    logging::redact_field(conflict.field, &conflict.value),
+                    conflict.row,
+                    logging::redact_field(conflict.field, &conflict.other_value),
+                    conflict.other_row
+                ));
+            }
+            if report.payee_conflict_count > report.payee_conflicts.len() {
+                emit_info_line(&format!(
+                    "Preflight payee conflicts: {} more not shown",
+                    report.payee_conflict_count - report.payee_conflicts.len()
+                ));
+            }
+            if let Some(payees) = &report.borderline_payees {
+                log_borderline_payees(payees, report.threshold, args.max_examples);
+            }
+        }
+    }
 
-    if report.error_count() > 0 {
-        return Err(format!(
+    if report.error_count() > 0 && args.fail_on != FailOn::Never {
+        summary.fail_with_exit_code(EXIT_PREFLIGHT_ERRORS);
+        return Err(codes::PF_FAILED.err(format!(
             "preflight failed with {} error(s)",
             report.error_count()
-        ));
+        )));
+    }
+    if report.warning_count() > 0 && args.fail_on == FailOn::Warning {
+        summary.fail_with_exit_code(EXIT_PREFLIGHT_WARNINGS);
+        return Err(codes::PF_FAILED.err(format!(
+            "preflight failed with {} warning(s)",
+            report.warning_count()
+        )));
     }
 
     Ok(())
 }
 
+// `12 (payment_id) payee_country="DE"`, as much as the example has.
+fn describe_issue_example(example: &preflight::IssueExample) -> String {
+    let mut text = example.row.to_string();
+    if !example.payment_id.is_empty() {
+        text.push_str(&format!(" ({})", example.payment_id));
+    }
+    if let (Some(field), Some(value)) = (example.field, example.value.as_deref()) {
+        text.push_str(&format!(
+            " {}={:?}",
+            field,
+            logging::redact_field(field, value)
+        ));
+    }
+    text
+}
+
 fn emit_issue_summary(
     label: &str,
-    issues: &[preflight::PreflightIssue],
+    rules: &[preflight::RuleSummary],
     level: preflight::IssueLevel,
 ) {
-    let mut counts = std::collections::HashMap::new();
-    for issue in issues.iter().filter(|issue| issue.level == level) {
-        *counts.entry(issue.message.as_str()).or_insert(0usize) += 1;
-    }
-    if counts.is_empty() {
+    let mut items: Vec<&preflight::RuleSummary> =
+        rules.iter().filter(|rule| rule.level == level).collect();
+    if items.is_empty() {
         return;
     }
-
-    let mut items: Vec<(&str, usize)> = counts.into_iter().collect();
-    items.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    items.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.message.cmp(&b.message)));
 
     let max_items = 5usize;
-    for (message, count) in items.iter().take(max_items) {
-        emit_info_line(&format!("Preflight {}s: {} = {}", label, message, count));
+    for rule in items.iter().take(max_items) {
+        let rows = rule
+            .examples
+            .iter()
+            .map(describe_issue_example)
+            .collect::<Vec<_>>()
+            .join(", ");
+        if rows.is_empty() {
+            emit_info_line(&format!(
+                "Preflight {}s: [{}] {} = {}",
+                label, rule.code, rule.message, rule.count
+            ));
+        } else {
+            emit_info_line(&format!(
+                "Preflight {}s: [{}] {} = {} (e.g. rows {})",
+                label, rule.code, rule.message, rule.count, rows
+            ));
+        }
     }
     if items.len() > max_items {
         emit_info_line(&format!(
@@ -412,12 +2410,25 @@ fn emit_issue_summary(
     }
 }
 
-fn run_validate(args: ValidateArgs) -> Result<(), String> {
+fn run_validate(args: ValidateArgs, summary: &mut RunSummary) -> Result<(), String> {
+    if args.xsd {
+        let files = xsd::collect_xml_files(&args.input)?;
+        if files.is_empty() {
+            return Err(codes::VM_INPUT_NOT_FOUND.err(format!(
+                "No XML files found in {}",
+                args.input.display()
+            )));
+        }
+        summary.counter("input", args.input.display().to_string());
+        let result = check_xsd(&files, args.output.as_deref(), summary);
+        emit_phase_timings(summary);
+        return result;
+    }
     let result = match validate_with_vm(&args.java, &args.jar, &args.input) {
         Ok(result) => result,
         Err(err) => {
             emit_info_line("Validation failed");
-            return Err(err);
+            return Err(err.into());
         }
     };
 
@@ -440,16 +2451,628 @@ fn run_validate(args: ValidateArgs) -> Result<(), String> {
 
     emit_info_line("Validation successful");
     emit_info_line(&format!("Validation time: {} ms", result.duration_ms));
+    summary.counter("input", args.input.display().to_string());
+    summary.counter("validation_ms", result.duration_ms as u64);
+    summary.phases.add(
+        "vm_run",
+        std::time::Duration::from_millis(result.duration_ms as u64),
+    );
+    emit_phase_timings(summary);
+    Ok(())
+}
+
+fn run_sample(args: SampleArgs, summary: &mut RunSummary) -> Result<(), String> {
+    let seed = args.seed.unwrap_or_else(random_seed);
+    let size = match (args.payees, args.fraction) {
+        (Some(payees), _) => sample::SampleSize::Payees(payees),
+        (None, Some(fraction)) => sample::SampleSize::Fraction(fraction),
+        (None, None) => {
+            return Err(codes::GEN_SAMPLE_EMPTY.err("either --payees or --fraction is required"))
+        }
+    };
+    let result = sample::sample_csv(
+        &args.input,
+        &args.output,
+        size,
+        seed,
+        &args.csv.options(),
+    )?;
+
+    emit_info_line(&format!(
+        "Sample: input={} output={} seed={}",
+        args.input.display(),
+        args.output.display(),
+        seed
+    ));
+    emit_info_line(&format!(
+        "Sampled payees: {} / {}",
+        result.selected_payees, result.total_payees
+    ));
+    emit_info_line(&format!(
+        "Sampled records: {} / {} ({} added to keep refund pairs intact)",
+        result.sampled_records, result.total_records, result.linked_records
+    ));
+    summary.counter("seed", seed);
+    summary.counter("input", args.input.display().to_string());
+    summary.counter("output", args.output.display().to_string());
+    summary.counter("total_payees", result.total_payees);
+    summary.counter("selected_payees", result.selected_payees);
+    summary.counter("total_records", result.total_records);
+    summary.counter("sampled_records", result.sampled_records);
+    summary.counter("linked_records", result.linked_records);
+    Ok(())
+}
+
+fn run_retract(args: RetractArgs, summary: &mut RunSummary) -> Result<(), String> {
+    let mut payees: Vec<String> = args
+        .payees
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(|payee| payee.trim().to_string())
+        .filter(|payee| !payee.is_empty())
+        .collect();
+    if let Some(path) = &args.payees_file {
+        let contents = std::fs::read_to_string(path).map_err(|err| {
+            codes::IN_READ_FAILED.err(format!("failed to read {}: {}", path.display(), err))
+        })?;
+        payees.extend(
+            contents
+                .lines()
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty() && !line.starts_with('#')),
+        );
+    }
+    let periods = args
+        .periods
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .filter(|period| !period.trim().is_empty())
+        .map(util::parse_period)
+        .collect::<Result<Vec<_>, _>>()?;
+    let selection = retract::RetractSelection {
+        payees,
+        periods,
+        psp_id: args.psp.clone(),
+    };
+    let output_dir = args.output_dir.clone().unwrap_or_else(|| {
+        args.registry
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+    });
+
+    let result = retract::retract(&args.registry, &output_dir, &selection)?;
+    for payee in &result.unknown_payees {
+        log::warn!("payee {} not found in the RefId registry", payee);
+    }
+    if result.already_deleted > 0 {
+        log::warn!(
+            "{} payee document(s) were already deleted and were skipped",
+            result.already_deleted
+        );
+    }
+    for (path, payees) in &result.messages {
+        emit_info_line(&format!(
+            "Deletion message: {} ({} payee(s))",
+            path.display(),
+            payees
+        ));
+    }
+    emit_info_line(&format!(
+        "Retracted {} payee document(s) in {} message(s); registry updated: {}",
+        result.deleted_payees,
+        result.messages.len(),
+        args.registry.display()
+    ));
+    summary.counter("registry", args.registry.display().to_string());
+    summary.counter("deletion_messages", result.messages.len());
+    summary.counter("deleted_payees", result.deleted_payees);
+    summary.counter("already_deleted", result.already_deleted);
+    summary.counter("unknown_payees", result.unknown_payees.len());
+    Ok(())
+}
+
+fn run_resubmit(args: ResubmitArgs, summary: &mut RunSummary) -> Result<(), String> {
+    let options = RenderOptions {
+        transmitting_country: args.transmitting_country.clone(),
+        licensed_countries: parse_licensed_countries(args.licensed_countries.as_deref())?,
+        address_format: args.address_format,
+        ref_seed: args.ref_seed.clone(),
+        timestamp: util::fixed_timestamp(args.timestamp.as_deref())?,
+        xml_style: args.xml_style,
+        payment_methods: args.payment_methods,
+        schema: args.schema_version,
+        psp_name_policy: args.psp_name_policy,
+        payer_ms: args.payer_ms,
+        payee_accounts: args.payee_accounts,
+        payee_conflicts: args.payee_conflicts,
+        csv: args.csv.options(),
+    };
+    let output_dir = args.output_dir.clone().unwrap_or_else(|| {
+        args.registry
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+    });
+    let mut tracer = RecordTracer::new(&[]);
+    let reports = build_reports_from_csv(&args.input, &options, &mut tracer, &mut summary.phases)?;
+    let result = summary.phases.time("xml_write", || {
+        resubmit::resubmit(&reports, &args.registry, &output_dir)
+    })?;
+    for (path, message_type_indic, payees) in &result.messages {
+        emit_info_line(&format!(
+            "{} message: {} ({} payee(s))",
+            message_type_indic,
+            path.display(),
+            payees
+        ));
+    }
+    emit_info_line(&format!(
+        "Resubmitted {}: corrected={} deleted={} new={} unchanged={}; registry updated: {}",
+        args.input.display(),
+        result.corrected_payees,
+        result.deleted_payees,
+        result.new_payees,
+        result.unchanged_payees,
+        args.registry.display()
+    ));
+    summary.counter("registry", args.registry.display().to_string());
+    summary.counter("messages", result.messages.len());
+    summary.counter("corrected_payees", result.corrected_payees);
+    summary.counter("deleted_payees", result.deleted_payees);
+    summary.counter("new_payees", result.new_payees);
+    summary.counter("unchanged_payees", result.unchanged_payees);
+    emit_phase_timings(summary);
+    Ok(())
+}
+
+fn run_refids(args: RefidsArgs, summary: &mut RunSummary) -> Result<(), String> {
+    let registry = refids::RefIdRegistry::load(&args.registry)?;
+    let query = refids::RefIdQuery {
+        psp_id: args.psp.clone(),
+        period: args.period.as_deref().map(util::parse_period).transpose()?,
+        payee_id: args.payee.clone(),
+        transaction_id: args.transaction.clone(),
+    };
+    let documents = registry.lookup(&query);
+    if args.json {
+        let json = serde_json::to_string_pretty(&documents).map_err(|err| err.to_string())?;
+        println!("{json}");
+    } else {
+        for document in &documents {
+            emit_info_line(&format!(
+                "{}Q{} {} payee={} {} DocRefId={} MessageRefId={} ({}){}",
+                document.year,
+                document.quarter,
+                document.psp_id,
+                document.payee_id,
+                document.doc_type_indic,
+                document.doc_ref_id,
+                document.message_ref_id,
+                document.file,
+                document
+                    .superseded_by
+                    .as_deref()
+                    .map(|doc_ref_id| format!(" superseded by {}", doc_ref_id))
+                    .unwrap_or_default()
+            ));
+        }
+    }
+    summary.counter("registry", args.registry.display().to_string());
+    summary.counter("documents", documents.len());
+    Ok(())
+}
+
+fn run_pseudonymize(args: PseudonymizeArgs, summary: &mut RunSummary) -> Result<(), String> {
+    let key = match args.key_file.as_deref() {
+        Some(path) => std::fs::read(path)
+            .map(|bytes| String::from_utf8_lossy(&bytes).trim().as_bytes().to_vec())
+            .map_err(|err| {
+                codes::IN_READ_FAILED.err(format!(
+                    "failed to read key file {}: {}",
+                    path.display(),
+                    err
+                ))
+            })?,
+        None => std::env::var("CESOP_PSEUDONYMIZE_KEY")
+            .map(|value| value.into_bytes())
+            .map_err(|_| {
+                "a key is required: pass --key-file or set CESOP_PSEUDONYMIZE_KEY".to_string()
+            })?,
+    };
+    let result = pseudonymize::pseudonymize_csv(
+        &args.input,
+        &args.output,
+        &key,
+        &args.csv.options(),
+    )?;
+
+    emit_info_line(&format!(
+        "Pseudonymize: input={} output={}",
+        args.input.display(),
+        args.output.display()
+    ));
+    emit_info_line(&format!(
+        "Pseudonymized fields: payee_id={} name={} email={} web={} account={} tax_id={} address={} (records={})",
+        result.payee_ids,
+        result.names,
+        result.emails,
+        result.webs,
+        result.accounts,
+        result.tax_ids,
+        result.addresses,
+        result.total_records
+    ));
+    summary.counter("input", args.input.display().to_string());
+    summary.counter("output", args.output.display().to_string());
+    summary.counter("total_records", result.total_records);
+    summary.counter("payee_ids", result.payee_ids);
+    summary.counter("names", result.names);
+    summary.counter("emails", result.emails);
+    summary.counter("webs", result.webs);
+    summary.counter("accounts", result.accounts);
+    summary.counter("tax_ids", result.tax_ids);
+    summary.counter("addresses", result.addresses);
+    Ok(())
+}
+
+fn run_requarter(args: RequarterArgs, summary: &mut RunSummary) -> Result<(), String> {
+    let target = util::parse_period(&args.period)?;
+    let source = args
+        .from_period
+        .as_deref()
+        .map(util::parse_period)
+        .transpose()?;
+    let result = requarter::requarter_csv(
+        &args.input,
+        &args.output,
+        target,
+        source,
+        &args.csv.options(),
+    )?;
+
+    emit_info_line(&format!(
+        "Requarter: input={} output={} {}-Q{} -> {}-Q{} (records={})",
+        args.input.display(),
+        args.output.display(),
+        result.source.0,
+        result.source.1,
+        result.target.0,
+        result.target.1,
+        result.total_records
+    ));
+    emit_info_line(&format!(
+        "Refund links kept: {} (dangling: {})",
+        result.linked_refunds, result.dangling_refunds
+    ));
+    if let Some(path) = &result.registry {
+        emit_info_line(&format!("Payee registry written: {}", path.display()));
+    }
+    summary.counter("input", args.input.display().to_string());
+    summary.counter("output", args.output.display().to_string());
+    summary.counter("total_records", result.total_records);
+    summary.counter("source_period", format!("{}-Q{}", result.source.0, result.source.1));
+    summary.counter("target_period", format!("{}-Q{}", result.target.0, result.target.1));
+    summary.counter("linked_refunds", result.linked_refunds);
+    summary.counter("dangling_refunds", result.dangling_refunds);
+    Ok(())
+}
+
+fn run_merge(args: MergeArgs, summary: &mut RunSummary) -> Result<(), String> {
+    let result = merge::merge_reports(&args.inputs, &args.output, args.max_size_mb * 1024 * 1024)?;
+
+    emit_info_line(&format!(
+        "Merge: inputs={} payees={} (merged duplicates: {}) transactions={} (duplicates dropped: {})",
+        result.inputs,
+        result.payees,
+        result.merged_payees,
+        result.transactions,
+        result.duplicate_transactions
+    ));
+    for file in &result.outputs {
+        emit_info_line(&format!(
+            "Merged report: {} MessageRefId={} payees={} transactions={}",
+            file.path.display(),
+            file.message_ref_id,
+            file.payees,
+            file.transactions
+        ));
+    }
+    if result.outputs.len() > 1 {
+        log::warn!(
+            "merged report exceeds {} MB; split into {} parts",
+            args.max_size_mb,
+            result.outputs.len()
+        );
+    }
+    summary.counter("inputs", result.inputs);
+    summary.counter("payees", result.payees);
+    summary.counter("merged_payees", result.merged_payees);
+    summary.counter("transactions", result.transactions);
+    summary.counter("duplicate_transactions", result.duplicate_transactions);
+    summary.counter("outputs", result.outputs.len());
+    Ok(())
+}
+
+fn run_import(args: ImportArgs, summary: &mut RunSummary) -> Result<(), String> {
+    let result = import::import_xml(&args.inputs, &args.output, args.registry.as_deref())?;
+
+    let messages = result
+        .messages
+        .iter()
+        .map(|(indic, count)| format!("{}={}", indic, count))
+        .collect::<Vec<_>>()
+        .join(" ");
+    emit_info_line(&format!(
+        "Import: files={} ({}) payees={} transactions={} output={}",
+        result.files,
+        messages,
+        result.payees,
+        result.transactions,
+        args.output.display()
+    ));
+    if result.deleted_payees > 0 {
+        emit_info_line(&format!(
+            "Deleted payees skipped (CESOP3): {}",
+            result.deleted_payees
+        ));
+    }
+    for path in &result.registries {
+        emit_info_line(&format!("Payee ids from registry: {}", path.display()));
+    }
+    if result.registry_payee_ids < result.payees {
+        log::warn!(
+            "{} payee(s) not found in a RefId registry; payee_id taken from account or tax ids",
+            result.payees - result.registry_payee_ids
+        );
+    }
+    summary.counter("files", result.files);
+    summary.counter("payees", result.payees);
+    summary.counter("deleted_payees", result.deleted_payees);
+    summary.counter("transactions", result.transactions);
+    summary.counter("registry_payee_ids", result.registry_payee_ids);
+    summary.counter("output", args.output.display().to_string());
+    Ok(())
+}
+
+fn run_package(args: PackageArgs, summary: &mut RunSummary) -> Result<(), String> {
+    let registry_file = args
+        .registry
+        .clone()
+        .unwrap_or_else(|| refids::registry_path(&args.output_dir));
+    let registry = refids::RefIdRegistry::load(&registry_file)?;
+    if registry.messages.is_empty() {
+        return Err(
+            codes::RND_NO_MESSAGES.err(format!("no messages in {}", registry_file.display()))
+        );
+    }
+    let recipient = package::load_recipient_key(&args.recipient_key)?;
+    let package_dir = args
+        .package_dir
+        .clone()
+        .unwrap_or_else(|| args.output_dir.join("packages"));
+    let packages = summary.phases.time("package", || {
+        package::encrypt_messages(&registry, &args.output_dir, &package_dir, &recipient)
+    })?;
+    for package in &packages {
+        emit_info_line(&format!(
+            "Package: {} (key {})",
+            package.package.display(),
+            package.key.display()
+        ));
+    }
+    emit_info_line(&format!(
+        "Encrypted {} message(s) to {}",
+        packages.len(),
+        package_dir.display()
+    ));
+    summary.counter("packages", packages.len());
+    summary.counter("package_dir", package_dir.display().to_string());
+    emit_phase_timings(summary);
+    Ok(())
+}
+
+fn run_xml_diff(args: XmlDiffArgs, summary: &mut RunSummary) -> Result<(), String> {
+    let diff = xml_diff::diff_messages(&args.left, &args.right)?;
+    let describe = |payee: &xml_diff::PayeeSummary| {
+        format!(
+            "{} ({}, {})",
+            payee.name,
+            payee.country,
+            if payee.accounts.is_empty() {
+                "no account".to_string()
+            } else {
+                payee.accounts.join(", ")
+            }
+        )
+    };
+    let change = |change: &xml_diff::FieldChange| {
+        format!("{} '{}' -> '{}'", change.field, change.left, change.right)
+    };
+
+    if args.json {
+        let json = serde_json::to_string_pretty(&diff).map_err(|err| err.to_string())?;
+        println!("{json}");
+    } else {
+        for header in &diff.header {
+            emit_info_line(&format!("Message: {}", change(header)));
+        }
+        for payee in &diff.removed_payees {
+            emit_info_line(&format!(
+                "Payee removed: {} transactions={}",
+                describe(payee),
+                payee.transactions
+            ));
+        }
+        for payee in &diff.added_payees {
+            emit_info_line(&format!(
+                "Payee added: {} transactions={}",
+                describe(payee),
+                payee.transactions
+            ));
+        }
+        for payee in &diff.changed_payees {
+            emit_info_line(&format!(
+                "Payee changed: {}: transactions added={} removed={} changed={}",
+                describe(&payee.payee),
+                payee.added_transactions.len(),
+                payee.removed_transactions.len(),
+                payee.changed_transactions.len()
+            ));
+            for field in &payee.fields {
+                emit_info_line(&format!("  {}", change(field)));
+            }
+            for id in &payee.removed_transactions {
+                emit_info_line(&format!("  Transaction removed: {}", id));
+            }
+            for id in &payee.added_transactions {
+                emit_info_line(&format!("  Transaction added: {}", id));
+            }
+            for tx in &payee.changed_transactions {
+                let fields: Vec<String> = tx.fields.iter().map(change).collect();
+                emit_info_line(&format!(
+                    "  Transaction changed: {}: {}",
+                    tx.transaction_id,
+                    fields.join("; ")
+                ));
+            }
+        }
+        emit_info_line(&format!(
+            "XML diff: payees added={} removed={} changed={} unchanged={}{}",
+            diff.added_payees.len(),
+            diff.removed_payees.len(),
+            diff.changed_payees.len(),
+            diff.unchanged_payees,
+            if diff.is_empty() { " (identical)" } else { "" }
+        ));
+    }
+    summary.counter("header_changes", diff.header.len());
+    summary.counter("added_payees", diff.added_payees.len());
+    summary.counter("removed_payees", diff.removed_payees.len());
+    summary.counter("changed_payees", diff.changed_payees.len());
+    summary.counter("unchanged_payees", diff.unchanged_payees);
+    if args.check && !diff.is_empty() {
+        return Err(codes::VM_OUTPUT_DIFFERS.err(format!(
+            "{} and {} differ",
+            args.left.display(),
+            args.right.display()
+        )));
+    }
+    Ok(())
+}
+
+fn run_codes(args: CodesArgs, summary: &mut RunSummary) -> Result<(), String> {
+    let entries: Vec<&codes::Code> = codes::CATALOG
+        .iter()
+        .filter(|code| args.category.is_none_or(|category| code.category == category))
+        .collect();
+    if args.json {
+        let json = serde_json::to_string_pretty(&entries).map_err(|err| err.to_string())?;
+        println!("{json}");
+    } else {
+        for code in &entries {
+            emit_info_line(&format!(
+                "{:<7} {:<9} {:<7} {}",
+                code.code,
+                code.category.as_str(),
+                code.level.as_str(),
+                code.summary
+            ));
+        }
+    }
+    summary.counter("codes", entries.len());
     Ok(())
 }
 
+fn run_snapshot(args: SnapshotArgs, summary: &mut RunSummary) -> Result<(), String> {
+    match args.action {
+        SnapshotAction::Record(args) => {
+            let licensed_countries = match args.licensed_countries.as_deref() {
+                Some(value) => Some(parse_country_list(value)?).filter(|list| !list.is_empty()),
+                None => None,
+            };
+            let scenario = SnapshotScenario {
+                name: args.name,
+                input: args.input,
+                threshold: args.threshold,
+                include_refunds: args.include_refunds,
+                transmitting_country: args.transmitting_country,
+                licensed_countries,
+            };
+            let written = snapshot::record_snapshot(&args.dir, &scenario)?;
+            emit_info_line(&format!(
+                "Snapshot '{}' recorded: {} file(s) in {}",
+                scenario.name,
+                written.len(),
+                args.dir.join(&scenario.name).display()
+            ));
+            summary.counter("action", "record");
+            summary.counter("scenario", scenario.name.as_str());
+            summary.counter("files", written.len());
+            Ok(())
+        }
+        SnapshotAction::Check(args) => {
+            let names = match args.name {
+                Some(name) => vec![name],
+                None => snapshot::list_snapshots(&args.dir)?,
+            };
+            if names.is_empty() {
+                return Err(codes::VM_INPUT_NOT_FOUND
+                    .err(format!("no snapshots found in {}", args.dir.display())));
+            }
+            let mut failed = Vec::new();
+            for name in &names {
+                let diffs = snapshot::check_snapshot(&args.dir, name)?;
+                if diffs.is_empty() {
+                    emit_info_line(&format!("Snapshot '{}': ok", name));
+                    continue;
+                }
+                emit_info_line(&format!(
+                    "Snapshot '{}': {} difference(s)",
+                    name,
+                    diffs.len()
+                ));
+                for diff in &diffs {
+                    emit_info_line(&format!("  {}", diff.describe()));
+                }
+                failed.push(name.clone());
+            }
+            summary.counter("action", "check");
+            summary.counter("scenarios", names.len());
+            summary.counter("failed", failed.clone());
+            if failed.is_empty() {
+                Ok(())
+            } else {
+                Err(codes::VM_OUTPUT_DIFFERS.err(format!(
+                    "{} of {} snapshot(s) differ: {}",
+                    failed.len(),
+                    names.len(),
+                    failed.join(", ")
+                )))
+            }
+        }
+    }
+}
+
+// `--year`/`--quarter` win over a period from a scenario or portfolio file,
+// which wins over the current quarter.
+fn generate_period(args: &GenerateArgs, file: Option<(i32, u8)>) -> Result<(i32, u8), String> {
+    let (year, quarter) = match file {
+        Some(period) => period,
+        None => resolve_year_quarter()?,
+    };
+    Ok((args.year.unwrap_or(year), args.quarter.unwrap_or(quarter)))
+}
+
 fn resolve_year_quarter() -> Result<(i32, u8), String> {
     let now = chrono::Utc::now();
     let current_quarter = ((now.month() - 1) / 3 + 1) as u8;
     let year = now.year();
     let quarter = current_quarter;
     if !(1..=4).contains(&quarter) {
-        return Err("quarter must be 1..4".to_string());
+        return Err(codes::GEN_INVALID_QUARTER.err_summary());
     }
     Ok((year, quarter))
 }
@@ -459,12 +3082,34 @@ fn random_seed() -> u64 {
     rng.gen()
 }
 
-fn write_csv(output: &Path, records: &[PaymentRecord]) -> Result<(), String> {
-    let mut writer = csv::Writer::from_path(output).map_err(|err| err.to_string())?;
-    for record in records {
+// Records generated to measure the average CSV row for `--max-output-size`.
+const OUTPUT_SIZE_SAMPLE_RECORDS: usize = 2000;
+
+// Records that fit `max_bytes` of CSV, from the average row of a sample
+// generated with the same options and seed. Row lengths vary, so 2% of the
+// budget is kept as headroom.
+fn records_for_output_size(
+    config: &GeneratorConfig,
+    seed: u64,
+    max_bytes: u64,
+) -> Result<usize, String> {
+    let sample = generate_records(config, seed)?;
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for record in &sample.records {
         writer.serialize(record).map_err(|err| err.to_string())?;
     }
-    writer.flush().map_err(|err| err.to_string())
+    let bytes = writer.into_inner().map_err(|err| err.to_string())?;
+    let header = bytes.iter().position(|byte| *byte == b'\n').map_or(0, |pos| pos + 1);
+    let row = (bytes.len() - header) as f64 / sample.records.len().max(1) as f64;
+    let budget = max_bytes.saturating_sub(header as u64) as f64 * 0.98;
+    let records = (budget / row).floor() as usize;
+    if records == 0 {
+        return Err(codes::GEN_RECORDS_OUT_OF_RANGE.err(format!(
+            "--max-output-size {} bytes does not fit a header and one row (~{:.0} bytes)",
+            max_bytes, row
+        )));
+    }
+    Ok(records)
 }
 
 #[derive(Debug, Clone)]
@@ -476,16 +3121,43 @@ struct DerivedGeneration {
     large_payees: usize,
 }
 
-fn derive_scaled_generation(records: usize) -> Result<DerivedGeneration, String> {
+// With a reportable target, exactly that many large payees and no
+// near-threshold ones (non-EU payees just above 25 would always report), and
+// at least 400 records per reportable payee.
+fn derive_scaled_generation(
+    records: usize,
+    target_reportable: Option<usize>,
+) -> Result<DerivedGeneration, String> {
     // Aim for ~24 transactions per payee to keep most payees below threshold.
     const TARGET_AVG: f64 = 24.0;
+    const RECORDS_PER_REPORTABLE: usize = 400;
+    let min_records = target_reportable.unwrap_or(0) * RECORDS_PER_REPORTABLE;
+    if records < min_records {
+        log::warn!(
+            "--scale {} is too small for {} reportable payees; using {} records",
+            records,
+            target_reportable.unwrap_or(0),
+            min_records
+        );
+    }
+    let records = records.max(min_records);
     let mut payees = ((records as f64) / TARGET_AVG).ceil() as usize;
     if payees == 0 {
         payees = 1;
     }
 
     for _ in 0..10_000 {
-        let (micro, near, large) = ratio_counts(payees, records);
+        let (micro, near, large) = match target_reportable {
+            Some(target) => {
+                let micro = ((payees as f64) * 0.25).round() as usize;
+                (micro.min(payees.saturating_sub(target)), 0, target)
+            }
+            None => ratio_counts(payees, records),
+        };
+        if micro + near + large > payees {
+            payees += 1;
+            continue;
+        }
         let (min_total, max_total) = record_bounds(payees, micro, near, large);
 
         if records < min_total {
@@ -509,7 +3181,7 @@ fn derive_scaled_generation(records: usize) -> Result<DerivedGeneration, String>
         });
     }
 
-    Err("could not derive a valid payee mix for the requested scale".to_string())
+    Err(codes::GEN_NO_PAYEE_MIX.err_summary())
 }
 
 fn ratio_counts(payees: usize, records: usize) -> (usize, usize, usize) {
@@ -551,7 +3223,7 @@ fn record_bounds(
     let near_below = near / 2;
     let near_above = near - near_below;
 
-    let min_total = micro * 1
+    let min_total = micro
         + small * 6
         + mid * 16
         + near_below * 24
@@ -578,6 +3250,106 @@ fn log_threshold_report(report: &ThresholdReport) {
     ));
 }
 
+fn log_eur_totals(totals: &EurTotals, top_payees: usize) {
+    emit_info_line(&format!(
+        "EUR totals (rates: {}) cross-border={:.2} EUR converted={} unconverted={}",
+        totals.rate_source, totals.total_eur, totals.converted_records, totals.unconverted_records
+    ));
+    if !totals.unconverted_currencies.is_empty() {
+        log::warn!(
+            "no EUR rate for currencies: {}",
+            totals.unconverted_currencies.join(", ")
+        );
+    }
+    for (country, total) in &totals.by_payee_ms {
+        emit_info_line(&format!("  MS {}: {:.2} EUR", country, total));
+    }
+    for payee in totals.by_payee.iter().take(top_payees) {
+        emit_info_line(&format!(
+            "  payee {} ({}, {}) records={} total={:.2} EUR",
+            payee.payee_id, payee.psp_id, payee.payee_country, payee.records, payee.total_eur
+        ));
+    }
+}
+
+fn log_identity_collisions(collisions: &IdentityCollisions, top: usize) {
+    emit_info_line(&format!(
+        "Identity collisions: shared_accounts={} conflicting_payees={}",
+        collisions.shared_accounts.len(),
+        collisions.conflicting_payees.len()
+    ));
+    for shared in collisions.shared_accounts.iter().take(top) {
+        log::warn!(
+            "account {} ({}) is used by payees {} (PSPs {}, records={})",
+            shared.account,
+            shared.account_type,
+            shared.payee_ids.join(", "),
+            shared.psp_ids.join(", "),
+            shared.records
+        );
+    }
+    for payee in collisions.conflicting_payees.iter().take(top) {
+        log::warn!(
+            "payee {} has conflicting names [{}] / countries [{}] (records={})",
+            payee.payee_id,
+            payee.names.join(" | "),
+            payee.countries.join(", "),
+            payee.records
+        );
+    }
+}
+
+fn log_borderline_payees(payees: &[BorderlinePayee], threshold: usize, top: usize) {
+    emit_info_line(&format!(
+        "Threshold proximity (>{}): payees={} refunds_change_outcome={}",
+        threshold,
+        payees.len(),
+        payees.iter().filter(|payee| payee.refunds_change_outcome).count()
+    ));
+    for payee in payees.iter().take(top) {
+        emit_info_line(&format!(
+            "  payee {} ({}, {}) count={} reportable={} without_refunds={} with_refunds={}{}",
+            payee.payee_id,
+            payee.psp_id,
+            payee.payee_country,
+            payee.count,
+            payee.reportable,
+            payee.count_without_refunds,
+            payee.count_with_refunds,
+            if payee.refunds_change_outcome {
+                " (refunds change the outcome)"
+            } else {
+                ""
+            }
+        ));
+    }
+    if payees.len() > top {
+        emit_info_line(&format!("  {} more not shown", payees.len() - top));
+    }
+}
+
+fn record_borderline_payees(summary: &mut RunSummary, payees: &[BorderlinePayee]) {
+    summary.counter("borderline_payees", payees.len());
+    summary.counter(
+        "borderline_refunds_change_outcome",
+        payees.iter().filter(|payee| payee.refunds_change_outcome).count(),
+    );
+}
+
+fn record_threshold_report(summary: &mut RunSummary, report: &ThresholdReport) {
+    summary.counter("threshold", report.threshold);
+    summary.counter("total_records", report.total_records);
+    summary.counter("cross_border_records", report.cross_border_records);
+    summary.counter("total_payees", report.total_payees);
+    summary.counter("payees_over_threshold", report.payees_over_threshold);
+}
+
+fn emit_phase_timings(summary: &RunSummary) {
+    if !summary.phases.is_empty() {
+        emit_info_line(&format!("Phase timings: {}", summary.phases.describe()));
+    }
+}
+
 fn emit_info_line(message: &str) {
     if log::log_enabled!(log::Level::Info) {
         log::info!("{}", message);
@@ -0,0 +1,59 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub const PSP_MANIFEST_FILE: &str = "manifest.json";
+pub const RENDER_MANIFEST_FILE: &str = "render_manifest.json";
+
+// Per-PSP deliverable listing written next to the PSP's reports.
+#[derive(Debug, Clone, Serialize)]
+pub struct PspManifest {
+    pub run_id: String,
+    pub psp_id: String,
+    pub psp_name: String,
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestFile {
+    pub file: String,
+    pub period: String,
+    pub transmitting_country: String,
+    pub message_type_indic: String,
+    pub message_ref_id: String,
+    pub payees: usize,
+    pub transactions: usize,
+    pub sha256: String,
+}
+
+// Every file of a render, for automation deciding what to submit.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RenderManifest {
+    pub run_id: String,
+    pub files: Vec<RenderManifestFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderManifestFile {
+    pub psp_id: String,
+    pub psp_name: String,
+    // `file` is relative to the output directory.
+    #[serde(flatten)]
+    pub message: ManifestFile,
+    // Sum of absolute transaction amounts per currency, refunds included.
+    pub total_abs_amounts: BTreeMap<String, String>,
+}
+
+impl RenderManifest {
+    pub fn write(&self, dir: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        std::fs::write(dir.join(RENDER_MANIFEST_FILE), json).map_err(|err| err.to_string())
+    }
+}
+
+impl PspManifest {
+    pub fn write(&self, dir: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        std::fs::write(dir.join(PSP_MANIFEST_FILE), json).map_err(|err| err.to_string())
+    }
+}
@@ -0,0 +1,359 @@
+use crate::codes;
+use crate::xml_tree::{parse_file, XmlNode};
+use chrono::{SecondsFormat, Utc};
+use quick_xml::Writer;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+// CESOP payment data messages must stay below 1 GB uncompressed; some Member
+// States set lower limits.
+pub const DEFAULT_MAX_SIZE_MB: u64 = 1024;
+
+#[derive(Debug, Clone, Default)]
+pub struct MergeSummary {
+    pub inputs: usize,
+    pub payees: usize,
+    pub merged_payees: usize,
+    pub transactions: usize,
+    pub duplicate_transactions: usize,
+    pub outputs: Vec<MergedFile>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MergedFile {
+    pub path: PathBuf,
+    pub message_ref_id: String,
+    pub payees: usize,
+    pub transactions: usize,
+}
+
+// A reported payee split into the elements before and after its transactions,
+// so the transactions of duplicates can be appended in schema order.
+struct MergedPayee {
+    head: Vec<XmlNode>,
+    transactions: Vec<XmlNode>,
+    tail: Vec<XmlNode>,
+    transaction_ids: HashSet<String>,
+}
+
+pub fn merge_reports(
+    inputs: &[PathBuf],
+    output: &Path,
+    max_size_bytes: u64,
+) -> Result<MergeSummary, String> {
+    if inputs.len() < 2 {
+        return Err(codes::RND_MERGE_MISMATCH.err("merge needs at least two input reports"));
+    }
+    let mut summary = MergeSummary {
+        inputs: inputs.len(),
+        ..MergeSummary::default()
+    };
+    let mut base: Option<(XmlNode, MessageKey)> = None;
+    let mut payees: Vec<MergedPayee> = Vec::new();
+    let mut payee_index: HashMap<String, usize> = HashMap::new();
+
+    for path in inputs {
+        let document = parse_file(path)?;
+        let key = message_key(&document).map_err(|err| {
+            codes::RND_MESSAGE_UNREADABLE.err(format!("{}: {}", path.display(), err))
+        })?;
+        match &base {
+            Some((_, expected)) if *expected != key => {
+                return Err(codes::RND_MERGE_MISMATCH.err(format!(
+                    "{} does not match the first report: expected {}, found {}",
+                    path.display(),
+                    expected,
+                    key
+                )));
+            }
+            Some(_) => {}
+            None => base = Some((document.clone(), key)),
+        }
+
+        let Some(body) = document.child("PaymentDataBody") else {
+            continue;
+        };
+        for payee in body.children_named("ReportedPayee") {
+            let identity = payee_identity(payee);
+            let Some(&idx) = payee_index.get(&identity) else {
+                payee_index.insert(identity, payees.len());
+                payees.push(split_payee(payee));
+                continue;
+            };
+            summary.merged_payees += 1;
+            let merged = &mut payees[idx];
+            for tx in payee.children_named("ReportedTransaction") {
+                if merged.transaction_ids.insert(transaction_id(tx)) {
+                    merged.transactions.push(tx.clone());
+                } else {
+                    summary.duplicate_transactions += 1;
+                }
+            }
+        }
+    }
+
+    let (document, key) = base.ok_or_else(|| "no input reports".to_string())?;
+    summary.payees = payees.len();
+    summary.transactions = payees.iter().map(|payee| payee.transactions.len()).sum();
+
+    let parts = pack_parts(&document, &payees, max_size_bytes)?;
+    let total = parts.len();
+    for (idx, part) in parts.into_iter().enumerate() {
+        let path = if total == 1 {
+            output.to_path_buf()
+        } else {
+            part_path(output, idx + 1, total)
+        };
+        let merged = build_message(&document, &key, &part)?;
+        let message_ref_id = merged
+            .path_text(&["MessageSpec", "MessageRefId"])
+            .unwrap_or_default()
+            .to_string();
+        write_document(&merged, &path)?;
+        summary.outputs.push(MergedFile {
+            path,
+            message_ref_id,
+            payees: part.len(),
+            transactions: part
+                .iter()
+                .map(|payee| payee.children_named("ReportedTransaction").count())
+                .sum(),
+        });
+    }
+    Ok(summary)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MessageKey {
+    psp_id: String,
+    transmitting_country: String,
+    year: String,
+    quarter: String,
+}
+
+impl std::fmt::Display for MessageKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "PSP {} / {} / {}-Q{}",
+            self.psp_id, self.transmitting_country, self.year, self.quarter
+        )
+    }
+}
+
+fn message_key(document: &XmlNode) -> Result<MessageKey, String> {
+    if document.name != "CESOP" {
+        return Err("not a CESOP document".to_string());
+    }
+    let spec = document
+        .child("MessageSpec")
+        .ok_or_else(|| "missing MessageSpec".to_string())?;
+    let indic = spec.child_text("MessageTypeIndic").unwrap_or_default();
+    if indic != "CESOP100" && indic != "CESOP102" {
+        return Err(format!(
+            "only CESOP100/CESOP102 messages can be merged, found {}",
+            indic
+        ));
+    }
+    let field = |names: &[&str]| {
+        document
+            .path_text(names)
+            .map(|text| text.trim().to_string())
+            .ok_or_else(|| format!("missing {}", names.join("/")))
+    };
+    Ok(MessageKey {
+        psp_id: field(&["PaymentDataBody", "ReportingPSP", "PSPId"])?,
+        transmitting_country: field(&["MessageSpec", "TransmittingCountry"])?,
+        year: field(&["MessageSpec", "ReportingPeriod", "Year"])?,
+        quarter: field(&["MessageSpec", "ReportingPeriod", "Quarter"])?,
+    })
+}
+
+// Reports from different systems carry no shared payee id, so payees are
+// matched on name, country and account identifiers.
+fn payee_identity(payee: &XmlNode) -> String {
+    let mut accounts: Vec<&str> = payee
+        .children_named("AccountIdentifier")
+        .map(|account| account.text.trim())
+        .collect();
+    accounts.sort_unstable();
+    format!(
+        "{}|{}|{}",
+        payee.child_text("Name").unwrap_or_default().trim(),
+        payee.child_text("Country").unwrap_or_default().trim(),
+        accounts.join(",")
+    )
+}
+
+fn transaction_id(tx: &XmlNode) -> String {
+    tx.child_text("TransactionIdentifier")
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+fn split_payee(payee: &XmlNode) -> MergedPayee {
+    let mut merged = MergedPayee {
+        head: Vec::new(),
+        transactions: Vec::new(),
+        tail: Vec::new(),
+        transaction_ids: HashSet::new(),
+    };
+    for child in &payee.children {
+        match child.name.as_str() {
+            "ReportedTransaction" => {
+                merged.transaction_ids.insert(transaction_id(child));
+                merged.transactions.push(child.clone());
+            }
+            "Representative" => merged.tail.push(child.clone()),
+            // Every merged payee gets a fresh DocSpec.
+            "DocSpec" => {}
+            _ => merged.head.push(child.clone()),
+        }
+    }
+    merged
+}
+
+fn payee_node(payee: &MergedPayee, transactions: &[XmlNode]) -> XmlNode {
+    let mut node = XmlNode::new("ReportedPayee", "");
+    node.children.extend(payee.head.iter().cloned());
+    node.children.extend(transactions.iter().cloned());
+    node.children.extend(payee.tail.iter().cloned());
+    let mut doc_spec = XmlNode::new("DocSpec", "");
+    doc_spec.children = vec![
+        XmlNode::new("cm:DocTypeIndic", "CESOP1"),
+        XmlNode::new("cm:DocRefId", &uuid::Uuid::new_v4().to_string()),
+    ];
+    node.children.push(doc_spec);
+    node
+}
+
+// Groups payees (or, for very large payees, slices of their transactions)
+// into parts that each stay below the size limit. The same payee may appear
+// in several parts, which CESOP allows for split submissions.
+fn pack_parts(
+    document: &XmlNode,
+    payees: &[MergedPayee],
+    max_size_bytes: u64,
+) -> Result<Vec<Vec<XmlNode>>, String> {
+    let envelope = size_at(document.child("MessageSpec").unwrap_or(document), 1)?
+        + size_at(
+            document
+                .path(&["PaymentDataBody", "ReportingPSP"])
+                .unwrap_or(document),
+            2,
+        )?
+        + 512;
+    let capacity = max_size_bytes.saturating_sub(envelope);
+    let mut parts: Vec<Vec<XmlNode>> = Vec::new();
+    let mut current: Vec<XmlNode> = Vec::new();
+    let mut used = 0u64;
+
+    for payee in payees {
+        let node = payee_node(payee, &payee.transactions);
+        let size = size_at(&node, 2)?;
+        if used + size > capacity && !current.is_empty() {
+            parts.push(std::mem::take(&mut current));
+            used = 0;
+        }
+        if size <= capacity {
+            current.push(node);
+            used += size;
+            continue;
+        }
+
+        let overhead = size_at(&payee_node(payee, &[]), 2)?;
+        let mut chunk: Vec<XmlNode> = Vec::new();
+        let mut chunk_size = overhead;
+        for tx in &payee.transactions {
+            let tx_size = size_at(tx, 3)?;
+            if overhead + tx_size > capacity {
+                return Err(codes::RND_MESSAGE_LIMIT.err(format!(
+                    "a single transaction does not fit in {} bytes; raise the size limit",
+                    max_size_bytes
+                )));
+            }
+            if used + chunk_size + tx_size > capacity {
+                if !chunk.is_empty() {
+                    current.push(payee_node(payee, &std::mem::take(&mut chunk)));
+                }
+                parts.push(std::mem::take(&mut current));
+                used = 0;
+                chunk_size = overhead;
+            }
+            chunk.push(tx.clone());
+            chunk_size += tx_size;
+        }
+        if !chunk.is_empty() {
+            current.push(payee_node(payee, &chunk));
+            used += chunk_size;
+        }
+    }
+    if !current.is_empty() || parts.is_empty() {
+        parts.push(current);
+    }
+    Ok(parts)
+}
+
+// Serialized size including the indentation the node gets at `depth`.
+fn size_at(node: &XmlNode, depth: u64) -> Result<u64, String> {
+    let bytes = node.to_bytes()?;
+    let lines = bytes.iter().filter(|byte| **byte == b'\n').count() as u64 + 1;
+    Ok(bytes.len() as u64 + lines * (depth * 2 + 1))
+}
+
+fn build_message(
+    document: &XmlNode,
+    key: &MessageKey,
+    payees: &[XmlNode],
+) -> Result<XmlNode, String> {
+    let mut spec = document
+        .child("MessageSpec")
+        .cloned()
+        .ok_or_else(|| "missing MessageSpec".to_string())?;
+    spec.children.retain(|child| child.name != "CorrMessageRefId");
+    spec.set_child_text(
+        "MessageTypeIndic",
+        if payees.is_empty() { "CESOP102" } else { "CESOP100" },
+    );
+    spec.set_child_text("MessageRefId", &uuid::Uuid::new_v4().to_string());
+    spec.set_child_text(
+        "Timestamp",
+        &Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+    );
+
+    let reporting_psp = document
+        .path(&["PaymentDataBody", "ReportingPSP"])
+        .cloned()
+        .ok_or_else(|| format!("missing ReportingPSP for {}", key))?;
+    let mut body = XmlNode::new("PaymentDataBody", "");
+    body.children.push(reporting_psp);
+    body.children.extend(payees.iter().cloned());
+
+    Ok(XmlNode {
+        name: document.name.clone(),
+        attrs: document.attrs.clone(),
+        text: String::new(),
+        children: vec![spec, body],
+    })
+}
+
+fn write_document(document: &XmlNode, path: &Path) -> Result<(), String> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+    let file = File::create(path).map_err(|err| err.to_string())?;
+    let mut writer = Writer::new_with_indent(BufWriter::new(file), b' ', 2);
+    document.write(&mut writer)
+}
+
+// Part numbering follows the CESOP "x-y" file naming convention.
+fn part_path(output: &Path, part: usize, total: usize) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "merged".to_string());
+    output.with_file_name(format!("{}-{}-{}.xml", stem, part, total))
+}
@@ -40,4 +40,22 @@ pub struct PaymentRecord {
     pub payee_psp_name: Option<String>,
     pub psp_id: String,
     pub psp_name: String,
+    #[serde(default)]
+    pub payment_method_other: Option<String>,
+    #[serde(default)]
+    pub sub_merchant_id: Option<String>,
+    #[serde(default)]
+    pub sub_merchant_name: Option<String>,
+    #[serde(default)]
+    pub sub_merchant_country: Option<String>,
+    #[serde(default)]
+    pub payer_account: Option<String>,
+    #[serde(default)]
+    pub payer_account_type: Option<String>,
+    #[serde(default)]
+    pub payee_mcc: Option<String>,
+    #[serde(default)]
+    pub payee_psp_country: Option<String>,
+    #[serde(default)]
+    pub payee_psp_address: Option<String>,
 }
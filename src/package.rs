@@ -0,0 +1,171 @@
+use crate::codes;
+use crate::refids::{MessageEntry, RefIdRegistry};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit};
+use aes_gcm::Aes256Gcm;
+use rand::rngs::OsRng;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Oaep, RsaPublicKey};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Seek, Write};
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+// File name, without extension, following the User Guide naming convention
+// `PMT-<quarter>-<year>-<countryMS>-<pspID>-<x>-<y>`: file x of the y files a
+// PSP sends to one Member State for the period.
+pub fn transmission_name(message: &MessageEntry, number: usize, total: usize) -> String {
+    format!(
+        "PMT-Q{}-{}-{}-{}-{}-{}",
+        message.quarter,
+        message.year,
+        message.transmitting_country,
+        message.psp_id,
+        number,
+        total
+    )
+}
+
+// An encrypted message ready for upload: the zip archive encrypted with a
+// one-off AES-256-GCM key, and that key wrapped for the recipient.
+#[derive(Debug, Clone)]
+pub struct EncryptedPackage {
+    pub message_ref_id: String,
+    pub package: PathBuf,
+    pub key: PathBuf,
+}
+
+// Every message in `registry` with its transmission name. Messages are
+// numbered in registry order, so the parts of a split report keep their
+// order.
+fn named_messages(registry: &RefIdRegistry) -> Vec<(&MessageEntry, String)> {
+    let group = |message: &MessageEntry| {
+        (
+            message.psp_id.clone(),
+            message.year,
+            message.quarter,
+            message.transmitting_country.clone(),
+        )
+    };
+    let mut totals: HashMap<_, usize> = HashMap::new();
+    for message in &registry.messages {
+        *totals.entry(group(message)).or_default() += 1;
+    }
+
+    let mut numbers: HashMap<_, usize> = HashMap::new();
+    registry
+        .messages
+        .iter()
+        .map(|message| {
+            let key = group(message);
+            let number = numbers.entry(key.clone()).or_default();
+            *number += 1;
+            (message, transmission_name(message, *number, totals[&key]))
+        })
+        .collect()
+}
+
+// Writes the XML at `xml_path` to a zip archive as `<name>.xml`. Archives
+// carry no timestamps of their own: packaging the same XML again gives the
+// same bytes.
+fn write_zip<W: Write + Seek>(xml_path: &Path, name: &str, out: W) -> Result<W, String> {
+    let mut xml = File::open(xml_path).map_err(|err| {
+        codes::IN_READ_FAILED.err(format!("failed to open {}: {}", xml_path.display(), err))
+    })?;
+    let mut zip = ZipWriter::new(out);
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(true);
+    zip.start_file(format!("{}.xml", name), options)
+        .map_err(|err| err.to_string())?;
+    std::io::copy(&mut xml, &mut zip).map_err(|err| err.to_string())?;
+    zip.finish().map_err(|err| err.to_string())
+}
+
+// Wraps every message in `registry` in a zip archive next to its XML file,
+// with the archive and the XML inside it named by the transmission naming
+// convention.
+pub fn package_messages(
+    registry: &RefIdRegistry,
+    output_dir: &Path,
+) -> Result<Vec<PathBuf>, String> {
+    let mut packages = Vec::with_capacity(registry.messages.len());
+    for (message, name) in named_messages(registry) {
+        let xml_path = output_dir.join(&message.file);
+        let zip_path = xml_path.with_file_name(format!("{}.zip", name));
+        let file = File::create(&zip_path)
+            .map_err(|err| format!("failed to create {}: {}", zip_path.display(), err))?;
+        write_zip(&xml_path, &name, BufWriter::new(file))?
+            .flush()
+            .map_err(|err| err.to_string())?;
+        log::debug!(
+            file:% = zip_path.display(),
+            message_ref_id = message.message_ref_id.as_str();
+            "message packaged"
+        );
+        packages.push(zip_path);
+    }
+    Ok(packages)
+}
+
+// Reads the recipient's RSA public key, PEM encoded as SubjectPublicKeyInfo
+// (`BEGIN PUBLIC KEY`) or PKCS#1 (`BEGIN RSA PUBLIC KEY`).
+pub fn load_recipient_key(path: &Path) -> Result<RsaPublicKey, String> {
+    let pem = std::fs::read_to_string(path).map_err(|err| {
+        codes::IN_READ_FAILED.err(format!("failed to read {}: {}", path.display(), err))
+    })?;
+    RsaPublicKey::from_public_key_pem(&pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(&pem))
+        .map_err(|err| format!("{}: not an RSA public key: {}", path.display(), err))
+}
+
+// Zips every message in `registry` under its transmission name and encrypts
+// the archive into `package_dir`: `<name>.zip.enc` holds the 12-byte GCM
+// nonce followed by the AES-256-GCM ciphertext and tag, `<name>.key` the
+// AES key wrapped with RSA-OAEP (SHA-256) for `recipient`. Every message
+// gets a fresh key.
+pub fn encrypt_messages(
+    registry: &RefIdRegistry,
+    output_dir: &Path,
+    package_dir: &Path,
+    recipient: &RsaPublicKey,
+) -> Result<Vec<EncryptedPackage>, String> {
+    std::fs::create_dir_all(package_dir).map_err(|err| err.to_string())?;
+    let mut packages = Vec::with_capacity(registry.messages.len());
+    for (message, name) in named_messages(registry) {
+        let archive = write_zip(&output_dir.join(&message.file), &name, Cursor::new(Vec::new()))?
+            .into_inner();
+
+        let key = Aes256Gcm::generate_key(OsRng);
+        let nonce = Aes256Gcm::generate_nonce(OsRng);
+        let ciphertext = Aes256Gcm::new(&key)
+            .encrypt(&nonce, archive.as_slice())
+            .map_err(|err| format!("failed to encrypt {}: {}", name, err))?;
+        let wrapped = recipient
+            .encrypt(&mut OsRng, Oaep::new::<Sha256>(), key.as_slice())
+            .map_err(|err| format!("failed to wrap the key of {}: {}", name, err))?;
+
+        let package = package_dir.join(format!("{}.zip.enc", name));
+        let mut content = nonce.to_vec();
+        content.extend_from_slice(&ciphertext);
+        std::fs::write(&package, content)
+            .map_err(|err| format!("failed to write {}: {}", package.display(), err))?;
+        let key_path = package_dir.join(format!("{}.key", name));
+        std::fs::write(&key_path, wrapped)
+            .map_err(|err| format!("failed to write {}: {}", key_path.display(), err))?;
+        log::debug!(
+            file:% = package.display(),
+            message_ref_id = message.message_ref_id.as_str();
+            "message encrypted"
+        );
+        packages.push(EncryptedPackage {
+            message_ref_id: message.message_ref_id.clone(),
+            package,
+            key: key_path,
+        });
+    }
+    Ok(packages)
+}
@@ -0,0 +1,123 @@
+use crate::codes;
+use crate::models::PaymentRecord;
+use crate::Error;
+
+// `cm:PaymentMethodType` code list of the CESOP common types.
+pub const PAYMENT_METHOD_TYPES: &[&str] = &[
+    "Card payment",
+    "Bank transfer",
+    "Direct debit",
+    "E-money",
+    "Money Remittance",
+    "Marketplace",
+    "Intermediary",
+    "Other",
+];
+
+// Free-text spellings seen in PSP extracts, normalized as by `normalize`.
+const ALIASES: &[(&str, &str)] = &[
+    ("card", "Card payment"),
+    ("cards", "Card payment"),
+    ("creditcard", "Card payment"),
+    ("debitcard", "Card payment"),
+    ("cardpresent", "Card payment"),
+    ("cardnotpresent", "Card payment"),
+    ("pos", "Card payment"),
+    ("transfer", "Bank transfer"),
+    ("credittransfer", "Bank transfer"),
+    ("sepacredittransfer", "Bank transfer"),
+    ("sct", "Bank transfer"),
+    ("sctinst", "Bank transfer"),
+    ("instantpayment", "Bank transfer"),
+    ("wiretransfer", "Bank transfer"),
+    ("wire", "Bank transfer"),
+    ("sepadirectdebit", "Direct debit"),
+    ("sdd", "Direct debit"),
+    ("electronicmoney", "E-money"),
+    ("ewallet", "E-money"),
+    ("wallet", "E-money"),
+    ("remittance", "Money Remittance"),
+    ("moneytransfer", "Money Remittance"),
+];
+
+// What to do with a payment method outside the code list: fail, or report
+// it as `Other` with the original text in `PaymentMethodOther`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PaymentMethodMode {
+    Strict,
+    #[default]
+    Lenient,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentMethodMatch {
+    // Already a code of the list.
+    Code(&'static str),
+    // A known spelling of a code, e.g. `SEPA credit transfer`.
+    Alias(&'static str),
+    Unknown,
+}
+
+// Case, spaces and punctuation are ignored.
+fn normalize(value: &str) -> String {
+    value
+        .chars()
+        .filter(|ch| ch.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+pub fn classify_payment_method(value: &str) -> PaymentMethodMatch {
+    if let Some(code) = PAYMENT_METHOD_TYPES.iter().find(|code| **code == value) {
+        return PaymentMethodMatch::Code(code);
+    }
+    let normalized = normalize(value);
+    if normalized.is_empty() {
+        return PaymentMethodMatch::Unknown;
+    }
+    PAYMENT_METHOD_TYPES
+        .iter()
+        .find(|code| normalize(code) == normalized)
+        .or_else(|| {
+            ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == normalized)
+                .map(|(_, code)| code)
+        })
+        .map_or(PaymentMethodMatch::Unknown, |code| {
+            PaymentMethodMatch::Alias(code)
+        })
+}
+
+// Rewrites the payment method of `record` to its code. In lenient mode an
+// unknown method becomes `Other`, keeping the original text (at most 200
+// characters, as the schema allows) unless `payment_method_other` is set.
+pub fn map_payment_method(
+    record: &mut PaymentRecord,
+    mode: PaymentMethodMode,
+) -> Result<(), Error> {
+    let original = record.payment_method.trim();
+    let code = match classify_payment_method(original) {
+        PaymentMethodMatch::Code(code) | PaymentMethodMatch::Alias(code) => code,
+        PaymentMethodMatch::Unknown => {
+            if mode == PaymentMethodMode::Strict {
+                return Err(codes::RND_PAYMENT_METHOD.fail(format!(
+                    "payment {} has payment_method {:?}",
+                    record.payment_id, record.payment_method
+                )));
+            }
+            let has_other = record
+                .payment_method_other
+                .as_deref()
+                .is_some_and(|text| !text.trim().is_empty());
+            if !has_other && !original.is_empty() {
+                record.payment_method_other = Some(original.chars().take(200).collect());
+            }
+            "Other"
+        }
+    };
+    if record.payment_method != code {
+        record.payment_method = code.to_string();
+    }
+    Ok(())
+}
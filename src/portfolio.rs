@@ -0,0 +1,212 @@
+use crate::codes;
+use crate::generator::{Perspective, DEFAULT_REFUND_DELAY_DAYS};
+use crate::seasonality::TimePreset;
+use crate::util::{sha256_hex, slugify};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const PORTFOLIO_MANIFEST_FILE: &str = "portfolio_manifest.json";
+
+// A fleet of simulated PSPs generated in one run. Each tenant maps to one
+// `generate` invocation with its own scale, ratios and output.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Portfolio {
+    pub seed: Option<u64>,
+    pub period: Option<String>,
+    #[serde(default = "default_output_dir")]
+    pub output_dir: PathBuf,
+    pub tenants: Vec<Tenant>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tenant {
+    pub psp_name: String,
+    #[serde(default = "default_scale")]
+    pub scale: usize,
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub licensed_countries: Vec<String>,
+    #[serde(default = "default_psps")]
+    pub psps: usize,
+    #[serde(default = "default_cross_border_ratio")]
+    pub cross_border_ratio: f64,
+    #[serde(default = "default_refund_ratio")]
+    pub refund_ratio: f64,
+    #[serde(default = "default_multi_account_ratio")]
+    pub multi_account_ratio: f64,
+    #[serde(default = "default_non_eu_payee_ratio")]
+    pub non_eu_payee_ratio: f64,
+    #[serde(default = "default_no_account_payee_ratio")]
+    pub no_account_payee_ratio: f64,
+    #[serde(default)]
+    pub marketplace_ratio: f64,
+    #[serde(default)]
+    pub unicode_name_ratio: f64,
+    #[serde(default)]
+    pub foreign_currency_ratio: f64,
+    #[serde(default)]
+    pub payer_account_ratio: f64,
+    #[serde(default)]
+    pub non_eu_payer_ratio: f64,
+    #[serde(default)]
+    pub local_time_ratio: f64,
+    #[serde(default)]
+    pub xi_payee_ratio: f64,
+    #[serde(default)]
+    pub xi_payer_ratio: f64,
+    #[serde(default)]
+    pub multi_psp_ratio: f64,
+    #[serde(default)]
+    pub perspective: Perspective,
+    #[serde(default)]
+    pub unknown_payee_psp_ratio: f64,
+    pub country_weights: Option<String>,
+    pub field_rates: Option<String>,
+    #[serde(default = "default_refund_delay_days")]
+    pub refund_delay_days: (u32, u32),
+    #[serde(default)]
+    pub partial_refund_ratio: f64,
+    #[serde(default = "default_max_refunds_per_payment")]
+    pub max_refunds_per_payment: u32,
+    #[serde(default)]
+    pub holiday_calendars: bool,
+    #[serde(default)]
+    pub mcc: bool,
+    #[serde(default)]
+    pub time_profile: TimePreset,
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PortfolioManifest {
+    pub run_id: String,
+    pub seed: u64,
+    pub year: i32,
+    pub quarter: u8,
+    pub tenants: Vec<TenantResult>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TenantResult {
+    pub psp_name: String,
+    pub psp_ids: Vec<String>,
+    pub licensed_countries: Vec<String>,
+    pub seed: u64,
+    pub records: usize,
+    pub payees: usize,
+    pub output: String,
+    pub registry: String,
+}
+
+fn default_output_dir() -> PathBuf {
+    PathBuf::from("data/portfolio")
+}
+
+fn default_scale() -> usize {
+    1200
+}
+
+fn default_psps() -> usize {
+    1
+}
+
+fn default_cross_border_ratio() -> f64 {
+    0.8
+}
+
+fn default_refund_ratio() -> f64 {
+    0.02
+}
+
+fn default_refund_delay_days() -> (u32, u32) {
+    DEFAULT_REFUND_DELAY_DAYS
+}
+
+fn default_max_refunds_per_payment() -> u32 {
+    1
+}
+
+fn default_multi_account_ratio() -> f64 {
+    0.15
+}
+
+fn default_non_eu_payee_ratio() -> f64 {
+    0.10
+}
+
+fn default_no_account_payee_ratio() -> f64 {
+    0.02
+}
+
+impl Portfolio {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|err| {
+            codes::IN_READ_FAILED.err(format!(
+                "failed to read portfolio {}: {}",
+                path.display(),
+                err
+            ))
+        })?;
+        let mut portfolio: Portfolio = serde_json::from_str(&contents).map_err(|err| {
+            codes::GEN_PORTFOLIO_INVALID.err(format!(
+                "invalid portfolio {}: {}",
+                path.display(),
+                err
+            ))
+        })?;
+        if portfolio.tenants.is_empty() {
+            return Err(codes::GEN_PORTFOLIO_INVALID
+                .err(format!("portfolio {} defines no tenants", path.display())));
+        }
+        for tenant in &mut portfolio.tenants {
+            tenant.licensed_countries = tenant
+                .licensed_countries
+                .iter()
+                .map(|country| country.trim().to_uppercase())
+                .collect();
+        }
+        let mut outputs: Vec<PathBuf> = Vec::new();
+        for tenant in &portfolio.tenants {
+            let output = portfolio.tenant_output(tenant);
+            if outputs.contains(&output) {
+                return Err(codes::GEN_PORTFOLIO_INVALID.err(format!(
+                    "portfolio tenants share the output {}",
+                    output.display()
+                )));
+            }
+            outputs.push(output);
+        }
+        Ok(portfolio)
+    }
+
+    pub fn tenant_output(&self, tenant: &Tenant) -> PathBuf {
+        match &tenant.output {
+            Some(output) if output.is_absolute() => output.clone(),
+            Some(output) => self.output_dir.join(output),
+            None => self
+                .output_dir
+                .join(format!("{}.csv", slugify(&tenant.psp_name))),
+        }
+    }
+}
+
+// Tenants without an explicit seed derive one from the portfolio seed and
+// their position, so adding a tenant at the end leaves the others unchanged.
+pub fn tenant_seed(portfolio_seed: u64, index: usize, tenant: &Tenant) -> u64 {
+    if let Some(seed) = tenant.seed {
+        return seed;
+    }
+    let digest = sha256_hex(format!("{}|{}|{}", portfolio_seed, index, tenant.psp_name).as_bytes());
+    u64::from_str_radix(&digest[..16], 16).unwrap_or(portfolio_seed)
+}
+
+impl PortfolioManifest {
+    pub fn write(&self, dir: &Path) -> Result<PathBuf, String> {
+        let path = dir.join(PORTFOLIO_MANIFEST_FILE);
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        std::fs::write(&path, json).map_err(|err| err.to_string())?;
+        Ok(path)
+    }
+}
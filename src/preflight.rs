@@ -1,193 +1,649 @@
-use crate::analysis::analyze_threshold_records;
-use crate::location::{account_country_code, normalize_country_code, resolve_payee_country};
+use crate::analysis::{analyze_threshold_records, threshold_proximity, BorderlinePayee};
+use crate::codes::{self, Code, CodeCategory};
+use crate::input::{load_payment_rows, CsvReadOptions};
+use crate::location::{
+    account_country_code, derive_payer_ms, normalize_country_code, resolve_payee_country,
+};
+use crate::logging;
 use crate::models::PaymentRecord;
-use crate::reference::{iban_length, is_eu_member_state, ACCOUNT_IDENTIFIER_TYPES};
+use crate::payment_methods::{classify_payment_method, PaymentMethodMatch, PaymentMethodMode};
+use crate::reference::{
+    bban_matches, iban_length, is_cesop_currency, is_eu_member_state, is_iso_country,
+    is_iso_currency, postcode_matches, ACCOUNT_IDENTIFIER_TYPES, NORTHERN_IRELAND,
+};
+use crate::run_summary::PhaseTimings;
 use crate::util::iban_check_digits;
-use chrono::DateTime;
-use std::collections::{HashMap, HashSet};
+use crate::Error;
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::Path;
+use std::time::Instant;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum IssueLevel {
-    Error,
-    Warning,
-}
+pub use crate::codes::IssueLevel;
 
 #[derive(Debug, Clone)]
 pub struct PreflightIssue {
+    pub code: &'static str,
     pub level: IssueLevel,
     pub message: String,
+    // The column at fault and its value in the row.
+    pub field: Option<&'static str>,
+    pub value: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+impl PreflightIssue {
+    fn on(mut self, field: &'static str, value: &str) -> Self {
+        self.field = Some(field);
+        self.value = Some(value.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IssueExample {
+    pub row: usize,
+    pub payment_id: String,
+    pub field: Option<&'static str>,
+    pub value: Option<String>,
+    // The issue as worded for this row, when it says more than the rule.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleSummary {
+    pub code: &'static str,
+    pub level: IssueLevel,
+    pub message: String,
+    pub count: usize,
+    pub examples: Vec<IssueExample>,
+}
+
+// How `preflight` prints its report: log lines with the most frequent issues,
+// or the whole report as JSON on stdout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PreflightFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+// How BICs (`psp_id`, `payee_psp_id` and BIC accounts) are checked: `basic`
+// only checks the length, characters and a letter country code, `strict` the
+// ISO 9362 structure too.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BicValidation {
+    #[default]
+    Basic,
+    Strict,
+}
+
+// Which issues fail `preflight`: errors (the default), warnings as well, or
+// none, only reporting them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum FailOn {
+    #[default]
+    Error,
+    Warning,
+    Never,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct PreflightReport {
     pub threshold: usize,
     pub total_records: usize,
     pub cross_border_records: usize,
     pub total_payees: usize,
     pub payees_over_threshold: usize,
-    pub issues: Vec<PreflightIssue>,
+    // Rows per reporting period (`2024Q2`) of their execution_time.
+    pub periods: BTreeMap<String, usize>,
+    // The first `max_examples` conflicts, of `payee_conflict_count`.
+    pub payee_conflicts: Vec<PayeeConflict>,
+    pub payee_conflict_count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub borderline_payees: Option<Vec<BorderlinePayee>>,
+    pub rules: Vec<RuleSummary>,
 }
 
+// Two values of one attribute given by rows of the same payee (PF051): the
+// value seen first and the other, each with the row it first appeared in.
+#[derive(Debug, Clone, Serialize)]
+pub struct PayeeConflict {
+    pub psp_id: String,
+    pub payee_id: String,
+    pub field: &'static str,
+    pub value: String,
+    pub row: usize,
+    pub other_value: String,
+    pub other_row: usize,
+}
+
+type PayeeAttribute = fn(&PaymentRecord) -> Option<&String>;
+
+// Distinct values of an attribute, each with the row it was first seen in.
+type SeenValues = Vec<(String, usize)>;
+
+// The payee attributes `render` takes from one of the payee's rows.
+const PAYEE_ATTRIBUTES: &[(&str, PayeeAttribute)] = &[
+    ("payee_name", |record| Some(&record.payee_name)),
+    ("payee_country", |record| Some(&record.payee_country)),
+    ("payee_vat_id", |record| record.payee_vat_id.as_ref()),
+    ("payee_email", |record| record.payee_email.as_ref()),
+];
+
 impl PreflightReport {
     pub fn error_count(&self) -> usize {
-        self.issues
-            .iter()
-            .filter(|issue| issue.level == IssueLevel::Error)
-            .count()
+        self.count_for(IssueLevel::Error)
     }
 
     pub fn warning_count(&self) -> usize {
-        self.issues
+        self.count_for(IssueLevel::Warning)
+    }
+
+    // Every rule with its level, code, count and examples, and the totals.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut json = serde_json::json!(self);
+        json["errors"] = self.error_count().into();
+        json["warnings"] = self.warning_count().into();
+        json
+    }
+
+    fn count_for(&self, level: IssueLevel) -> usize {
+        self.rules
             .iter()
-            .filter(|issue| issue.level == IssueLevel::Warning)
-            .count()
+            .filter(|rule| rule.level == level)
+            .map(|rule| rule.count)
+            .sum()
+    }
+}
+
+// A rules file (TOML) adapting preflight to how a national portal reads the
+// rules: rule codes to skip, levels to change and parameters of the checks.
+//
+//     disabled = ["PF017"]
+//
+//     [levels]
+//     PF015 = "warning"
+//
+//     [params]
+//     account_types = ["IBAN", "BIC"]
+//     max_amount = 1000000.0
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PreflightRules {
+    #[serde(default)]
+    pub disabled: Vec<String>,
+    #[serde(default)]
+    pub levels: BTreeMap<String, IssueLevel>,
+    #[serde(default)]
+    pub params: RuleParams,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleParams {
+    // Accepted payee_account_type values; every schema type when unset.
+    pub account_types: Option<Vec<String>>,
+    // Accepted currencies, replacing the ISO 4217 and CESOP schema lists.
+    pub currencies: Option<Vec<String>>,
+    // Bounds of PF045 on the absolute amount. A bound that is not set is not
+    // checked.
+    pub min_amount: Option<f64>,
+    pub max_amount: Option<f64>,
+}
+
+impl PreflightRules {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let contents = std::fs::read_to_string(path).map_err(|err| {
+            codes::IN_READ_FAILED.fail(format!("failed to read rules {}: {}", path.display(), err))
+        })?;
+        let rules: PreflightRules = toml::from_str(&contents).map_err(|err| {
+            codes::PF_RULES_INVALID.fail(format!("invalid rules {}: {}", path.display(), err))
+        })?;
+        for code in rules.disabled.iter().chain(rules.levels.keys()) {
+            let preflight =
+                codes::lookup(code).is_some_and(|entry| entry.category == CodeCategory::Preflight);
+            if !preflight {
+                return Err(codes::PF_RULES_INVALID.fail(format!(
+                    "rules {}: {} is not a preflight rule code",
+                    path.display(),
+                    code
+                )));
+            }
+        }
+        for account_type in rules.params.account_types.iter().flatten() {
+            if !ACCOUNT_IDENTIFIER_TYPES.contains(&account_type.as_str()) {
+                return Err(codes::PF_RULES_INVALID.fail(format!(
+                    "rules {}: account type {} is not one of {}",
+                    path.display(),
+                    account_type,
+                    ACCOUNT_IDENTIFIER_TYPES.join(", ")
+                )));
+            }
+        }
+        for currency in rules.params.currencies.iter().flatten() {
+            if !is_iso_currency(currency) && !is_cesop_currency(currency) {
+                return Err(codes::PF_RULES_INVALID.fail(format!(
+                    "rules {}: currency {} is not an ISO 4217 code",
+                    path.display(),
+                    currency
+                )));
+            }
+        }
+        if let (Some(min), Some(max)) = (rules.params.min_amount, rules.params.max_amount) {
+            if min > max {
+                return Err(codes::PF_RULES_INVALID.fail(format!(
+                    "rules {}: min_amount {} is above max_amount {}",
+                    path.display(),
+                    min,
+                    max
+                )));
+            }
+        }
+        Ok(rules)
+    }
+
+    // The issue at the level the rules give it, or None when its rule is
+    // disabled.
+    fn apply(&self, mut issue: PreflightIssue) -> Option<PreflightIssue> {
+        if !self.enabled(issue.code) {
+            return None;
+        }
+        if let Some(level) = self.levels.get(issue.code) {
+            issue.level = *level;
+        }
+        Some(issue)
+    }
+
+    fn enabled(&self, code: &str) -> bool {
+        !self.disabled.iter().any(|disabled| disabled == code)
     }
 }
 
+impl RuleParams {
+    fn allows_account_type(&self, account_type: &str) -> bool {
+        match &self.account_types {
+            Some(types) => types.iter().any(|value| value == account_type),
+            None => ACCOUNT_IDENTIFIER_TYPES.contains(&account_type),
+        }
+    }
+
+    fn amount_in_bounds(&self, amount: f64) -> bool {
+        self.min_amount.is_none_or(|min| amount >= min)
+            && self.max_amount.is_none_or(|max| amount <= max)
+    }
+}
+
+// Counts issues per rule code, keeping the first `max_examples` rows of each,
+// so messages naming row values do not grow the summary with the input.
+struct IssueCollector<'a> {
+    max_examples: usize,
+    settings: &'a PreflightRules,
+    rules: Vec<RuleSummary>,
+    index: HashMap<&'static str, usize>,
+}
+
+impl<'a> IssueCollector<'a> {
+    fn new(max_examples: usize, settings: &'a PreflightRules) -> Self {
+        Self {
+            max_examples,
+            settings,
+            rules: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, row: usize, payment_id: &str, issue: PreflightIssue) {
+        let Some(issue) = self.settings.apply(issue) else {
+            return;
+        };
+        let idx = match self.index.get(issue.code) {
+            Some(idx) => *idx,
+            None => {
+                let summary = codes::lookup(issue.code).map(|code| code.summary);
+                self.rules.push(RuleSummary {
+                    code: issue.code,
+                    level: issue.level,
+                    message: summary.unwrap_or(&issue.message).to_string(),
+                    count: 0,
+                    examples: Vec::new(),
+                });
+                self.index.insert(issue.code, self.rules.len() - 1);
+                self.rules.len() - 1
+            }
+        };
+        let rule = &mut self.rules[idx];
+        rule.count += 1;
+        if rule.examples.len() < self.max_examples {
+            rule.examples.push(IssueExample {
+                row,
+                payment_id: payment_id.to_string(),
+                field: issue.field,
+                value: issue.value,
+                message: Some(issue.message).filter(|message| *message != rule.message),
+            });
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PreflightOptions {
+    pub threshold: usize,
+    pub include_refunds: bool,
+    pub max_examples: usize,
+    pub payment_methods: PaymentMethodMode,
+    pub rules: PreflightRules,
+    // Declared reporting period (year, quarter); rows executed outside it
+    // are errors. Without one, rows outside the most common quarter are
+    // warnings, as `render` would report them separately.
+    pub period: Option<(i32, u8)>,
+    pub bic_validation: BicValidation,
+    // Payees within this many transactions of the threshold are listed.
+    pub proximity: Option<usize>,
+    pub csv: CsvReadOptions,
+}
+
 pub fn preflight_csv(
     path: &Path,
-    threshold: usize,
-    include_refunds: bool,
-) -> Result<PreflightReport, String> {
-    let mut reader = csv::Reader::from_path(path).map_err(|err| err.to_string())?;
+    options: &PreflightOptions,
+    timings: &mut PhaseTimings,
+) -> Result<PreflightReport, Error> {
+    let read_start = Instant::now();
+    let load = load_payment_rows(path, &options.csv)?;
     let mut records = Vec::new();
-    let mut issues = Vec::new();
+    let mut record_rows = Vec::new();
+    let rules = &options.rules;
+    let mut collector = IssueCollector::new(options.max_examples, rules);
+    for bad_row in &load.bad_rows {
+        collector.add(bad_row.row, "", issue(&codes::PF_ROW_UNPARSEABLE));
+    }
     let mut payment_ids: HashSet<String> = HashSet::new();
     let mut psp_names: HashMap<String, String> = HashMap::new();
+    let mut periods: BTreeMap<(i32, u8), usize> = BTreeMap::new();
+    let mut row_periods = Vec::new();
+    // Values of each payee attribute per (psp_id, payee_id).
+    let mut payee_values: HashMap<(String, String), Vec<SeenValues>> = HashMap::new();
+    let mut payee_conflicts = Vec::new();
+    let mut payee_conflict_count = 0;
+    let check_payees = rules.enabled(codes::PF_PAYEE_ATTRIBUTE_CONFLICT.code);
+
+    for (row, record) in load.rows {
+        let mut issues = Vec::new();
+        validate_record(&record, &rules.params, &mut issues);
+        if options.bic_validation == BicValidation::Strict {
+            validate_bics(&record, &mut issues);
+        }
+        validate_payment_method(&record, options.payment_methods, &mut issues);
 
-    for result in reader.deserialize() {
-        let record: PaymentRecord = result.map_err(|err| err.to_string())?;
-        validate_record(&record, &mut issues);
+        if let Some(period) = period_of(&record.execution_time) {
+            *periods.entry(period).or_default() += 1;
+            match options.period {
+                Some(declared) if declared != period => issues.push(
+                    issue_with(
+                        &codes::PF_OUTSIDE_PERIOD,
+                        format!(
+                            "{} {}",
+                            codes::PF_OUTSIDE_PERIOD.summary,
+                            period_label(declared)
+                        ),
+                    )
+                    .on("execution_time", &record.execution_time),
+                ),
+                Some(_) => {}
+                None => row_periods.push((records.len(), period)),
+            }
+        }
 
         if !payment_ids.insert(record.payment_id.clone()) {
-            issues.push(PreflightIssue {
-                level: IssueLevel::Error,
-                message: "duplicate payment_id detected".to_string(),
-            });
+            issues
+                .push(issue(&codes::PF_DUPLICATE_PAYMENT_ID).on("payment_id", &record.payment_id));
         }
 
         if let Some(existing) = psp_names.get(&record.psp_id) {
             if existing != &record.psp_name {
-                issues.push(PreflightIssue {
-                    level: IssueLevel::Error,
-                    message: format!(
-                        "multiple PSP names found for {}: '{}' vs '{}'",
-                        record.psp_id, existing, record.psp_name
-                    ),
-                });
+                issues.push(
+                    issue_with(
+                        &codes::PF_MULTIPLE_PSP_NAMES,
+                        format!(
+                            "multiple PSP names found for {}: '{}' vs '{}'",
+                            record.psp_id, existing, record.psp_name
+                        ),
+                    )
+                    .on("psp_name", &record.psp_name),
+                );
             }
         } else {
             psp_names.insert(record.psp_id.clone(), record.psp_name.clone());
         }
 
+        if check_payees {
+            let seen = payee_values
+                .entry((record.psp_id.clone(), record.payee_id.clone()))
+                .or_insert_with(|| vec![Vec::new(); PAYEE_ATTRIBUTES.len()]);
+            for ((field, get), values) in PAYEE_ATTRIBUTES.iter().zip(seen.iter_mut()) {
+                let Some(value) = get(&record)
+                    .map(|value| value.trim())
+                    .filter(|value| !value.is_empty())
+                else {
+                    continue;
+                };
+                if values.iter().any(|(seen, _)| seen == value) {
+                    continue;
+                }
+                for (other, other_row) in values.iter() {
+                    payee_conflict_count += 1;
+                    if payee_conflicts.len() < options.max_examples {
+                        payee_conflicts.push(PayeeConflict {
+                            psp_id: record.psp_id.clone(),
+                            payee_id: record.payee_id.clone(),
+                            field,
+                            value: other.clone(),
+                            row: *other_row,
+                            other_value: value.to_string(),
+                            other_row: row,
+                        });
+                    }
+                    issues.push(
+                        issue_with(
+                            &codes::PF_PAYEE_ATTRIBUTE_CONFLICT,
+                            format!("{} differs between rows of the same payee", field),
+                        )
+                        .on(field, value),
+                    );
+                }
+                values.push((value.to_string(), row));
+            }
+        }
+
+        for found in issues {
+            let field = found.field.unwrap_or_default();
+            log::debug!(
+                file:% = path.display(),
+                row = row,
+                payment_id = record.payment_id.as_str(),
+                payee_id = record.payee_id.as_str(),
+                code = found.code,
+                field = field,
+                value = logging::redact_field(field, found.value.as_deref().unwrap_or_default())
+                    .as_str();
+                "preflight issue"
+            );
+            collector.add(row, &record.payment_id, found);
+        }
+
+        record_rows.push(row);
         records.push(record);
     }
+    timings.add("csv_read_validate", read_start.elapsed());
+
+    // Ties go to the earliest quarter.
+    let main_period = periods
+        .iter()
+        .max_by_key(|(period, count)| (**count, std::cmp::Reverse(**period)))
+        .map(|(period, _)| *period);
+    if let (Some(main_period), true) = (main_period, periods.len() > 1) {
+        let message = format!(
+            "{} ({})",
+            codes::PF_MIXED_PERIODS.summary,
+            period_label(main_period)
+        );
+        for (index, period) in row_periods {
+            if period != main_period {
+                let record = &records[index];
+                collector.add(
+                    record_rows[index],
+                    &record.payment_id,
+                    issue_with(&codes::PF_MIXED_PERIODS, message.clone())
+                        .on("execution_time", &record.execution_time),
+                );
+            }
+        }
+    }
 
     let payment_id_set: HashSet<String> = payment_ids;
-    for record in &records {
+    for (row, record) in record_rows.iter().zip(records.iter()) {
         if record.is_refund {
             match record.corr_payment_id.as_deref() {
                 Some(corr) if payment_id_set.contains(corr) => {}
-                Some(corr) => issues.push(PreflightIssue {
-                    level: IssueLevel::Warning,
-                    message: format!("refund references missing payment_id {}", corr),
-                }),
+                Some(corr) => collector.add(
+                    *row,
+                    &record.payment_id,
+                    issue(&codes::PF_REFUND_TARGET_MISSING).on("corr_payment_id", corr),
+                ),
                 None => {}
             }
         }
     }
 
-    let report = analyze_threshold_records(&records, threshold, include_refunds)?;
+    let report = timings.time("threshold", || {
+        analyze_threshold_records(&records, options.threshold, options.include_refunds)
+    })?;
+    let borderline_payees = match options.proximity {
+        Some(margin) => Some(timings.time("threshold_proximity", || {
+            threshold_proximity(&records, options.threshold, options.include_refunds, margin)
+        })?),
+        None => None,
+    };
 
     Ok(PreflightReport {
-        threshold,
+        threshold: options.threshold,
         total_records: report.total_records,
         cross_border_records: report.cross_border_records,
         total_payees: report.total_payees,
         payees_over_threshold: report.payees_over_threshold,
-        issues,
+        periods: periods
+            .into_iter()
+            .map(|(period, count)| (period_label(period), count))
+            .collect(),
+        payee_conflicts,
+        payee_conflict_count,
+        borderline_payees,
+        rules: collector.rules,
     })
 }
 
-fn validate_record(record: &PaymentRecord, issues: &mut Vec<PreflightIssue>) {
+fn validate_record(record: &PaymentRecord, params: &RuleParams, issues: &mut Vec<PreflightIssue>) {
     if record.payment_id.trim().is_empty() {
-        issues.push(issue(IssueLevel::Error, "payment_id is required"));
+        issues.push(issue(&codes::PF_PAYMENT_ID_REQUIRED).on("payment_id", &record.payment_id));
     }
     if record.execution_time.trim().is_empty() {
-        issues.push(issue(IssueLevel::Error, "execution_time is required"));
+        issues.push(
+            issue(&codes::PF_EXECUTION_TIME_REQUIRED).on("execution_time", &record.execution_time),
+        );
     } else if DateTime::parse_from_rfc3339(&record.execution_time).is_err() {
-        issues.push(issue(
-            IssueLevel::Error,
-            "execution_time must be RFC3339 with timezone",
-        ));
+        issues.push(
+            issue(&codes::PF_EXECUTION_TIME_FORMAT).on("execution_time", &record.execution_time),
+        );
     }
     if !is_valid_amount(&record.amount) {
-        issues.push(issue(
-            IssueLevel::Error,
-            "amount must be a decimal with two digits",
-        ));
+        issues.push(issue(&codes::PF_AMOUNT_FORMAT).on("amount", &record.amount));
+    } else if let Ok(amount) = record.amount.parse::<f64>() {
+        // `render` writes the absolute value, negated for refunds, so the
+        // sign is lost and the bounds apply to the absolute value.
+        if amount == 0.0 {
+            issues.push(issue(&codes::PF_AMOUNT_ZERO).on("amount", &record.amount));
+        } else {
+            if amount < 0.0 {
+                issues.push(issue(&codes::PF_AMOUNT_NEGATIVE).on("amount", &record.amount));
+            }
+            if !params.amount_in_bounds(amount.abs()) {
+                issues.push(issue(&codes::PF_AMOUNT_OUT_OF_BOUNDS).on("amount", &record.amount));
+            }
+        }
     }
-    if !is_valid_currency(&record.currency) {
-        issues.push(issue(
-            IssueLevel::Error,
-            "currency must be ISO-4217 alpha-3",
-        ));
+    if let Some(code) = currency_issue(&record.currency, params) {
+        issues.push(issue(code).on("currency", &record.currency));
     }
     if !is_valid_country(&record.payer_country) {
-        issues.push(issue(
-            IssueLevel::Error,
-            "payer_country must be ISO-3166 alpha-2",
-        ));
+        issues.push(
+            issue(&codes::PF_PAYER_COUNTRY_FORMAT).on("payer_country", &record.payer_country),
+        );
+    } else if record.payer_country == NORTHERN_IRELAND {
+        issues.push(
+            issue(&codes::PF_PAYER_NORTHERN_IRELAND).on("payer_country", &record.payer_country),
+        );
     } else if !is_eu_member_state(&record.payer_country) {
-        issues.push(issue(
-            IssueLevel::Error,
-            "payer_country must be an EU Member State",
-        ));
+        issues.push(
+            issue(&codes::PF_PAYER_COUNTRY_NOT_EU).on("payer_country", &record.payer_country),
+        );
     }
     let derived_payee_country = match resolve_payee_country(record) {
         Ok(country) => Some(country),
         Err(err) => {
-            issues.push(issue(IssueLevel::Error, &err));
+            issues.push(
+                issue_with(&codes::PF_PAYEE_COUNTRY_UNDERIVABLE, err)
+                    .on("payee_account", &record.payee_account),
+            );
             None
         }
     };
     let provided_payee_country = normalize_country_code(&record.payee_country);
     if record.payee_country.trim().is_empty() {
         if derived_payee_country.is_some() {
-            issues.push(issue(
-                IssueLevel::Warning,
-                "payee_country missing; derived from identifier",
-            ));
+            issues.push(
+                issue(&codes::PF_PAYEE_COUNTRY_DERIVED).on("payee_country", &record.payee_country),
+            );
         }
     } else if provided_payee_country.is_none() {
-        issues.push(issue(
-            IssueLevel::Error,
-            "payee_country must be ISO-3166 alpha-2",
-        ));
-    } else if let (Some(provided), Some(derived)) =
-        (provided_payee_country.as_deref(), derived_payee_country.as_deref())
-    {
+        issues.push(
+            issue(&codes::PF_PAYEE_COUNTRY_FORMAT).on("payee_country", &record.payee_country),
+        );
+    } else if let (Some(provided), Some(derived)) = (
+        provided_payee_country.as_deref(),
+        derived_payee_country.as_deref(),
+    ) {
         if provided != derived {
-            issues.push(issue(
-                IssueLevel::Error,
-                "payee_country does not match derived location",
-            ));
+            issues.push(
+                issue(&codes::PF_PAYEE_COUNTRY_MISMATCH).on("payee_country", &record.payee_country),
+            );
+        }
+    }
+    if let (Some(postcode), Some(country)) = (
+        record
+            .payee_postcode
+            .as_deref()
+            .filter(|value| !value.trim().is_empty()),
+        derived_payee_country
+            .as_deref()
+            .or(provided_payee_country.as_deref()),
+    ) {
+        if postcode_matches(country, postcode) == Some(false) {
+            issues.push(issue(&codes::PF_POSTCODE_MISMATCH).on("payee_postcode", postcode));
         }
     }
     if let Some(derived) = derived_payee_country.as_deref() {
         if record.payer_country == derived {
-            issues.push(issue(
-                IssueLevel::Warning,
-                "payment is not cross-border (not reportable)",
-            ));
+            issues.push(
+                issue(&codes::PF_NOT_CROSS_BORDER).on("payer_country", &record.payer_country),
+            );
         }
     }
     if record.payee_id.trim().is_empty() {
-        issues.push(issue(IssueLevel::Error, "payee_id is required"));
+        issues.push(issue(&codes::PF_PAYEE_ID_REQUIRED).on("payee_id", &record.payee_id));
     }
     if record.payee_name.trim().is_empty() {
-        issues.push(issue(IssueLevel::Error, "payee_name is required"));
+        issues.push(issue(&codes::PF_PAYEE_NAME_REQUIRED).on("payee_name", &record.payee_name));
     }
     let has_payee_account = !record.payee_account.trim().is_empty();
     if !has_payee_account {
@@ -197,84 +653,217 @@ fn validate_record(record: &PaymentRecord, issues: &mut Vec<PreflightIssue>) {
             .map(is_valid_bic)
             .unwrap_or(false);
         if has_representative {
-            issues.push(issue(
-                IssueLevel::Warning,
-                "payee_account missing; representative PSP will be reported",
+            issues.push(issue(&codes::PF_REPRESENTATIVE_REPORTED).on(
+                "payee_psp_id",
+                record.payee_psp_id.as_deref().unwrap_or_default(),
             ));
         } else {
-            issues.push(issue(
-                IssueLevel::Error,
-                "payee_account missing without a valid payee PSP identifier",
-            ));
+            issues.push(
+                issue(&codes::PF_PAYEE_ACCOUNT_MISSING).on("payee_account", &record.payee_account),
+            );
         }
         if !record.payee_account_type.trim().is_empty() {
-            issues.push(issue(
-                IssueLevel::Warning,
-                "payee_account_type provided without payee_account",
-            ));
+            issues.push(
+                issue(&codes::PF_ACCOUNT_TYPE_WITHOUT_ACCOUNT)
+                    .on("payee_account_type", &record.payee_account_type),
+            );
         }
-    } else if !ACCOUNT_IDENTIFIER_TYPES
-        .iter()
-        .any(|value| *value == record.payee_account_type)
-    {
-        issues.push(issue(
-            IssueLevel::Error,
-            "payee_account_type must be IBAN/OBAN/BIC/Other",
-        ));
+    } else if !params.allows_account_type(&record.payee_account_type) {
+        let message = match &params.account_types {
+            Some(types) => format!("payee_account_type must be {}", types.join("/")),
+            None => codes::PF_ACCOUNT_TYPE_INVALID.summary.to_string(),
+        };
+        issues.push(
+            issue_with(&codes::PF_ACCOUNT_TYPE_INVALID, message)
+                .on("payee_account_type", &record.payee_account_type),
+        );
     } else if record.payee_account_type == "IBAN" {
         let country = account_country_code("IBAN", &record.payee_account)
             .or_else(|| normalize_country_code(&record.payee_country));
         if let Some(code) = country {
             validate_iban(&record.payee_account, &code, issues);
         } else {
-            issues.push(issue(IssueLevel::Error, "IBAN country code missing"));
+            issues.push(
+                issue(&codes::PF_IBAN_COUNTRY_MISSING).on("payee_account", &record.payee_account),
+            );
         }
     }
     if !ACCOUNT_IDENTIFIER_TYPES
         .iter()
         .any(|value| *value == record.payer_ms_source)
     {
-        issues.push(issue(
-            IssueLevel::Error,
-            "payer_ms_source must be IBAN/OBAN/BIC/Other",
-        ));
+        issues.push(
+            issue(&codes::PF_PAYER_MS_SOURCE_INVALID)
+                .on("payer_ms_source", &record.payer_ms_source),
+        );
+    }
+    if let Some((country, source)) = derive_payer_ms(record) {
+        if record.payer_country != country {
+            issues.push(
+                issue(&codes::PF_PAYER_COUNTRY_ACCOUNT_MISMATCH)
+                    .on("payer_country", &record.payer_country),
+            );
+        }
+        if record.payer_ms_source != source {
+            issues.push(
+                issue(&codes::PF_PAYER_MS_SOURCE_ACCOUNT_MISMATCH)
+                    .on("payer_ms_source", &record.payer_ms_source),
+            );
+        }
     }
     if record.payment_method.trim().is_empty() {
-        issues.push(issue(IssueLevel::Error, "payment_method is required"));
+        issues.push(
+            issue(&codes::PF_PAYMENT_METHOD_REQUIRED).on("payment_method", &record.payment_method),
+        );
     }
     if record.is_refund && record.corr_payment_id.is_none() {
-        issues.push(issue(
-            IssueLevel::Error,
-            "refunds must include corr_payment_id",
+        issues.push(issue(&codes::PF_REFUND_LINK_REQUIRED).on(
+            "corr_payment_id",
+            record.corr_payment_id.as_deref().unwrap_or_default(),
         ));
     }
     if !record.is_refund && record.corr_payment_id.is_some() {
-        issues.push(issue(
-            IssueLevel::Warning,
-            "corr_payment_id set on non-refund",
+        issues.push(issue(&codes::PF_LINK_ON_NON_REFUND).on(
+            "corr_payment_id",
+            record.corr_payment_id.as_deref().unwrap_or_default(),
         ));
     }
     if record.psp_id.trim().is_empty() {
-        issues.push(issue(IssueLevel::Error, "psp_id is required"));
+        issues.push(issue(&codes::PF_PSP_ID_REQUIRED).on("psp_id", &record.psp_id));
     } else if !is_valid_bic(&record.psp_id) {
-        issues.push(issue(
-            IssueLevel::Warning,
-            "psp_id is not a valid BIC format",
-        ));
+        issues.push(issue(&codes::PF_PSP_ID_FORMAT).on("psp_id", &record.psp_id));
     }
     if record.psp_name.trim().is_empty() {
-        issues.push(issue(IssueLevel::Error, "psp_name is required"));
+        issues.push(issue(&codes::PF_PSP_NAME_REQUIRED).on("psp_name", &record.psp_name));
+    }
+}
+
+// Checks every BIC of the row against the ISO 9362 structure. Issues name
+// the column and the part at fault.
+fn validate_bics(record: &PaymentRecord, issues: &mut Vec<PreflightIssue>) {
+    let is_bic = |account_type: &str| account_type.trim().eq_ignore_ascii_case("BIC");
+    let mut bics = vec![("psp_id", record.psp_id.as_str())];
+    if let Some(id) = record.payee_psp_id.as_deref() {
+        bics.push(("payee_psp_id", id));
+    }
+    if is_bic(&record.payee_account_type) {
+        bics.push(("payee_account", record.payee_account.as_str()));
+    }
+    if let (Some(account), Some(account_type)) = (
+        record.payer_account.as_deref(),
+        record.payer_account_type.as_deref(),
+    ) {
+        if is_bic(account_type) {
+            bics.push(("payer_account", account));
+        }
+    }
+    for (field, bic) in bics {
+        if bic.trim().is_empty() {
+            continue;
+        }
+        if let Some(reason) = bic_structure_error(bic.trim()) {
+            issues.push(
+                issue_with(&codes::PF_BIC_STRUCTURE, format!("{}: {}", field, reason))
+                    .on(field, bic),
+            );
+        }
     }
 }
 
-fn issue(level: IssueLevel, message: &str) -> PreflightIssue {
+// ISO 9362: a 4-letter bank code, an ISO 3166 country (or XK, which SWIFT
+// uses for Kosovo), a 2-character location code whose first character is not
+// 0 or 1 and whose second is not the letter O, and an optional 3-character
+// branch code starting with X only as XXX.
+fn bic_structure_error(bic: &str) -> Option<&'static str> {
+    if !(bic.len() == 8 || bic.len() == 11) {
+        return Some("BIC must have 8 or 11 characters");
+    }
+    if !bic
+        .chars()
+        .all(|ch| ch.is_ascii_uppercase() || ch.is_ascii_digit())
+    {
+        return Some("BIC must be upper-case letters and digits");
+    }
+    let country = &bic[4..6];
+    let location = &bic.as_bytes()[6..8];
+    let branch = &bic[8..];
+    if !bic[0..4].chars().all(|ch| ch.is_ascii_uppercase()) {
+        Some("bank code must be 4 letters")
+    } else if !is_iso_country(country) && country != "XK" {
+        Some("country code is not an ISO 3166 country")
+    } else if matches!(location[0], b'0' | b'1') {
+        Some("location code must not start with 0 or 1")
+    } else if location[1] == b'O' {
+        Some("location code must not end in the letter O")
+    } else if branch.starts_with('X') && branch != "XXX" {
+        Some("branch code must not start with X unless it is XXX")
+    } else {
+        None
+    }
+}
+
+// Checks payment_method against the code list as render maps it. Issues name
+// the value, so each distinct spelling gets its own rule.
+fn validate_payment_method(
+    record: &PaymentRecord,
+    mode: PaymentMethodMode,
+    issues: &mut Vec<PreflightIssue>,
+) {
+    let value = record.payment_method.trim();
+    if value.is_empty() {
+        return;
+    }
+    match classify_payment_method(value) {
+        PaymentMethodMatch::Code(_) => {}
+        PaymentMethodMatch::Alias(code) => issues.push(
+            issue_with(
+                &codes::PF_PAYMENT_METHOD_ALIAS,
+                format!("payment_method '{}' is reported as '{}'", value, code),
+            )
+            .on("payment_method", value),
+        ),
+        PaymentMethodMatch::Unknown => {
+            let code = match mode {
+                PaymentMethodMode::Strict => &codes::PF_PAYMENT_METHOD_UNKNOWN,
+                PaymentMethodMode::Lenient => &codes::PF_PAYMENT_METHOD_OTHER,
+            };
+            issues.push(
+                issue_with(code, format!("{}: '{}'", code.summary, value))
+                    .on("payment_method", value),
+            );
+        }
+    }
+}
+
+fn issue(code: &Code) -> PreflightIssue {
+    issue_with(code, code.summary.to_string())
+}
+
+fn issue_with(code: &Code, message: String) -> PreflightIssue {
     PreflightIssue {
-        level,
-        message: message.to_string(),
+        code: code.code,
+        level: code.level,
+        message,
+        field: None,
+        value: None,
     }
 }
 
+// Quarter of an execution_time, counted in UTC as `render` does.
+fn period_of(execution_time: &str) -> Option<(i32, u8)> {
+    let time = DateTime::parse_from_rfc3339(execution_time.trim())
+        .ok()?
+        .with_timezone(&Utc);
+    Some((time.year(), ((time.month() - 1) / 3 + 1) as u8))
+}
+
+fn period_label((year, quarter): (i32, u8)) -> String {
+    format!("{}Q{}", year, quarter)
+}
+
+// A negative amount is well-formed; its sign is checked separately.
 fn is_valid_amount(amount: &str) -> bool {
+    let amount = amount.strip_prefix('-').unwrap_or(amount);
     let mut parts = amount.split('.');
     let whole = match parts.next() {
         Some(part) if !part.is_empty() => part,
@@ -292,6 +881,23 @@ fn is_valid_amount(amount: &str) -> bool {
     }
 }
 
+fn currency_issue(currency: &str, params: &RuleParams) -> Option<&'static Code> {
+    if !is_valid_currency(currency) {
+        return Some(&codes::PF_CURRENCY_FORMAT);
+    }
+    if let Some(allowed) = &params.currencies {
+        return (!allowed.iter().any(|code| code == currency))
+            .then_some(&codes::PF_CURRENCY_NOT_ALLOWED);
+    }
+    if is_cesop_currency(currency) {
+        None
+    } else if is_iso_currency(currency) {
+        Some(&codes::PF_CURRENCY_NOT_CESOP)
+    } else {
+        Some(&codes::PF_CURRENCY_UNKNOWN)
+    }
+}
+
 fn is_valid_currency(code: &str) -> bool {
     code.len() == 3 && code.chars().all(|ch| ch.is_ascii_uppercase())
 }
@@ -312,46 +918,32 @@ fn is_valid_bic(bic: &str) -> bool {
     country.chars().all(|ch| ch.is_ascii_alphabetic())
 }
 
-fn validate_iban(
-    iban: &str,
-    country: &str,
-    issues: &mut Vec<PreflightIssue>,
-) {
+fn validate_iban(iban: &str, country: &str, issues: &mut Vec<PreflightIssue>) {
     if iban.len() < 4 {
-        issues.push(issue(IssueLevel::Error, "IBAN is too short"));
+        issues.push(issue(&codes::PF_IBAN_TOO_SHORT).on("payee_account", iban));
         return;
     }
     if !iban.chars().all(|ch| ch.is_ascii_alphanumeric()) {
-        issues.push(issue(IssueLevel::Error, "IBAN must be alphanumeric"));
+        issues.push(issue(&codes::PF_IBAN_NOT_ALPHANUMERIC).on("payee_account", iban));
     }
     let iban_country = &iban[0..2];
     if iban_country != country {
-        issues.push(issue(
-            IssueLevel::Error,
-            "IBAN country code does not match derived payee country",
-        ));
+        issues.push(issue(&codes::PF_IBAN_COUNTRY_MISMATCH).on("payee_account", iban));
     }
     if let Some(expected) = iban_length(country) {
         if iban.len() != expected {
-            issues.push(issue(
-                IssueLevel::Error,
-                "IBAN length does not match country specification",
-            ));
+            issues.push(issue(&codes::PF_IBAN_LENGTH).on("payee_account", iban));
+        } else if bban_matches(country, &iban[4..]) == Some(false) {
+            issues.push(issue(&codes::PF_IBAN_BBAN_FORMAT).on("payee_account", iban));
         }
     } else {
-        issues.push(issue(
-            IssueLevel::Warning,
-            "IBAN length not known for country",
-        ));
+        issues.push(issue(&codes::PF_IBAN_LENGTH_UNKNOWN).on("payee_account", iban));
     }
     let check_digits = &iban[2..4];
     let bban = &iban[4..];
     if let Ok(expected) = iban_check_digits(iban_country, bban) {
         if expected != check_digits {
-            issues.push(issue(
-                IssueLevel::Error,
-                "IBAN check digits are invalid",
-            ));
+            issues.push(issue(&codes::PF_IBAN_CHECK_DIGITS).on("payee_account", iban));
         }
     }
 }
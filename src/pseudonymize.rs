@@ -0,0 +1,308 @@
+use crate::codes;
+use crate::input::{read_payment_records, CsvReadOptions};
+use crate::models::PaymentRecord;
+use crate::util::iban_check_digits;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::path::Path;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const NAME_PREFIX: &[&str] = &[
+    "Silver", "North", "Blue", "Cobalt", "Summit", "Urban", "Prime", "Atlas", "Green", "Nova",
+    "Bright", "Vertex", "Golden", "River", "Oak", "Pioneer",
+];
+const NAME_NOUN: &[&str] = &[
+    "Trading", "Supply", "Commerce", "Retail", "Imports", "Foods", "Devices", "Logistics",
+];
+const NAME_LEGAL: &[&str] = &["BV", "NV", "Ltd", "LLC", "GmbH", "SA"];
+const STREETS: &[&str] = &[
+    "Market", "Station", "Oak", "River", "Park", "Hill", "Lake", "Maple", "Mill", "Garden",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct PseudonymizeSummary {
+    pub total_records: usize,
+    pub payee_ids: usize,
+    pub names: usize,
+    pub emails: usize,
+    pub webs: usize,
+    pub accounts: usize,
+    pub tax_ids: usize,
+    pub addresses: usize,
+}
+
+pub fn pseudonymize_csv(
+    input: &Path,
+    output: &Path,
+    key: &[u8],
+    csv_options: &CsvReadOptions,
+) -> Result<PseudonymizeSummary, String> {
+    if key.is_empty() {
+        return Err(codes::GEN_PSEUDONYMIZE_KEY_EMPTY.err("pseudonymization key must not be empty"));
+    }
+    let pseudonymizer = Pseudonymizer { key };
+    let mut records = read_payment_records(input, csv_options)?;
+    let mut summary = PseudonymizeSummary::default();
+    for record in &mut records {
+        summary.total_records += 1;
+        pseudonymizer.apply(record, &mut summary)?;
+    }
+
+    let mut writer = csv::Writer::from_path(output).map_err(|err| err.to_string())?;
+    for record in &records {
+        writer.serialize(record).map_err(|err| err.to_string())?;
+    }
+    writer.flush().map_err(|err| err.to_string())?;
+    Ok(summary)
+}
+
+struct Pseudonymizer<'a> {
+    key: &'a [u8],
+}
+
+impl Pseudonymizer<'_> {
+    fn apply(
+        &self,
+        record: &mut PaymentRecord,
+        summary: &mut PseudonymizeSummary,
+    ) -> Result<(), String> {
+        if !record.payee_id.trim().is_empty() {
+            let prefix = letter_prefix(&record.payee_id);
+            record.payee_id = self.same_shape("payee_id", &record.payee_id, prefix);
+            summary.payee_ids += 1;
+        }
+        if !record.payee_name.trim().is_empty() {
+            record.payee_name = self.company_name(&record.payee_name);
+            summary.names += 1;
+        }
+        if let Some(name) = record
+            .sub_merchant_name
+            .as_mut()
+            .filter(|value| !value.trim().is_empty())
+        {
+            *name = self.company_name(name);
+            summary.names += 1;
+        }
+        // Generated sub-merchant ids extend the payee id, so they follow it.
+        if let Some(id) = record.sub_merchant_id.as_mut().filter(|value| !value.is_empty()) {
+            *id = match id.rsplit_once('-') {
+                Some((_, suffix)) => format!("{}-{}", record.payee_id, suffix),
+                None => self.same_shape("sub_merchant_id", id, letter_prefix(id)),
+            };
+        }
+        if let Some(email) = record.payee_email.as_mut().filter(|value| !value.is_empty()) {
+            *email = format!("billing@{}.example", self.token("email", email, 10));
+            summary.emails += 1;
+        }
+        if let Some(web) = record.payee_web.as_mut().filter(|value| !value.is_empty()) {
+            *web = format!("https://{}.example", self.token("web", web, 10));
+            summary.webs += 1;
+        }
+        if !record.payee_account.trim().is_empty()
+            && !record.payee_account_type.eq_ignore_ascii_case("BIC")
+        {
+            record.payee_account =
+                self.account(&record.payee_account_type, &record.payee_account)?;
+            summary.accounts += 1;
+        }
+        if let (Some(account), Some(account_type)) =
+            (record.payer_account.as_mut(), record.payer_account_type.as_deref())
+        {
+            if !account.trim().is_empty() && !account_type.eq_ignore_ascii_case("BIC") {
+                *account = self.account(account_type, account)?;
+                summary.accounts += 1;
+            }
+        }
+        for tax_id in [record.payee_tax_id.as_mut(), record.payee_vat_id.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            if !tax_id.is_empty() {
+                *tax_id = self.same_shape("tax_id", tax_id, letter_prefix(tax_id));
+                summary.tax_ids += 1;
+            }
+        }
+        if let Some(address) = record
+            .payee_address_line
+            .as_mut()
+            .filter(|value| !value.is_empty())
+        {
+            let digest = self.digest("address", address, 0);
+            *address = format!(
+                "{} {} St",
+                1 + u16::from_be_bytes([digest[0], digest[1]]) % 249,
+                STREETS[digest[2] as usize % STREETS.len()]
+            );
+            summary.addresses += 1;
+        }
+        if let Some(postcode) = record.payee_postcode.as_mut() {
+            *postcode = self.same_shape("postcode", postcode, letter_prefix(postcode));
+        }
+        Ok(())
+    }
+
+    fn company_name(&self, value: &str) -> String {
+        let digest = self.digest("name", value, 0);
+        format!(
+            "{} {} {:02X}{:02X} {}",
+            NAME_PREFIX[digest[0] as usize % NAME_PREFIX.len()],
+            NAME_NOUN[digest[1] as usize % NAME_NOUN.len()],
+            digest[2],
+            digest[3],
+            NAME_LEGAL[digest[4] as usize % NAME_LEGAL.len()]
+        )
+    }
+
+    // IBANs keep country, length and valid check digits, and lose any space
+    // grouping; other identifiers, and IBANs that do not look like one, keep
+    // their two-letter country prefix and character classes.
+    fn account(&self, account_type: &str, value: &str) -> Result<String, String> {
+        let value = value.trim();
+        if account_type.eq_ignore_ascii_case("IBAN") {
+            let iban: String = value
+                .chars()
+                .filter(|ch| !ch.is_whitespace())
+                .map(|ch| ch.to_ascii_uppercase())
+                .collect();
+            if is_iban_shaped(&iban) {
+                let country = &iban[..2];
+                let bban = self.same_shape("account", &iban[4..], 0);
+                let check = iban_check_digits(country, &bban)?;
+                return Ok(format!("{}{}{}", country, check, bban));
+            }
+        }
+        Ok(self.same_shape("account", value, 2))
+    }
+
+    fn same_shape(&self, domain: &str, value: &str, keep_prefix: usize) -> String {
+        let mut stream = self.stream(domain, value);
+        value
+            .chars()
+            .enumerate()
+            .map(|(idx, ch)| {
+                if idx < keep_prefix && ch.is_ascii_alphabetic() {
+                    return ch;
+                }
+                let byte = stream.next_byte();
+                if ch.is_ascii_digit() {
+                    char::from(b'0' + byte % 10)
+                } else if ch.is_ascii_uppercase() {
+                    char::from(b'A' + byte % 26)
+                } else if ch.is_ascii_lowercase() {
+                    char::from(b'a' + byte % 26)
+                } else {
+                    ch
+                }
+            })
+            .collect()
+    }
+
+    fn token(&self, domain: &str, value: &str, len: usize) -> String {
+        let mut stream = self.stream(domain, value);
+        (0..len)
+            .map(|_| {
+                const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+                char::from(CHARSET[stream.next_byte() as usize % CHARSET.len()])
+            })
+            .collect()
+    }
+
+    fn digest(&self, domain: &str, value: &str, counter: u32) -> [u8; 32] {
+        let mut mac =
+            HmacSha256::new_from_slice(self.key).expect("HMAC accepts keys of any length");
+        mac.update(domain.as_bytes());
+        mac.update(&[0]);
+        mac.update(value.trim().as_bytes());
+        mac.update(&counter.to_be_bytes());
+        mac.finalize().into_bytes().into()
+    }
+
+    fn stream<'b>(&'b self, domain: &'b str, value: &'b str) -> DigestStream<'b> {
+        DigestStream {
+            pseudonymizer: self,
+            domain,
+            value,
+            counter: 0,
+            block: self.digest(domain, value, 0),
+            offset: 0,
+        }
+    }
+}
+
+// Two letters, two check digits and an alphanumeric BBAN, all ASCII.
+fn is_iban_shaped(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    bytes.len() > 4
+        && bytes[..2].iter().all(u8::is_ascii_uppercase)
+        && bytes[2..4].iter().all(u8::is_ascii_digit)
+        && bytes[4..]
+            .iter()
+            .all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit())
+}
+
+fn letter_prefix(value: &str) -> usize {
+    value
+        .chars()
+        .take_while(|ch| ch.is_ascii_alphabetic())
+        .count()
+}
+
+struct DigestStream<'a> {
+    pseudonymizer: &'a Pseudonymizer<'a>,
+    domain: &'a str,
+    value: &'a str,
+    counter: u32,
+    block: [u8; 32],
+    offset: usize,
+}
+
+impl DigestStream<'_> {
+    fn next_byte(&mut self) -> u8 {
+        if self.offset == self.block.len() {
+            self.counter += 1;
+            self.block = self
+                .pseudonymizer
+                .digest(self.domain, self.value, self.counter);
+            self.offset = 0;
+        }
+        let byte = self.block[self.offset];
+        self.offset += 1;
+        byte
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"test-key";
+
+    fn valid_iban(iban: &str) -> bool {
+        iban_check_digits(&iban[..2], &iban[4..]).as_deref() == Ok(&iban[2..4])
+    }
+
+    #[test]
+    fn grouped_iban_is_pseudonymized_as_an_iban() {
+        let pseudonymizer = Pseudonymizer { key: KEY };
+        let grouped = pseudonymizer
+            .account("IBAN", "IE29 AIBK 9311 5212 3456 78")
+            .unwrap();
+        let plain = pseudonymizer
+            .account("IBAN", "IE29AIBK93115212345678")
+            .unwrap();
+        assert_eq!(grouped, plain);
+        assert_eq!(grouped.len(), 22);
+        assert!(grouped.starts_with("IE"));
+        assert!(valid_iban(&grouped));
+    }
+
+    #[test]
+    fn non_ascii_account_keeps_its_shape() {
+        let pseudonymizer = Pseudonymizer { key: KEY };
+        let value = "ÄÖ12 Ü345";
+        let account = pseudonymizer.account("IBAN", value).unwrap();
+        assert_eq!(account.chars().count(), value.chars().count());
+        assert_eq!(account.chars().nth(4), Some(' '));
+    }
+}
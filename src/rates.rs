@@ -0,0 +1,70 @@
+use crate::codes;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+// Euro reference rates (units per EUR) read from a rate table CSV. There is
+// no built-in table: EUR totals are only reported from rates the caller
+// supplies for the reporting period.
+#[derive(Debug, Clone)]
+pub struct RateTable {
+    source: String,
+    rates: HashMap<(String, Option<String>), f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RateRow {
+    currency: String,
+    units_per_eur: f64,
+    #[serde(default)]
+    period: Option<String>,
+}
+
+impl RateTable {
+    pub fn from_csv(path: &Path) -> Result<Self, String> {
+        let mut reader = csv::Reader::from_path(path).map_err(|err| {
+            codes::IN_READ_FAILED.err(format!(
+                "failed to open rate table {}: {}",
+                path.display(),
+                err
+            ))
+        })?;
+        let mut rates = HashMap::new();
+        rates.insert(("EUR".to_string(), None), 1.0);
+        for (idx, row) in reader.deserialize::<RateRow>().enumerate() {
+            let row = row.map_err(|err| {
+                codes::PF_RATES_INVALID.err(format!("{} row {}: {}", path.display(), idx + 1, err))
+            })?;
+            if row.units_per_eur.is_nan() || row.units_per_eur <= 0.0 {
+                return Err(codes::PF_RATES_INVALID.err(format!(
+                    "{} row {}: units_per_eur must be positive",
+                    path.display(),
+                    idx + 1
+                )));
+            }
+            let period = row
+                .period
+                .map(|period| period.trim().to_uppercase())
+                .filter(|period| !period.is_empty());
+            rates.insert((row.currency.trim().to_uppercase(), period), row.units_per_eur);
+        }
+        Ok(Self {
+            source: path.display().to_string(),
+            rates,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    // Period-specific rates (e.g. "2025-Q1") win over period-less ones.
+    pub fn to_eur(&self, amount: f64, currency: &str, period: &str) -> Option<f64> {
+        let currency = currency.trim().to_uppercase();
+        let rate = self
+            .rates
+            .get(&(currency.clone(), Some(period.to_string())))
+            .or_else(|| self.rates.get(&(currency, None)))?;
+        Some(amount / rate)
+    }
+}
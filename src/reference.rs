@@ -3,6 +3,38 @@ pub const EU_MEMBER_STATES: &[&str] = &[
     "LV", "LT", "LU", "MT", "NL", "PL", "PT", "RO", "SK", "SI", "ES", "SE",
 ];
 
+// Approximate share of EU card-not-present e-commerce volume per Member State
+// (percent), used by `--country-weights realistic`.
+pub const EU_COUNTRY_WEIGHTS: &[(&str, f64)] = &[
+    ("DE", 22.0),
+    ("FR", 17.0),
+    ("IT", 10.0),
+    ("ES", 9.0),
+    ("NL", 7.0),
+    ("PL", 5.0),
+    ("SE", 4.0),
+    ("BE", 3.5),
+    ("AT", 3.0),
+    ("DK", 2.5),
+    ("IE", 2.5),
+    ("FI", 2.0),
+    ("CZ", 2.0),
+    ("PT", 2.0),
+    ("RO", 1.5),
+    ("GR", 1.5),
+    ("HU", 1.2),
+    ("SK", 0.8),
+    ("BG", 0.6),
+    ("HR", 0.5),
+    ("SI", 0.4),
+    ("LT", 0.4),
+    ("LU", 0.4),
+    ("LV", 0.3),
+    ("EE", 0.3),
+    ("CY", 0.2),
+    ("MT", 0.1),
+];
+
 pub const IBAN_LENGTHS: &[(&str, usize)] = &[
     ("AT", 20),
     ("BE", 16),
@@ -38,6 +70,133 @@ pub const IBAN_LENGTHS: &[(&str, usize)] = &[
     ("NO", 15),
 ];
 
+// BBAN structure per country from the IBAN registry, in the postcode pattern
+// syntax. A leading `AAAA` is the bank code, which matches the bank code of
+// the bank's BIC.
+pub const BBAN_PATTERNS: &[(&str, &str)] = &[
+    ("AT", "9999999999999999"),
+    ("BE", "999999999999"),
+    ("BG", "AAAA999999XXXXXXXX"),
+    ("HR", "99999999999999999"),
+    ("CY", "99999999XXXXXXXXXXXXXXXX"),
+    ("CZ", "99999999999999999999"),
+    ("DK", "99999999999999"),
+    ("EE", "9999999999999999"),
+    ("FI", "99999999999999"),
+    ("FR", "9999999999XXXXXXXXXXX99"),
+    ("DE", "999999999999999999"),
+    ("GR", "9999999XXXXXXXXXXXXXXXX"),
+    ("HU", "999999999999999999999999"),
+    ("IE", "AAAA99999999999999"),
+    ("IT", "A9999999999XXXXXXXXXXXX"),
+    ("LV", "AAAAXXXXXXXXXXXXX"),
+    ("LT", "9999999999999999"),
+    ("LU", "999XXXXXXXXXXXXX"),
+    ("MT", "AAAA99999XXXXXXXXXXXXXXXXXX"),
+    ("NL", "AAAA9999999999"),
+    ("PL", "999999999999999999999999"),
+    ("PT", "999999999999999999999"),
+    ("RO", "AAAAXXXXXXXXXXXXXXXX"),
+    ("SK", "99999999999999999999"),
+    ("SI", "999999999999999"),
+    ("ES", "99999999999999999999"),
+    ("SE", "99999999999999999999"),
+    ("CH", "99999XXXXXXXXXXXX"),
+    ("GB", "AAAA99999999999999"),
+    ("IS", "9999999999999999999999"),
+    ("LI", "99999XXXXXXXXXXXX"),
+    ("NO", "99999999999"),
+];
+
+// Pattern syntax: 9 = digit, A = letter, X = letter or digit, anything else is
+// literal. The first pattern per country is the one generated.
+pub const POSTCODE_PATTERNS: &[(&str, &[&str])] = &[
+    ("AT", &["9999"]),
+    ("BE", &["9999"]),
+    ("BG", &["9999"]),
+    ("HR", &["99999"]),
+    ("CY", &["9999"]),
+    ("CZ", &["999 99", "99999"]),
+    ("DK", &["9999"]),
+    ("EE", &["99999"]),
+    ("FI", &["99999"]),
+    ("FR", &["99999"]),
+    ("DE", &["99999"]),
+    ("GR", &["999 99", "99999"]),
+    ("HU", &["9999"]),
+    ("IE", &["A99 XXXX", "A99XXXX"]),
+    ("IT", &["99999"]),
+    ("LV", &["LV-9999"]),
+    ("LT", &["LT-99999", "99999"]),
+    ("LU", &["L-9999", "9999"]),
+    ("MT", &["AAA 9999"]),
+    ("NL", &["9999 AA", "9999AA"]),
+    ("PL", &["99-999"]),
+    ("PT", &["9999-999"]),
+    ("RO", &["999999"]),
+    ("SK", &["999 99", "99999"]),
+    ("SI", &["9999", "SI-9999"]),
+    ("ES", &["99999"]),
+    ("SE", &["999 99", "99999"]),
+    ("GB", &["AA9 9AA", "A9 9AA", "A99 9AA", "AA99 9AA", "A9A 9AA", "AA9A 9AA"]),
+    ("NO", &["9999"]),
+    ("CH", &["9999"]),
+    ("IS", &["999"]),
+    ("LI", &["9999"]),
+    ("XI", &["BT9 9AA", "BT99 9AA"]),
+    ("US", &["99999", "99999-9999"]),
+    ("CA", &["A9A 9A9"]),
+];
+
+// Cities per country with the leading characters of their postcodes, so
+// generated addresses agree with the payee country.
+pub const CITY_POSTCODES: &[(&str, &[(&str, &str)])] = &[
+    ("AT", &[("Vienna", "1"), ("Graz", "80"), ("Linz", "40"), ("Salzburg", "50")]),
+    ("BE", &[("Brussels", "10"), ("Antwerp", "20"), ("Ghent", "90"), ("Liège", "40")]),
+    ("BG", &[("Sofia", "1"), ("Plovdiv", "40"), ("Varna", "90")]),
+    ("HR", &[("Zagreb", "10"), ("Split", "21"), ("Rijeka", "51")]),
+    ("CY", &[("Nicosia", "1"), ("Limassol", "3"), ("Larnaca", "6")]),
+    ("CZ", &[("Prague", "1"), ("Brno", "6"), ("Ostrava", "7")]),
+    ("DK", &[("Copenhagen", "1"), ("Aarhus", "8"), ("Odense", "5")]),
+    ("EE", &[("Tallinn", "1"), ("Tartu", "5")]),
+    ("FI", &[("Helsinki", "00"), ("Espoo", "02"), ("Tampere", "33"), ("Turku", "20")]),
+    ("FR", &[("Paris", "75"), ("Lyon", "69"), ("Marseille", "13"), ("Toulouse", "31")]),
+    (
+        "DE",
+        &[
+            ("Berlin", "10"),
+            ("Munich", "80"),
+            ("Hamburg", "20"),
+            ("Cologne", "50"),
+            ("Frankfurt am Main", "60"),
+        ],
+    ),
+    ("GR", &[("Athens", "10"), ("Thessaloniki", "54"), ("Patras", "26")]),
+    ("HU", &[("Budapest", "1"), ("Debrecen", "40"), ("Szeged", "67")]),
+    ("IE", &[("Dublin", "D01"), ("Cork", "T12"), ("Galway", "H91"), ("Limerick", "V94")]),
+    ("IT", &[("Rome", "00"), ("Milan", "20"), ("Naples", "80"), ("Turin", "10")]),
+    ("LV", &[("Riga", "LV-10"), ("Daugavpils", "LV-54")]),
+    ("LT", &[("Vilnius", "LT-0"), ("Kaunas", "LT-4"), ("Klaipėda", "LT-9")]),
+    ("LU", &[("Luxembourg", "L-1"), ("Esch-sur-Alzette", "L-4")]),
+    ("MT", &[("Valletta", "VLT"), ("Sliema", "SLM"), ("Birkirkara", "BKR")]),
+    ("NL", &[("Amsterdam", "10"), ("Rotterdam", "30"), ("The Hague", "25"), ("Utrecht", "35")]),
+    ("PL", &[("Warsaw", "0"), ("Kraków", "3"), ("Gdańsk", "80"), ("Wrocław", "5")]),
+    ("PT", &[("Lisbon", "1"), ("Porto", "4"), ("Braga", "47")]),
+    ("RO", &[("Bucharest", "0"), ("Cluj-Napoca", "40"), ("Timișoara", "30")]),
+    ("SK", &[("Bratislava", "8"), ("Košice", "04")]),
+    ("SI", &[("Ljubljana", "1"), ("Maribor", "2")]),
+    ("ES", &[("Madrid", "28"), ("Barcelona", "08"), ("Valencia", "46"), ("Seville", "41")]),
+    ("SE", &[("Stockholm", "1"), ("Gothenburg", "4"), ("Malmö", "2")]),
+    ("GB", &[("London", "EC1"), ("Manchester", "M1"), ("Edinburgh", "EH1")]),
+    ("NO", &[("Oslo", "0"), ("Bergen", "50")]),
+    ("CH", &[("Zurich", "80"), ("Geneva", "12"), ("Basel", "40")]),
+    ("IS", &[("Reykjavík", "10")]),
+    ("LI", &[("Vaduz", "949")]),
+    ("XI", &[("Belfast", "BT1"), ("Derry", "BT48"), ("Newry", "BT34")]),
+    ("US", &[("New York", "100"), ("San Francisco", "941"), ("Chicago", "606")]),
+    ("CA", &[("Toronto", "M5V"), ("Montreal", "H2X"), ("Vancouver", "V6B")]),
+];
+
 pub const ACCOUNT_IDENTIFIER_TYPES: &[&str] = &["IBAN", "OBAN", "BIC", "Other"];
 
 pub fn iban_length(country: &str) -> Option<usize> {
@@ -47,8 +206,65 @@ pub fn iban_length(country: &str) -> Option<usize> {
         .map(|(_, len)| *len)
 }
 
+pub fn bban_pattern(country: &str) -> Option<&'static str> {
+    BBAN_PATTERNS
+        .iter()
+        .find(|(code, _)| *code == country)
+        .map(|(_, pattern)| *pattern)
+}
+
+// None when no BBAN structure is known for the country.
+pub fn bban_matches(country: &str, bban: &str) -> Option<bool> {
+    let pattern = bban_pattern(country)?;
+    Some(pattern_matches(pattern, &bban.trim().to_ascii_uppercase()))
+}
+
+pub fn postcode_patterns(country: &str) -> Option<&'static [&'static str]> {
+    POSTCODE_PATTERNS
+        .iter()
+        .find(|(code, _)| *code == country)
+        .map(|(_, patterns)| *patterns)
+}
+
+pub fn city_postcodes(country: &str) -> Option<&'static [(&'static str, &'static str)]> {
+    CITY_POSTCODES
+        .iter()
+        .find(|(code, _)| *code == country)
+        .map(|(_, cities)| *cities)
+}
+
+// None when no format is known for the country.
+pub fn postcode_matches(country: &str, postcode: &str) -> Option<bool> {
+    let postcode = postcode.trim().to_ascii_uppercase();
+    let patterns = postcode_patterns(country)?;
+    Some(patterns.iter().any(|pattern| pattern_matches(pattern, &postcode)))
+}
+
+fn pattern_matches(pattern: &str, value: &str) -> bool {
+    pattern.len() == value.len()
+        && pattern.chars().zip(value.chars()).all(|(p, c)| match p {
+            '9' => c.is_ascii_digit(),
+            'A' => c.is_ascii_uppercase(),
+            'X' => c.is_ascii_digit() || c.is_ascii_uppercase(),
+            literal => literal == c,
+        })
+}
+
 pub fn is_eu_member_state(code: &str) -> bool {
-    EU_MEMBER_STATES.iter().any(|ms| *ms == code)
+    EU_MEMBER_STATES.contains(&code)
+}
+
+// Northern Ireland follows EU VAT rules for goods, so its traders carry `XI`
+// VAT ids, but it is not a Member State for CESOP and banks in the UK.
+pub const NORTHERN_IRELAND: &str = "XI";
+
+// The country whose IBANs and BICs serve `country`.
+pub fn banking_country(country: &str) -> &str {
+    if country == NORTHERN_IRELAND {
+        "GB"
+    } else {
+        country
+    }
 }
 
 pub fn currency_for_country(country: &str) -> &'static str {
@@ -60,6 +276,83 @@ pub fn currency_for_country(country: &str) -> &'static str {
         "PL" => "PLN",
         "RO" => "RON",
         "SE" => "SEK",
+        "GB" | "XI" => "GBP",
+        "CH" | "LI" => "CHF",
+        "NO" => "NOK",
+        "IS" => "ISK",
+        "US" => "USD",
+        "CA" => "CAD",
         _ => "EUR",
     }
 }
+
+// Active ISO 4217 alphabetic codes, including fund codes, precious metals and
+// the testing and no-currency codes.
+pub const ISO_4217_CURRENCIES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT",
+    "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BOV", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD",
+    "CAD", "CDF", "CHE", "CHF", "CHW", "CLF", "CLP", "CNY", "COP", "COU", "CRC", "CUP", "CVE",
+    "CZK", "DJF", "DKK", "DOP", "DZD", "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP", "GEL",
+    "GHS", "GIP", "GMD", "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR",
+    "IQD", "IRR", "ISK", "JMD", "JOD", "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW", "KWD",
+    "KYD", "KZT", "LAK", "LBP", "LKR", "LRD", "LSL", "LYD", "MAD", "MDL", "MGA", "MKD", "MMK",
+    "MNT", "MOP", "MRU", "MUR", "MVR", "MWK", "MXN", "MXV", "MYR", "MZN", "NAD", "NGN", "NIO",
+    "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN", "PYG", "QAR", "RON",
+    "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD", "SHP", "SLE", "SLL", "SOS",
+    "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB", "TJS", "TMT", "TND", "TOP", "TRY", "TTD",
+    "TWD", "TZS", "UAH", "UGX", "USD", "USN", "UYI", "UYU", "UYW", "UZS", "VED", "VES", "VND",
+    "VUV", "WST", "XAF", "XAG", "XAU", "XBA", "XBB", "XBC", "XBD", "XCD", "XCG", "XDR", "XOF",
+    "XPD", "XPF", "XPT", "XSU", "XTS", "XUA", "XXX", "YER", "ZAR", "ZMW", "ZWG", "ZWL",
+];
+
+// The currencies of `iso:currCode_Type` in the CESOP schema. It lags ISO 4217:
+// SLL, VED, XCG, XTS and ZWG are missing, the withdrawn HRK and CUC listed.
+pub const CESOP_CURRENCIES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT",
+    "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BOV", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD",
+    "CAD", "CDF", "CHE", "CHF", "CHW", "CLF", "CLP", "CNY", "COP", "COU", "CRC", "CUC", "CUP",
+    "CVE", "CZK", "DJF", "DKK", "DOP", "DZD", "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP",
+    "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ", "GYD", "HKD", "HNL", "HRK", "HTG", "HUF", "IDR",
+    "ILS", "INR", "IQD", "IRR", "ISK", "JMD", "JOD", "JPY", "KES", "KGS", "KHR", "KMF", "KPW",
+    "KRW", "KWD", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD", "LSL", "LYD", "MAD", "MDL", "MGA",
+    "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK", "MXN", "MXV", "MYR", "MZN", "NAD",
+    "NGN", "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN", "PYG",
+    "QAR", "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD", "SHP", "SLE",
+    "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB", "TJS", "TMT", "TND", "TOP", "TRY",
+    "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "USN", "UYI", "UYU", "UYW", "UZS", "VES", "VND",
+    "VUV", "WST", "XAF", "XAG", "XAU", "XBA", "XBB", "XBC", "XBD", "XCD", "XDR", "XOF", "XPD",
+    "XPF", "XPT", "XSU", "XUA", "XXX", "YER", "ZAR", "ZMW", "ZWL",
+];
+
+pub fn is_iso_currency(code: &str) -> bool {
+    ISO_4217_CURRENCIES.contains(&code)
+}
+
+pub fn is_cesop_currency(code: &str) -> bool {
+    CESOP_CURRENCIES.contains(&code)
+}
+
+// ISO 3166-1 alpha-2 country codes. The schema's `iso:CountryCode_Type` has
+// these and EL, XK and XX.
+pub const ISO_3166_COUNTRIES: &[&str] = &[
+    "AD", "AE", "AF", "AG", "AI", "AL", "AM", "AO", "AQ", "AR", "AS", "AT", "AU", "AW", "AX", "AZ",
+    "BA", "BB", "BD", "BE", "BF", "BG", "BH", "BI", "BJ", "BL", "BM", "BN", "BO", "BQ", "BR", "BS",
+    "BT", "BV", "BW", "BY", "BZ", "CA", "CC", "CD", "CF", "CG", "CH", "CI", "CK", "CL", "CM", "CN",
+    "CO", "CR", "CU", "CV", "CW", "CX", "CY", "CZ", "DE", "DJ", "DK", "DM", "DO", "DZ", "EC", "EE",
+    "EG", "EH", "ER", "ES", "ET", "FI", "FJ", "FK", "FM", "FO", "FR", "GA", "GB", "GD", "GE", "GF",
+    "GG", "GH", "GI", "GL", "GM", "GN", "GP", "GQ", "GR", "GS", "GT", "GU", "GW", "GY", "HK", "HM",
+    "HN", "HR", "HT", "HU", "ID", "IE", "IL", "IM", "IN", "IO", "IQ", "IR", "IS", "IT", "JE", "JM",
+    "JO", "JP", "KE", "KG", "KH", "KI", "KM", "KN", "KP", "KR", "KW", "KY", "KZ", "LA", "LB", "LC",
+    "LI", "LK", "LR", "LS", "LT", "LU", "LV", "LY", "MA", "MC", "MD", "ME", "MF", "MG", "MH", "MK",
+    "ML", "MM", "MN", "MO", "MP", "MQ", "MR", "MS", "MT", "MU", "MV", "MW", "MX", "MY", "MZ", "NA",
+    "NC", "NE", "NF", "NG", "NI", "NL", "NO", "NP", "NR", "NU", "NZ", "OM", "PA", "PE", "PF", "PG",
+    "PH", "PK", "PL", "PM", "PN", "PR", "PS", "PT", "PW", "PY", "QA", "RE", "RO", "RS", "RU", "RW",
+    "SA", "SB", "SC", "SD", "SE", "SG", "SH", "SI", "SJ", "SK", "SL", "SM", "SN", "SO", "SR", "SS",
+    "ST", "SV", "SX", "SY", "SZ", "TC", "TD", "TF", "TG", "TH", "TJ", "TK", "TL", "TM", "TN", "TO",
+    "TR", "TT", "TV", "TW", "TZ", "UA", "UG", "UM", "US", "UY", "UZ", "VA", "VC", "VE", "VG", "VI",
+    "VN", "VU", "WF", "WS", "YE", "YT", "ZA", "ZM", "ZW",
+];
+
+pub fn is_iso_country(code: &str) -> bool {
+    ISO_3166_COUNTRIES.contains(&code)
+}
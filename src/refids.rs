@@ -0,0 +1,193 @@
+use crate::codes;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const REGISTRY_FILE: &str = "refid_registry.json";
+
+// MessageRefIds and DocRefIds handed out by render (and retract), so later
+// messages can reference previously submitted documents.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RefIdRegistry {
+    pub messages: Vec<MessageEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageEntry {
+    pub file: String,
+    pub message_ref_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub corr_message_ref_id: Option<String>,
+    pub message_type_indic: String,
+    pub transmitting_country: String,
+    pub psp_id: String,
+    pub psp_name: String,
+    pub year: i32,
+    pub quarter: u8,
+    // How the message was written (`render --schema-version`, `--xml-style`
+    // and `--ref-seed`), so corrections and deletions of it are written the
+    // same way. Registries written before they were recorded lack them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xml_style: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ref_seed: Option<String>,
+    pub payees: Vec<PayeeEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayeeEntry {
+    pub payee_id: String,
+    pub name: String,
+    pub country: String,
+    pub doc_type_indic: String,
+    pub doc_ref_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub corr_doc_ref_id: Option<String>,
+    // DocRefId of the document that corrected or deleted this one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub superseded_by: Option<String>,
+    // Digest of the reported payee and its transactions, so `resubmit` only
+    // corrects payees whose data changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    // TransactionIdentifiers reported in this document.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transactions: Vec<String>,
+}
+
+// Filters for `RefIdRegistry::lookup`; unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct RefIdQuery {
+    pub psp_id: Option<String>,
+    pub period: Option<(i32, u8)>,
+    pub payee_id: Option<String>,
+    pub transaction_id: Option<String>,
+}
+
+// One registered document with the message it was submitted in.
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentRef {
+    pub psp_id: String,
+    pub year: i32,
+    pub quarter: u8,
+    pub payee_id: String,
+    pub doc_type_indic: String,
+    pub doc_ref_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub corr_doc_ref_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub superseded_by: Option<String>,
+    pub message_ref_id: String,
+    pub message_type_indic: String,
+    pub file: String,
+}
+
+pub fn registry_path(output_dir: &Path) -> PathBuf {
+    output_dir.join(REGISTRY_FILE)
+}
+
+impl RefIdRegistry {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|err| {
+            codes::IN_READ_FAILED.err(format!(
+                "failed to read RefId registry {}: {}",
+                path.display(),
+                err
+            ))
+        })?;
+        serde_json::from_str(&contents).map_err(|err| {
+            codes::IN_FILE_INVALID.err(format!(
+                "invalid RefId registry {}: {}",
+                path.display(),
+                err
+            ))
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        std::fs::write(path, json).map_err(|err| err.to_string())
+    }
+
+    // Documents matching `query` in registration order, superseded ones
+    // included, so a payee's correction history reads top to bottom.
+    pub fn lookup(&self, query: &RefIdQuery) -> Vec<DocumentRef> {
+        let mut documents = Vec::new();
+        for message in &self.messages {
+            if query
+                .psp_id
+                .as_ref()
+                .is_some_and(|psp_id| *psp_id != message.psp_id)
+                || query
+                    .period
+                    .is_some_and(|period| period != (message.year, message.quarter))
+            {
+                continue;
+            }
+            for payee in &message.payees {
+                if query
+                    .payee_id
+                    .as_ref()
+                    .is_some_and(|payee_id| *payee_id != payee.payee_id)
+                    || query
+                        .transaction_id
+                        .as_ref()
+                        .is_some_and(|id| !payee.transactions.contains(id))
+                {
+                    continue;
+                }
+                documents.push(DocumentRef {
+                    psp_id: message.psp_id.clone(),
+                    year: message.year,
+                    quarter: message.quarter,
+                    payee_id: payee.payee_id.clone(),
+                    doc_type_indic: payee.doc_type_indic.clone(),
+                    doc_ref_id: payee.doc_ref_id.clone(),
+                    corr_doc_ref_id: payee.corr_doc_ref_id.clone(),
+                    superseded_by: payee.superseded_by.clone(),
+                    message_ref_id: message.message_ref_id.clone(),
+                    message_type_indic: message.message_type_indic.clone(),
+                    file: message.file.clone(),
+                });
+            }
+        }
+        documents
+    }
+
+    pub fn message_for_file(&self, file: &str) -> Option<&MessageEntry> {
+        self.messages.iter().find(|message| message.file == file)
+    }
+
+    // Adds a written message, marking the documents it corrects or deletes
+    // in message `original` as superseded. Registering right away gives later
+    // files of the same run their own sequence numbers.
+    pub fn register(&mut self, original: Option<usize>, entry: MessageEntry) {
+        if let Some(message_idx) = original {
+            let original = &mut self.messages[message_idx];
+            for superseding in &entry.payees {
+                if let Some(payee) = original
+                    .payees
+                    .iter_mut()
+                    .find(|payee| Some(&payee.doc_ref_id) == superseding.corr_doc_ref_id.as_ref())
+                {
+                    payee.superseded_by = Some(superseding.doc_ref_id.clone());
+                }
+            }
+        }
+        self.messages.push(entry);
+    }
+
+    // `<directory>/<stem>_<n>.xml` with the lowest `n` not yet registered.
+    pub fn unused_file(&self, directory: &Path, stem: &str) -> String {
+        (1..)
+            .map(|sequence| {
+                directory
+                    .join(format!("{}_{}.xml", stem, sequence))
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .find(|file| self.message_for_file(file).is_none())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,196 @@
+use crate::codes;
+use crate::generator::{self, quarter_bounds, PayeeRegistry};
+use crate::input::{read_payment_records, CsvReadOptions};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Timelike, Utc};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct RequarterSummary {
+    pub total_records: usize,
+    pub source: (i32, u8),
+    pub target: (i32, u8),
+    pub linked_refunds: usize,
+    pub dangling_refunds: usize,
+    pub registry: Option<PathBuf>,
+}
+
+// Moves every execution_time to the same relative position in the target
+// quarter. The mapping is monotonic, so ordering (and refunds following their
+// payments) is preserved; payment ids are kept, so refund links stay valid.
+pub fn requarter_csv(
+    input: &Path,
+    output: &Path,
+    target: (i32, u8),
+    source: Option<(i32, u8)>,
+    csv_options: &CsvReadOptions,
+) -> Result<RequarterSummary, String> {
+    let mut records = read_payment_records(input, csv_options)?;
+    let mut times = Vec::with_capacity(records.len());
+    for record in &records {
+        let text = record.execution_time.trim();
+        let parsed = DateTime::parse_from_rfc3339(text).map_err(|err| {
+            format!(
+                "payment {}: invalid execution_time '{}': {}",
+                record.payment_id, record.execution_time, err
+            )
+        })?;
+        times.push((parsed.with_timezone(&Utc), TimeFormat::of(text, &parsed)));
+    }
+    let source = match source {
+        Some(source) => source,
+        None => {
+            let earliest = times.iter().map(|(time, _)| time).min().ok_or_else(|| {
+                codes::GEN_REQUARTER_SOURCE.err(format!("{} contains no records", input.display()))
+            })?;
+            (earliest.year(), (earliest.month0() / 3 + 1) as u8)
+        }
+    };
+    let (source_start, source_end) = quarter_bounds(source.0, source.1)?;
+    let (target_start, target_end) = quarter_bounds(target.0, target.1)?;
+
+    let outside = times
+        .iter()
+        .filter(|(time, _)| *time < source_start || *time >= source_end)
+        .count();
+    if outside > 0 {
+        return Err(codes::GEN_REQUARTER_SOURCE.err(format!(
+            "{} record(s) fall outside {}-Q{}; pass the source period explicitly or split the file",
+            outside, source.0, source.1
+        )));
+    }
+
+    let nanos = |duration: Duration| duration.num_nanoseconds().unwrap_or_default() as i128;
+    let source_len = nanos(source_end - source_start);
+    let target_len = nanos(target_end - target_start);
+    for (record, (time, format)) in records.iter_mut().zip(&times) {
+        let offset = nanos(*time - source_start);
+        let shifted =
+            target_start + Duration::nanoseconds((offset * target_len / source_len) as i64);
+        record.execution_time = format.write(shifted);
+    }
+
+    let payment_ids: HashSet<&str> = records
+        .iter()
+        .map(|record| record.payment_id.as_str())
+        .collect();
+    let (linked_refunds, dangling_refunds) = records
+        .iter()
+        .filter_map(|record| record.corr_payment_id.as_deref())
+        .filter(|id| !id.trim().is_empty())
+        .fold((0, 0), |(linked, dangling), id| {
+            if payment_ids.contains(id) {
+                (linked + 1, dangling)
+            } else {
+                (linked, dangling + 1)
+            }
+        });
+
+    let mut writer = csv::Writer::from_path(output).map_err(|err| err.to_string())?;
+    for record in &records {
+        writer.serialize(record).map_err(|err| err.to_string())?;
+    }
+    writer.flush().map_err(|err| err.to_string())?;
+
+    // Carry the payee registry along so `generate --append` works on the copy.
+    let source_registry = generator::registry_path(input);
+    let registry = if source_registry.exists() {
+        let mut payees = PayeeRegistry::load(&source_registry)?;
+        payees.set_period(target.0, target.1);
+        let path = generator::registry_path(output);
+        payees.write(&path)?;
+        Some(path)
+    } else {
+        None
+    };
+
+    Ok(RequarterSummary {
+        total_records: records.len(),
+        source,
+        target,
+        linked_refunds,
+        dangling_refunds,
+        registry,
+    })
+}
+
+// How an input execution_time is written: its UTC offset (`Z` or numeric)
+// and the number of fractional second digits, so shifted times keep them.
+#[derive(Debug, Clone, Copy)]
+struct TimeFormat {
+    offset: FixedOffset,
+    zulu: bool,
+    fraction_digits: usize,
+}
+
+impl TimeFormat {
+    fn of(text: &str, parsed: &DateTime<FixedOffset>) -> Self {
+        let fraction_digits = text
+            .split_once('.')
+            .map(|(_, rest)| rest.chars().take_while(char::is_ascii_digit).count())
+            .unwrap_or(0);
+        Self {
+            offset: parsed.timezone(),
+            zulu: text.ends_with(['Z', 'z']),
+            fraction_digits: fraction_digits.min(9),
+        }
+    }
+
+    // `time` in this format, truncated to its fractional digits.
+    fn write(&self, time: DateTime<Utc>) -> String {
+        let local = time.with_timezone(&self.offset);
+        let mut text = local.format("%Y-%m-%dT%H:%M:%S").to_string();
+        if self.fraction_digits > 0 {
+            let fraction = format!("{:09}", local.nanosecond() % 1_000_000_000);
+            text.push('.');
+            text.push_str(&fraction[..self.fraction_digits]);
+        }
+        if self.zulu {
+            text.push('Z');
+        } else {
+            text.push_str(&local.format("%:z").to_string());
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{generate_records, GeneratorConfig};
+    use crate::input::read_payment_records;
+
+    #[test]
+    fn shifted_times_keep_their_offset_and_precision() {
+        let dir = std::env::temp_dir().join(format!("cesop-requarter-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = GeneratorConfig {
+            year: 2024,
+            quarter: 1,
+            ..GeneratorConfig::default()
+        };
+        let mut records = generate_records(&config, 11).unwrap().records;
+        records[0].execution_time = "2024-01-15T10:00:00+02:00".to_string();
+        records[1].execution_time = "2024-02-01T08:30:15.123456Z".to_string();
+        let input = dir.join("payments.csv");
+        let output = dir.join("payments_q2.csv");
+        crate::compression::write_csv(&input, &records).unwrap();
+
+        let options = CsvReadOptions::default();
+        requarter_csv(&input, &output, (2024, 2), Some((2024, 1)), &options).unwrap();
+        let shifted = read_payment_records(&output, &options).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // 2024-01-15T08:00:00Z is 14 days and 8 hours into a 91-day quarter;
+        // the same share of the 91-day Q2 starts on 2024-04-15 at 08:00 UTC.
+        assert_eq!(shifted[0].execution_time, "2024-04-15T10:00:00+02:00");
+        let micro = &shifted[1].execution_time;
+        assert!(
+            micro.starts_with("2024-05-0") && micro.ends_with('Z'),
+            "{}",
+            micro
+        );
+        let fraction = micro.split_once('.').unwrap().1;
+        assert_eq!(fraction.len(), "123456Z".len(), "{}", micro);
+    }
+}
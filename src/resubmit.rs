@@ -0,0 +1,189 @@
+use crate::cesop_xml::{
+    payee_digest, write_correction_message, write_report, CesopReport, MessageFormat, PayeeGroup,
+};
+use crate::refids::{PayeeEntry, RefIdRegistry};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default)]
+pub struct ResubmitSummary {
+    // Written files with their MessageTypeIndic and payee count.
+    pub messages: Vec<(PathBuf, String, usize)>,
+    pub corrected_payees: usize,
+    pub deleted_payees: usize,
+    pub new_payees: usize,
+    pub unchanged_payees: usize,
+}
+
+// (reporting PSP, year, quarter, payee id) of live documents: accepted new
+// or corrected data that has not been corrected or deleted since. A payee
+// split across messages has one document per message.
+type DocKey = (String, i32, u8, String);
+
+// Compares reports rendered from an amended dataset with the live documents
+// in the RefId registry. For every reporting PSP and period in `reports`,
+// changed payees get a CESOP2 correction and payees no longer reported a
+// CESOP3 deletion, in one CESOP101 message per original message; payees not
+// reported before go into a new CESOP100 message. Other PSPs and periods are
+// left alone.
+pub fn resubmit(
+    reports: &[CesopReport],
+    registry_path: &Path,
+    output_dir: &Path,
+) -> Result<ResubmitSummary, String> {
+    let mut registry = RefIdRegistry::load(registry_path)?;
+    let mut live: BTreeMap<DocKey, Vec<(usize, usize)>> = BTreeMap::new();
+    for (message_idx, message) in registry.messages.iter().enumerate() {
+        if message.message_type_indic == "CESOP102" {
+            continue;
+        }
+        for (payee_idx, payee) in message.payees.iter().enumerate() {
+            if payee.doc_type_indic == "CESOP3" || payee.superseded_by.is_some() {
+                continue;
+            }
+            let key = (
+                message.psp_id.clone(),
+                message.year,
+                message.quarter,
+                payee.payee_id.clone(),
+            );
+            live.entry(key).or_default().push((message_idx, payee_idx));
+        }
+    }
+
+    let mut summary = ResubmitSummary::default();
+    let mut corrections: BTreeMap<usize, Vec<(usize, &PayeeGroup)>> = BTreeMap::new();
+    let mut additions: Vec<(&CesopReport, Vec<PayeeGroup>)> = Vec::new();
+    let mut deletions: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    let mut matched: HashSet<(usize, usize)> = HashSet::new();
+    let mut periods: HashSet<(&str, i32, u8)> = HashSet::new();
+    for report in reports {
+        let (year, quarter) = report.period();
+        periods.insert((report.reporting_psp_id.as_str(), year, quarter));
+        let mut new_payees = Vec::new();
+        for payee in &report.payees {
+            let key = (
+                report.reporting_psp_id.clone(),
+                year,
+                quarter,
+                payee.payee_id.clone(),
+            );
+            let Some(docs) = live.get(&key) else {
+                new_payees.push(payee.clone());
+                continue;
+            };
+            matched.extend(docs.iter().copied());
+            let (message_idx, payee_idx) = docs[0];
+            let previous = &registry.messages[message_idx].payees[payee_idx];
+            if docs.len() == 1 && previous.digest.as_deref() == Some(payee_digest(payee).as_str())
+            {
+                summary.unchanged_payees += 1;
+                continue;
+            }
+            // A payee split across messages is corrected in the first and
+            // deleted from the others.
+            corrections
+                .entry(message_idx)
+                .or_default()
+                .push((payee_idx, payee));
+            for &(message_idx, payee_idx) in &docs[1..] {
+                deletions.entry(message_idx).or_default().push(payee_idx);
+            }
+        }
+        if !new_payees.is_empty() {
+            additions.push((report, new_payees));
+        }
+    }
+    for ((psp_id, year, quarter, _), docs) in &live {
+        for &(message_idx, payee_idx) in docs {
+            if periods.contains(&(psp_id.as_str(), *year, *quarter))
+                && !matched.contains(&(message_idx, payee_idx))
+            {
+                deletions.entry(message_idx).or_default().push(payee_idx);
+            }
+        }
+    }
+
+    std::fs::create_dir_all(output_dir).map_err(|err| err.to_string())?;
+    let format = reports.first().map(MessageFormat::of).unwrap_or_default();
+    let corrected: BTreeSet<usize> = corrections.keys().chain(deletions.keys()).copied().collect();
+    for message_idx in corrected {
+        let original = &registry.messages[message_idx];
+        let corrected_payees: Vec<(&PayeeEntry, &PayeeGroup)> = corrections
+            .get(&message_idx)
+            .map(|payees| {
+                payees
+                    .iter()
+                    .map(|(payee_idx, payee)| (&original.payees[*payee_idx], *payee))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let deleted_payees: Vec<&PayeeEntry> = deletions
+            .get(&message_idx)
+            .map(|payees| payees.iter().map(|idx| &original.payees[*idx]).collect())
+            .unwrap_or_default();
+        // Corrections go next to the original report, as deletions do.
+        let relative = registry.unused_file(
+            Path::new(&original.file).parent().unwrap_or(Path::new("")),
+            &format!(
+                "cesop_{}_Q{}_{}_{}_correction",
+                original.year, original.quarter, original.transmitting_country, original.psp_id
+            ),
+        );
+        let path = output_dir.join(&relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+        let mut entry = write_correction_message(
+            original,
+            &corrected_payees,
+            &deleted_payees,
+            &format,
+            &path,
+        )?;
+        entry.file = relative;
+        log::debug!(
+            file:% = path.display(),
+            psp_id = original.psp_id.as_str(),
+            corrected = corrected_payees.len(),
+            deleted = deleted_payees.len();
+            "correction message written"
+        );
+        summary.corrected_payees += corrected_payees.len();
+        summary.deleted_payees += deleted_payees.len();
+        summary
+            .messages
+            .push((path, entry.message_type_indic.clone(), entry.payees.len()));
+        registry.register(Some(message_idx), entry);
+    }
+
+    for (report, payees) in additions {
+        let (year, quarter) = report.period();
+        let directory = registry
+            .messages
+            .iter()
+            .find(|message| message.psp_id == report.reporting_psp_id)
+            .and_then(|message| Path::new(&message.file).parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+        let relative = registry.unused_file(
+            &directory,
+            &format!(
+                "cesop_{}_Q{}_{}_{}_new",
+                year, quarter, report.transmitting_country, report.reporting_psp_id
+            ),
+        );
+        let path = output_dir.join(&relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+        let mut entry = write_report(&report.with_payees(payees), &path)?;
+        entry.file = relative;
+        summary.new_payees += entry.payees.len();
+        summary
+            .messages
+            .push((path, entry.message_type_indic.clone(), entry.payees.len()));
+        registry.register(None, entry);
+    }
+    registry.write(registry_path)?;
+    Ok(summary)
+}
@@ -0,0 +1,133 @@
+use crate::cesop_xml::write_deletion_message;
+use crate::codes;
+use crate::refids::{MessageEntry, PayeeEntry, RefIdRegistry};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default)]
+pub struct RetractSelection {
+    pub payees: Vec<String>,
+    pub periods: Vec<(i32, u8)>,
+    pub psp_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RetractSummary {
+    pub messages: Vec<(PathBuf, usize)>,
+    pub deleted_payees: usize,
+    pub already_deleted: usize,
+    pub unknown_payees: Vec<String>,
+}
+
+pub fn retract(
+    registry_path: &Path,
+    output_dir: &Path,
+    selection: &RetractSelection,
+) -> Result<RetractSummary, String> {
+    if selection.payees.is_empty() && selection.periods.is_empty() {
+        return Err(codes::RND_NOTHING_TO_RETRACT.err("select payees and/or periods to retract"));
+    }
+    let mut registry = RefIdRegistry::load(registry_path)?;
+    let requested: HashSet<&str> = selection.payees.iter().map(String::as_str).collect();
+    let mut summary = RetractSummary::default();
+    let mut seen_payees: HashSet<String> = HashSet::new();
+
+    let deletion_ids: HashSet<&str> = registry
+        .messages
+        .iter()
+        .flat_map(|message| &message.payees)
+        .filter(|payee| payee.doc_type_indic == "CESOP3")
+        .map(|payee| payee.doc_ref_id.as_str())
+        .collect();
+    let mut deletions: Vec<(usize, Vec<usize>)> = Vec::new();
+    for (message_idx, message) in registry.messages.iter().enumerate() {
+        if !message_selected(message, selection) {
+            continue;
+        }
+        let mut payee_idxs = Vec::new();
+        for (payee_idx, payee) in message.payees.iter().enumerate() {
+            if payee.doc_type_indic == "CESOP3" {
+                continue;
+            }
+            if !requested.is_empty() && !requested.contains(payee.payee_id.as_str()) {
+                continue;
+            }
+            seen_payees.insert(payee.payee_id.clone());
+            // A corrected document lives on in its correction message.
+            if let Some(superseded_by) = payee.superseded_by.as_deref() {
+                if deletion_ids.contains(superseded_by) {
+                    summary.already_deleted += 1;
+                }
+                continue;
+            }
+            payee_idxs.push(payee_idx);
+        }
+        if !payee_idxs.is_empty() {
+            deletions.push((message_idx, payee_idxs));
+        }
+    }
+    summary.unknown_payees = selection
+        .payees
+        .iter()
+        .filter(|payee| !seen_payees.contains(payee.as_str()))
+        .cloned()
+        .collect();
+    if deletions.is_empty() {
+        if summary.already_deleted > 0 {
+            return Err(codes::RND_NOTHING_TO_RETRACT.err(format!(
+                "all {} matching payee document(s) were already deleted",
+                summary.already_deleted
+            )));
+        }
+        return Err(codes::RND_NOTHING_TO_RETRACT
+            .err("no matching payees in the RefId registry to retract"));
+    }
+
+    std::fs::create_dir_all(output_dir).map_err(|err| err.to_string())?;
+    for (message_idx, payee_idxs) in deletions {
+        let original = &registry.messages[message_idx];
+        let payees: Vec<&PayeeEntry> = payee_idxs.iter().map(|idx| &original.payees[*idx]).collect();
+        // Deletions go next to the original report (per-PSP layouts included).
+        // The payee may live in a correction message, so numbering follows
+        // the registered files rather than the correlated messages.
+        let relative = registry.unused_file(
+            Path::new(&original.file).parent().unwrap_or(Path::new("")),
+            &format!(
+                "cesop_{}_Q{}_{}_{}_deletion",
+                original.year, original.quarter, original.transmitting_country, original.psp_id
+            ),
+        );
+        let path = output_dir.join(&relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+        let mut entry = write_deletion_message(original, &payees, &path)?;
+        entry.file = relative;
+        log::debug!(
+            file:% = path.display(),
+            psp_id = original.psp_id.as_str(),
+            payees = payees.len();
+            "deletion message written"
+        );
+        summary.deleted_payees += payees.len();
+        summary.messages.push((path, payees.len()));
+        registry.register(Some(message_idx), entry);
+    }
+    registry.write(registry_path)?;
+    Ok(summary)
+}
+
+fn message_selected(message: &MessageEntry, selection: &RetractSelection) -> bool {
+    if message.message_type_indic == "CESOP102" {
+        return false;
+    }
+    if let Some(psp_id) = selection.psp_id.as_deref() {
+        if !message.psp_id.eq_ignore_ascii_case(psp_id) {
+            return false;
+        }
+    }
+    selection.periods.is_empty()
+        || selection
+            .periods
+            .contains(&(message.year, message.quarter))
+}
@@ -0,0 +1,140 @@
+use crate::codes;
+use crate::location::{bic_country_code, normalize_country_code};
+use crate::reference::is_eu_member_state;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+// Real institutions to use instead of invented PSPs. EU rows act as payee or
+// payer PSPs of EU payees; rows outside the EU serve non-EU payees.
+#[derive(Debug, Clone)]
+pub struct PspRoster {
+    source: String,
+    psps: Vec<RosterPsp>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RosterPsp {
+    pub bic: String,
+    pub name: String,
+    pub country: String,
+    pub licensed_countries: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RosterRow {
+    bic: String,
+    name: String,
+    country: String,
+    #[serde(default)]
+    licensed_countries: String,
+}
+
+impl PspRoster {
+    // Columns: bic, name, country, licensed_countries (separated by `;`,
+    // `,` or spaces; defaults to the PSP's own country).
+    pub fn from_csv(path: &Path) -> Result<Self, String> {
+        let mut reader = csv::Reader::from_path(path).map_err(|err| {
+            codes::IN_READ_FAILED.err(format!(
+                "failed to open PSP roster {}: {}",
+                path.display(),
+                err
+            ))
+        })?;
+        let mut psps = Vec::new();
+        let mut seen = HashSet::new();
+        for (idx, row) in reader.deserialize::<RosterRow>().enumerate() {
+            let context = |err: String| {
+                codes::GEN_ROSTER_INVALID.err(format!(
+                    "{} row {}: {}",
+                    path.display(),
+                    idx + 1,
+                    err
+                ))
+            };
+            let row = row.map_err(|err| context(err.to_string()))?;
+            let psp = RosterPsp::from_row(row).map_err(context)?;
+            if !seen.insert(psp.bic.clone()) {
+                return Err(context(format!("duplicate BIC {}", psp.bic)));
+            }
+            psps.push(psp);
+        }
+        if !psps.iter().any(RosterPsp::is_eu) {
+            return Err(codes::GEN_PSP_COUNTRY_NOT_EU.err(format!(
+                "PSP roster {} lists no PSP in an EU Member State",
+                path.display()
+            )));
+        }
+        Ok(Self {
+            source: path.display().to_string(),
+            psps,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn psps(&self) -> &[RosterPsp] {
+        &self.psps
+    }
+
+    // Member States any EU PSP of the roster is licensed in, sorted.
+    pub fn licensed_countries(&self) -> Vec<String> {
+        let mut countries: Vec<String> = self
+            .psps
+            .iter()
+            .filter(|psp| psp.is_eu())
+            .flat_map(|psp| psp.licensed_countries.iter().cloned())
+            .collect();
+        countries.sort();
+        countries.dedup();
+        countries
+    }
+}
+
+impl RosterPsp {
+    fn from_row(row: RosterRow) -> Result<Self, String> {
+        let bic = row.bic.trim().to_uppercase();
+        if !bic.chars().all(|ch| ch.is_ascii_alphanumeric()) {
+            return Err(format!("invalid BIC {}", bic));
+        }
+        let bic_country =
+            bic_country_code(&bic).ok_or_else(|| format!("invalid BIC {}", bic))?;
+        let country = normalize_country_code(&row.country)
+            .ok_or_else(|| format!("invalid country code {}", row.country.trim()))?;
+        if bic_country != country {
+            return Err(format!("BIC {} is not from {}", bic, country));
+        }
+        let name = row.name.trim().to_string();
+        if name.is_empty() {
+            return Err(format!("PSP {} has no name", bic));
+        }
+        let mut licensed_countries = Vec::new();
+        for code in row
+            .licensed_countries
+            .split(|ch: char| ch == ';' || ch == ',' || ch.is_whitespace())
+            .filter(|code| !code.is_empty())
+        {
+            let code = normalize_country_code(code)
+                .filter(|code| is_eu_member_state(code))
+                .ok_or_else(|| format!("licensed country {} is not an EU Member State", code))?;
+            if !licensed_countries.contains(&code) {
+                licensed_countries.push(code);
+            }
+        }
+        if licensed_countries.is_empty() && is_eu_member_state(&country) {
+            licensed_countries.push(country.clone());
+        }
+        Ok(Self {
+            bic,
+            name,
+            country,
+            licensed_countries,
+        })
+    }
+
+    pub fn is_eu(&self) -> bool {
+        is_eu_member_state(&self.country)
+    }
+}
@@ -0,0 +1,195 @@
+use crate::codes::code_in;
+use chrono::{Local, SecondsFormat, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+static RUN_ID: OnceLock<RunId> = OnceLock::new();
+
+#[derive(Debug, Clone)]
+struct RunId {
+    value: String,
+    external: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    pub run_id: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub duration_ms: u128,
+    pub exit_status: String,
+    pub exit_code: i32,
+    pub error: Option<String>,
+    pub error_code: Option<&'static str>,
+    pub counters: BTreeMap<String, serde_json::Value>,
+    pub phases: PhaseTimings,
+    pub peak_rss_kb: Option<u64>,
+    #[serde(skip)]
+    started: Instant,
+    #[serde(skip)]
+    failure_exit_code: Option<i32>,
+}
+
+impl RunSummary {
+    pub fn new(command: &str) -> Self {
+        Self {
+            run_id: run_id().to_string(),
+            command: command.to_string(),
+            args: std::env::args().skip(1).collect(),
+            started_at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            finished_at: None,
+            duration_ms: 0,
+            exit_status: "running".to_string(),
+            exit_code: 0,
+            error: None,
+            error_code: None,
+            counters: BTreeMap::new(),
+            phases: PhaseTimings::default(),
+            peak_rss_kb: None,
+            started: Instant::now(),
+            failure_exit_code: None,
+        }
+    }
+
+    // Exit code of the run if the command fails, instead of 1.
+    pub fn fail_with_exit_code(&mut self, code: i32) {
+        self.failure_exit_code = Some(code);
+    }
+
+    pub fn counter<V: Into<serde_json::Value>>(&mut self, key: &str, value: V) {
+        self.counters.insert(key.to_string(), value.into());
+    }
+
+    pub fn finish(&mut self, result: &Result<(), String>) {
+        self.duration_ms = self.started.elapsed().as_millis();
+        self.finished_at = Some(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true));
+        self.peak_rss_kb = peak_rss_kb();
+        match result {
+            Ok(()) => {
+                self.exit_status = "success".to_string();
+                self.exit_code = 0;
+            }
+            Err(err) => {
+                self.exit_status = "failure".to_string();
+                self.exit_code = self.failure_exit_code.unwrap_or(1);
+                self.error = Some(err.clone());
+                self.error_code = code_in(err);
+            }
+        }
+    }
+
+    pub fn write(&self, runs_dir: &Path) -> Result<PathBuf, String> {
+        let stamp = Local::now().format("%Y%m%d-%H%M%S%.3f");
+        let run_dir = runs_dir.join(format!("{}-{}", stamp, self.command));
+        std::fs::create_dir_all(&run_dir).map_err(|err| err.to_string())?;
+        let path = run_dir.join("run-summary.json");
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        std::fs::write(&path, json).map_err(|err| err.to_string())?;
+        Ok(path)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct PhaseTiming<'a> {
+    phase: &'a str,
+    duration_ms: u128,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PhaseTimings {
+    phases: Vec<(String, Duration)>,
+}
+
+impl PhaseTimings {
+    pub fn add(&mut self, phase: &str, elapsed: Duration) {
+        if let Some((_, total)) = self.phases.iter_mut().find(|(name, _)| name == phase) {
+            *total += elapsed;
+        } else {
+            self.phases.push((phase.to_string(), elapsed));
+        }
+    }
+
+    pub fn time<T>(&mut self, phase: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let value = f();
+        self.add(phase, start.elapsed());
+        value
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.phases.is_empty()
+    }
+
+    pub fn describe(&self) -> String {
+        self.phases
+            .iter()
+            .map(|(phase, elapsed)| format!("{}={} ms", phase, elapsed.as_millis()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl Serialize for PhaseTimings {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.phases.iter().map(|(phase, elapsed)| PhaseTiming {
+            phase,
+            duration_ms: elapsed.as_millis(),
+        }))
+    }
+}
+
+pub fn init_run_id(explicit: Option<&str>) -> &'static str {
+    let resolved = RUN_ID.get_or_init(|| {
+        let provided = explicit
+            .map(|value| value.to_string())
+            .or_else(|| std::env::var("CESOP_RUN_ID").ok())
+            .filter(|value| !value.trim().is_empty());
+        match provided {
+            Some(value) => RunId {
+                value: value.trim().to_string(),
+                external: true,
+            },
+            None => RunId {
+                value: uuid::Uuid::new_v4().to_string(),
+                external: false,
+            },
+        }
+    });
+    resolved.value.as_str()
+}
+
+pub fn run_id() -> &'static str {
+    init_run_id(None)
+}
+
+pub fn run_id_is_external() -> bool {
+    init_run_id(None);
+    RUN_ID.get().map(|id| id.external).unwrap_or(false)
+}
+
+#[cfg(target_os = "linux")]
+pub fn peak_rss_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn peak_rss_kb() -> Option<u64> {
+    None
+}
+
+pub fn runs_dir() -> Option<PathBuf> {
+    match std::env::var("CESOP_RUNS_DIR").ok().as_deref() {
+        Some("off") | Some("none") | Some("") => None,
+        Some(path) => Some(PathBuf::from(path)),
+        None => Some(PathBuf::from("runs")),
+    }
+}
@@ -0,0 +1,117 @@
+use crate::codes;
+use crate::input::{read_payment_records, CsvReadOptions};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy)]
+pub enum SampleSize {
+    Payees(usize),
+    Fraction(f64),
+}
+
+#[derive(Debug, Clone)]
+pub struct SampleSummary {
+    pub total_records: usize,
+    pub total_payees: usize,
+    pub selected_payees: usize,
+    pub sampled_records: usize,
+    pub linked_records: usize,
+}
+
+pub fn sample_csv(
+    input: &Path,
+    output: &Path,
+    size: SampleSize,
+    seed: u64,
+    csv_options: &CsvReadOptions,
+) -> Result<SampleSummary, String> {
+    let records = read_payment_records(input, csv_options)?;
+    let payee_ids: BTreeSet<&str> = records
+        .iter()
+        .map(|record| record.payee_id.as_str())
+        .collect();
+    let target = match size {
+        SampleSize::Payees(count) => count,
+        SampleSize::Fraction(fraction) => {
+            if !(0.0..=1.0).contains(&fraction) || fraction == 0.0 {
+                return Err(codes::GEN_RATIO_OUT_OF_RANGE.err("fraction must be in (0, 1]"));
+            }
+            ((payee_ids.len() as f64) * fraction).ceil() as usize
+        }
+    };
+    if target == 0 {
+        return Err(codes::GEN_SAMPLE_EMPTY.err("sample must select at least one payee"));
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut candidates: Vec<&str> = payee_ids.iter().copied().collect();
+    candidates.shuffle(&mut rng);
+    let selected: HashSet<&str> = candidates.into_iter().take(target).collect();
+
+    let mut by_payment_id: HashMap<&str, usize> = HashMap::new();
+    let mut refunds_by_original: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (idx, record) in records.iter().enumerate() {
+        by_payment_id.insert(record.payment_id.as_str(), idx);
+        if let Some(corr) = record.corr_payment_id.as_deref().filter(|_| record.is_refund) {
+            refunds_by_original.entry(corr).or_default().push(idx);
+        }
+    }
+
+    let mut keep = vec![false; records.len()];
+    let mut pending = Vec::new();
+    for (idx, record) in records.iter().enumerate() {
+        if selected.contains(record.payee_id.as_str()) {
+            keep[idx] = true;
+            pending.push(idx);
+        }
+    }
+    let direct = pending.len();
+
+    // Pull in the other half of every refund pair so links stay resolvable.
+    while let Some(idx) = pending.pop() {
+        let record = &records[idx];
+        let mut linked = Vec::new();
+        if record.is_refund {
+            if let Some(original) = record
+                .corr_payment_id
+                .as_deref()
+                .and_then(|corr| by_payment_id.get(corr))
+            {
+                linked.push(*original);
+            }
+        }
+        if let Some(refunds) = refunds_by_original.get(record.payment_id.as_str()) {
+            linked.extend(refunds.iter().copied());
+        }
+        for other in linked {
+            if !keep[other] {
+                keep[other] = true;
+                pending.push(other);
+            }
+        }
+    }
+
+    if let Some(parent) = output.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+    }
+    let mut writer = csv::Writer::from_path(output).map_err(|err| err.to_string())?;
+    let mut sampled = 0;
+    for (record, _) in records.iter().zip(&keep).filter(|(_, keep)| **keep) {
+        writer.serialize(record).map_err(|err| err.to_string())?;
+        sampled += 1;
+    }
+    writer.flush().map_err(|err| err.to_string())?;
+
+    Ok(SampleSummary {
+        total_records: records.len(),
+        total_payees: payee_ids.len(),
+        selected_payees: selected.len(),
+        sampled_records: sampled,
+        linked_records: sampled - direct,
+    })
+}
@@ -0,0 +1,317 @@
+use crate::codes;
+use crate::generator::{
+    validate_config, Churn, CountryWeights, FieldRates, GeneratorConfig, Perspective,
+    DEFAULT_REFUND_DELAY_DAYS,
+};
+use crate::seasonality::{TimePreset, TimeProfile};
+use crate::util::{parse_period, sha256_hex};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+// A versioned `generate` scenario. Every key is optional and falls back to the
+// `generate` flag defaults; `segments` overrides what `scale` would derive.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub seed: Option<u64>,
+    pub scale: Option<usize>,
+    pub period: Option<String>,
+    pub output: Option<PathBuf>,
+    #[serde(default)]
+    pub holiday_calendars: bool,
+    #[serde(default)]
+    pub mcc: bool,
+    #[serde(default)]
+    pub deterministic_ids: bool,
+    pub country_weights: Option<String>,
+    #[serde(default)]
+    pub perspective: Perspective,
+    #[serde(default)]
+    pub ratios: Ratios,
+    #[serde(default)]
+    pub field_rates: FieldRates,
+    #[serde(default)]
+    pub segments: Segments,
+    #[serde(default)]
+    pub psps: Psps,
+    #[serde(default)]
+    pub time: Time,
+    #[serde(default)]
+    pub refunds: Refunds,
+    #[serde(default)]
+    pub churn: ChurnRatios,
+    #[serde(skip)]
+    pub fingerprint: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ratios {
+    #[serde(default = "default_cross_border")]
+    pub cross_border: f64,
+    #[serde(default = "default_refund")]
+    pub refund: f64,
+    #[serde(default = "default_multi_account")]
+    pub multi_account: f64,
+    #[serde(default = "default_non_eu_payee")]
+    pub non_eu_payee: f64,
+    #[serde(default = "default_no_account_payee")]
+    pub no_account_payee: f64,
+    #[serde(default)]
+    pub marketplace: f64,
+    #[serde(default)]
+    pub unicode_name: f64,
+    #[serde(default)]
+    pub foreign_currency: f64,
+    #[serde(default)]
+    pub payer_account: f64,
+    #[serde(default)]
+    pub non_eu_payer: f64,
+    #[serde(default)]
+    pub local_time: f64,
+    #[serde(default)]
+    pub xi_payee: f64,
+    #[serde(default)]
+    pub xi_payer: f64,
+    #[serde(default)]
+    pub multi_psp: f64,
+    #[serde(default)]
+    pub unknown_payee_psp: f64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Segments {
+    pub records: Option<usize>,
+    pub payees: Option<usize>,
+    pub micro: Option<usize>,
+    pub near_threshold: Option<usize>,
+    pub large: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Psps {
+    #[serde(default = "default_psp_count")]
+    pub count: usize,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub countries: Vec<String>,
+}
+
+// A time profile preset, optionally with its weekday, hourly or monthly
+// weights replaced.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Time {
+    #[serde(default)]
+    pub profile: TimePreset,
+    pub weekday: Option<[f64; 7]>,
+    pub hourly: Option<[f64; 24]>,
+    pub monthly: Option<[f64; 12]>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Refunds {
+    #[serde(default = "default_refund_delay_days")]
+    pub delay_days: (u32, u32),
+    #[serde(default)]
+    pub partial_ratio: f64,
+    #[serde(default = "default_max_per_payment")]
+    pub max_per_payment: u32,
+}
+
+// Payee churn between quarters of a `--quarters` run.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChurnRatios {
+    #[serde(default)]
+    pub leave: f64,
+    #[serde(default)]
+    pub join: f64,
+    #[serde(default)]
+    pub account_change: f64,
+}
+
+fn default_cross_border() -> f64 {
+    0.8
+}
+
+fn default_refund() -> f64 {
+    0.02
+}
+
+fn default_multi_account() -> f64 {
+    0.15
+}
+
+fn default_non_eu_payee() -> f64 {
+    0.10
+}
+
+fn default_no_account_payee() -> f64 {
+    0.02
+}
+
+fn default_refund_delay_days() -> (u32, u32) {
+    DEFAULT_REFUND_DELAY_DAYS
+}
+
+fn default_max_per_payment() -> u32 {
+    1
+}
+
+fn default_psp_count() -> usize {
+    1
+}
+
+impl Default for Ratios {
+    fn default() -> Self {
+        Self {
+            cross_border: default_cross_border(),
+            refund: default_refund(),
+            multi_account: default_multi_account(),
+            non_eu_payee: default_non_eu_payee(),
+            no_account_payee: default_no_account_payee(),
+            marketplace: 0.0,
+            unicode_name: 0.0,
+            foreign_currency: 0.0,
+            payer_account: 0.0,
+            non_eu_payer: 0.0,
+            local_time: 0.0,
+            xi_payee: 0.0,
+            xi_payer: 0.0,
+            multi_psp: 0.0,
+            unknown_payee_psp: 0.0,
+        }
+    }
+}
+
+impl Default for Psps {
+    fn default() -> Self {
+        Self {
+            count: default_psp_count(),
+            name: None,
+            countries: Vec::new(),
+        }
+    }
+}
+
+impl Default for Refunds {
+    fn default() -> Self {
+        Self {
+            delay_days: default_refund_delay_days(),
+            partial_ratio: 0.0,
+            max_per_payment: default_max_per_payment(),
+        }
+    }
+}
+
+impl Scenario {
+    // TOML by default; `.json` files are read as JSON like portfolios.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|err| {
+            codes::IN_READ_FAILED.err(format!(
+                "failed to read scenario {}: {}",
+                path.display(),
+                err
+            ))
+        })?;
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let mut scenario: Scenario = if is_json {
+            serde_json::from_str(&contents).map_err(|err| err.to_string())
+        } else {
+            toml::from_str(&contents).map_err(|err| err.to_string())
+        }
+        .map_err(|err| {
+            codes::GEN_SCENARIO_INVALID.err(format!("invalid scenario {}: {}", path.display(), err))
+        })?;
+        scenario.psps.countries = scenario
+            .psps
+            .countries
+            .iter()
+            .map(|country| country.trim().to_uppercase())
+            .collect();
+        if let Some(period) = scenario.period.as_deref() {
+            parse_period(period).map_err(|err| format!("scenario {}: {}", path.display(), err))?;
+        }
+        if scenario.output.as_ref().is_some_and(|output| output.as_os_str().is_empty()) {
+            return Err(codes::GEN_SCENARIO_INVALID.err(format!(
+                "scenario {}: output cannot be empty",
+                path.display()
+            )));
+        }
+        scenario.fingerprint = sha256_hex(contents.as_bytes());
+        Ok(scenario)
+    }
+
+    pub fn period(&self) -> Result<Option<(i32, u8)>, String> {
+        self.period.as_deref().map(parse_period).transpose()
+    }
+
+    // Applies the scenario on top of a scale-derived config and validates the
+    // result, so a broken scenario fails before any record is generated.
+    pub fn apply(&self, config: &mut GeneratorConfig) -> Result<(), String> {
+        let segments = &self.segments;
+        config.records = segments.records.unwrap_or(config.records);
+        config.payees = segments.payees.unwrap_or(config.payees);
+        config.micro_payees = segments.micro.unwrap_or(config.micro_payees);
+        config.near_threshold_payees = segments
+            .near_threshold
+            .unwrap_or(config.near_threshold_payees);
+        config.large_payees = segments.large.unwrap_or(config.large_payees);
+        config.psps = if self.psps.countries.is_empty() {
+            self.psps.count
+        } else {
+            self.psps.countries.len()
+        };
+        config.psp_name = self.psps.name.clone();
+        config.psp_countries = self.psps.countries.clone();
+        config.cross_border_ratio = self.ratios.cross_border;
+        config.refund_ratio = self.ratios.refund;
+        config.multi_account_ratio = self.ratios.multi_account;
+        config.non_eu_payee_ratio = self.ratios.non_eu_payee;
+        config.no_account_payee_ratio = self.ratios.no_account_payee;
+        config.marketplace_ratio = self.ratios.marketplace;
+        config.unicode_name_ratio = self.ratios.unicode_name;
+        config.foreign_currency_ratio = self.ratios.foreign_currency;
+        config.payer_account_ratio = self.ratios.payer_account;
+        config.non_eu_payer_ratio = self.ratios.non_eu_payer;
+        config.local_time_ratio = self.ratios.local_time;
+        config.xi_payee_ratio = self.ratios.xi_payee;
+        config.xi_payer_ratio = self.ratios.xi_payer;
+        config.multi_psp_ratio = self.ratios.multi_psp;
+        config.perspective = self.perspective;
+        config.unknown_payee_psp_ratio = self.ratios.unknown_payee_psp;
+        config.refund_delay_days = self.refunds.delay_days;
+        config.partial_refund_ratio = self.refunds.partial_ratio;
+        config.max_refunds_per_payment = self.refunds.max_per_payment;
+        config.churn = Churn {
+            leave_ratio: self.churn.leave,
+            join_ratio: self.churn.join,
+            account_change_ratio: self.churn.account_change,
+        };
+        config.country_weights = self
+            .country_weights
+            .as_deref()
+            .map(CountryWeights::parse)
+            .transpose()
+            .map_err(|err| codes::GEN_COUNTRY_WEIGHTS_INVALID.err(err))?
+            .unwrap_or_default();
+        config.holiday_calendars = self.holiday_calendars;
+        config.mcc = self.mcc;
+        config.field_rates = self.field_rates;
+        config.deterministic_ids |= self.deterministic_ids;
+        config.time_profile = TimeProfile::with_overrides(
+            self.time.profile,
+            self.time.weekday,
+            self.time.hourly,
+            self.time.monthly,
+        )
+        .map_err(|err| codes::GEN_TIME_PROFILE_INVALID.err(err))?;
+        validate_config(config).map_err(String::from)
+    }
+}
@@ -0,0 +1,60 @@
+use crate::xsd;
+
+const CESOP_NAMESPACE: &str = "urn:ec.europa.eu:taxud:fiscalis:cesop:v1";
+const COMMON_TYPES_NAMESPACE: &str = "urn:eu:taxud:commontypes:v1";
+const ISO_TYPES_NAMESPACE: &str = "urn:eu:taxud:isotypes:v1";
+
+// What the renderer writes differently per CESOP XSD version. Messages are
+// otherwise built the same way; supporting a new version means a profile
+// here, overriding what changed.
+pub trait SchemaProfile: Sync {
+    // Value of the `version` attribute on the CESOP root element.
+    fn version(&self) -> &'static str;
+
+    // Namespace declarations on the root element, the default namespace
+    // first.
+    fn namespaces(&self) -> &'static [(&'static str, &'static str)] {
+        &[
+            ("xmlns", CESOP_NAMESPACE),
+            ("xmlns:cm", COMMON_TYPES_NAMESPACE),
+            ("xmlns:iso", ISO_TYPES_NAMESPACE),
+        ]
+    }
+
+    // Whether the bundled XSDs describe this version, so `--validate` can
+    // check messages written for it.
+    fn bundled_xsd(&self) -> bool {
+        self.version() == xsd::XSD_VERSION
+    }
+}
+
+// User Guide v6.00.
+struct V4_03;
+
+impl SchemaProfile for V4_03 {
+    fn version(&self) -> &'static str {
+        "4.03"
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SchemaVersion {
+    #[default]
+    #[value(name = "4.03")]
+    V4_03,
+}
+
+impl SchemaVersion {
+    pub fn profile(self) -> &'static dyn SchemaProfile {
+        match self {
+            SchemaVersion::V4_03 => &V4_03,
+        }
+    }
+
+    // The version whose root `version` attribute is `version`.
+    pub fn from_version(version: &str) -> Option<Self> {
+        [SchemaVersion::V4_03]
+            .into_iter()
+            .find(|schema| schema.profile().version() == version)
+    }
+}
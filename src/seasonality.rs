@@ -0,0 +1,138 @@
+use crate::codes;
+use crate::Error;
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TimePreset {
+    // Every second of the quarter is equally likely.
+    #[default]
+    Uniform,
+    // Consumer shopping: evening and weekend peaks, busy November/December.
+    Retail,
+    // B2B invoicing: office hours on weekdays, quiet weekends and August.
+    Business,
+}
+
+impl TimePreset {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TimePreset::Uniform => "uniform",
+            TimePreset::Retail => "retail",
+            TimePreset::Business => "business",
+        }
+    }
+}
+
+// Relative transaction volume by weekday (Monday first), hour of day (UTC) and
+// month. Weights are scaled so the busiest slot of each table is 1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeProfile {
+    pub preset: TimePreset,
+    pub weekday: [f64; 7],
+    pub hourly: [f64; 24],
+    pub monthly: [f64; 12],
+}
+
+impl Default for TimeProfile {
+    fn default() -> Self {
+        TimeProfile::preset(TimePreset::Uniform)
+    }
+}
+
+const RETAIL_WEEKDAY: [f64; 7] = [0.9, 0.9, 0.95, 1.0, 1.15, 1.3, 0.85];
+const RETAIL_HOURLY: [f64; 24] = [
+    0.1, 0.05, 0.03, 0.03, 0.05, 0.1, 0.25, 0.45, 0.6, 0.75, 0.85, 0.95, 1.0, 0.95, 0.85, 0.85,
+    0.9, 0.95, 1.0, 1.0, 0.9, 0.7, 0.45, 0.25,
+];
+const RETAIL_MONTHLY: [f64; 12] = [
+    0.8, 0.8, 0.9, 0.95, 1.0, 1.0, 1.05, 1.05, 0.95, 1.0, 1.2, 1.45,
+];
+const BUSINESS_WEEKDAY: [f64; 7] = [1.0, 1.0, 1.0, 1.0, 0.95, 0.15, 0.08];
+const BUSINESS_HOURLY: [f64; 24] = [
+    0.02, 0.02, 0.02, 0.02, 0.02, 0.02, 0.05, 0.2, 0.6, 1.0, 1.0, 1.0, 0.7, 0.95, 0.95, 0.95,
+    0.95, 0.6, 0.3, 0.15, 0.05, 0.05, 0.05, 0.05,
+];
+const BUSINESS_MONTHLY: [f64; 12] = [
+    0.95, 1.0, 1.1, 1.0, 1.0, 1.1, 0.9, 0.75, 1.1, 1.0, 1.0, 0.85,
+];
+
+impl TimeProfile {
+    pub fn preset(preset: TimePreset) -> Self {
+        let (weekday, hourly, monthly) = match preset {
+            TimePreset::Uniform => ([1.0; 7], [1.0; 24], [1.0; 12]),
+            TimePreset::Retail => (RETAIL_WEEKDAY, RETAIL_HOURLY, RETAIL_MONTHLY),
+            TimePreset::Business => (BUSINESS_WEEKDAY, BUSINESS_HOURLY, BUSINESS_MONTHLY),
+        };
+        TimeProfile {
+            preset,
+            weekday: normalized(weekday),
+            hourly: normalized(hourly),
+            monthly: normalized(monthly),
+        }
+    }
+
+    // Replaces tables of a preset; the result is validated and rescaled.
+    pub fn with_overrides(
+        preset: TimePreset,
+        weekday: Option<[f64; 7]>,
+        hourly: Option<[f64; 24]>,
+        monthly: Option<[f64; 12]>,
+    ) -> Result<Self, Error> {
+        let mut profile = TimeProfile::preset(preset);
+        if let Some(weekday) = weekday {
+            check_weights("weekday", &weekday)?;
+            profile.weekday = normalized(weekday);
+        }
+        if let Some(hourly) = hourly {
+            check_weights("hourly", &hourly)?;
+            profile.hourly = normalized(hourly);
+        }
+        if let Some(monthly) = monthly {
+            check_weights("monthly", &monthly)?;
+            profile.monthly = normalized(monthly);
+        }
+        Ok(profile)
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        check_weights("weekday", &self.weekday)?;
+        check_weights("hourly", &self.hourly)?;
+        check_weights("monthly", &self.monthly)
+    }
+
+    pub fn is_uniform(&self) -> bool {
+        [&self.weekday[..], &self.hourly[..], &self.monthly[..]]
+            .iter()
+            .all(|table| table.iter().all(|weight| *weight == 1.0))
+    }
+
+    // Acceptance probability (0..=1) for a candidate timestamp.
+    pub fn weight(&self, time: DateTime<Utc>) -> f64 {
+        self.weekday[time.weekday().num_days_from_monday() as usize]
+            * self.hourly[time.hour() as usize]
+            * self.monthly[time.month0() as usize]
+    }
+}
+
+fn check_weights(name: &str, weights: &[f64]) -> Result<(), Error> {
+    if weights.iter().any(|weight| !weight.is_finite() || *weight < 0.0) {
+        return Err(codes::GEN_TIME_PROFILE_INVALID
+            .fail(format!("{} weights must be finite and non-negative", name)));
+    }
+    if weights.iter().all(|weight| *weight == 0.0) {
+        return Err(
+            codes::GEN_TIME_PROFILE_INVALID.fail(format!("{} weights cannot all be zero", name))
+        );
+    }
+    Ok(())
+}
+
+fn normalized<const N: usize>(weights: [f64; N]) -> [f64; N] {
+    let max = weights.iter().copied().fold(0.0, f64::max);
+    if max <= 0.0 {
+        return weights;
+    }
+    weights.map(|weight| weight / max)
+}
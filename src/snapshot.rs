@@ -0,0 +1,233 @@
+use crate::analysis::{analyze_threshold_csv, AnalyzeOptions};
+use crate::cesop_xml::{
+    build_reports_from_csv, write_reports, MessageLimits, OutputLayout, RenderOptions,
+};
+use crate::codes;
+use crate::run_summary::PhaseTimings;
+use crate::trace::RecordTracer;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const SCENARIO_FILE: &str = "scenario.json";
+const ANALYSIS_FILE: &str = "analysis.json";
+const NORMALIZED_PLACEHOLDER: &str = "[normalized]";
+const VOLATILE_XML_ELEMENTS: &[&str] = &["MessageRefId", "Timestamp", "cm:DocRefId"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotScenario {
+    pub name: String,
+    pub input: PathBuf,
+    pub threshold: usize,
+    pub include_refunds: bool,
+    pub transmitting_country: String,
+    pub licensed_countries: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum SnapshotDiff {
+    Missing(String),
+    Unexpected(String),
+    Changed {
+        file: String,
+        line: usize,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl SnapshotDiff {
+    pub fn describe(&self) -> String {
+        match self {
+            SnapshotDiff::Missing(file) => format!("{}: missing from current run", file),
+            SnapshotDiff::Unexpected(file) => format!("{}: not present in snapshot", file),
+            SnapshotDiff::Changed {
+                file,
+                line,
+                expected,
+                actual,
+            } => format!(
+                "{}:{}: expected '{}', got '{}'",
+                file, line, expected, actual
+            ),
+        }
+    }
+}
+
+pub fn record_snapshot(dir: &Path, scenario: &SnapshotScenario) -> Result<Vec<PathBuf>, String> {
+    let outputs = capture_outputs(scenario)?;
+    let scenario_dir = dir.join(&scenario.name);
+    if scenario_dir.exists() {
+        std::fs::remove_dir_all(&scenario_dir).map_err(|err| err.to_string())?;
+    }
+    std::fs::create_dir_all(&scenario_dir).map_err(|err| err.to_string())?;
+
+    let scenario_json = serde_json::to_string_pretty(scenario).map_err(|err| err.to_string())?;
+    std::fs::write(scenario_dir.join(SCENARIO_FILE), scenario_json + "\n")
+        .map_err(|err| err.to_string())?;
+
+    let mut written = Vec::new();
+    for (name, contents) in outputs {
+        let path = scenario_dir.join(&name);
+        std::fs::write(&path, contents).map_err(|err| err.to_string())?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+pub fn check_snapshot(dir: &Path, name: &str) -> Result<Vec<SnapshotDiff>, String> {
+    let scenario_dir = dir.join(name);
+    let scenario_path = scenario_dir.join(SCENARIO_FILE);
+    let scenario_json = std::fs::read_to_string(&scenario_path).map_err(|err| {
+        codes::IN_READ_FAILED.err(format!(
+            "failed to read {}: {}",
+            scenario_path.display(),
+            err
+        ))
+    })?;
+    let scenario: SnapshotScenario = serde_json::from_str(&scenario_json).map_err(|err| {
+        codes::IN_FILE_INVALID.err(format!("invalid {}: {}", scenario_path.display(), err))
+    })?;
+
+    let actual = capture_outputs(&scenario)?;
+    let mut expected = BTreeMap::new();
+    for entry in std::fs::read_dir(&scenario_dir).map_err(|err| err.to_string())? {
+        let entry = entry.map_err(|err| err.to_string())?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name == SCENARIO_FILE {
+            continue;
+        }
+        let contents = std::fs::read_to_string(entry.path()).map_err(|err| err.to_string())?;
+        expected.insert(file_name, contents);
+    }
+
+    let mut diffs = Vec::new();
+    for (file, expected_contents) in &expected {
+        match actual.get(file) {
+            Some(actual_contents) => {
+                if let Some(diff) = first_difference(file, expected_contents, actual_contents) {
+                    diffs.push(diff);
+                }
+            }
+            None => diffs.push(SnapshotDiff::Missing(file.clone())),
+        }
+    }
+    for file in actual.keys() {
+        if !expected.contains_key(file) {
+            diffs.push(SnapshotDiff::Unexpected(file.clone()));
+        }
+    }
+    Ok(diffs)
+}
+
+pub fn list_snapshots(dir: &Path) -> Result<Vec<String>, String> {
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(dir).map_err(|err| {
+        codes::IN_READ_FAILED.err(format!("failed to read {}: {}", dir.display(), err))
+    })? {
+        let entry = entry.map_err(|err| err.to_string())?;
+        if entry.path().join(SCENARIO_FILE).is_file() {
+            names.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+fn capture_outputs(scenario: &SnapshotScenario) -> Result<BTreeMap<String, String>, String> {
+    let mut outputs = BTreeMap::new();
+    let mut timings = PhaseTimings::default();
+
+    let analyze_options = AnalyzeOptions {
+        threshold: scenario.threshold,
+        include_refunds: scenario.include_refunds,
+        ..AnalyzeOptions::default()
+    };
+    let report = analyze_threshold_csv(&scenario.input, &analyze_options, &mut timings)?;
+    let analysis = serde_json::to_string_pretty(&report).map_err(|err| err.to_string())?;
+    outputs.insert(ANALYSIS_FILE.to_string(), analysis + "\n");
+
+    let options = RenderOptions {
+        transmitting_country: scenario.transmitting_country.clone(),
+        licensed_countries: scenario.licensed_countries.clone(),
+        ..RenderOptions::default()
+    };
+    let mut tracer = RecordTracer::new(&[]);
+    let reports = build_reports_from_csv(&scenario.input, &options, &mut tracer, &mut timings)?;
+
+    let render_dir = std::env::temp_dir().join(format!("cesop-snapshot-{}", uuid::Uuid::new_v4()));
+    let rendered = write_reports(
+        &reports,
+        &render_dir,
+        OutputLayout::Flat,
+        &MessageLimits::default(),
+        1,
+        None,
+    ).and_then(|paths| {
+        let mut files = Vec::new();
+        for path in paths {
+            let contents = std::fs::read_to_string(&path).map_err(|err| err.to_string())?;
+            let file_name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            files.push((file_name, contents));
+        }
+        Ok(files)
+    });
+    let _ = std::fs::remove_dir_all(&render_dir);
+    for (file_name, contents) in rendered? {
+        outputs.insert(file_name, normalize_xml(&contents));
+    }
+
+    Ok(outputs)
+}
+
+fn normalize_xml(xml: &str) -> String {
+    let mut out = xml.to_string();
+    for element in VOLATILE_XML_ELEMENTS {
+        let open = format!("<{}>", element);
+        let close = format!("</{}>", element);
+        let mut normalized = String::with_capacity(out.len());
+        let mut rest = out.as_str();
+        while let Some(start) = rest.find(&open) {
+            let value_start = start + open.len();
+            let Some(end) = rest[value_start..].find(&close) else {
+                break;
+            };
+            normalized.push_str(&rest[..value_start]);
+            normalized.push_str(NORMALIZED_PLACEHOLDER);
+            rest = &rest[value_start + end..];
+        }
+        normalized.push_str(rest);
+        out = normalized;
+    }
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+fn first_difference(file: &str, expected: &str, actual: &str) -> Option<SnapshotDiff> {
+    if expected == actual {
+        return None;
+    }
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 0;
+    loop {
+        line += 1;
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(left), Some(right)) if left == right => continue,
+            (None, None) => return None,
+            (left, right) => {
+                return Some(SnapshotDiff::Changed {
+                    file: file.to_string(),
+                    line,
+                    expected: left.unwrap_or("<end of file>").trim().to_string(),
+                    actual: right.unwrap_or("<end of file>").trim().to_string(),
+                })
+            }
+        }
+    }
+}
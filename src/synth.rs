@@ -0,0 +1,68 @@
+//! Record-level building blocks for property tests.
+//!
+//! Each function draws from the caller's RNG, so a `proptest` strategy can
+//! produce single payees, their payments and identifiers without generating a
+//! whole dataset. The [`GeneratorConfig`] ratios apply as in `generate`; start
+//! from `GeneratorConfig::default()` and override what the test needs.
+
+use crate::error::Result;
+use crate::generator::{self, GeneratorConfig, PayeeProfile};
+use crate::models::PaymentRecord;
+use rand::Rng;
+
+// A payee with its accounts and reporting PSP, as a `generate` run would
+// produce it.
+#[derive(Debug, Clone)]
+pub struct Payee(PayeeProfile);
+
+impl Payee {
+    pub fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    pub fn country(&self) -> &str {
+        &self.0.country
+    }
+
+    // `(account, account_type)` pairs; empty for payees reported through
+    // their PSP.
+    pub fn accounts(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .accounts
+            .iter()
+            .map(|account| (account.id.as_str(), account.account_type.as_str()))
+    }
+
+    pub fn reporting_psp_id(&self) -> &str {
+        &self.0.reporting_psp_id
+    }
+
+    // `PAYEE` or `PAYER`: the side the reporting PSP acts for.
+    pub fn psp_role(&self) -> &str {
+        &self.0.psp_role
+    }
+}
+
+pub fn gen_payee<R: Rng + ?Sized>(rng: &mut R, config: &GeneratorConfig) -> Result<Payee> {
+    generator::synth_payee(rng, config).map(Payee)
+}
+
+// A payment to `payee` in the config's quarter. Refunds need an earlier
+// payment, so none are produced here.
+pub fn gen_transaction_for<R: Rng + ?Sized>(
+    rng: &mut R,
+    payee: &Payee,
+    config: &GeneratorConfig,
+) -> Result<PaymentRecord> {
+    generator::synth_payment(rng, &payee.0, config)
+}
+
+// An IBAN with valid check digits and, where known, the country's length,
+// BBAN structure and bank codes.
+pub fn gen_iban<R: Rng + ?Sized>(rng: &mut R, country: &str) -> String {
+    generator::generate_iban(rng, country)
+}
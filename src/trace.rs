@@ -0,0 +1,160 @@
+use crate::codes::Code;
+use crate::models::PaymentRecord;
+use crate::run_summary::run_id;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceEvent {
+    pub run_id: String,
+    pub payment_id: String,
+    pub payee_id: String,
+    pub psp_id: String,
+    pub stage: &'static str,
+    pub decision: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Exclusion {
+    exclusion_stage: &'static str,
+    exclusion_code: &'static str,
+    exclusion_reason: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RecordTracer {
+    targets: HashSet<String>,
+    events: Vec<TraceEvent>,
+    // Every excluded record (regardless of targets) when a quarantine file is requested.
+    quarantine: Option<Vec<(PaymentRecord, Exclusion)>>,
+}
+
+impl RecordTracer {
+    pub fn new(targets: &[String]) -> Self {
+        Self {
+            targets: targets.iter().cloned().collect(),
+            events: Vec::new(),
+            quarantine: None,
+        }
+    }
+
+    pub fn with_quarantine(mut self) -> Self {
+        self.quarantine = Some(Vec::new());
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.targets.is_empty()
+    }
+
+    // Whether per-record decisions are observed at all (tracing or quarantine).
+    pub fn is_recording(&self) -> bool {
+        self.is_enabled() || self.quarantine.is_some()
+    }
+
+    pub fn matches(&self, record: &PaymentRecord) -> bool {
+        self.is_enabled()
+            && (self.targets.contains(&record.payment_id)
+                || self.targets.contains(&record.payee_id))
+    }
+
+    pub fn include(&mut self, record: &PaymentRecord, stage: &'static str, reason: &str) {
+        self.push(record, stage, "included", None, reason);
+    }
+
+    pub fn exclude(
+        &mut self,
+        record: &PaymentRecord,
+        stage: &'static str,
+        code: &Code,
+        reason: &str,
+    ) {
+        if let Some(quarantine) = self.quarantine.as_mut() {
+            quarantine.push((
+                record.clone(),
+                Exclusion {
+                    exclusion_stage: stage,
+                    exclusion_code: code.code,
+                    exclusion_reason: reason.to_string(),
+                },
+            ));
+        }
+        self.push(record, stage, "excluded", Some(code.code), reason);
+    }
+
+    pub fn quarantine_counts(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+        for (_, exclusion) in self.quarantine.iter().flatten() {
+            *counts.entry(exclusion.exclusion_stage).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    // Input columns plus exclusion_stage, exclusion_code and exclusion_reason.
+    pub fn write_quarantine_csv(&self, path: &Path) -> Result<usize, String> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+            }
+        }
+        let mut writer = csv::Writer::from_path(path).map_err(|err| err.to_string())?;
+        let quarantine = self.quarantine.as_deref().unwrap_or_default();
+        for row in quarantine {
+            writer.serialize(row).map_err(|err| err.to_string())?;
+        }
+        writer.flush().map_err(|err| err.to_string())?;
+        Ok(quarantine.len())
+    }
+
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+
+    pub fn write_jsonl(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+        let mut file = std::fs::File::create(path).map_err(|err| err.to_string())?;
+        for event in &self.events {
+            let line = serde_json::to_string(event).map_err(|err| err.to_string())?;
+            writeln!(file, "{}", line).map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn push(
+        &mut self,
+        record: &PaymentRecord,
+        stage: &'static str,
+        decision: &'static str,
+        code: Option<&'static str>,
+        reason: &str,
+    ) {
+        if !self.matches(record) {
+            return;
+        }
+        log::debug!(
+            payment_id = record.payment_id.as_str(),
+            payee_id = record.payee_id.as_str(),
+            stage = stage,
+            decision = decision;
+            "trace: {}",
+            reason
+        );
+        self.events.push(TraceEvent {
+            run_id: run_id().to_string(),
+            payment_id: record.payment_id.clone(),
+            payee_id: record.payee_id.clone(),
+            psp_id: record.psp_id.clone(),
+            stage,
+            decision,
+            code,
+            reason: reason.to_string(),
+        });
+    }
+}
@@ -1,4 +1,8 @@
+use chrono::{DateTime, Utc};
 use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::Path;
 
 pub fn random_digits<R: Rng + ?Sized>(rng: &mut R, len: usize) -> String {
     let mut out = String::with_capacity(len);
@@ -73,3 +77,133 @@ pub fn iban_check_digits(country: &str, bban: &str) -> Result<String, String> {
     let check = 98 - remainder;
     Ok(format!("{:02}", check))
 }
+
+pub fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut file = std::fs::File::open(path).map_err(|err| err.to_string())?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).map_err(|err| err.to_string())?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+// Accepts `2025Q1`, `2025-Q1` and `2025-q1`.
+pub fn parse_period(value: &str) -> Result<(i32, u8), String> {
+    let normalized = value.trim().to_uppercase().replace('-', "");
+    let (year, quarter) = normalized
+        .split_once('Q')
+        .ok_or_else(|| format!("invalid period '{}': expected YYYYQn", value))?;
+    let year = year
+        .parse::<i32>()
+        .map_err(|_| format!("invalid period '{}': expected YYYYQn", value))?;
+    match quarter.parse::<u8>() {
+        Ok(quarter @ 1..=4) => Ok((year, quarter)),
+        _ => Err(format!("invalid period '{}': quarter must be 1..4", value)),
+    }
+}
+
+// "1..30" (inclusive) or a single number of days.
+pub fn parse_day_range(value: &str) -> Result<(u32, u32), String> {
+    let parse = |part: &str| {
+        part.trim()
+            .parse::<u32>()
+            .map_err(|_| format!("invalid day range '{}': expected MIN..MAX", value))
+    };
+    let (min, max) = match value.split_once("..") {
+        Some((min, max)) => (parse(min)?, parse(max)?),
+        None => {
+            let days = parse(value)?;
+            (days, days)
+        }
+    };
+    if min > max {
+        return Err(format!("invalid day range '{}': {} is after {}", value, min, max));
+    }
+    Ok((min, max))
+}
+
+// "500MB", "1.5GB", "64KiB" or plain bytes; KB/MB/GB are powers of 1000 and
+// KiB/MiB/GiB powers of 1024.
+pub fn parse_byte_size(value: &str) -> Result<u64, String> {
+    let trimmed = value.trim();
+    let split = trimmed
+        .find(|ch: char| !(ch.is_ascii_digit() || ch == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "KB" | "K" => 1_000,
+        "MB" | "M" => 1_000_000,
+        "GB" | "G" => 1_000_000_000,
+        "KIB" => 1 << 10,
+        "MIB" => 1 << 20,
+        "GIB" => 1 << 30,
+        _ => return Err(format!("invalid size '{}': unknown unit '{}'", value, unit.trim())),
+    };
+    let number = number
+        .parse::<f64>()
+        .map_err(|_| format!("invalid size '{}': expected e.g. 500MB", value))?;
+    let bytes = number * multiplier as f64;
+    if !bytes.is_finite() || bytes < 1.0 {
+        return Err(format!("invalid size '{}': must be at least 1 byte", value));
+    }
+    Ok(bytes as u64)
+}
+
+// "2024Q1..2024Q4" (inclusive) or a single period; quarters in order.
+pub fn parse_period_range(value: &str) -> Result<Vec<(i32, u8)>, String> {
+    let (first, last) = match value.split_once("..") {
+        Some((first, last)) => (parse_period(first)?, parse_period(last)?),
+        None => {
+            let period = parse_period(value)?;
+            (period, period)
+        }
+    };
+    if first > last {
+        return Err(format!(
+            "invalid period range '{}': {}Q{} is after {}Q{}",
+            value, first.0, first.1, last.0, last.1
+        ));
+    }
+    let mut periods = Vec::new();
+    let (mut year, mut quarter) = first;
+    while (year, quarter) <= last {
+        periods.push((year, quarter));
+        if quarter == 4 {
+            year += 1;
+            quarter = 1;
+        } else {
+            quarter += 1;
+        }
+    }
+    Ok(periods)
+}
+
+// Fixed MessageSpec timestamp: `value` as RFC 3339, else `SOURCE_DATE_EPOCH`
+// (seconds since the Unix epoch, as for reproducible builds), else none.
+pub fn fixed_timestamp(value: Option<&str>) -> Result<Option<DateTime<Utc>>, String> {
+    if let Some(value) = value {
+        return DateTime::parse_from_rfc3339(value.trim())
+            .map(|time| Some(time.with_timezone(&Utc)))
+            .map_err(|err| format!("invalid timestamp '{}': {}", value, err));
+    }
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) if !epoch.trim().is_empty() => epoch
+            .trim()
+            .parse::<i64>()
+            .ok()
+            .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+            .map(Some)
+            .ok_or_else(|| format!("invalid SOURCE_DATE_EPOCH '{}': expected seconds", epoch)),
+        _ => Ok(None),
+    }
+}
@@ -1,3 +1,5 @@
+use crate::codes;
+use crate::Error;
 use std::path::Path;
 use std::process::Command;
 use std::time::Instant;
@@ -12,12 +14,18 @@ pub fn validate_with_vm(
     java_bin: &str,
     jar_path: &Path,
     input: &Path,
-) -> Result<ValidationResult, String> {
+) -> Result<ValidationResult, Error> {
     if !jar_path.exists() {
-        return Err(format!("Validation module jar not found: {}", jar_path.display()));
+        return Err(codes::VM_JAR_NOT_FOUND.fail(format!(
+            "Validation module jar not found: {}",
+            jar_path.display()
+        )));
     }
     if !input.exists() {
-        return Err(format!("Input file not found: {}", input.display()));
+        return Err(codes::VM_INPUT_NOT_FOUND.fail(format!(
+            "Input file not found: {}",
+            input.display()
+        )));
     }
 
     let start = Instant::now();
@@ -28,12 +36,12 @@ pub fn validate_with_vm(
         .output()
         .map_err(|err| {
             if err.kind() == std::io::ErrorKind::NotFound {
-                format!(
+                codes::VM_JAVA_NOT_FOUND.fail(format!(
                     "Java runtime not found (expected `{}`). Install Java or set --java.",
                     java_bin
-                )
+                ))
             } else {
-                err.to_string()
+                Error::from(err.to_string())
             }
         })?;
 
@@ -47,15 +55,15 @@ pub fn validate_with_vm(
         } else {
             stderr.trim()
         };
-        return Err(format!("Validation failed: {}", details));
+        return Err(codes::VM_FAILED.fail(format!("Validation failed: {}", details)));
     }
 
     if let Some(summary) = read_validation_summary(input)? {
         if summary.has_rejections() {
-            return Err(format!(
+            return Err(codes::VM_REJECTED.fail(format!(
                 "Validation rejected: validated={} partial={} full={}",
                 summary.validated, summary.partially_rejected, summary.fully_rejected
-            ));
+            )));
         }
     }
 
@@ -0,0 +1,269 @@
+use crate::import::{read_message, ImportedMessage, ImportedPayee};
+use crate::models::PaymentRecord;
+use chrono::DateTime;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub left: String,
+    pub right: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PayeeSummary {
+    pub name: String,
+    pub country: String,
+    pub accounts: Vec<String>,
+    pub transactions: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionDiff {
+    pub transaction_id: String,
+    pub fields: Vec<FieldChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PayeeDiff {
+    pub payee: PayeeSummary,
+    pub fields: Vec<FieldChange>,
+    pub added_transactions: Vec<String>,
+    pub removed_transactions: Vec<String>,
+    pub changed_transactions: Vec<TransactionDiff>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct XmlDiff {
+    pub header: Vec<FieldChange>,
+    pub added_payees: Vec<PayeeSummary>,
+    pub removed_payees: Vec<PayeeSummary>,
+    pub changed_payees: Vec<PayeeDiff>,
+    pub unchanged_payees: usize,
+}
+
+impl XmlDiff {
+    pub fn is_empty(&self) -> bool {
+        self.header.is_empty()
+            && self.added_payees.is_empty()
+            && self.removed_payees.is_empty()
+            && self.changed_payees.is_empty()
+    }
+}
+
+// Compares two CESOP messages by content: the message header, then payees
+// matched on country and account identifiers (on name when a payee has no
+// account) and their transactions matched on TransactionIdentifier.
+// MessageRefId, DocRefId, Timestamp and element order are ignored, as are
+// payees deleted by a CESOP3 document.
+pub fn diff_messages(left: &Path, right: &Path) -> Result<XmlDiff, String> {
+    let left = read_message(left)?;
+    let right = read_message(right)?;
+    let mut diff = XmlDiff {
+        header: header_changes(&left, &right),
+        ..XmlDiff::default()
+    };
+
+    let mut right_payees = payees_by_identity(&right);
+    for (identity, left_payees) in payees_by_identity(&left) {
+        let mut matches = right_payees.remove(&identity).unwrap_or_default().into_iter();
+        for left_payee in left_payees {
+            match matches.next() {
+                Some(right_payee) => match payee_diff(left_payee, right_payee) {
+                    Some(changed) => diff.changed_payees.push(changed),
+                    None => diff.unchanged_payees += 1,
+                },
+                None => diff.removed_payees.push(payee_summary(left_payee)),
+            }
+        }
+        diff.added_payees.extend(matches.map(payee_summary));
+    }
+    for payees in right_payees.into_values() {
+        diff.added_payees.extend(payees.into_iter().map(payee_summary));
+    }
+    Ok(diff)
+}
+
+fn header_changes(left: &ImportedMessage, right: &ImportedMessage) -> Vec<FieldChange> {
+    let period = |message: &ImportedMessage| format!("{}Q{}", message.period.0, message.period.1);
+    let mut changes = Vec::new();
+    for (field, left, right) in [
+        (
+            "MessageTypeIndic",
+            left.message_type_indic.clone(),
+            right.message_type_indic.clone(),
+        ),
+        (
+            "TransmittingCountry",
+            left.transmitting_country.clone(),
+            right.transmitting_country.clone(),
+        ),
+        ("ReportingPeriod", period(left), period(right)),
+        ("PSPId", left.psp_id.clone(), right.psp_id.clone()),
+        ("PSPName", left.psp_name.clone(), right.psp_name.clone()),
+    ] {
+        if left != right {
+            changes.push(FieldChange { field, left, right });
+        }
+    }
+    changes
+}
+
+// Reported payees by identity, in document order. The same identity can
+// occur more than once, e.g. in a message merged from several systems.
+fn payees_by_identity(message: &ImportedMessage) -> BTreeMap<String, Vec<&ImportedPayee>> {
+    let mut payees: BTreeMap<String, Vec<&ImportedPayee>> = BTreeMap::new();
+    for payee in &message.payees {
+        if payee.doc_type_indic == "CESOP3" {
+            continue;
+        }
+        let summary = payee_summary(payee);
+        let identity = if summary.accounts.is_empty() {
+            format!("{}|name:{}", summary.country, summary.name)
+        } else {
+            format!("{}|{}", summary.country, summary.accounts.join(","))
+        };
+        payees.entry(identity).or_default().push(payee);
+    }
+    payees
+}
+
+fn payee_summary(payee: &ImportedPayee) -> PayeeSummary {
+    let first = payee.records.first();
+    // The importer spreads a payee's accounts over its transactions.
+    let accounts: BTreeSet<String> = payee
+        .records
+        .iter()
+        .map(|record| record.payee_account.clone())
+        .filter(|account| !account.is_empty())
+        .collect();
+    PayeeSummary {
+        name: first.map(|record| record.payee_name.clone()).unwrap_or_default(),
+        country: first.map(|record| record.payee_country.clone()).unwrap_or_default(),
+        accounts: accounts.into_iter().collect(),
+        transactions: payee.records.len(),
+    }
+}
+
+fn payee_diff(left: &ImportedPayee, right: &ImportedPayee) -> Option<PayeeDiff> {
+    let mut diff = PayeeDiff {
+        payee: payee_summary(right),
+        fields: match (left.records.first(), right.records.first()) {
+            (Some(left), Some(right)) => field_changes(&payee_fields(left), &payee_fields(right)),
+            _ => Vec::new(),
+        },
+        added_transactions: Vec::new(),
+        removed_transactions: Vec::new(),
+        changed_transactions: Vec::new(),
+    };
+
+    let mut right_transactions: BTreeMap<&str, &PaymentRecord> = right
+        .records
+        .iter()
+        .map(|record| (record.payment_id.as_str(), record))
+        .collect();
+    for left_tx in &left.records {
+        match right_transactions.remove(left_tx.payment_id.as_str()) {
+            Some(right_tx) => {
+                let fields =
+                    field_changes(&transaction_fields(left_tx), &transaction_fields(right_tx));
+                if !fields.is_empty() {
+                    diff.changed_transactions.push(TransactionDiff {
+                        transaction_id: left_tx.payment_id.clone(),
+                        fields,
+                    });
+                }
+            }
+            None => diff.removed_transactions.push(left_tx.payment_id.clone()),
+        }
+    }
+    diff.added_transactions = right
+        .records
+        .iter()
+        .filter(|record| right_transactions.contains_key(record.payment_id.as_str()))
+        .map(|record| record.payment_id.clone())
+        .collect();
+
+    let unchanged = diff.fields.is_empty()
+        && diff.added_transactions.is_empty()
+        && diff.removed_transactions.is_empty()
+        && diff.changed_transactions.is_empty();
+    (!unchanged).then_some(diff)
+}
+
+// Field name, value as written and value as compared.
+type Field = (&'static str, String, String);
+
+fn payee_fields(record: &PaymentRecord) -> Vec<Field> {
+    let optional = |value: &Option<String>| value.clone().unwrap_or_default();
+    as_written(vec![
+        ("Name", record.payee_name.clone()),
+        ("VATId", optional(&record.payee_vat_id)),
+        ("TAXId", optional(&record.payee_tax_id)),
+        ("EmailAddress", optional(&record.payee_email)),
+        ("WebPage", optional(&record.payee_web)),
+        ("Address", optional(&record.payee_address_line)),
+        ("PostCode", optional(&record.payee_postcode)),
+        ("City", optional(&record.payee_city)),
+        ("RepresentativeId", optional(&record.payee_psp_id)),
+        ("RepresentativeName", optional(&record.payee_psp_name)),
+    ])
+}
+
+fn as_written(fields: Vec<(&'static str, String)>) -> Vec<Field> {
+    fields
+        .into_iter()
+        .map(|(name, value)| (name, value.clone(), value))
+        .collect()
+}
+
+// Amounts and times are compared by value, so `10.5` equals `10.50` and the
+// same instant in another offset is no change.
+fn transaction_fields(record: &PaymentRecord) -> Vec<Field> {
+    let amount = record
+        .amount
+        .parse::<f64>()
+        .map(|value| format!("{:.2}", value))
+        .unwrap_or_else(|_| record.amount.clone());
+    let time = DateTime::parse_from_rfc3339(&record.execution_time)
+        .map(|time| time.timestamp_millis().to_string())
+        .unwrap_or_else(|_| record.execution_time.clone());
+    let mut fields = vec![
+        ("DateTime", record.execution_time.clone(), time),
+        ("Amount", record.amount.clone(), amount),
+    ];
+    fields.extend(as_written(vec![
+        ("currency", record.currency.clone()),
+        ("IsRefund", record.is_refund.to_string()),
+        (
+            "CorrTransactionIdentifier",
+            record.corr_payment_id.clone().unwrap_or_default(),
+        ),
+        ("PaymentMethodType", record.payment_method.clone()),
+        (
+            "PaymentMethodOther",
+            record.payment_method_other.clone().unwrap_or_default(),
+        ),
+        (
+            "InitiatedAtPhysicalPremisesOfMerchant",
+            record.initiated_at_pos.to_string(),
+        ),
+        ("PayerMS", record.payer_country.clone()),
+        ("PayerMSSource", record.payer_ms_source.clone()),
+    ]));
+    fields
+}
+
+fn field_changes(left: &[Field], right: &[Field]) -> Vec<FieldChange> {
+    left.iter()
+        .zip(right)
+        .filter(|((_, _, left), (_, _, right))| left != right)
+        .map(|((field, left, _), (_, right, _))| FieldChange {
+            field,
+            left: left.clone(),
+            right: right.clone(),
+        })
+        .collect()
+}
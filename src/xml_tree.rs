@@ -0,0 +1,152 @@
+use crate::codes;
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use std::path::Path;
+
+// Minimal element tree for reading back CESOP XML files. Text and child
+// elements are kept separately; mixed content does not occur in CESOP.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct XmlNode {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub text: String,
+    pub children: Vec<XmlNode>,
+}
+
+impl XmlNode {
+    pub fn new(name: &str, text: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            text: text.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn child(&self, name: &str) -> Option<&XmlNode> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    pub fn child_mut(&mut self, name: &str) -> Option<&mut XmlNode> {
+        self.children.iter_mut().find(|child| child.name == name)
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlNode> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    pub fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|child| child.text.as_str())
+    }
+
+    pub fn path(&self, names: &[&str]) -> Option<&XmlNode> {
+        names.iter().try_fold(self, |node, name| node.child(name))
+    }
+
+    pub fn path_text(&self, names: &[&str]) -> Option<&str> {
+        self.path(names).map(|node| node.text.as_str())
+    }
+
+    pub fn set_child_text(&mut self, name: &str, text: &str) {
+        if let Some(child) = self.child_mut(name) {
+            child.text = text.to_string();
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+        self.write(&mut writer)?;
+        Ok(writer.into_inner())
+    }
+
+    pub fn write<W: std::io::Write>(&self, writer: &mut Writer<W>) -> Result<(), String> {
+        let mut start = BytesStart::new(self.name.as_str());
+        for (key, value) in &self.attrs {
+            start.push_attribute((key.as_str(), value.as_str()));
+        }
+        writer
+            .write_event(Event::Start(start))
+            .map_err(|err| err.to_string())?;
+        if self.children.is_empty() {
+            writer
+                .write_event(Event::Text(BytesText::new(&self.text)))
+                .map_err(|err| err.to_string())?;
+        } else {
+            for child in &self.children {
+                child.write(writer)?;
+            }
+        }
+        writer
+            .write_event(Event::End(BytesEnd::new(self.name.as_str())))
+            .map_err(|err| err.to_string())
+    }
+}
+
+pub fn parse_file(path: &Path) -> Result<XmlNode, String> {
+    let contents = std::fs::read_to_string(path).map_err(|err| {
+        codes::IN_READ_FAILED.err(format!("failed to read {}: {}", path.display(), err))
+    })?;
+    parse_str(&contents).map_err(|err| {
+        codes::IN_FILE_INVALID.err(format!("invalid XML in {}: {}", path.display(), err))
+    })
+}
+
+pub fn parse_str(contents: &str) -> Result<XmlNode, String> {
+    let mut reader = Reader::from_str(contents);
+    reader.trim_text(true);
+    let mut stack: Vec<XmlNode> = Vec::new();
+    let mut root = None;
+    loop {
+        match reader.read_event().map_err(|err| err.to_string())? {
+            Event::Start(start) => stack.push(node_from_start(&start)?),
+            Event::Empty(start) => {
+                let node = node_from_start(&start)?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => root = Some(node),
+                }
+            }
+            Event::Text(text) => {
+                if let Some(node) = stack.last_mut() {
+                    node.text.push_str(&text.unescape().map_err(|err| err.to_string())?);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(node) = stack.last_mut() {
+                    node.text.push_str(&String::from_utf8_lossy(&data));
+                }
+            }
+            Event::End(_) => {
+                let node = stack
+                    .pop()
+                    .ok_or_else(|| "unbalanced closing tag".to_string())?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => root = Some(node),
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if !stack.is_empty() {
+        return Err("unexpected end of document".to_string());
+    }
+    root.ok_or_else(|| "document has no root element".to_string())
+}
+
+fn node_from_start(start: &BytesStart) -> Result<XmlNode, String> {
+    let mut node = XmlNode {
+        name: String::from_utf8_lossy(start.name().as_ref()).to_string(),
+        ..XmlNode::default()
+    };
+    for attr in start.attributes() {
+        let attr = attr.map_err(|err| err.to_string())?;
+        node.attrs.push((
+            String::from_utf8_lossy(attr.key.as_ref()).to_string(),
+            attr.unescape_value()
+                .map_err(|err| err.to_string())?
+                .to_string(),
+        ));
+    }
+    Ok(node)
+}
@@ -0,0 +1,734 @@
+use crate::codes;
+use crate::xml_tree::{parse_file, XmlNode};
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// The CESOP v4.03 schemas (User Guide v6.00), bundled so rendered messages
+// can be checked without the Java Validation Module.
+const PAYMENT_DATA_XSD: &str = include_str!("../schemas/v6_00/PaymentData.xsd");
+const COMMON_TYPES_XSD: &str = include_str!("../schemas/v6_00/commontypes.xsd");
+const ISO_TYPES_XSD: &str = include_str!("../schemas/v6_00/isotypes.xsd");
+
+pub const XSD_VERSION: &str = "4.03";
+
+const XS: &str = "http://www.w3.org/2001/XMLSchema";
+const XSI: &str = "http://www.w3.org/2001/XMLSchema-instance";
+
+// (namespace, local name)
+type QName = (String, String);
+
+// One schema violation, located by an element path such as
+// `/CESOP/PaymentDataBody/ReportedPayee[3]/DocSpec`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub path: String,
+    pub message: String,
+}
+
+// The subset of XML Schema the CESOP XSDs use: global and local element
+// declarations, sequences and choices with occurrence bounds, simple content
+// extensions with attributes, and simple type restrictions by enumeration,
+// pattern, length, inclusive bounds and whitespace collapsing.
+pub struct Schema {
+    root: ElementDecl,
+    complex_types: HashMap<QName, ComplexType>,
+    simple_types: HashMap<QName, SimpleType>,
+}
+
+#[derive(Debug, Clone)]
+struct ElementDecl {
+    name: QName,
+    kind: TypeRef,
+    min: usize,
+    max: Option<usize>,
+    nillable: bool,
+}
+
+#[derive(Debug, Clone)]
+enum TypeRef {
+    Named(QName),
+    Inline(Box<ComplexType>),
+}
+
+#[derive(Debug, Clone)]
+struct ComplexType {
+    content: Content,
+    attributes: Vec<AttributeDecl>,
+}
+
+#[derive(Debug, Clone)]
+enum Content {
+    Empty,
+    Elements(Particle),
+    Simple(QName),
+}
+
+#[derive(Debug, Clone)]
+enum Particle {
+    Element(ElementDecl),
+    Sequence(Vec<Particle>, usize, Option<usize>),
+    Choice(Vec<Particle>, usize, Option<usize>),
+}
+
+#[derive(Debug, Clone)]
+struct AttributeDecl {
+    name: String,
+    type_name: QName,
+    required: bool,
+    fixed: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct SimpleType {
+    base: QName,
+    enumeration: Vec<String>,
+    patterns: Vec<Regex>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    min_inclusive: Option<f64>,
+    max_inclusive: Option<f64>,
+    collapse: bool,
+}
+
+// Target namespace and prefixes of the schema document being loaded.
+struct SchemaFile {
+    target: String,
+    prefixes: HashMap<String, String>,
+}
+
+impl SchemaFile {
+    fn qname(&self, value: &str) -> Result<QName, String> {
+        let (prefix, local) = value.split_once(':').unwrap_or(("", value));
+        let namespace = self
+            .prefixes
+            .get(prefix)
+            .ok_or_else(|| format!("undeclared schema prefix in '{}'", value))?;
+        Ok((namespace.clone(), local.to_string()))
+    }
+
+    fn local(&self, name: &str) -> QName {
+        (self.target.clone(), name.to_string())
+    }
+}
+
+impl Schema {
+    // The bundled CESOP PaymentData schema.
+    pub fn cesop() -> Result<Schema, String> {
+        let mut root = None;
+        let mut complex_types = HashMap::new();
+        let mut simple_types = HashMap::new();
+        for source in [ISO_TYPES_XSD, COMMON_TYPES_XSD, PAYMENT_DATA_XSD] {
+            let document = crate::xml_tree::parse_str(source)?;
+            let file = SchemaFile {
+                target: attr(&document, "targetNamespace").unwrap_or("").to_string(),
+                prefixes: document
+                    .attrs
+                    .iter()
+                    .filter_map(|(key, value)| {
+                        key.strip_prefix("xmlns:")
+                            .map(|prefix| (prefix.to_string(), value.clone()))
+                    })
+                    .collect(),
+            };
+            for node in &document.children {
+                match local_name(&node.name) {
+                    "element" => root = Some(parse_element(node, &file)?),
+                    "complexType" => {
+                        let name = attr(node, "name").ok_or("unnamed global complexType")?;
+                        complex_types.insert(file.local(name), parse_complex(node, &file)?);
+                    }
+                    "simpleType" => {
+                        let name = attr(node, "name").ok_or("unnamed global simpleType")?;
+                        simple_types.insert(file.local(name), parse_simple(node, &file)?);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(Schema {
+            root: root.ok_or("schema declares no root element")?,
+            complex_types,
+            simple_types,
+        })
+    }
+
+    pub fn validate_file(&self, path: &Path) -> Result<Vec<Violation>, String> {
+        Ok(self.validate(&parse_file(path)?))
+    }
+
+    pub fn validate(&self, document: &XmlNode) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let scope = namespace_scope(&HashMap::new(), document);
+        let path = format!("/{}", document.name);
+        if expanded_name(&scope, &document.name) != self.root.name {
+            violations.push(Violation {
+                path,
+                message: format!("root element must be {}", self.root.name.1),
+            });
+            return violations;
+        }
+        self.validate_element(document, &self.root, &scope, &path, &mut violations);
+        violations
+    }
+
+    fn validate_element(
+        &self,
+        node: &XmlNode,
+        decl: &ElementDecl,
+        scope: &HashMap<String, String>,
+        path: &str,
+        violations: &mut Vec<Violation>,
+    ) {
+        let mut report = |message: String| {
+            violations.push(Violation {
+                path: path.to_string(),
+                message,
+            })
+        };
+        let nil = node.attrs.iter().any(|(key, value)| {
+            expanded_name(scope, key) == (XSI.to_string(), "nil".to_string()) && value == "true"
+        });
+        if nil {
+            if !decl.nillable {
+                report("element is not nillable".to_string());
+            } else if !node.children.is_empty() || !node.text.trim().is_empty() {
+                report("nil element must be empty".to_string());
+            }
+            return;
+        }
+
+        let complex = match &decl.kind {
+            TypeRef::Inline(complex) => Some(complex.as_ref()),
+            TypeRef::Named(name) => self.complex_types.get(name),
+        };
+        let Some(complex) = complex else {
+            let TypeRef::Named(name) = &decl.kind else {
+                return;
+            };
+            self.validate_attributes(node, &[], scope, &mut report);
+            if !node.children.is_empty() {
+                report("element has simple content but contains elements".to_string());
+                return;
+            }
+            if let Err(message) = self.check_simple(name, &node.text) {
+                report(message);
+            }
+            return;
+        };
+
+        self.validate_attributes(node, &complex.attributes, scope, &mut report);
+        match &complex.content {
+            Content::Empty => {
+                if !node.children.is_empty() || !node.text.trim().is_empty() {
+                    report("element must be empty".to_string());
+                }
+            }
+            Content::Simple(base) => {
+                if !node.children.is_empty() {
+                    report("element has simple content but contains elements".to_string());
+                } else if let Err(message) = self.check_simple(base, &node.text) {
+                    report(message);
+                }
+            }
+            Content::Elements(particle) => {
+                if !node.text.trim().is_empty() {
+                    report("element has element-only content but contains text".to_string());
+                }
+                let scopes: Vec<HashMap<String, String>> = node
+                    .children
+                    .iter()
+                    .map(|child| namespace_scope(scope, child))
+                    .collect();
+                let names: Vec<QName> = node
+                    .children
+                    .iter()
+                    .zip(&scopes)
+                    .map(|(child, scope)| expanded_name(scope, &child.name))
+                    .collect();
+                let mut totals: HashMap<&str, usize> = HashMap::new();
+                for child in &node.children {
+                    *totals.entry(child.name.as_str()).or_default() += 1;
+                }
+                let mut seen: HashMap<&str, usize> = HashMap::new();
+                let paths: Vec<String> = node
+                    .children
+                    .iter()
+                    .map(|child| {
+                        let index = seen.entry(child.name.as_str()).or_default();
+                        *index += 1;
+                        if totals[child.name.as_str()] > 1 {
+                            format!("{}/{}[{}]", path, child.name, index)
+                        } else {
+                            format!("{}/{}", path, child.name)
+                        }
+                    })
+                    .collect();
+                let children = Children {
+                    nodes: &node.children,
+                    names: &names,
+                    scopes: &scopes,
+                    paths: &paths,
+                    path,
+                };
+                let mut pos = 0;
+                let matched = self.match_particle(particle, &children, &mut pos, violations);
+                if let Some(child) = node.children.get(pos).filter(|_| matched) {
+                    violations.push(Violation {
+                        path: children.paths[pos].clone(),
+                        message: format!("unexpected element {}", child.name),
+                    });
+                }
+            }
+        }
+    }
+
+    fn validate_attributes(
+        &self,
+        node: &XmlNode,
+        declared: &[AttributeDecl],
+        scope: &HashMap<String, String>,
+        report: &mut impl FnMut(String),
+    ) {
+        for (key, value) in &node.attrs {
+            if key == "xmlns" || key.starts_with("xmlns:") || expanded_name(scope, key).0 == XSI {
+                continue;
+            }
+            let Some(decl) = declared.iter().find(|decl| &decl.name == key) else {
+                report(format!("attribute {} is not allowed", key));
+                continue;
+            };
+            if let Err(message) = self.check_simple(&decl.type_name, value) {
+                report(format!("attribute {}: {}", key, message));
+            }
+            if let Some(fixed) = decl.fixed.as_deref() {
+                if value.trim() != fixed {
+                    report(format!("attribute {} must be {}", key, fixed));
+                }
+            }
+        }
+        for decl in declared.iter().filter(|decl| decl.required) {
+            if !node.attrs.iter().any(|(key, _)| key == &decl.name) {
+                report(format!("missing required attribute {}", decl.name));
+            }
+        }
+    }
+
+    // Consumes the children matching `particle` from `pos` on. The CESOP
+    // content models are deterministic, so a greedy match is exact. Stops at
+    // the first content error (returning false) so one misplaced element is
+    // reported once.
+    fn match_particle(
+        &self,
+        particle: &Particle,
+        children: &Children,
+        pos: &mut usize,
+        violations: &mut Vec<Violation>,
+    ) -> bool {
+        match particle {
+            Particle::Element(decl) => {
+                let mut count = 0;
+                while decl.max.is_none_or(|max| count < max)
+                    && children.names.get(*pos) == Some(&decl.name)
+                {
+                    self.validate_element(
+                        &children.nodes[*pos],
+                        decl,
+                        &children.scopes[*pos],
+                        &children.paths[*pos],
+                        violations,
+                    );
+                    *pos += 1;
+                    count += 1;
+                }
+                if count < decl.min {
+                    violations.push(children.missing(*pos, std::slice::from_ref(&decl.name)));
+                    return false;
+                }
+                true
+            }
+            Particle::Sequence(items, min, max) => {
+                let mut count = 0;
+                while max.is_none_or(|max| count < max) {
+                    if count >= *min && !children.starts(particle, *pos) {
+                        break;
+                    }
+                    let start = *pos;
+                    for item in items {
+                        if !self.match_particle(item, children, pos, violations) {
+                            return false;
+                        }
+                    }
+                    count += 1;
+                    if *pos == start && count >= *min {
+                        break;
+                    }
+                }
+                true
+            }
+            Particle::Choice(items, min, max) => {
+                let mut count = 0;
+                while max.is_none_or(|max| count < max) {
+                    let chosen = items.iter().find(|item| children.starts(item, *pos));
+                    let Some(item) = chosen else {
+                        if count < *min && !items.iter().any(emptiable) {
+                            let expected: Vec<QName> = items.iter().flat_map(first_names).collect();
+                            violations.push(children.missing(*pos, &expected));
+                            return false;
+                        }
+                        break;
+                    };
+                    if !self.match_particle(item, children, pos, violations) {
+                        return false;
+                    }
+                    count += 1;
+                }
+                true
+            }
+        }
+    }
+
+    fn check_simple(&self, type_name: &QName, value: &str) -> Result<(), String> {
+        if type_name.0 == XS {
+            return check_builtin(&type_name.1, value);
+        }
+        let Some(simple) = self.simple_types.get(type_name) else {
+            return Err(format!("unknown type {}", type_name.1));
+        };
+        let value = if simple.collapse {
+            value.split_whitespace().collect::<Vec<_>>().join(" ")
+        } else {
+            value.to_string()
+        };
+        if !simple.enumeration.is_empty() && !simple.enumeration.contains(&value) {
+            return Err(format!("'{}' is not a valid {}", value, type_name.1));
+        }
+        if !simple.patterns.is_empty() && !simple.patterns.iter().any(|re| re.is_match(&value)) {
+            return Err(format!("'{}' does not match the {} pattern", value, type_name.1));
+        }
+        let length = value.chars().count();
+        if simple.min_length.is_some_and(|min| length < min) {
+            return Err(format!(
+                "'{}' is shorter than {} character(s) ({})",
+                value,
+                simple.min_length.unwrap_or_default(),
+                type_name.1
+            ));
+        }
+        if simple.max_length.is_some_and(|max| length > max) {
+            return Err(format!(
+                "value is longer than {} character(s) ({})",
+                simple.max_length.unwrap_or_default(),
+                type_name.1
+            ));
+        }
+        if simple.min_inclusive.is_some() || simple.max_inclusive.is_some() {
+            let number: f64 = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("'{}' is not a number", value))?;
+            if simple.min_inclusive.is_some_and(|min| number < min)
+                || simple.max_inclusive.is_some_and(|max| number > max)
+            {
+                return Err(format!("'{}' is out of range for {}", value, type_name.1));
+            }
+        }
+        self.check_simple(&simple.base, &value)
+    }
+}
+
+// Children of one element with their expanded names and namespace scopes.
+struct Children<'a> {
+    nodes: &'a [XmlNode],
+    names: &'a [QName],
+    scopes: &'a [HashMap<String, String>],
+    paths: &'a [String],
+    path: &'a str,
+}
+
+impl Children<'_> {
+    fn starts(&self, particle: &Particle, pos: usize) -> bool {
+        self.names
+            .get(pos)
+            .is_some_and(|name| first_names(particle).contains(name))
+    }
+
+
+    fn missing(&self, pos: usize, expected: &[QName]) -> Violation {
+        let names: Vec<&str> = expected.iter().map(|name| name.1.as_str()).collect();
+        match self.nodes.get(pos) {
+            Some(found) => {
+                // Same local name in another namespace, e.g. a missing `cm:`.
+                let message = match expected.iter().find(|name| name.1 == self.names[pos].1) {
+                    Some(name) => format!("{} must be in namespace {}", found.name, name.0),
+                    None => format!("expected {}, found {}", names.join(" or "), found.name),
+                };
+                Violation {
+                    path: self.paths[pos].clone(),
+                    message,
+                }
+            }
+            None => Violation {
+                path: self.path.to_string(),
+                message: format!("missing element {}", names.join(" or ")),
+            },
+        }
+    }
+}
+
+fn first_names(particle: &Particle) -> Vec<QName> {
+    match particle {
+        Particle::Element(decl) => vec![decl.name.clone()],
+        Particle::Sequence(items, _, _) => {
+            let mut names = Vec::new();
+            for item in items {
+                names.extend(first_names(item));
+                if !emptiable(item) {
+                    break;
+                }
+            }
+            names
+        }
+        Particle::Choice(items, _, _) => items.iter().flat_map(first_names).collect(),
+    }
+}
+
+fn emptiable(particle: &Particle) -> bool {
+    match particle {
+        Particle::Element(decl) => decl.min == 0,
+        Particle::Sequence(items, min, _) => *min == 0 || items.iter().all(emptiable),
+        Particle::Choice(items, min, _) => *min == 0 || items.iter().any(emptiable),
+    }
+}
+
+fn check_builtin(name: &str, value: &str) -> Result<(), String> {
+    let trimmed = value.trim();
+    let valid = match name {
+        "decimal" => {
+            let digits = trimmed.strip_prefix(['+', '-']).unwrap_or(trimmed);
+            let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+            !(int.is_empty() && frac.is_empty())
+                && int.chars().all(|ch| ch.is_ascii_digit())
+                && frac.chars().all(|ch| ch.is_ascii_digit())
+        }
+        "integer" => {
+            let digits = trimmed.strip_prefix(['+', '-']).unwrap_or(trimmed);
+            !digits.is_empty() && digits.chars().all(|ch| ch.is_ascii_digit())
+        }
+        "boolean" => matches!(trimmed, "true" | "false" | "1" | "0"),
+        "dateTime" => {
+            chrono::DateTime::parse_from_rfc3339(trimmed).is_ok()
+                || chrono::NaiveDateTime::parse_from_str(trimmed, "%Y-%m-%dT%H:%M:%S%.f").is_ok()
+        }
+        _ => true,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("'{}' is not a valid xs:{}", value, name))
+    }
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn attr<'a>(node: &'a XmlNode, name: &str) -> Option<&'a str> {
+    node.attrs
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn schema_children(node: &XmlNode) -> impl Iterator<Item = &XmlNode> {
+    node.children
+        .iter()
+        .filter(|child| local_name(&child.name) != "annotation")
+}
+
+fn parse_occurs(node: &XmlNode) -> Result<(usize, Option<usize>), String> {
+    let min = match attr(node, "minOccurs") {
+        Some(value) => value.parse().map_err(|_| format!("invalid minOccurs '{}'", value))?,
+        None => 1,
+    };
+    let max = match attr(node, "maxOccurs") {
+        Some("unbounded") => None,
+        Some(value) => Some(value.parse().map_err(|_| format!("invalid maxOccurs '{}'", value))?),
+        None => Some(1),
+    };
+    Ok((min, max))
+}
+
+fn parse_element(node: &XmlNode, file: &SchemaFile) -> Result<ElementDecl, String> {
+    let name = attr(node, "name").ok_or("element without a name")?;
+    let kind = match attr(node, "type") {
+        Some(type_name) => TypeRef::Named(file.qname(type_name)?),
+        None => {
+            let complex = schema_children(node)
+                .find(|child| local_name(&child.name) == "complexType")
+                .ok_or_else(|| format!("element {} has no type", name))?;
+            TypeRef::Inline(Box::new(parse_complex(complex, file)?))
+        }
+    };
+    let (min, max) = parse_occurs(node)?;
+    Ok(ElementDecl {
+        name: file.local(name),
+        kind,
+        min,
+        max,
+        nillable: attr(node, "nillable") == Some("true"),
+    })
+}
+
+fn parse_particle(node: &XmlNode, file: &SchemaFile) -> Result<Particle, String> {
+    match local_name(&node.name) {
+        "element" => Ok(Particle::Element(parse_element(node, file)?)),
+        group @ ("sequence" | "choice") => {
+            let items = schema_children(node)
+                .map(|child| parse_particle(child, file))
+                .collect::<Result<Vec<_>, _>>()?;
+            let (min, max) = parse_occurs(node)?;
+            Ok(if group == "sequence" {
+                Particle::Sequence(items, min, max)
+            } else {
+                Particle::Choice(items, min, max)
+            })
+        }
+        other => Err(format!("unsupported schema particle {}", other)),
+    }
+}
+
+fn parse_attribute(node: &XmlNode, file: &SchemaFile) -> Result<AttributeDecl, String> {
+    let name = attr(node, "name").ok_or("attribute without a name")?;
+    Ok(AttributeDecl {
+        name: name.to_string(),
+        type_name: file.qname(attr(node, "type").unwrap_or("xs:string"))?,
+        required: attr(node, "use") == Some("required"),
+        fixed: attr(node, "fixed").map(str::to_string),
+    })
+}
+
+fn parse_complex(node: &XmlNode, file: &SchemaFile) -> Result<ComplexType, String> {
+    let mut complex = ComplexType {
+        content: Content::Empty,
+        attributes: Vec::new(),
+    };
+    for child in schema_children(node) {
+        match local_name(&child.name) {
+            "sequence" | "choice" => complex.content = Content::Elements(parse_particle(child, file)?),
+            "attribute" => complex.attributes.push(parse_attribute(child, file)?),
+            "simpleContent" => {
+                let extension = schema_children(child)
+                    .find(|child| local_name(&child.name) == "extension")
+                    .ok_or("simpleContent without an extension")?;
+                let base = attr(extension, "base").ok_or("extension without a base")?;
+                complex.content = Content::Simple(file.qname(base)?);
+                for attribute in schema_children(extension) {
+                    complex.attributes.push(parse_attribute(attribute, file)?);
+                }
+            }
+            other => return Err(format!("unsupported complexType content {}", other)),
+        }
+    }
+    Ok(complex)
+}
+
+fn parse_simple(node: &XmlNode, file: &SchemaFile) -> Result<SimpleType, String> {
+    let restriction = schema_children(node)
+        .find(|child| local_name(&child.name) == "restriction")
+        .ok_or("simpleType without a restriction")?;
+    let base = file.qname(attr(restriction, "base").ok_or("restriction without a base")?)?;
+    let mut simple = SimpleType {
+        collapse: base == (XS.to_string(), "token".to_string()),
+        base,
+        enumeration: Vec::new(),
+        patterns: Vec::new(),
+        min_length: None,
+        max_length: None,
+        min_inclusive: None,
+        max_inclusive: None,
+    };
+    for facet in schema_children(restriction) {
+        let value = attr(facet, "value").unwrap_or_default();
+        let invalid = || format!("invalid {} facet '{}'", local_name(&facet.name), value);
+        match local_name(&facet.name) {
+            "enumeration" => simple.enumeration.push(value.to_string()),
+            // XSD patterns match the whole value.
+            "pattern" => simple.patterns.push(
+                Regex::new(&format!("^(?:{})$", value)).map_err(|err| err.to_string())?,
+            ),
+            "minLength" => simple.min_length = Some(value.parse().map_err(|_| invalid())?),
+            "maxLength" => simple.max_length = Some(value.parse().map_err(|_| invalid())?),
+            "minInclusive" => simple.min_inclusive = Some(value.parse().map_err(|_| invalid())?),
+            "maxInclusive" => simple.max_inclusive = Some(value.parse().map_err(|_| invalid())?),
+            "whiteSpace" => simple.collapse = value == "collapse",
+            other => return Err(format!("unsupported facet {}", other)),
+        }
+    }
+    Ok(simple)
+}
+
+fn namespace_scope(parent: &HashMap<String, String>, node: &XmlNode) -> HashMap<String, String> {
+    let mut scope = parent.clone();
+    for (key, value) in &node.attrs {
+        if key == "xmlns" {
+            scope.insert(String::new(), value.clone());
+        } else if let Some(prefix) = key.strip_prefix("xmlns:") {
+            scope.insert(prefix.to_string(), value.clone());
+        }
+    }
+    scope
+}
+
+fn expanded_name(scope: &HashMap<String, String>, name: &str) -> QName {
+    match name.split_once(':') {
+        Some(("xsi", local)) if !scope.contains_key("xsi") => (XSI.to_string(), local.to_string()),
+        Some((prefix, local)) => (
+            scope.get(prefix).cloned().unwrap_or_default(),
+            local.to_string(),
+        ),
+        None => (
+            scope.get("").cloned().unwrap_or_default(),
+            name.to_string(),
+        ),
+    }
+}
+
+// Validates each file against the bundled schema; files that cannot be read
+// or parsed report that as their only violation.
+pub fn validate_files(paths: &[PathBuf]) -> Result<Vec<(PathBuf, Vec<Violation>)>, String> {
+    let schema = Schema::cesop().map_err(|err| codes::VM_SCHEMA_UNREADABLE.err(err))?;
+    Ok(paths
+        .iter()
+        .map(|path| {
+            let violations = schema.validate_file(path).unwrap_or_else(|err| {
+                vec![Violation {
+                    path: "/".to_string(),
+                    message: err,
+                }]
+            });
+            (path.clone(), violations)
+        })
+        .collect())
+}
+
+// `input` itself, or every .xml file below the directory `input`, sorted.
+pub fn collect_xml_files(input: &Path) -> Result<Vec<PathBuf>, String> {
+    if !input.is_dir() {
+        return Ok(vec![input.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    let mut pending = vec![input.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir).map_err(|err| err.to_string())? {
+            let path = entry.map_err(|err| err.to_string())?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "xml") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}